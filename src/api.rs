@@ -0,0 +1,241 @@
+//! The logic behind `fetch`, `ls`, `pull`, `rm`, and `run`, for Rust tools that want to drive blrs
+//! without spawning the binary.
+//!
+//! A [`Session`] never prompts on the terminal. Ambiguous choices are made like with `--non-interactive`,
+//! unless a [`Prompter`] is installed, and progress is reported to a [`Progress`] as [`Event`]s.
+//!
+//! The settings of a session apply to the whole process, like the global flags of the binary do:
+//! the library, its layout, the hooks, aliases, proxy, and deletion policy. Every session of a process
+//! has to use the same ones, and building a session with different settings fails.
+//!
+//! Ctrl+C is left to the app blrs runs in.
+
+use std::sync::{atomic::Ordering, OnceLock};
+
+use blrs::{repos::RepoEntry, BLRSConfig};
+
+use crate::{
+    cancel,
+    commands::{
+        fetch, fetch_error, gather_and_filter_repos, pull_builds, remove_builds, select_repos,
+        sync_shortcuts, LaunchOutputArgs, RunCommand, StalePolicy,
+    },
+    deletion::set_policy,
+    errs::IoErrorOrigin,
+    events::set_listener,
+    hooks::set_hooks,
+    network::{set_proxy, within_deadline, ProxySetting},
+    prompt::set_prompter,
+    repo_cache::Window,
+    repo_formatting::SortFormat,
+    resolving::{parse_query, set_aliases, set_layout, set_library, ASSUME_YES, NON_INTERACTIVE},
+    run,
+    tasks::ConfigTask,
+};
+pub use crate::{
+    cli_config::CliConfig,
+    commands::{BlenderArgs, FetchPolicy, FetchSummary, PullOptions},
+    errs::CommandError,
+    events::Event,
+    prompt::Prompter,
+    resolving::VariantPick,
+};
+
+/// Receives the events of the commands a session runs, like downloads starting and builds being installed.
+pub trait Progress: Send + Sync {
+    fn event(&self, event: &Event);
+}
+
+/// The configs that commands run with. Changes commands make to them, like the time builds were
+/// last fetched, are kept in the session and only written to disk by [`Session::save`].
+pub struct Session {
+    pub cfg: BLRSConfig,
+    pub cli_cfg: CliConfig,
+}
+
+/// The settings of the first session, which every later session has to match.
+static SETTINGS: OnceLock<serde_json::Value> = OnceLock::new();
+
+/// The parts of the configs that are kept for the whole process once a session is built.
+fn process_settings(cfg: &BLRSConfig, cli_cfg: &CliConfig) -> serde_json::Value {
+    serde_json::json!({
+        "paths": cfg.paths,
+        "repos": cfg.repos,
+        "library_layout": cli_cfg.library_layout,
+        "hooks": cli_cfg.hooks,
+        "aliases": cli_cfg.aliases,
+        "proxy": cli_cfg.network.proxy,
+        "deletion": cli_cfg.deletion,
+    })
+}
+
+impl Session {
+    /// Fails if another session of this process was built with a different library, layout, hooks,
+    /// aliases, proxy, or deletion policy, as those cannot change once set.
+    pub fn new(cfg: BLRSConfig, cli_cfg: CliConfig) -> Result<Self, CommandError> {
+        let settings = process_settings(&cfg, &cli_cfg);
+        if *SETTINGS.get_or_init(|| settings.clone()) != settings {
+            return Err(CommandError::InvalidConfig(
+                "another session of this process uses a different library, layout, hooks, aliases, proxy, \
+                 or deletion policy"
+                    .to_string(),
+            ));
+        }
+
+        cancel::leave_ctrl_c_to_caller();
+        NON_INTERACTIVE.store(true, Ordering::Release);
+        set_policy(cli_cfg.deletion.policy);
+        set_proxy(match &cli_cfg.network.proxy {
+            Some(proxy) => ProxySetting::Url(proxy.clone()),
+            None => ProxySetting::System,
+        });
+        set_aliases(cli_cfg.aliases.clone());
+        set_hooks(cli_cfg.hooks.clone());
+        set_layout(cli_cfg.library_layout.clone());
+        set_library(&cfg);
+
+        Ok(Self { cfg, cli_cfg })
+    }
+
+    /// Reads the configs from where the binary reads them by default.
+    pub fn load() -> Result<Self, CommandError> {
+        let cfg = BLRSConfig::default_figment(None)
+            .extract()
+            .map_err(|e| CommandError::InvalidConfig(e.to_string()))?;
+        let cli_cfg = CliConfig::load().map_err(|e| {
+            CommandError::IoError(IoErrorOrigin::ReadingObject(CliConfig::path()), e)
+        })?;
+
+        Self::new(cfg, cli_cfg)
+    }
+
+    /// Asks `prompter` whenever a command would prompt, instead of choosing like `--non-interactive`.
+    pub fn with_prompter(self, prompter: impl Prompter + 'static) -> Self {
+        set_prompter(prompter);
+        NON_INTERACTIVE.store(false, Ordering::Release);
+        self
+    }
+
+    pub fn with_progress(self, progress: impl Progress + 'static) -> Self {
+        set_listener(move |event| progress.event(event));
+        self
+    }
+
+    /// Answers yes to every confirmation, like `--yes`. Without a prompter, `rm` needs this.
+    pub fn with_assume_yes(self) -> Self {
+        ASSUME_YES.store(true, Ordering::Release);
+        self
+    }
+
+    /// Writes the configs to their files.
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        crate::save_config(&self.cfg, &self.cli_cfg)
+    }
+
+    fn apply(&mut self, tasks: Vec<ConfigTask>) {
+        tasks
+            .into_iter()
+            .for_each(|task| task.eval(&mut self.cfg, &mut self.cli_cfg));
+    }
+
+    /// Fetches the build lists of the given repos, or of every repo if none are given.
+    /// Unlike `blrs fetch`, this does not wait for the fetch interval to pass.
+    pub fn fetch(&mut self, repos: &[String]) -> Result<Vec<FetchSummary>, CommandError> {
+        let selected = select_repos(&self.cfg, repos)?;
+
+        let rt = runtime();
+        let (task, summaries) = rt.block_on(within_deadline(async {
+            fetch(
+                &self.cfg,
+                &self.cli_cfg,
+                &selected,
+                true,
+                false,
+                StalePolicy::default(),
+                false,
+            )
+            .await
+            .map_err(fetch_error)
+        }))?;
+        if repos.is_empty() {
+            self.apply(vec![task]);
+        }
+
+        Ok(summaries)
+    }
+
+    /// The repos with their installed and cached builds for this platform, sorted by version.
+    pub fn ls(&self, installed_only: bool) -> Result<Vec<RepoEntry>, CommandError> {
+        gather_and_filter_repos(
+            &self.cfg,
+            installed_only,
+            false,
+            Some(SortFormat::Version),
+            &Window::default(),
+        )
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))
+    }
+
+    /// Downloads and installs the builds matching the queries.
+    pub fn pull(&mut self, queries: &[&str], opts: &PullOptions) -> Result<(), CommandError> {
+        let queries = parse_queries(queries)?;
+
+        let rt = runtime();
+        let tasks = rt.block_on(within_deadline(pull_builds(
+            &self.cfg,
+            &self.cli_cfg,
+            queries,
+            opts,
+        )))?;
+        if !opts.dry_run {
+            sync_shortcuts(&self.cfg, &self.cli_cfg);
+        }
+        self.apply(tasks);
+
+        Ok(())
+    }
+
+    /// Removes the installed builds matching the queries. With `all_matching`, every match is removed
+    /// without asking which ones.
+    pub fn rm(&self, queries: &[&str], all_matching: bool) -> Result<(), CommandError> {
+        remove_builds(&self.cfg, parse_queries(queries)?, all_matching)?;
+        sync_shortcuts(&self.cfg, &self.cli_cfg);
+
+        Ok(())
+    }
+
+    /// Launches the build matching `query` and waits for it to exit.
+    pub fn run(&self, query: &str, blender: BlenderArgs) -> Result<(), CommandError> {
+        let cmd = RunCommand::Build {
+            build_or_file: Some(query.to_string()),
+            open_last: false,
+            isolation: Default::default(),
+            profile: vec![],
+            blender,
+        };
+
+        run::run(
+            &self.cfg,
+            &self.cli_cfg,
+            cmd,
+            &LaunchOutputArgs::default(),
+            false,
+        )
+        .map(|_| ())
+    }
+}
+
+fn parse_queries(queries: &[&str]) -> Result<Vec<blrs::search::VersionSearchQuery>, CommandError> {
+    if queries.is_empty() {
+        return Err(CommandError::MissingQuery);
+    }
+    queries.iter().map(|s| parse_query(s)).collect()
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .enable_io()
+        .build()
+        .expect("failed to create runtime")
+}
@@ -0,0 +1,161 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::dirs;
+
+/// How many hex digits of the URL's hash lead the name of a cached archive.
+const KEY_LEN: usize = 16;
+
+/// Downloaded archives are kept in one folder shared by every repo, so pulling a build again,
+/// or from another repo listing the same file, does not download it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveCacheConfig {
+    /// Keep archives after their build is extracted. Without it, they are removed after every pull.
+    pub enabled: bool,
+    /// The most the cache may hold, like `10G`. The least recently used archives are removed first.
+    pub max_size: String,
+}
+
+impl Default for ArchiveCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size: "10G".to_string(),
+        }
+    }
+}
+
+/// Parses a size like `500M`, `10G` or `1.5GB` into bytes. Suffixes are powers of 1024.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    let trimmed = trimmed.strip_suffix(['B', 'b']).unwrap_or(trimmed);
+
+    let (number, multiplier) = match trimmed.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let multiplier: u64 = match c.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => return Err(format!["unknown unit {:?} in {:?}", c, s]),
+            };
+            (&trimmed[..i], multiplier)
+        }
+        _ => (trimmed, 1),
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(number) if number >= 0.0 => Ok((number * multiplier as f64) as u64),
+        _ => Err(format!["expected a size like 10G, got {:?}", s]),
+    }
+}
+
+/// Where the archive downloaded from `url` is kept. The name starts with a hash of the URL
+/// and the time the repo lists the file with, so files with the same name from different hosts
+/// do not collide, and a file rebuilt at the same URL is not mistaken for the cached one.
+pub fn path_for(url: &Url, listed: &DateTime<Utc>, file_name: &Path) -> PathBuf {
+    let key = format!["{}\n{}", url, listed.timestamp()];
+    let hash = format!["{:x}", Sha256::digest(key.as_bytes())];
+    let mut name = hash[..KEY_LEN].to_string();
+    name.push('-');
+    name.push_str(&file_name.to_string_lossy());
+
+    dirs::archive_dir().join(name)
+}
+
+/// Whether a file was put in the folder by the cache, as `--download-dir` may point somewhere shared.
+fn is_cached_archive(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+
+    !name.ends_with(".part")
+        && name.len() > KEY_LEN
+        && name.as_bytes()[KEY_LEN] == b'-'
+        && name[..KEY_LEN].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Marks an archive as just used, so it is among the last to be evicted.
+pub fn touch(path: &Path) {
+    if let Err(e) = File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(SystemTime::now()))
+    {
+        debug!["Failed to update the time of {}: {}", path.display(), e];
+    }
+}
+
+/// The cached archives with their sizes, least recently used first.
+pub fn archives() -> Vec<(PathBuf, u64)> {
+    let Ok(items) = dirs::archive_dir().read_dir() else {
+        return vec![];
+    };
+
+    let mut archives: Vec<_> = items
+        .flatten()
+        .filter(|item| is_cached_archive(&item.path()))
+        .filter_map(|item| {
+            let m = item.metadata().ok().filter(|m| m.is_file())?;
+            Some((item.path(), m.len(), m.modified().ok()?))
+        })
+        .collect();
+    archives.sort_by_key(|(_, _, modified)| *modified);
+
+    archives
+        .into_iter()
+        .map(|(path, size, _)| (path, size))
+        .collect()
+}
+
+/// Removes the least recently used archives until the cache holds at most `max_size` bytes.
+/// Partial downloads are left alone.
+pub fn trim(max_size: u64) {
+    let archives = archives();
+    let mut total: u64 = archives.iter().map(|(_, size)| size).sum();
+    let mut evicted = 0;
+    for (path, size) in archives {
+        if total <= max_size {
+            break;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(_) => {
+                total -= size;
+                evicted += 1;
+            }
+            Err(e) => warn![
+                "Failed to remove the cached archive {}: {}",
+                path.display(),
+                e
+            ],
+        }
+    }
+
+    if evicted > 0 {
+        debug!["Removed {} archive(s) from the cache", evicted];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_parsed() {
+        assert_eq!(parse_size("10G"), Ok(10 << 30));
+        assert_eq!(parse_size("1.5 MB"), Ok(3 << 19));
+        assert_eq!(parse_size("0"), Ok(0));
+        assert!(parse_size("10G/s").is_err());
+        assert!(parse_size("-1K").is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use log::warn;
+
+use crate::errs::{exit_codes, CommandError};
+
+/// Set by the first Ctrl+C. Long-running work checks it between steps and stops with `Cancelled`.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Set when the `--deadline` passes, so work left running on blocking threads stops as well.
+static EXPIRED: AtomicBool = AtomicBool::new(false);
+
+/// Cleared when blrs is embedded through `api`, as the app it runs in handles its own signals.
+static HANDLES_CTRL_C: AtomicBool = AtomicBool::new(true);
+
+/// How often `cancelled` looks at the flag.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Makes Ctrl+C cancel the running command instead of killing blrs, so it can clean up after itself.
+/// A second Ctrl+C exits right away, for anything that does not stop in time.
+pub fn handle_ctrl_c() {
+    if !HANDLES_CTRL_C.load(Ordering::Acquire) {
+        return;
+    }
+    let _ = ctrlc::set_handler(|| {
+        if CANCELLED.swap(true, Ordering::AcqRel) {
+            std::process::exit(exit_codes::CANCELLED);
+        }
+        warn!["Cancelling, press Ctrl+C again to quit immediately"];
+    });
+}
+
+/// Keeps commands from installing the Ctrl+C handler, which would exit the whole process on a second Ctrl+C.
+pub fn leave_ctrl_c_to_caller() {
+    HANDLES_CTRL_C.store(false, Ordering::Release);
+}
+
+/// Stops the work still running once the command has given up at its deadline.
+pub fn expire() {
+    EXPIRED.store(true, Ordering::Release);
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::Acquire) || EXPIRED.load(Ordering::Acquire)
+}
+
+/// Fails with `Cancelled` once Ctrl+C has been pressed, or `TimedOut` once the deadline has passed.
+pub fn check() -> Result<(), CommandError> {
+    if EXPIRED.load(Ordering::Acquire) {
+        return Err(CommandError::TimedOut(
+            "the command did not finish before its deadline".to_string(),
+        ));
+    }
+    match is_cancelled() {
+        true => Err(CommandError::Cancelled),
+        false => Ok(()),
+    }
+}
+
+/// Resolves once Ctrl+C has been pressed.
+pub async fn cancelled() {
+    while !is_cancelled() {
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Runs a future until it finishes or Ctrl+C is pressed, in which case it is dropped.
+pub async fn or_cancelled<T>(
+    f: impl Future<Output = Result<T, CommandError>>,
+) -> Result<T, CommandError> {
+    tokio::select! {
+        result = f => result,
+        _ = cancelled() => Err(CommandError::Cancelled),
+    }
+}
@@ -1,14 +1,42 @@
+use std::ffi::OsString;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use blrs::config::BLRSConfig;
 
-use clap::{arg, Parser};
+use clap::{arg, parser::ValueSource, ArgAction, CommandFactory, FromArgMatches, Parser};
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 
-use crate::{commands::Command, errs::CommandError, tasks::ConfigTask};
+use crate::{
+    cli_config::CliConfig,
+    commands::Command,
+    deletion::{set_policy, DeletionPolicy},
+    dirs::portable_root,
+    errs::CommandError,
+    events::{OutputFormat, JSON_OUTPUT},
+    hooks::set_hooks,
+    network::{set_proxy, set_timeouts, ProxySetting},
+    notify::NOTIFY,
+    resolving::{parse_layout, set_aliases, set_layout, ASSUME_YES, NON_INTERACTIVE},
+    tasks::ConfigTask,
+};
+
+/// Shown at the end of `--help`. Keep in sync with `errs::exit_codes`.
+const EXIT_CODES: &str = "Exit codes:
+  0    Success
+  1    Failure, including IO errors
+  2    Invalid command line or config
+  3    No build matches a query, or choosing one needed a prompt
+  4    A request failed
+  5    An archive could not be extracted
+  6    Builds failed verification or a checksum did not match
+  124  A request stalled past --timeout, or the command ran past --deadline
+  130  Cancelled";
 
 #[derive(Parser, Debug, Clone, Serialize, Deserialize)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, after_long_help = EXIT_CODES)]
 pub struct Cli {
     /// Alias of blrs-cli launch.
     pub build_or_file: Option<String>,
@@ -17,18 +45,288 @@ pub struct Cli {
     pub commands: Option<Command>,
 
     /// Override the path to the library.
-    #[arg(short, long)]
+    #[arg(short, long, env = "BLRS_LIBRARY")]
     pub library: Option<PathBuf>,
+
+    /// Override the folder that `config.toml` and `cli.toml` are read from and saved to.
+    #[arg(long, env = "BLRS_CONFIG_DIR")]
+    pub config_dir: Option<PathBuf>,
+
+    /// Override the folder that fetched build lists are cached in.
+    #[arg(long, env = "BLRS_REMOTE_CACHE_DIR")]
+    pub remote_cache_dir: Option<PathBuf>,
+
+    /// Download and cache archives in this folder instead of the cache folder.
+    #[arg(long, env = "BLRS_DOWNLOAD_DIR")]
+    pub download_dir: Option<PathBuf>,
+
+    /// How the folders of newly installed builds are named inside their repo's folder, like
+    /// `{version}+{short_hash}` or `{branch}/{version}`. Overrides `cli.library_layout`.
+    /// Placeholders: {repo}, {version}, {major}, {minor}, {patch}, {branch}, {hash}, {short_hash}, {target}.
+    #[arg(long, env = "BLRS_LIBRARY_LAYOUT", value_parser = parse_layout)]
+    pub library_layout: Option<String>,
+
+    /// Keep the config, the build list cache, and the library next to the blrs executable,
+    /// so they can be carried between machines. Also turned on by a `portable.marker` file there.
+    /// The other path overrides still take precedence.
+    #[arg(long, env = "BLRS_PORTABLE")]
+    pub portable: bool,
+
+    /// Never prompt. Ambiguous matches resolve to the newest build, or fail if there is no clear choice.
+    #[arg(long, global = true)]
+    pub non_interactive: bool,
+
+    /// Answer yes to every confirmation.
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
+    /// How pull, rm, fetch, and verify report their progress on stdout.
+    /// With json, a failure is written to stderr as one JSON object with its kind, message, paths, and exit code.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub output: OutputFormat,
+
+    /// Show desktop notifications when pulls finish and watch finds new builds.
+    /// Can be turned on permanently with `blrs config set cli.notify true`.
+    #[arg(long, global = true)]
+    pub notify: bool,
+
+    /// Sends every request through this proxy, e.g. `http://proxy:8080` or `socks5://localhost:1080`.
+    /// Overrides `cli.network.proxy` and the proxy from the environment.
+    #[arg(long, global = true)]
+    pub proxy: Option<String>,
+
+    /// Connects directly, ignoring any configured proxy.
+    #[arg(long, global = true, conflicts_with = "proxy")]
+    pub no_proxy: bool,
+
+    /// Fails a request if connecting or receiving data stalls for this long, e.g. "30s".
+    /// Downloads are retried like other network errors.
+    #[arg(long, global = true, value_parser = humantime::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Gives up on fetch, pull, and upgrade if they are still running after this long, e.g. "10m".
+    #[arg(long, global = true, value_parser = humantime::parse_duration)]
+    pub deadline: Option<Duration>,
+
+    /// Also writes progress events as JSON lines to this Unix socket, FIFO, or named pipe,
+    /// while still showing the normal progress bars. Meant for GUI frontends.
+    #[arg(long, global = true)]
+    pub progress_ipc: Option<PathBuf>,
+
+    /// Deletes removed builds and files permanently instead of sending them to the trash.
+    /// Overrides `cli.deletion.policy`.
+    #[arg(long, global = true)]
+    pub no_trash: bool,
+}
+
+/// Turns a configured default into the command line arguments that set it.
+fn default_args(arg: &clap::Arg, long: &str, value: &toml::Value) -> Vec<OsString> {
+    let values = match value {
+        toml::Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+    };
+
+    values
+        .into_iter()
+        .flat_map(|value| match (arg.get_action(), value) {
+            (ArgAction::SetTrue, toml::Value::Boolean(true)) => vec![format!["--{}", long]],
+            (ArgAction::SetTrue, _) => vec![],
+            (ArgAction::Count, toml::Value::Integer(n)) => {
+                vec![format!["--{}", long]; (*n).max(0) as usize]
+            }
+            (_, toml::Value::String(s)) => vec![format!["--{}={}", long, s]],
+            (_, value) => vec![format!["--{}={}", long, value]],
+        })
+        .map(OsString::from)
+        .collect()
+}
+
+/// Flags that decide where `cli.toml` is read from, so they cannot be given defaults in it.
+const UNDEFAULTABLE: [&str; 2] = ["config_dir", "portable"];
+
+/// The id of the hidden `--no-<flag>` that turns off a flag given by `cli.defaults`.
+fn negation_id(id: &str) -> String {
+    format!["negated_{}", id]
+}
+
+/// Adds a hidden `--no-<flag>` for every flag of `command`, or `--<flag>` for flags starting with `no-`,
+/// unless the command already has a flag of that name.
+fn add_negations(command: clap::Command, globals: bool) -> clap::Command {
+    let negations: Vec<clap::Arg> = command
+        .get_arguments()
+        .filter(|arg| matches!(arg.get_action(), ArgAction::SetTrue))
+        .filter(|arg| arg.is_global_set() == globals)
+        .filter_map(|arg| {
+            let long = arg.get_long()?;
+            let negated = match long.strip_prefix("no-") {
+                Some(long) => long.to_string(),
+                None => format!["no-{}", long],
+            };
+            let taken = command
+                .get_arguments()
+                .any(|a| a.get_long() == Some(negated.as_str()));
+            (!taken).then(|| {
+                clap::Arg::new(negation_id(arg.get_id().as_str()))
+                    .long(negated)
+                    .action(ArgAction::SetTrue)
+                    .global(globals)
+                    .hide(true)
+            })
+        })
+        .collect();
+
+    command.args(negations)
 }
 
 impl Cli {
+    /// The command line, with the hidden flags that turn off the flags `cli.defaults` turns on.
+    fn command_with_negations() -> clap::Command {
+        let command = add_negations(Cli::command(), true);
+        let names: Vec<String> = command
+            .get_subcommands()
+            .map(|sub| sub.get_name().to_string())
+            .collect();
+        names.into_iter().fold(command, |command, name| {
+            command.mut_subcommand(name, |sub| add_negations(sub, false))
+        })
+    }
+
+    /// Parses the command line the process was started with.
+    pub fn parse_args() -> Self {
+        Self::parse_args_from(std::env::args_os())
+    }
+
+    fn parse_args_from(args: impl IntoIterator<Item = impl Into<OsString> + Clone>) -> Self {
+        let matches = Self::command_with_negations().get_matches_from(args);
+        Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Re-parses the command line with the defaults of `cli.defaults` for the chosen command,
+    /// like `[defaults.ls] sort_by = "datetime"`. Flags given on the command line win over them.
+    ///
+    /// Only the flags of top level commands, including the global flags, can be given defaults, except the
+    /// ones deciding where the config is read from. A flag turned on by a default is turned off by `--no-<flag>`.
+    pub fn apply_config_defaults(&mut self, cli_cfg: &CliConfig) -> Result<(), CommandError> {
+        let mut command = Self::command_with_negations();
+        command.build();
+        let mut args: Vec<OsString> = std::env::args_os().collect();
+        let Ok(matches) = command.clone().try_get_matches_from(&args) else {
+            return Ok(());
+        };
+        let Some((name, sub_matches)) = matches.subcommand() else {
+            return Ok(());
+        };
+        let (Some(defaults), Some(subcommand)) =
+            (cli_cfg.defaults.get(name), command.find_subcommand(name))
+        else {
+            return Ok(());
+        };
+
+        let mut extra = vec![];
+        for (key, value) in defaults {
+            if UNDEFAULTABLE.contains(&key.as_str()) {
+                return Err(CommandError::InvalidConfig(format![
+                    "defaults.{}.{} cannot be set, as it decides where the config is read from",
+                    name, key
+                ]));
+            }
+            let Some(arg) = subcommand.get_arguments().find(|a| a.get_id() == key) else {
+                warn![
+                    "Ignoring the default {}.{}, as {} has no such flag",
+                    name, key, name
+                ];
+                continue;
+            };
+            let Some(long) = arg.get_long() else {
+                warn![
+                    "Ignoring the default {}.{}, as only flags can have defaults",
+                    name, key
+                ];
+                continue;
+            };
+            let negated = sub_matches
+                .try_get_one::<bool>(&negation_id(key))
+                .is_ok_and(|flag| flag == Some(&true));
+            if negated
+                || matches!(
+                    sub_matches.value_source(key),
+                    Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+                )
+            {
+                continue;
+            }
+            extra.extend(default_args(arg, long, value));
+        }
+        if extra.is_empty() {
+            return Ok(());
+        }
+        debug!["Adding the configured defaults {:?}", extra];
+
+        // Anything after `--` is not parsed as flags
+        let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+        args.splice(end..end, extra);
+        *self = Self::parse_args_from(args);
+        self.apply_portable();
+        Ok(())
+    }
+
+    /// Fills in the path overrides that were not given with folders next to the executable, in portable mode.
+    pub fn apply_portable(&mut self) {
+        let Some(root) = portable_root(self.portable) else {
+            return;
+        };
+        debug!["Running in portable mode from {}", root.display()];
+
+        self.config_dir.get_or_insert_with(|| root.join("config"));
+        self.remote_cache_dir
+            .get_or_insert_with(|| root.join("remote-repos"));
+        self.library.get_or_insert_with(|| root.join("library"));
+        self.download_dir
+            .get_or_insert_with(|| root.join("archives"));
+    }
+
     pub fn apply_overrides(&self, config: &mut BLRSConfig) {
         if let Some(pth) = &self.library {
             config.paths.library = pth.clone()
         }
+        if let Some(pth) = &self.remote_cache_dir {
+            config.paths.remote_repos = pth.clone()
+        }
+    }
+
+    /// Applies flags that change how every command prompts and reports.
+    pub fn apply_global_flags(&self, cli_cfg: &CliConfig) {
+        NON_INTERACTIVE.store(self.non_interactive, Ordering::Release);
+        ASSUME_YES.store(self.yes, Ordering::Release);
+        JSON_OUTPUT.store(self.output == OutputFormat::Json, Ordering::Release);
+        NOTIFY.store(self.notify || cli_cfg.notify, Ordering::Release);
+        let no_trash = self.no_trash || self.commands.as_ref().is_some_and(Command::no_trash);
+        set_policy(match no_trash {
+            true => DeletionPolicy::Delete,
+            false => cli_cfg.deletion.policy,
+        });
+        set_proxy(match (self.no_proxy, &self.proxy, &cli_cfg.network.proxy) {
+            (true, _, _) => ProxySetting::Disabled,
+            (false, Some(proxy), _) | (false, None, Some(proxy)) => {
+                ProxySetting::Url(proxy.clone())
+            }
+            (false, None, None) => ProxySetting::System,
+        });
+        set_timeouts(self.timeout, self.deadline);
+        set_aliases(cli_cfg.aliases.clone());
+        set_hooks(cli_cfg.hooks.clone());
+        set_layout(
+            self.library_layout
+                .clone()
+                .or_else(|| cli_cfg.library_layout.clone()),
+        );
     }
 
-    pub fn eval(self, cfg: &BLRSConfig) -> Result<Vec<ConfigTask>, CommandError> {
-        self.commands.unwrap().eval(cfg)
+    pub fn eval(
+        self,
+        cfg: &BLRSConfig,
+        cli_cfg: &CliConfig,
+    ) -> Result<Vec<ConfigTask>, CommandError> {
+        self.commands.unwrap().eval(cfg, cli_cfg)
     }
 }
@@ -0,0 +1,115 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use blrs::config::PROJECT_DIRS;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive_cache::ArchiveCacheConfig,
+    commands::IndexTemplate,
+    deletion::DeletionConfig,
+    dirs,
+    hooks::Hooks,
+    network::NetworkConfig,
+    repo_auth::RepoAuth,
+    resolving::VariantPreference,
+    run::{FallbackPolicy, LaunchProfile},
+};
+
+/// Settings that only concern blrs-cli. These live next to the blrs config in their own file,
+/// so the library's config format does not need to know about them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CliConfig {
+    /// The query of the build that is launched when `run build` is not given one.
+    pub default_build: Option<String>,
+    /// Which build to use when a blendfile's version is not installed, unless `--prefer` is given.
+    pub file_fallback: FallbackPolicy,
+    /// Extra arguments passed to specific builds when they are launched, keyed by the build's query.
+    pub build_args: HashMap<String, Vec<String>>,
+    /// The folder that per-build isolated user folders are created in by `run build --isolated`.
+    pub isolation_root: Option<PathBuf>,
+    /// Globs that release assets of GitHub repos must match to be listed, keyed by the repo's id.
+    pub asset_patterns: HashMap<String, String>,
+    /// How the listings of repos registered with `repos add-index` are parsed, keyed by the repo's id.
+    pub index_repos: HashMap<String, IndexTemplate>,
+    /// Credentials sent to the hosts of specific repos, keyed by the repo's id.
+    pub repo_auth: HashMap<String, RepoAuth>,
+    /// Always show desktop notifications, as if `--notify` was given.
+    pub notify: bool,
+    pub network: NetworkConfig,
+    /// The default download speed limit of `pull`, like `5M`.
+    pub limit_rate: Option<String>,
+    /// Which variant `pull` picks when several fit the platform, like `["arch=x64", "package=portable"]`.
+    pub variant_preferences: Vec<VariantPreference>,
+    /// URL templates of mirrors that are tried when a download fails, keyed by the repo's id.
+    /// `{path}` is replaced by the path of the original URL and `{file}` by its file name.
+    pub mirrors: HashMap<String, Vec<String>>,
+    /// Launch profiles picked with `run build --profile`, like `vulkan = ["--gpu-backend", "vulkan"]`,
+    /// or `bench = { args = [...], env = { ... } }`.
+    pub profiles: HashMap<String, LaunchProfile>,
+    /// Runs `shortcuts sync` after builds are pulled or removed, so the OS launcher always lists the installed builds.
+    pub sync_shortcuts: bool,
+    /// Names that can be used in place of a query, like `studio = "4.2.3-lts"`.
+    pub aliases: HashMap<String, String>,
+    /// Whether removed builds and files go to the trash, with `policy = "trash"`, `"delete"`, or `"ask"`.
+    pub deletion: DeletionConfig,
+    /// Commands run on build lifecycle events, like `post-pull = ["~/bin/install-addons"]`.
+    /// See `hooks::Hooks` for the events and the environment the commands get.
+    pub hooks: Hooks,
+    /// Addon folders and files linked into the `scripts/addons` folder of every pulled build, see `blrs addons link`.
+    pub addons: Vec<PathBuf>,
+    /// Flags that are used unless given on the command line, keyed by command and then by flag,
+    /// like `[defaults.ls] sort_by = "datetime"` or `[defaults.rm] no_trash = true`.
+    /// A flag turned on here is turned off with `--no-<flag>`, or `--<flag>` for flags like `--no-trash`.
+    pub defaults: HashMap<String, toml::Table>,
+    /// Whether downloaded archives are kept for pulling their builds again, and how much space they may take.
+    pub archive_cache: ArchiveCacheConfig,
+    /// Fetches a repo's build list again when its cached copy is corrupt, instead of failing.
+    pub refetch_corrupt: bool,
+    /// Records a manifest of every file of pulled, installed, and adopted builds, so `verify --deep` can tell
+    /// if they changed. Hashing every file takes a while for large builds, so it is off by default.
+    pub record_manifests: bool,
+    /// How the folders of installed builds are named inside their repo's folder, see `--library-layout`.
+    /// Builds that are already installed keep their folders, as they are found by their build info.
+    pub library_layout: Option<String>,
+}
+
+impl CliConfig {
+    pub fn path() -> PathBuf {
+        dirs::config_dir().join("cli.toml")
+    }
+
+    pub fn isolation_root(&self) -> PathBuf {
+        self.isolation_root
+            .clone()
+            .unwrap_or_else(|| PROJECT_DIRS.data_local_dir().join("isolated"))
+    }
+
+    /// Reads the config from disk. A missing file is treated as the default config.
+    pub fn load() -> Result<Self, std::io::Error> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = std::fs::read_to_string(&path)?;
+        toml::from_str(&data).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!["Failed to read {}: {}", path.display(), e],
+            )
+        })
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        std::fs::create_dir_all(dirs::config_dir())?;
+
+        let data = toml::to_string_pretty(self).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!["Failed to save cli config data: {:?}", e],
+            )
+        })?;
+        std::fs::write(Self::path(), data)
+    }
+}
@@ -1,27 +1,81 @@
-use std::path::PathBuf;
-
-use blrs::{
-    config::FETCH_INTERVAL, fetching::authentication::GithubAuthentication,
-    search::VersionSearchQuery, BLRSConfig,
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
 };
-use chrono::Utc;
-use clap::Subcommand;
-use log::{debug, info};
-use ls::LsFormat;
+
+use blrs::{config::FETCH_INTERVAL, search::VersionSearchQuery, BLRSConfig};
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand};
+use clean::CleanTarget;
+pub use config::config_path;
+use config::BuildSettings;
+pub use fetcher::FetchSummary;
+pub(crate) use fetcher::{fetch, fetch_error, select_repos, StalePolicy};
+pub use index_repo::IndexTemplate;
+use log::{debug, info, warn};
+pub(crate) use ls::gather_and_filter_repos;
+use ls::{LsColumn, LsFormat, LsOptions};
+use prune::PrunePolicy;
+pub(crate) use pull::pull_builds;
+pub use pull::{FetchPolicy, PullOptions};
+pub(crate) use rm::remove_builds;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    errs::{CommandError, IoErrorOrigin},
+    cancel,
+    cli_config::CliConfig,
+    errs::CommandError,
+    events::json_output,
+    network::within_deadline,
+    pin::pinned_build,
     repo_formatting::SortFormat,
-    run,
+    resolving::{parse_query, VariantPick, VariantPreference},
+    run::{self, FallbackPolicy},
+    target,
     tasks::ConfigTask,
 };
 
+mod addons;
+mod adopt;
+mod alias;
+mod auth;
+mod bench;
+mod clean;
+mod config;
+mod current;
+mod default;
+mod diff;
+mod doctor;
+mod favorite;
 mod fetcher;
+mod handler;
+mod history;
+mod http_cache;
+mod index_repo;
+mod info;
+mod install;
+mod listing;
 mod ls;
+mod manifest;
+mod net_failure;
+mod pin;
+mod prune;
 mod pull;
+mod rate_limit;
+mod recent;
+mod render;
+mod repos;
 mod rm;
+mod scheduler;
+mod search;
+mod self_update;
+mod shortcuts;
+mod tui;
+mod upgrade;
+mod variants;
 mod verify;
+mod watch;
+mod which;
 
 #[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
 pub enum Command {
@@ -39,11 +93,53 @@ pub enum Command {
         /// The return code of the program reflects the very first error that occurs.
         #[arg(short, long)]
         ignore_errors: bool,
+
+        /// Only fetches the repo with this nickname or id. Can be given multiple times.
+        /// Fetching only some repos does not reset the fetch interval.
+        #[arg(long = "repo", value_name = "REPO")]
+        repos: Vec<String>,
+
+        /// Reconciles the cached build lists even if the repos report no change, dropping the builds they
+        /// no longer list except installed ones. Fetching does this whenever a listing changed.
+        #[arg(long, conflicts_with = "keep_stale")]
+        prune_cache: bool,
+
+        /// Keeps every cached build the repos no longer list, instead of only the installed ones.
+        #[arg(long)]
+        keep_stale: bool,
+
+        /// Sleeps until GitHub's API rate limit resets when it is used up, instead of failing.
+        #[arg(long)]
+        wait_for_ratelimit: bool,
     },
 
     /// Verifies that all the builds available to blrs has the required information. If one does not,
-    /// we will run the build and gather data from it to generate the information we need
-    Verify { repos: Option<Vec<String>> },
+    /// we will run the build and gather data from it to generate the information we need.
+    ///
+    /// Also checks that every build's executable exists, and with --launch, that it reports the recorded version.
+    /// Exits with a nonzero code if any build could not be repaired.
+    Verify {
+        repos: Option<Vec<String>>,
+
+        /// Also re-hash every file and compare them to the manifest recorded when the build was pulled,
+        /// reporting modified, missing, and extra files. Manifests are only recorded by `pull --record-manifest`,
+        /// or with the `cli.record_manifests` setting.
+        #[arg(long)]
+        deep: bool,
+
+        /// Also runs every build's executable to check that it reports the recorded version,
+        /// fixing the build info if it does not.
+        #[arg(long)]
+        launch: bool,
+
+        /// The number of builds probed at the same time.
+        #[arg(short, long, default_value_t = 4)]
+        jobs: usize,
+
+        /// Counts a build as broken if its executable does not report its version within this long, e.g. "30s".
+        #[arg(long, value_parser = humantime::parse_duration)]
+        timeout_per_build: Option<Duration>,
+    },
 
     /// Download a build from the saved database
     Pull {
@@ -52,19 +148,217 @@ pub enum Command {
 
         #[arg(short, long)]
         all_platforms: bool,
+
+        /// The number of connections used to download each build. Values above 1 split the
+        /// download into ranges that are fetched concurrently, if the server supports it.
+        #[arg(short, long, default_value_t = 1)]
+        connections: usize,
+
+        /// The number of builds downloaded at the same time.
+        #[arg(short, long, default_value_t = 3)]
+        jobs: usize,
+
+        /// The minimum time in milliseconds between requests to the same host.
+        #[arg(long, default_value_t = 500)]
+        host_delay: u64,
+
+        /// How many times a download is retried when the host responds with "429 Too Many Requests",
+        /// a server error, or the connection drops.
+        #[arg(long, default_value_t = 3)]
+        retries: u32,
+
+        /// Always refresh the build lists before pulling. By default they are only refreshed when they are missing or stale.
+        #[arg(long, conflicts_with = "no_fetch")]
+        fetch: bool,
+
+        /// Never refresh the build lists before pulling, even when they are missing or stale.
+        #[arg(long)]
+        no_fetch: bool,
+
+        /// Extract tarballs while they download instead of saving the archive first.
+        /// Other archive formats are downloaded normally.
+        #[arg(long, conflicts_with = "connections")]
+        stream: bool,
+
+        /// Resolves the queries against the cached build lists and prints the builds, sizes,
+        /// and destinations that would be downloaded, without fetching or downloading anything.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Limits the combined download speed, e.g. "500K" or "5M" per second.
+        /// Defaults to the `cli.limit_rate` setting.
+        #[arg(long, value_parser = scheduler::parse_rate)]
+        limit_rate: Option<u64>,
+
+        /// Picks between variants that fit the platform instead of asking, e.g. "arch=x64" or "package=portable".
+        /// Can be given multiple times, earlier preferences win. Defaults to the `cli.variant_preferences` setting.
+        /// Keys: arch, platform, libc, package (portable or installer), ext.
+        #[arg(long, value_name = "KEY=VALUE")]
+        prefer_variant: Vec<VariantPreference>,
+
+        /// Downloads the variant with this target, e.g. "windows-x64", instead of choosing one.
+        /// Builds without it are skipped. Implies --all-platforms.
+        #[arg(long, value_name = "TARGET", conflicts_with = "filename_glob")]
+        variant: Option<String>,
+
+        /// Downloads the variant whose file name matches this glob, e.g. "*linux*.tar.xz", instead of choosing one.
+        /// Builds without one are skipped. Implies --all-platforms.
+        #[arg(long, value_parser = search::validate_glob)]
+        filename_glob: Option<String>,
+
+        /// Pulls even if the library does not seem to have enough free space for the archives and extracted builds.
+        /// Implies --reinstall.
+        #[arg(long)]
+        force: bool,
+
+        /// Pulls builds that are already installed again, replacing them. They are skipped otherwise.
+        #[arg(long)]
+        reinstall: bool,
+
+        /// Hashes every extracted file into a manifest, so `verify --deep` can tell if the build changes later.
+        /// Defaults to the `cli.record_manifests` setting.
+        #[arg(long)]
+        record_manifest: bool,
+
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+
+    /// Pulls the newest build of every installed branch and series that has a newer one available.
+    Upgrade {
+        /// Only upgrade installed builds matching these queries. Every installed build is considered if none are given.
+        queries: Vec<String>,
+
+        /// Removes the builds that were superseded once the upgrades are installed. Favorited builds are kept.
+        #[arg(long)]
+        remove_old: bool,
+
+        /// Only print the upgrades that are available.
+        #[arg(short, long)]
+        dry_run: bool,
+    },
+
+    /// Installs a build from an archive or folder on disk, like an official download or your own compile
+    Install {
+        /// The archive or build folder. The Blender executable must be at the top of the build.
+        path: PathBuf,
+
+        /// The repo the build is listed under. Defaults to "local".
+        #[arg(long)]
+        repo: Option<String>,
+
+        /// A custom name for the build.
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Finds Blender builds in folders outside the library, like one from before blrs, and moves them into it
+    Adopt {
+        /// The folders to search. Builds up to 4 folders deep are found.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// The repo the builds are listed under. Defaults to "local".
+        #[arg(long)]
+        repo: Option<String>,
+
+        /// Links the builds into the library instead of moving them, so they stay where they are.
+        #[arg(long)]
+        link: bool,
+
+        /// Prints where every build would go without moving anything.
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Tries to send a specified build to the trash.
     Rm {
         queries: Vec<String>,
 
-        /// Tries to fully delete a file, and does not send the file to the trash
+        /// Removes every build matching the queries instead of asking which ones to remove.
+        /// Asks for confirmation once unless --yes is given.
+        #[arg(long)]
+        all_matching: bool,
+
+        /// Deletes the cached build list and leftover downloads of this repo instead of builds,
+        /// so it is fetched from scratch. Installed builds are kept.
+        #[arg(long, value_name = "REPO", conflicts_with = "queries")]
+        cache: Option<String>,
+
+        /// Removes this installed build folder without reading its build info, for builds too broken
+        /// to match by a query. Can be given multiple times.
+        #[arg(long, value_name = "FOLDER", conflicts_with_all = ["queries", "cache", "repo"])]
+        path: Vec<PathBuf>,
+
+        /// Removes every installed build of this repo. Requires --all.
+        #[arg(long, requires = "all", conflicts_with_all = ["queries", "cache"])]
+        repo: Option<String>,
+
+        /// Confirms that --repo removes all of the repo's builds.
+        #[arg(long, requires = "repo")]
+        all: bool,
+
+        /// Deletes the builds permanently instead of sending them to the trash, like --no-trash.
+        #[arg(short = 'n')]
+        delete: bool,
+    },
+
+    /// Removes old installed builds. Favorited builds are never pruned.
+    Prune {
+        /// Only consider builds matching these queries. All installed builds are considered if none are given.
+        queries: Vec<String>,
+
+        /// Remove builds whose commit is older than this many days.
+        #[arg(short, long)]
+        older_than: Option<i64>,
+
+        /// Keep only the newest N builds of every repo and branch.
+        #[arg(short, long)]
+        keep: Option<usize>,
+
+        /// Only print the builds that would be removed.
+        #[arg(short, long)]
+        dry_run: bool,
+
+        /// Deletes the builds permanently instead of sending them to the trash, like --no-trash.
+        #[arg(short = 'n')]
+        delete: bool,
+    },
+
+    /// Deletes caches and leftovers to reclaim space, reporting how much each target took.
+    /// Installed builds are kept, see `prune` and `rm` for removing those.
+    /// Without targets, deletes what cancelled or failed pulls and installs left in the library, like with --temp.
+    Clean {
+        /// Deletes the archives kept in the download cache.
+        #[arg(long)]
+        archives: bool,
+
+        /// Deletes the cached build lists. They are downloaded again by the next fetch or pull.
+        #[arg(long)]
+        repo_cache: bool,
+
+        /// Deletes the archives, partial downloads, and staging folders left behind by cancelled or failed pulls and installs.
+        #[arg(long)]
+        temp: bool,
+
+        /// Clears the history of launched builds.
+        #[arg(long)]
+        history: bool,
+
+        /// Deletes everything above.
+        #[arg(long)]
+        all: bool,
+
+        /// Only print the files that would be deleted.
         #[arg(short, long)]
-        no_trash: bool,
+        dry_run: bool,
     },
 
     /// Lists builds available to download and builds that are installed
     Ls {
+        /// Only lists builds matching this version matcher.
+        query: Option<String>,
+
         #[arg(short, long)]
         format: Option<LsFormat>,
 
@@ -75,6 +369,10 @@ pub enum Command {
         #[arg(short, long)]
         installed_only: bool,
 
+        /// Only show builds that are not installed.
+        #[arg(short, long, conflicts_with_all = ["installed_only", "favorites"])]
+        remote_only: bool,
+
         /// Show individual variants for remote builds.
         #[arg(short, long)]
         variants: bool,
@@ -82,6 +380,191 @@ pub enum Command {
         /// Shows all builds, even if they are not for your target os. Our filtering is not perfect. this may be necessary for you to find the proper build.
         #[arg(short, long)]
         all_builds: bool,
+
+        /// Only show installed builds that are marked as favorites.
+        #[arg(long)]
+        favorites: bool,
+
+        /// Shows how much disk space every installed build and repo uses.
+        #[arg(short, long)]
+        usage: bool,
+
+        /// The columns to show in the table and csv formats, separated by commas. Shows all columns by default.
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<LsColumn>,
+
+        /// Nests builds in the tree under their version series (4.2, 4.3) and branch.
+        #[arg(short, long)]
+        group: bool,
+
+        /// Only expands the groups containing builds that match this version matcher. Implies --group.
+        #[arg(long)]
+        expand: Option<String>,
+
+        /// Only lists the builds and repos that could not be read, with what went wrong and how to fix it.
+        /// Supports the tree, json, ndjson, and yaml formats.
+        #[arg(long, conflicts_with_all = ["remote_only", "favorites", "query"])]
+        problems: bool,
+
+        /// Only lists builds committed after this date, e.g. "2024-06-01", or within this long, e.g. "2w".
+        #[arg(long, value_parser = search::parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only lists the last N builds of each repo in the sort order, which are the newest ones by default.
+        #[arg(short = 'n', long, value_name = "N")]
+        limit: Option<usize>,
+
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+
+    /// Writes a manifest of the installed builds, so the same builds can be installed elsewhere with `import`
+    Export {
+        /// Where to write the manifest. Prints it if omitted.
+        path: Option<PathBuf>,
+    },
+
+    /// Pulls every build in a manifest made by `export` that is not installed yet
+    Import {
+        /// The manifest to read.
+        path: PathBuf,
+
+        #[arg(short, long)]
+        all_platforms: bool,
+    },
+
+    /// Makes blrs the program that opens .blend files, so they launch with the matching Blender version
+    RegisterHandler {
+        /// Removes the registration instead.
+        #[arg(long)]
+        unregister: bool,
+    },
+
+    /// Lists the builds and files launched recently
+    History {
+        /// How many launches to show.
+        #[arg(short, long, default_value_t = 20)]
+        number: usize,
+
+        /// Forgets every launch.
+        #[arg(long)]
+        clear: bool,
+    },
+
+    /// Lists the blendfiles opened recently and lets you pick one to open again
+    Recent {
+        /// How many files to show.
+        #[arg(short, long, default_value_t = 20)]
+        number: usize,
+
+        /// Only prints the files instead of asking which one to open.
+        #[arg(short, long)]
+        list: bool,
+
+        #[command(flatten)]
+        output: LaunchOutputArgs,
+    },
+
+    /// Shows detailed information about a single installed or remote build
+    Info {
+        /// The version matcher of the build.
+        query: String,
+
+        /// Prints the information as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Times renders of a blendfile in installed builds and compares them, e.g. to check a new daily for regressions
+    Bench {
+        /// The version matchers of the builds to compare. The first one is the baseline.
+        #[arg(required = true)]
+        queries: Vec<String>,
+
+        /// The blendfile every build renders.
+        #[arg(long)]
+        blend: PathBuf,
+
+        /// The frames to render, like "1-10" or "5". Defaults to the file's own frame range.
+        #[arg(long)]
+        frames: Option<bench::Frames>,
+
+        /// How many times each build renders the file. The times are averaged to even out noise.
+        #[arg(long, default_value_t = 1)]
+        runs: usize,
+    },
+
+    /// Renders a blendfile in the background with an installed build, showing the progress of the frames
+    Render {
+        /// The blendfile to render.
+        file: PathBuf,
+
+        /// The version matcher of the build to render with. Defaults to the build matching the file's version.
+        #[arg(long)]
+        build: Option<String>,
+
+        /// The frames to render, like "1-250" or "5". Defaults to the file's own frame range.
+        #[arg(long)]
+        frames: Option<bench::Frames>,
+
+        /// Where the frames are written, like "//renders/" relative to the file, or "/tmp/frame_####".
+        /// Defaults to the output path set in the file.
+        #[arg(long)]
+        output: Option<String>,
+    },
+
+    /// Searches the cached build lists with filters the query syntax lacks, like a branch regex or the platform
+    Search {
+        /// A glob matched against the version, e.g. "4.3*".
+        #[arg(value_parser = search::validate_glob)]
+        version: Option<String>,
+
+        /// A regex matched against the branch, e.g. "experimental" or "^(alpha|beta)$".
+        #[arg(long, value_parser = search::validate_regex)]
+        branch: Option<String>,
+
+        /// Only lists builds with a variant for this platform, e.g. "windows", "linux", or "macos".
+        #[arg(long)]
+        os: Option<String>,
+
+        /// Only lists builds with a variant for this architecture, e.g. "x64" or "arm64".
+        #[arg(long)]
+        arch: Option<String>,
+
+        /// Only lists builds committed after this date, e.g. "2024-06-01", or within this long, e.g. "2w".
+        #[arg(long, value_parser = search::parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only searches the repo with this nickname. Can be given multiple times.
+        #[arg(long = "repo", value_name = "REPO")]
+        repos: Vec<String>,
+
+        /// Prints the matches as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Lists every platform variant of a remote build, with its file type, download size, and URL
+    Variants {
+        /// The version matcher of the build.
+        query: String,
+
+        /// Prints the variants as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compares two builds: their versions, commits, and, if both are installed, their files
+    Diff {
+        /// The version matcher of the first build.
+        first: String,
+
+        /// The version matcher of the second build.
+        second: String,
+
+        /// Lists every file that changed, was added, or was removed instead of only counting them.
+        #[arg(long)]
+        files: bool,
     },
 
     /// Launch a build
@@ -97,37 +580,546 @@ pub enum Command {
 
         #[command(subcommand)]
         command: Option<RunCommand>,
+
+        #[command(flatten)]
+        output: LaunchOutputArgs,
+    },
+
+    /// Opens an interactive browser for installing, removing, favoriting, and launching builds
+    Tui {
+        /// Shows all builds, even if they are not for your target os.
+        #[arg(short, long)]
+        all_builds: bool,
+    },
+
+    /// Views and changes settings
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Points a stable `current` link next to the library at an installed build, for external tools and shortcuts to use.
+    /// Shows the build in use if no query is given.
+    Use {
+        /// The version matcher of the build to use.
+        query: Option<String>,
+
+        /// Also writes a `blender` launcher for the current build into this folder, which should be on your PATH.
+        #[arg(long)]
+        shim: Option<PathBuf>,
+    },
+
+    /// Periodically fetches the repos and reports new builds, optionally pulling them right away
+    Watch {
+        /// How long to wait between checks, e.g. "6h" or "30m".
+        #[arg(long, default_value = "6h", value_parser = humantime::parse_duration)]
+        interval: Duration,
+
+        /// Pulls new builds that match this version matcher. Can be given multiple times.
+        #[arg(long)]
+        auto_pull: Vec<String>,
+
+        /// Checks once and exits, for running from cron or a timer.
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// Prints the path of the executable of an installed build
+    Which {
+        /// The version matcher of the build.
+        query: String,
+
+        /// Prints the build's folder instead.
+        #[arg(long)]
+        folder: bool,
+    },
+
+    /// Marks an installed build as a favorite
+    Favorite {
+        /// The version matcher of the build to favorite.
+        query: String,
+    },
+
+    /// Removes an installed build from the favorites
+    Unfavorite {
+        /// The version matcher of the build to unfavorite.
+        query: String,
+    },
+
+    /// Pins the current folder to a build by writing a `.blrs.toml` into it.
+    /// Launches of blendfiles below it, and `run build` without a build from inside it, use the pinned build.
+    /// Shows the pinned build if no query is given.
+    Pin {
+        /// The version matcher of the build to pin.
+        query: Option<String>,
+
+        /// Removes the pin of the current folder.
+        #[arg(long, conflicts_with = "query")]
+        remove: bool,
+    },
+
+    /// Shows or sets the build that is launched by `run build` when no build is given
+    Default {
+        /// The version matcher of the build to use as the default. Shows the current default if omitted.
+        query: Option<String>,
+
+        /// Clears the default build.
+        #[arg(short, long)]
+        unset: bool,
+    },
+
+    /// Names queries, so `blrs run build studio` can stand for `blrs run build 4.2.3-lts`
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommand,
+    },
+
+    /// Links addons into builds, so every new build has them
+    Addons {
+        #[command(subcommand)]
+        command: AddonsCommand,
+    },
+
+    /// Stores repo credentials in the OS credential store instead of the config
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+
+    /// Updates blrs itself to the newest release on GitHub
+    SelfUpdate {
+        /// Only reports whether a newer version is available.
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Checks the config, library, build lists, network, and installed builds, and suggests fixes
+    Doctor,
+
+    /// Manages the OS launcher entries of installed builds
+    Shortcuts {
+        #[command(subcommand)]
+        command: ShortcutsCommand,
+    },
+
+    /// Lists, adds, and removes the repositories builds are fetched from
+    Repos {
+        #[command(subcommand)]
+        command: ReposCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ShortcutsCommand {
+    /// Adds a Start Menu entry, desktop file, or app link for every installed build, and removes those of removed builds.
+    /// Set `cli.sync_shortcuts` to do this after every pull and rm.
+    Sync,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum AliasCommand {
+    /// Saves a name that can be used in place of a query
+    Set {
+        /// The name of the alias.
+        name: String,
+
+        /// The version matcher the alias stands for.
+        query: String,
+    },
+
+    /// Lists the aliases and the queries they stand for
+    List,
+
+    /// Removes an alias
+    Rm {
+        /// The name of the alias.
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum AddonsCommand {
+    /// Records an addon folder or file, which is linked into the scripts/addons folder of every build pulled from now on
+    Link {
+        /// The addon's folder or `.py` file.
+        path: PathBuf,
+    },
+
+    /// Stops linking an addon into new builds. Builds keep the links they already have
+    Unlink {
+        /// The addon's folder or `.py` file.
+        path: PathBuf,
+    },
+
+    /// Lists the linked addons
+    List,
+
+    /// Links the addons into every installed build that lacks them
+    Sync,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum AuthCommand {
+    /// Asks for a repo's token and saves it in the credential store.
+    /// The token is read from stdin when prompting is disabled.
+    Login {
+        /// The nickname or id of the repo.
+        repo: String,
+
+        /// Uses basic auth with this user, and asks for the password instead of a token.
+        #[arg(long)]
+        user: Option<String>,
+    },
+
+    /// Removes a repo's secret from the credential store
+    Logout {
+        /// The nickname or id of the repo.
+        repo: String,
     },
-    // /// Saves authentication data for github.
-    // ///
-    // /// This is useful for remote repositories based on github releases.
-    // ///
-    // /// WARNING! This is not encrypted and is readily available in your config location.
-    // GithubAuth { user: String, token: String },
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ReposCommand {
+    /// Lists the registered repos
+    List,
+
+    /// Registers a GitHub repo whose releases contain builds
+    AddGithub {
+        /// The repo, written as owner/name.
+        repo: String,
+
+        /// The name used for the repo in queries. Defaults to the repo's name.
+        #[arg(long)]
+        nickname: Option<String>,
+
+        /// Only lists release assets whose file name matches this glob, e.g. "*linux*.tar.xz".
+        #[arg(long)]
+        asset: Option<String>,
+    },
+
+    /// Registers a build server that only serves a directory index or a JSON document, by matching
+    /// the file names it links to
+    AddIndex {
+        /// The URL of the index.
+        url: String,
+
+        /// The name used for the repo in queries.
+        nickname: String,
+
+        /// A regex matched against every file name, whose named groups fill in the build.
+        /// `version` is required. `branch`, `hash`, `platform`, `arch`, and `date` are optional,
+        /// e.g. "blender-(?<version>\d+\.\d+\.\d+)-(?<platform>linux|windows)-(?<arch>x64|arm64)".
+        #[arg(long)]
+        pattern: String,
+
+        /// The branch of builds whose file name has no `branch` group.
+        #[arg(long, default_value = "stable")]
+        branch: String,
+    },
+
+    /// Registers a built-in repo, like "experimental" for the builds of Blender's feature branches.
+    /// Their builds are grouped by branch in `ls --group`, and can be pulled with queries like `branch:<name>`
+    Enable {
+        /// The name of the built-in repo. `repos list` shows the ones that are not enabled.
+        name: String,
+    },
+
+    /// Unregisters a repo. Builds installed from it are kept.
+    Remove {
+        /// The nickname or id of the repo.
+        name: String,
+    },
+
+    /// Sets the credentials sent to a repo's host when fetching from it and downloading its builds
+    SetAuth {
+        /// The nickname or id of the repo.
+        name: String,
+
+        #[command(flatten)]
+        auth: RepoAuthArgs,
+    },
+
+    /// Saves authentication data for GitHub, needed for private repos and to avoid ratelimits.
+    ///
+    /// WARNING! This is not encrypted and is readily available in your config location.
+    Auth { user: String, token: String },
+}
+
+/// Credentials for `repos set-auth`. Secrets given with the `-env` variants are read from the
+/// environment each time they are needed, instead of being saved in the config.
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct RepoAuthArgs {
+    /// Sends this token as a bearer token.
+    #[arg(long, group = "bearer")]
+    pub token: Option<String>,
+
+    /// Sends the token in this environment variable as a bearer token.
+    #[arg(long, group = "bearer")]
+    pub token_env: Option<String>,
+
+    /// Uses basic auth with this user.
+    #[arg(long, conflicts_with = "bearer")]
+    pub user: Option<String>,
+
+    /// The password for basic auth.
+    #[arg(long, requires = "user", conflicts_with = "password_env")]
+    pub password: Option<String>,
+
+    /// The environment variable holding the password for basic auth.
+    #[arg(long, requires = "user")]
+    pub password_env: Option<String>,
+
+    /// Removes the repo's credentials.
+    #[arg(long, conflicts_with_all = ["bearer", "user"])]
+    pub clear: bool,
 }
 
 #[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
 pub enum RunCommand {
     /// Open a specific file and assume the correct build
-    File { path: PathBuf },
+    File {
+        /// The blendfiles to open. Each is opened by its own launch unless --together is given.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Opens every file in a single launch of the build matched by the first file.
+        /// Blender's options are repeated after each file, so a render or script runs once per file.
+        #[arg(long)]
+        together: bool,
+
+        /// Which build to use when no installed build matches the version of the file.
+        #[arg(short, long)]
+        prefer: Option<FallbackPolicy>,
+
+        #[command(flatten)]
+        blender: BlenderArgs,
+    },
 
     /// Launch a specific build of blender
     Build {
+        /// The version match or blendfile to open. Falls back to the build pinned for the current folder,
+        /// then the default build, if omitted.
         build_or_file: Option<String>,
 
         #[arg(short, long)]
         open_last: bool,
+
+        #[command(flatten)]
+        isolation: IsolationArgs,
+
+        /// Adds the arguments and environment of a launch profile from `cli.profiles`.
+        /// Can be given multiple times.
+        #[arg(long)]
+        profile: Vec<String>,
+
+        #[command(flatten)]
+        blender: BlenderArgs,
     },
+
+    /// Relaunch the build and file that were launched most recently
+    Last,
+}
+
+/// Blender's own command line options, for driving it from render farms and CI without remembering their order.
+#[derive(Args, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlenderArgs {
+    /// Runs Blender without its UI (`-b`).
+    #[arg(short, long)]
+    pub background: bool,
+
+    /// Runs a Python script once the file is loaded (`-P`). Can be given multiple times.
+    #[arg(long, value_name = "SCRIPT")]
+    pub python: Vec<PathBuf>,
+
+    /// Runs a Python expression once the file is loaded, after the scripts (`--python-expr`).
+    /// Can be given multiple times.
+    #[arg(long, value_name = "EXPR")]
+    pub python_expr: Vec<String>,
+
+    /// Renders the file's animation after the scripts ran, writing the frames to this path (`-o`, `-a`).
+    /// `#` in the path is replaced by the frame number. Implies --background.
+    #[arg(long, value_name = "OUTPUT")]
+    pub render: Option<PathBuf>,
+
+    /// Only renders this frame instead of the whole animation (`-f`).
+    #[arg(long, requires = "render")]
+    pub frame: Option<i32>,
+}
+
+impl BlenderArgs {
+    /// The arguments in the order Blender needs them. Rendering starts as soon as `-a` or `-f` is read,
+    /// so they come last.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if self.background || self.render.is_some() {
+            args.push("-b".into());
+        }
+        for script in &self.python {
+            args.extend(["-P".into(), script.to_string_lossy().into_owned()]);
+        }
+        for expr in &self.python_expr {
+            args.extend(["--python-expr".into(), expr.clone()]);
+        }
+        if let Some(output) = &self.render {
+            args.extend(["-o".into(), output.to_string_lossy().into_owned()]);
+            match self.frame {
+                Some(frame) => args.extend(["-f".into(), frame.to_string()]),
+                None => args.push("-a".into()),
+            }
+        }
+        args
+    }
+}
+
+/// Options for where a launched build's output goes, and whether blrs waits for it to exit.
+#[derive(Args, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LaunchOutputArgs {
+    /// Starts Blender and returns immediately instead of waiting for it to exit.
+    /// Its output is discarded unless --log-file or --capture-logs is given.
+    #[arg(long, global = true)]
+    pub detach: bool,
+
+    /// Writes Blender's stdout and stderr into this file.
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Writes Blender's stdout and stderr into a new file in the `logs` folder of the config directory.
+    #[arg(long, global = true, conflicts_with = "log_file")]
+    pub capture_logs: bool,
+
+    /// Waits for Blender to exit and reports how it did. Its stderr is searched for crash signatures,
+    /// and a crash is recorded in the launch history along with where Blender's crash log is.
+    #[arg(long, global = true, conflicts_with = "detach")]
+    pub wait_and_report: bool,
+}
+
+/// Chooses builds for another platform than the one blrs runs on. Ignored when all platforms are shown.
+#[derive(Args, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetArgs {
+    /// Chooses builds for this platform instead of this system's, e.g. "windows", "linux", or "macos".
+    #[arg(long)]
+    pub os: Option<String>,
+
+    /// Chooses builds for this architecture instead of this system's, e.g. "x64" or "arm64".
+    #[arg(long)]
+    pub arch: Option<String>,
+
+    /// Sets both the platform and architecture, like "windows-x64".
+    #[arg(long, conflicts_with_all = ["os", "arch"], value_parser = target::parse_target)]
+    pub target: Option<(String, String)>,
+}
+
+impl TargetArgs {
+    pub fn apply(self) {
+        let (os, arch) = match self.target {
+            Some((os, arch)) => (Some(os), Some(arch)),
+            None => (self.os, self.arch),
+        };
+        target::set_override(os, arch);
+    }
+}
+
+/// Options for launching a build with its own user folders, separate from the shared Blender preferences.
+#[derive(Args, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IsolationArgs {
+    /// Launches the build with its own preferences, scripts, and add-ons, kept in a per-build folder.
+    #[arg(long)]
+    pub isolated: bool,
+
+    /// The folder to keep the isolated user files in. Implies --isolated.
+    #[arg(long)]
+    pub config_dir: Option<PathBuf>,
+
+    /// Keeps the isolated user files in a temporary folder that is deleted once Blender exits. Implies --isolated.
+    #[arg(long, conflicts_with = "config_dir")]
+    pub temporary: bool,
+}
+
+impl IsolationArgs {
+    pub fn enabled(&self) -> bool {
+        self.isolated || self.config_dir.is_some() || self.temporary
+    }
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ConfigCommand {
+    /// Prints the value of a setting. Settings of blrs-cli itself start with `cli.`
+    Get { key: String },
+
+    /// Changes a setting. Values are written like in the TOML file, e.g. `true`, `5`, or `"text"`.
+    Set { key: String, value: String },
+
+    /// Lists every setting and its value
+    List,
+
+    /// Opens the config file in $EDITOR, and only saves it if it is still valid
+    Edit,
+
+    /// Prints where the config file is stored
+    Path {
+        /// Prints the path of the blrs-cli settings instead.
+        #[arg(long)]
+        cli: bool,
+    },
+
+    /// Shows or changes how an installed build is launched. Shows the current settings if no changes are given.
+    Build {
+        query: String,
+
+        /// Extra arguments passed to the build, separated by spaces. An empty string clears them.
+        #[arg(long, value_delimiter = ' ', allow_hyphen_values = true)]
+        set_args: Option<Vec<String>>,
+
+        /// Adds an environment variable in the form KEY=VALUE. Can be given multiple times.
+        #[arg(long, value_parser = parse_key_value)]
+        set_env: Vec<(String, String)>,
+
+        /// The executable to launch, relative to the build folder. An empty string clears it.
+        #[arg(long)]
+        set_exe: Option<String>,
+
+        /// A custom name for the build. An empty string clears it.
+        #[arg(long)]
+        set_name: Option<String>,
+
+        /// Clears every custom setting before applying the others.
+        #[arg(long)]
+        clear: bool,
+    },
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!["expected KEY=VALUE, got {:?}", s])
 }
 
 impl Command {
-    pub fn eval(self, cfg: &BLRSConfig) -> Result<Vec<ConfigTask>, CommandError> {
+    /// Whether the command was told to delete permanently with its own `-n`, kept from before --no-trash was global.
+    pub fn no_trash(&self) -> bool {
+        matches!(
+            self,
+            Command::Rm { delete: true, .. } | Command::Prune { delete: true, .. }
+        )
+    }
+
+    pub fn eval(
+        self,
+        cfg: &BLRSConfig,
+        cli_cfg: &CliConfig,
+    ) -> Result<Vec<ConfigTask>, CommandError> {
         match self {
             Command::Fetch {
                 force,
                 parallel,
                 ignore_errors,
+                repos,
+                prune_cache,
+                keep_stale,
+                wait_for_ratelimit,
             } => {
+                let selected = fetcher::select_repos(cfg, &repos)?;
+
                 let checked_time = cfg.history.last_time_checked.unwrap_or_default();
                 let ready_time = checked_time + FETCH_INTERVAL;
                 // Check if we are past the time we should be able to check for new builds.
@@ -136,21 +1128,43 @@ impl Command {
                 if ready_to_check | force {
                     debug!["We are ready to check for new builds. Initializing tokio"];
 
+                    cancel::handle_ctrl_c();
                     let rt = tokio::runtime::Runtime::new().unwrap();
-                    let result = rt.block_on(fetcher::fetch(cfg, parallel, ignore_errors));
+                    let result = rt.block_on(within_deadline(cancel::or_cancelled(async {
+                        fetcher::fetch(
+                            cfg,
+                            cli_cfg,
+                            &selected,
+                            parallel,
+                            ignore_errors,
+                            match (prune_cache, keep_stale) {
+                                (true, _) => StalePolicy::Prune,
+                                (_, true) => StalePolicy::KeepAll,
+                                _ => StalePolicy::KeepInstalled,
+                            },
+                            wait_for_ratelimit,
+                        )
+                        .await
+                        .map_err(fetcher::fetch_error)
+                    })));
 
-                    if result.is_ok() {
+                    if let Ok((_, summaries)) = &result {
                         info![
                             "{}",
                             ansi_term::Color::Green
                                 .bold()
                                 .paint("Fetching builds finished successfully")
                         ];
+                        if !json_output() {
+                            fetcher::print_summary(cfg, summaries);
+                        }
                     }
 
-                    result
-                        .map(|v| vec![v])
-                        .map_err(|e| CommandError::IoError(IoErrorOrigin::Fetching, e))
+                    // The other repos were not refreshed, so the fetch interval is left alone
+                    result.map(|(v, _)| match repos.is_empty() {
+                        true => vec![v],
+                        false => vec![],
+                    })
                 } else {
                     let time_remaining = ready_time - Utc::now();
                     Err(CommandError::FetchingTooFast {
@@ -158,12 +1172,80 @@ impl Command {
                     })
                 }
             }
-            Command::Verify { repos } => verify::verify(cfg, repos).map(|_| vec![]),
+            Command::Verify {
+                repos,
+                deep,
+                launch,
+                jobs,
+                timeout_per_build,
+            } => {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
+
+                let result = rt.block_on(verify::verify(
+                    cfg,
+                    repos,
+                    deep,
+                    launch,
+                    jobs,
+                    timeout_per_build,
+                ));
+                // Builds that timed out may still be running, and should not keep blrs from exiting
+                rt.shutdown_background();
+                result.map(|_| vec![])
+            }
             Command::Pull {
                 queries,
                 all_platforms,
+                connections,
+                jobs,
+                host_delay,
+                retries,
+                fetch,
+                no_fetch,
+                stream,
+                dry_run,
+                limit_rate,
+                prefer_variant,
+                variant,
+                filename_glob,
+                force,
+                reinstall,
+                record_manifest,
+                target,
             } => {
+                target.apply();
                 let queries = strings_to_queries(queries)?;
+                let opts = PullOptions {
+                    all_platforms,
+                    connections,
+                    jobs,
+                    host_delay: Duration::from_millis(host_delay),
+                    retries,
+                    fetch: match (fetch, no_fetch) {
+                        (true, _) => FetchPolicy::Always,
+                        (_, true) => FetchPolicy::Never,
+                        _ => FetchPolicy::Auto,
+                    },
+                    stream,
+                    dry_run,
+                    limit_rate,
+                    prefer_variant,
+                    variant: match (variant, filename_glob) {
+                        (Some(target), _) => Some(VariantPick::Target(target)),
+                        (_, Some(glob)) => {
+                            Some(VariantPick::FileName(glob::Pattern::new(&glob).unwrap()))
+                        }
+                        _ => None,
+                    },
+                    force,
+                    reinstall: reinstall || force,
+                    record_manifest,
+                    ..Default::default()
+                };
 
                 debug!["We are ready to download new builds. Initializing tokio"];
 
@@ -173,95 +1255,503 @@ impl Command {
                     .build()
                     .expect("failed to create runtime");
 
-                let result = rt.block_on(pull::pull_builds(cfg, queries, all_platforms));
+                let result = rt.block_on(within_deadline(pull::pull_builds(
+                    cfg, cli_cfg, queries, &opts,
+                )));
 
                 match result {
-                    Ok(_) => {
+                    Ok(tasks) if dry_run => Ok(tasks),
+                    Ok(tasks) => {
                         info![
                             "{}",
                             ansi_term::Color::Green
                                 .bold()
                                 .paint("Downloading builds finished successfully")
                         ];
-                        Ok(vec![])
+                        sync_shortcuts(cfg, cli_cfg);
+                        Ok(tasks)
                     }
                     Err(e) => Err(e),
                 }
             }
-            Command::Rm { queries, no_trash } => {
-                let queries = strings_to_queries(queries)?;
+            Command::Upgrade {
+                queries,
+                remove_old,
+                dry_run,
+            } => {
+                let queries = match queries.is_empty() {
+                    true => vec![],
+                    false => strings_to_queries(queries)?,
+                };
+
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
+
+                rt.block_on(within_deadline(upgrade::upgrade(
+                    cfg, cli_cfg, queries, remove_old, dry_run,
+                )))
+            }
+            Command::Install { path, repo, name } => {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
 
-                rm::remove_builds(cfg, queries, no_trash).map(|_| vec![])
+                rt.block_on(install::install(cfg, path, repo, name, cli_cfg))
+                    .map(|_| vec![])
+            }
+            Command::Adopt {
+                paths,
+                repo,
+                link,
+                dry_run,
+            } => adopt::adopt(cfg, paths, repo, link, dry_run, cli_cfg.record_manifests)
+                .map(|_| vec![]),
+            Command::Rm {
+                cache: Some(repo), ..
+            } => clean::purge_cache(cfg, &repo).map(|_| vec![]),
+            Command::Rm {
+                queries,
+                all_matching,
+                cache: None,
+                path,
+                repo,
+                all: _,
+                delete: _,
+            } => {
+                match (path.is_empty(), repo) {
+                    (false, _) => rm::remove_folders(cfg, path)?,
+                    (true, Some(repo)) => rm::remove_repo(cfg, repo)?,
+                    (true, None) => {
+                        rm::remove_builds(cfg, strings_to_queries(queries)?, all_matching)?
+                    }
+                }
+                sync_shortcuts(cfg, cli_cfg);
+                Ok(vec![])
+            }
+            Command::Prune {
+                queries,
+                older_than,
+                keep,
+                dry_run,
+                delete: _,
+            } => {
+                let queries = match queries.is_empty() {
+                    true => vec![],
+                    false => strings_to_queries(queries)?,
+                };
+                let policy = PrunePolicy { older_than, keep };
+
+                prune::prune_builds(cfg, queries, policy, dry_run).map(|_| vec![])
+            }
+            Command::Clean {
+                archives,
+                repo_cache,
+                temp,
+                history,
+                all,
+                dry_run,
+            } => {
+                let targets: Vec<CleanTarget> = match all {
+                    true => CleanTarget::ALL.to_vec(),
+                    false => [
+                        (archives, CleanTarget::Archives),
+                        (repo_cache, CleanTarget::RepoCache),
+                        (temp, CleanTarget::Temp),
+                        (history, CleanTarget::History),
+                    ]
+                    .into_iter()
+                    .filter_map(|(on, target)| on.then_some(target))
+                    .collect(),
+                };
+                clean::clean(cfg, &targets, dry_run).map(|_| vec![])
             }
             Command::Ls {
+                query,
                 format,
                 sort_by,
                 installed_only,
+                remote_only,
                 variants,
                 all_builds,
-            } => ls::list_builds(
-                cfg,
-                format.unwrap_or_default(),
-                sort_by.unwrap_or_default(),
-                installed_only,
-                variants,
-                all_builds,
-            )
-            .map(|_| vec![]),
-            Command::Run { query, mut command } => {
+                favorites,
+                usage,
+                columns,
+                group,
+                expand,
+                problems,
+                since,
+                limit,
+                target,
+            } => {
+                target.apply();
+                let expand = expand.map(|q| parse_query(&q)).transpose()?;
+                let options = LsOptions {
+                    format: format.unwrap_or_default(),
+                    sort_format: sort_by.unwrap_or_default(),
+                    installed_only,
+                    remote_only,
+                    show_variants: variants,
+                    all_builds,
+                    favorites_only: favorites,
+                    columns,
+                    usage,
+                    group: group || expand.is_some(),
+                    expand,
+                    query: query.map(|q| parse_query(&q)).transpose()?,
+                    problems,
+                    since,
+                    limit,
+                };
+
+                let mut tasks = vec![];
+                if cli_cfg.refetch_corrupt && !fetcher::corrupt_caches(cfg).is_empty() {
+                    let rt = tokio::runtime::Builder::new_current_thread()
+                        .enable_time()
+                        .enable_io()
+                        .build()
+                        .expect("failed to create runtime");
+                    tasks.extend(rt.block_on(fetcher::repair_caches(cfg, cli_cfg))?);
+                }
+
+                ls::list_builds(cfg, options, cli_cfg.default_build.as_deref()).map(|_| tasks)
+            }
+            Command::Export { path } => manifest::export(cfg, path.as_deref()).map(|_| vec![]),
+            Command::Import {
+                path,
+                all_platforms,
+            } => {
+                let opts = PullOptions {
+                    all_platforms,
+                    ..Default::default()
+                };
+
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
+
+                rt.block_on(manifest::import(cfg, cli_cfg, &path, &opts))
+            }
+            Command::Info { query, json } => {
+                let query = parse_query(&query)?;
+
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
+
+                rt.block_on(info::show_info(cfg, query, json))
+                    .map(|_| vec![])
+            }
+            Command::Bench {
+                queries,
+                blend,
+                frames,
+                runs,
+            } => {
+                let queries = strings_to_queries(queries)?;
+                let opts = bench::BenchOptions {
+                    blend,
+                    frames,
+                    runs: runs.max(1),
+                };
+
+                bench::bench(cfg, cli_cfg, queries, &opts).map(|_| vec![])
+            }
+            Command::Render {
+                file,
+                build,
+                frames,
+                output,
+            } => render::render(cfg, cli_cfg, file, build, frames, output).map(|_| vec![]),
+            Command::Search {
+                version,
+                branch,
+                os,
+                arch,
+                since,
+                repos,
+                json,
+            } => {
+                let variants = [("platform", os), ("arch", arch)]
+                    .into_iter()
+                    .filter_map(|(key, value)| value.map(|v| format!["{}={}", key, v]))
+                    .map(|pref| pref.parse::<VariantPreference>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| CommandError::InvalidInput)?;
+                // Both were validated while parsing the arguments
+                let filter = search::SearchFilter {
+                    version: version.map(|v| glob::Pattern::new(&v).unwrap()),
+                    branch: branch.map(|b| regex::Regex::new(&b).unwrap()),
+                    variants,
+                    since,
+                    repos,
+                };
+
+                search::search(cfg, filter, json).map(|_| vec![])
+            }
+            Command::Variants { query, json } => {
+                let query = parse_query(&query)?;
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
+
+                rt.block_on(within_deadline(variants::variants(
+                    cfg, cli_cfg, query, json,
+                )))
+                .map(|_| vec![])
+            }
+            Command::Diff {
+                first,
+                second,
+                files,
+            } => {
+                let first = parse_query(&first)?;
+                let second = parse_query(&second)?;
+
+                diff::diff(cfg, first, second, files).map(|_| vec![])
+            }
+            Command::Run {
+                query,
+                mut command,
+                output,
+            } => {
                 if let Some(q) = query {
-                    if let Ok(q) = VersionSearchQuery::try_from(q.as_str()) {
+                    if let Ok(q) = parse_query(&q) {
                         command = Some(RunCommand::Build {
                             build_or_file: Some(q.to_string()),
                             open_last: false,
+                            isolation: IsolationArgs::default(),
+                            profile: vec![],
+                            blender: BlenderArgs::default(),
                         });
                     } else {
                         command = Some(RunCommand::File {
-                            path: PathBuf::from(q),
+                            paths: vec![PathBuf::from(q)],
+                            together: false,
+                            prefer: None,
+                            blender: BlenderArgs::default(),
                         });
                     }
                 }
 
                 let command = match command {
+                    Some(RunCommand::Build {
+                        build_or_file: None,
+                        open_last,
+                        isolation,
+                        profile,
+                        blender,
+                    }) => match pinned_build(Path::new("."))?.or(cli_cfg.default_build.clone()) {
+                        Some(default) => RunCommand::Build {
+                            build_or_file: Some(default),
+                            open_last,
+                            isolation,
+                            profile,
+                            blender,
+                        },
+                        None => return Err(CommandError::NotEnoughInput),
+                    },
                     Some(c) => c,
                     None => return Err(CommandError::NotEnoughInput),
                 };
 
-                run::run(cfg, command, false).map(|_| vec![])
-            } // Command::GithubAuth { user, token } => {
-              //     let auth = GithubAuthentication { user, token };
-              //     Ok(vec![ConfigTask::UpdateGHAuth(auth)])
-              // }
+                run::run(cfg, cli_cfg, command, &output, false).map(|_| vec![])
+            }
+            Command::RegisterHandler { unregister } => {
+                handler::register_handler(unregister).map(|_| vec![])
+            }
+            Command::History { number, clear } => {
+                history::show_history(number, clear).map(|_| vec![])
+            }
+            Command::Recent {
+                number,
+                list,
+                output,
+            } => recent::recent(cfg, cli_cfg, number, list, &output).map(|_| vec![]),
+            Command::Tui { all_builds } => tui::run_tui(cfg, cli_cfg, all_builds).map(|_| vec![]),
+            Command::Config { command } => match command {
+                ConfigCommand::Get { key } => config::get(cfg, cli_cfg, &key).map(|_| vec![]),
+                ConfigCommand::Set { key, value } => config::set(cfg, cli_cfg, &key, &value),
+                ConfigCommand::List => {
+                    config::list(cfg, cli_cfg);
+                    Ok(vec![])
+                }
+                ConfigCommand::Edit => config::edit(cfg).map(|_| vec![]),
+                ConfigCommand::Path { cli } => {
+                    match cli {
+                        true => println!["{}", CliConfig::path().display()],
+                        false => println!["{}", config_path().display()],
+                    }
+                    Ok(vec![])
+                }
+                ConfigCommand::Build {
+                    query,
+                    set_args,
+                    set_env,
+                    set_exe,
+                    set_name,
+                    clear,
+                } => {
+                    let query = parse_query(&query)?;
+                    let settings = BuildSettings {
+                        args: set_args,
+                        env: set_env,
+                        exe: set_exe,
+                        name: set_name,
+                        clear,
+                    };
+
+                    config::configure_build(cfg, cli_cfg, query, settings)
+                }
+            },
+            Command::Use { query, shim } => {
+                let query = query.map(|q| parse_query(&q)).transpose()?;
+
+                current::use_build(cfg, cli_cfg, query, shim).map(|_| vec![])
+            }
+            Command::Watch {
+                interval,
+                auto_pull,
+                once,
+            } => {
+                let opts = watch::WatchOptions {
+                    interval,
+                    auto_pull: strings_to_queries(auto_pull)?,
+                    once,
+                };
+
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
+
+                rt.block_on(watch::watch(cfg, cli_cfg, opts))
+            }
+            Command::Which { query, folder } => {
+                let query = parse_query(&query)?;
+
+                which::which(cfg, cli_cfg, query, folder).map(|_| vec![])
+            }
+            Command::Favorite { query } => {
+                let query = parse_query(&query)?;
+
+                favorite::set_favorite(cfg, query, true).map(|_| vec![])
+            }
+            Command::Unfavorite { query } => {
+                let query = parse_query(&query)?;
+
+                favorite::set_favorite(cfg, query, false).map(|_| vec![])
+            }
+            Command::Pin { query, remove } => pin::pin(query, remove).map(|_| vec![]),
+            Command::Default { query, unset } => {
+                let query = query.map(|q| parse_query(&q)).transpose()?;
+
+                default::set_default(cfg, cli_cfg, query, unset)
+            }
+            Command::Alias { command } => match command {
+                AliasCommand::Set { name, query } => alias::set(cli_cfg, name, query),
+                AliasCommand::List => {
+                    alias::list(cli_cfg);
+                    Ok(vec![])
+                }
+                AliasCommand::Rm { name } => alias::remove(cli_cfg, &name),
+            },
+            Command::Addons { command } => match command {
+                AddonsCommand::Link { path } => addons::add(cli_cfg, path),
+                AddonsCommand::Unlink { path } => addons::remove(cli_cfg, path),
+                AddonsCommand::List => {
+                    addons::list(cli_cfg);
+                    Ok(vec![])
+                }
+                AddonsCommand::Sync => addons::sync(cfg, cli_cfg).map(|_| vec![]),
+            },
+            Command::Auth { command } => match command {
+                AuthCommand::Login { repo, user } => auth::login(cfg, cli_cfg, &repo, user),
+                AuthCommand::Logout { repo } => auth::logout(cfg, cli_cfg, &repo),
+            },
+            Command::SelfUpdate { check } => {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
+
+                rt.block_on(self_update::self_update(cfg, check))
+                    .map(|_| vec![])
+            }
+            Command::Shortcuts {
+                command: ShortcutsCommand::Sync,
+            } => shortcuts::sync(cfg).map(|_| vec![]),
+            Command::Doctor => {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .enable_io()
+                    .build()
+                    .expect("failed to create runtime");
+
+                rt.block_on(doctor::doctor(cfg, cli_cfg)).map(|_| vec![])
+            }
+            Command::Repos { command } => match command {
+                ReposCommand::List => {
+                    repos::list(cfg, cli_cfg);
+                    Ok(vec![])
+                }
+                ReposCommand::AddGithub {
+                    repo,
+                    nickname,
+                    asset,
+                } => repos::add_github(cfg, cli_cfg, &repo, nickname, asset),
+                ReposCommand::AddIndex {
+                    url,
+                    nickname,
+                    pattern,
+                    branch,
+                } => repos::add_index(
+                    cfg,
+                    cli_cfg,
+                    &url,
+                    nickname,
+                    IndexTemplate { pattern, branch },
+                ),
+                ReposCommand::Enable { name } => repos::enable(cfg, &name),
+                ReposCommand::Remove { name } => repos::remove(cfg, cli_cfg, &name),
+                ReposCommand::SetAuth { name, auth } => repos::set_auth(cfg, cli_cfg, &name, auth),
+                ReposCommand::Auth { user, token } => Ok(repos::auth(user, token)),
+            },
         }
     }
 }
 
-fn strings_to_queries(queries: Vec<String>) -> Result<Vec<VersionSearchQuery>, CommandError> {
-    // parse the query into an actual query
-    let queries: Vec<(String, Result<_, _>)> = queries
-        .into_iter()
-        .map(|s| {
-            let try_from = VersionSearchQuery::try_from(s.as_str());
-            (s, try_from)
-        })
-        .collect();
-
-    // Any of the queries failed to parse
-    if let Some((s, Err(e))) = queries.iter().find(|(_, v)| v.is_err()) {
-        return Err(CommandError::CouldNotParseQuery(s.clone(), e.clone()));
+/// Runs `shortcuts sync` if `cli.sync_shortcuts` is set. A failure only warns, as the builds themselves changed fine.
+pub(crate) fn sync_shortcuts(cfg: &BLRSConfig, cli_cfg: &CliConfig) {
+    if !cli_cfg.sync_shortcuts {
+        return;
     }
+    if let Err(e) = shortcuts::sync(cfg) {
+        warn![
+            "Failed to update the shortcuts of the installed builds: {}",
+            e
+        ];
+    }
+}
+
+fn strings_to_queries(queries: Vec<String>) -> Result<Vec<VersionSearchQuery>, CommandError> {
     // The query list is empty
     if queries.is_empty() {
         return Err(CommandError::MissingQuery);
     }
 
-    let queries: Vec<VersionSearchQuery> = queries
-        .into_iter()
-        .map(|(_, o)| {
-            debug!["{:?}", o];
-            o.unwrap()
-        })
-        .collect();
-
-    Ok(queries)
+    // Fails on the first query that does not parse
+    queries.iter().map(|s| parse_query(s)).collect()
 }
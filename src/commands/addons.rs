@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+
+use blrs::{BLRSConfig, LocalBuild};
+use log::{info, warn};
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{error_reading, error_writing, CommandError},
+    resolving::installed_builds,
+    tasks::ConfigTask,
+};
+
+/// The folder Blender loads bundled addons from, like `4.2/scripts/addons`.
+/// macOS builds keep it inside the app bundle.
+fn addons_dir(build: &LocalBuild) -> Option<PathBuf> {
+    let ver = build.info.basic.version();
+    let version_dir = format!["{}.{}", ver.major, ver.minor];
+
+    [
+        build.folder.clone(),
+        build.folder.join("Blender.app/Contents/Resources"),
+    ]
+    .into_iter()
+    .map(|root| root.join(&version_dir))
+    .find(|dir| dir.is_dir())
+    .map(|dir| dir.join("scripts").join("addons"))
+}
+
+/// Links, or on Windows without the privilege to make symlinks, copies `source` to `destination`.
+fn link(source: &Path, destination: &Path) -> Result<(), CommandError> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(source, destination)
+            .map_err(|e| error_writing(destination.to_path_buf(), e))
+    }
+
+    #[cfg(windows)]
+    {
+        let linked = match source.is_dir() {
+            true => std::os::windows::fs::symlink_dir(source, destination),
+            false => std::os::windows::fs::symlink_file(source, destination),
+        };
+        if linked.is_ok() {
+            return Ok(());
+        }
+
+        match source.is_dir() {
+            true => super::install::copy_dir(source, destination),
+            false => std::fs::copy(source, destination)
+                .map(|_| ())
+                .map_err(|e| error_writing(destination.to_path_buf(), e)),
+        }
+    }
+}
+
+/// Links every addon into a build that does not have it yet. Returns how many were added.
+///
+/// Anything already in the way is left alone, so the build's own addons are never replaced.
+pub fn link_into(build: &LocalBuild, addons: &[PathBuf]) -> Result<usize, CommandError> {
+    if addons.is_empty() {
+        return Ok(0);
+    }
+    let Some(dir) = addons_dir(build) else {
+        warn![
+            "{} has no scripts folder, so no addons were linked into it",
+            build.folder.display()
+        ];
+        return Ok(0);
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| error_writing(dir.clone(), e))?;
+
+    let mut added = 0;
+    for source in addons {
+        let Some(name) = source.file_name() else {
+            continue;
+        };
+        if !source.exists() {
+            warn!["Skipping {}, as it no longer exists", source.display()];
+            continue;
+        }
+
+        let destination = dir.join(name);
+        if destination.symlink_metadata().is_ok() {
+            if destination
+                .read_link()
+                .is_ok_and(|target| target != *source)
+            {
+                warn![
+                    "Skipping {}, as {} links somewhere else",
+                    source.display(),
+                    destination.display()
+                ];
+            }
+            continue;
+        }
+
+        link(source, &destination)?;
+        added += 1;
+    }
+
+    Ok(added)
+}
+
+/// Records an addon folder or file, so it is linked into every build pulled from now on.
+pub fn add(cli_cfg: &CliConfig, path: PathBuf) -> Result<Vec<ConfigTask>, CommandError> {
+    let path = path.canonicalize().map_err(|e| error_reading(path, e))?;
+    if cli_cfg.addons.contains(&path) {
+        info!["{} is already linked", path.display()];
+        return Ok(vec![]);
+    }
+
+    info![
+        "{} will be linked into every new build. Run `blrs addons sync` to link it into the installed ones",
+        path.display()
+    ];
+    let mut new_cli_cfg = cli_cfg.clone();
+    new_cli_cfg.addons.push(path);
+    Ok(vec![ConfigTask::SetCliConfig(Box::new(new_cli_cfg))])
+}
+
+/// Stops linking an addon into new builds. Builds keep the links they already have.
+pub fn remove(cli_cfg: &CliConfig, path: PathBuf) -> Result<Vec<ConfigTask>, CommandError> {
+    // The addon may be gone already, in which case it can only be matched as written
+    let path = path.canonicalize().unwrap_or(path);
+    let mut new_cli_cfg = cli_cfg.clone();
+    new_cli_cfg.addons.retain(|addon| *addon != path);
+    if new_cli_cfg.addons.len() == cli_cfg.addons.len() {
+        return Err(CommandError::UnknownAddon(path));
+    }
+
+    info![
+        "{} will no longer be linked into new builds",
+        path.display()
+    ];
+    Ok(vec![ConfigTask::SetCliConfig(Box::new(new_cli_cfg))])
+}
+
+/// Prints every recorded addon.
+pub fn list(cli_cfg: &CliConfig) {
+    if cli_cfg.addons.is_empty() {
+        info!["No addons have been linked, see `blrs addons link`"];
+        return;
+    }
+
+    cli_cfg
+        .addons
+        .iter()
+        .for_each(|addon| match addon.exists() {
+            true => println!["{}", addon.display()],
+            false => println![
+                "{} {}",
+                addon.display(),
+                ansi_term::Color::Red.paint("(missing)")
+            ],
+        });
+}
+
+/// Links the recorded addons into every installed build that lacks them.
+pub fn sync(cfg: &BLRSConfig, cli_cfg: &CliConfig) -> Result<(), CommandError> {
+    if cli_cfg.addons.is_empty() {
+        info!["No addons have been linked, see `blrs addons link`"];
+        return Ok(());
+    }
+
+    let builds = installed_builds(cfg)?;
+    let mut added = 0;
+    for (build, _) in &builds {
+        added += link_into(build, &cli_cfg.addons)?;
+    }
+
+    info![
+        "{} addon link(s) added across {} build(s)",
+        added,
+        builds.len()
+    ];
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+
+use blrs::{info::launching::OSLaunchTarget, BLRSConfig, LocalBuild};
+use log::{debug, error, info, warn};
+
+use crate::{
+    errs::{error_reading, error_renaming, error_writing, CommandError},
+    events::{emit, Event},
+    integrity,
+    resolving::folder_name,
+};
+
+use super::install::{copy_dir, repo_folder};
+
+/// How many folders deep `adopt` looks for builds below each given path.
+const MAX_DEPTH: usize = 4;
+
+/// Finds the folders below `path` that have a Blender executable at their top level.
+/// The folders of found builds are not searched any further.
+fn find_builds(path: &Path, exe_name: &str, depth: usize, found: &mut Vec<PathBuf>) {
+    if path.join(exe_name).is_file() {
+        found.push(path.to_path_buf());
+        return;
+    }
+    if depth == 0 {
+        return;
+    }
+
+    let Ok(items) = path.read_dir() else {
+        debug!["Skipping unreadable folder {}", path.display()];
+        return;
+    };
+    for item in items.flatten() {
+        if item.file_type().is_ok_and(|t| t.is_dir()) {
+            find_builds(&item.path(), exe_name, depth - 1, found);
+        }
+    }
+}
+
+/// Moves a folder, copying it when it is on another drive than `destination`.
+fn move_dir(source: &Path, destination: &Path) -> Result<(), CommandError> {
+    match std::fs::rename(source, destination) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            debug!["{} is on another drive, copying it", source.display()]
+        }
+        Err(e) => {
+            return Err(error_renaming(
+                source.to_path_buf(),
+                destination.to_path_buf(),
+                e,
+            ))
+        }
+    }
+
+    // A partial copy would be taken for an installed build
+    if let Err(e) = copy_dir(source, destination) {
+        if destination.exists() {
+            if let Err(e) = std::fs::remove_dir_all(destination) {
+                warn!["Failed to clean up {}: {}", destination.display(), e];
+            }
+        }
+        return Err(e);
+    }
+    std::fs::remove_dir_all(source)
+        .map_err(|e| error_renaming(source.to_path_buf(), destination.to_path_buf(), e))
+}
+
+fn link_dir(source: &Path, destination: &Path) -> Result<(), CommandError> {
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(source, destination);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_dir(source, destination);
+
+    linked.map_err(|e| error_writing(destination.to_path_buf(), e))
+}
+
+/// Puts one found build into the library and writes its build info.
+fn adopt_build(
+    mut build: LocalBuild,
+    source: &Path,
+    destination: &Path,
+    link: bool,
+    record_manifest: bool,
+) -> Result<(), CommandError> {
+    // Nested library layouts put builds in folders that may not exist yet
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| error_writing(parent.to_path_buf(), e))?;
+    }
+    match link {
+        true => link_dir(source, destination)?,
+        false => move_dir(source, destination)?,
+    }
+
+    if record_manifest {
+        integrity::write(destination)?;
+    }
+    build.folder = destination.to_path_buf();
+    build
+        .write()
+        .map_err(|e| error_writing(destination.to_path_buf(), e))?;
+    emit(Event::BuildInstalled {
+        version: build.info.basic.ver.to_string(),
+        path: destination,
+    });
+
+    Ok(())
+}
+
+/// Finds Blender builds below the given paths, like a library from before blrs, and moves or links them
+/// into `<library>/<repo>/<version>`. Every build is attempted before the first error is returned.
+pub fn adopt(
+    cfg: &BLRSConfig,
+    paths: Vec<PathBuf>,
+    repo: Option<String>,
+    link: bool,
+    dry_run: bool,
+    record_manifest: bool,
+) -> Result<(), CommandError> {
+    let exe_name = OSLaunchTarget::try_default().unwrap().exe_name();
+    let library = cfg
+        .paths
+        .library
+        .canonicalize()
+        .unwrap_or(cfg.paths.library.clone());
+
+    let mut found = vec![];
+    for path in &paths {
+        let path = path
+            .canonicalize()
+            .map_err(|e| error_reading(path.clone(), e))?;
+        find_builds(&path, exe_name, MAX_DEPTH, &mut found);
+    }
+    // Builds that are already in the library have their own build info
+    found.retain(|folder| !folder.starts_with(&library));
+
+    if found.is_empty() {
+        info!["No builds outside the library were found"];
+        return Ok(());
+    }
+
+    let (repo, repo_path) = repo_folder(cfg, repo);
+    let mut planned = vec![];
+    for folder in found {
+        let exe = folder.join(exe_name);
+        info!["Reading the version of {}", exe.display()];
+        let build = match LocalBuild::generate_from_exe(&exe) {
+            Ok(build) => build,
+            Err(e) => {
+                warn![
+                    "Skipping {}, as its version could not be read: {}",
+                    folder.display(),
+                    e
+                ];
+                continue;
+            }
+        };
+
+        let destination = repo_path.join(folder_name(&build.info.basic, &repo));
+        if destination.exists() || planned.iter().any(|(_, _, d)| *d == destination) {
+            warn![
+                "Skipping {}, as {}/{} is already in the library",
+                folder.display(),
+                repo,
+                build.info.basic.ver
+            ];
+            continue;
+        }
+        planned.push((build, folder, destination));
+    }
+
+    let verb = match link {
+        true => "Link",
+        false => "Move",
+    };
+    for (_, source, destination) in &planned {
+        println!["{} {} -> {}", verb, source.display(), destination.display()];
+    }
+    if dry_run {
+        println!["{} build(s) would be adopted", planned.len()];
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&repo_path).map_err(|e| error_writing(repo_path.clone(), e))?;
+    let results: Vec<_> = planned
+        .into_iter()
+        .map(|(build, source, destination)| {
+            adopt_build(build, &source, &destination, link, record_manifest)
+                .inspect_err(|e| error!["Failed to adopt {}: {}", source.display(), e])
+        })
+        .collect();
+
+    let adopted = results.iter().filter(|r| r.is_ok()).count();
+    info!["Adopted {} build(s) into {}", adopted, repo];
+    results.into_iter().find(|r| r.is_err()).unwrap_or(Ok(()))
+}
@@ -0,0 +1,58 @@
+use blrs::search::VersionSearchQuery;
+use log::{info, warn};
+
+use crate::{cli_config::CliConfig, errs::CommandError, resolving::parse_query, tasks::ConfigTask};
+
+/// Saves `name` as a shorthand for `query`. An existing alias of the same name is replaced.
+pub fn set(
+    cli_cfg: &CliConfig,
+    name: String,
+    query: String,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    parse_query(&query)?;
+    if VersionSearchQuery::try_from(name.as_str()).is_ok() {
+        warn![
+            "{} is also a query, which it will no longer be treated as",
+            name
+        ];
+    }
+
+    info!["{} now stands for {}", name, query];
+    let mut new_cli_cfg = cli_cfg.clone();
+    new_cli_cfg.aliases.insert(name, query);
+    Ok(vec![ConfigTask::SetCliConfig(Box::new(new_cli_cfg))])
+}
+
+/// Prints every alias and the query it stands for.
+pub fn list(cli_cfg: &CliConfig) {
+    let mut aliases: Vec<_> = cli_cfg.aliases.iter().collect();
+    if aliases.is_empty() {
+        info!["No aliases have been set"];
+        return;
+    }
+
+    aliases.sort();
+    let width = aliases
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    aliases.into_iter().for_each(|(name, query)| {
+        println![
+            "{} {}",
+            ansi_term::Color::Green.paint(format!["{:<width$}", name]),
+            query
+        ];
+    });
+}
+
+/// Removes an alias. Builds it stood for are kept.
+pub fn remove(cli_cfg: &CliConfig, name: &str) -> Result<Vec<ConfigTask>, CommandError> {
+    let mut new_cli_cfg = cli_cfg.clone();
+    let Some(query) = new_cli_cfg.aliases.remove(name) else {
+        return Err(CommandError::UnknownAlias(name.to_string()));
+    };
+
+    info!["Removed {} ({})", name, query];
+    Ok(vec![ConfigTask::SetCliConfig(Box::new(new_cli_cfg))])
+}
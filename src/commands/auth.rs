@@ -0,0 +1,124 @@
+use std::io::BufRead;
+
+use blrs::BLRSConfig;
+use log::{info, warn};
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{unknown_repo, CommandError, IoErrorOrigin},
+    repo_auth::{delete_from_keyring, save_to_keyring, RepoAuth, Secret},
+    resolving::is_interactive,
+    tasks::ConfigTask,
+};
+
+/// Asks for the secret, or reads it from stdin when prompting is disabled so it can be piped in.
+fn read_secret(prompt: &str) -> Result<String, CommandError> {
+    if !is_interactive() {
+        let mut line = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| CommandError::IoError(IoErrorOrigin::Terminal, e))?;
+        return Ok(line.trim_end().to_string());
+    }
+
+    inquire::Password::new(prompt)
+        .without_confirmation()
+        .prompt()
+        .map_err(|_| CommandError::Cancelled)
+}
+
+/// Stores a repo's secret in the OS credential store and points the repo's credentials at it.
+///
+/// The secret is sent as a bearer token, or as the password for basic auth if `user` is given.
+pub fn login(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    name: &str,
+    user: Option<String>,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let repo = cfg
+        .repos
+        .iter()
+        .find(|r| r.nickname == name || r.repo_id == name)
+        .ok_or_else(|| unknown_repo(cfg, name))?;
+
+    let prompt = match &user {
+        Some(user) => format!["Password of {} for {}:", user, repo.nickname],
+        None => format!["Token for {}:", repo.nickname],
+    };
+    let secret = read_secret(&prompt)?;
+    if secret.is_empty() {
+        return Err(CommandError::NotEnoughInput);
+    }
+
+    save_to_keyring(&repo.repo_id, &secret)?;
+
+    let stored = Secret::Keyring(repo.repo_id.clone());
+    let auth = match user {
+        Some(user) => RepoAuth::Basic {
+            user,
+            password: stored,
+        },
+        None => RepoAuth::Bearer { token: stored },
+    };
+
+    let mut new_cli_cfg = cli_cfg.clone();
+    new_cli_cfg.repo_auth.insert(repo.repo_id.clone(), auth);
+    info![
+        "Saved the credentials of {} to the credential store",
+        repo.nickname
+    ];
+
+    Ok(vec![ConfigTask::SetCliConfig(Box::new(new_cli_cfg))])
+}
+
+/// Removes a repo's secret from the OS credential store, along with the credentials that refer to it.
+pub fn logout(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    name: &str,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let repo = cfg
+        .repos
+        .iter()
+        .find(|r| r.nickname == name || r.repo_id == name)
+        .ok_or_else(|| unknown_repo(cfg, name))?;
+
+    let removed = delete_from_keyring(&repo.repo_id)?;
+
+    let mut new_cli_cfg = cli_cfg.clone();
+    let uses_keyring = matches!(
+        new_cli_cfg.repo_auth.get(&repo.repo_id),
+        Some(
+            RepoAuth::Bearer {
+                token: Secret::Keyring(_)
+            } | RepoAuth::Basic {
+                password: Secret::Keyring(_),
+                ..
+            }
+        )
+    );
+    match (removed, uses_keyring) {
+        (true, _) => info![
+            "Removed the credentials of {} from the credential store",
+            repo.nickname
+        ],
+        (false, true) => warn![
+            "The credential store held no credentials for {}, only the reference to them was removed",
+            repo.nickname
+        ],
+        (false, false) => {
+            warn![
+                "{} has no credentials in the credential store, nothing was removed",
+                repo.nickname
+            ];
+            return Ok(vec![]);
+        }
+    }
+    if uses_keyring {
+        new_cli_cfg.repo_auth.remove(&repo.repo_id);
+    }
+
+    Ok(vec![ConfigTask::SetCliConfig(Box::new(new_cli_cfg))])
+}
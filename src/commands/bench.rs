@@ -0,0 +1,231 @@
+use std::{
+    path::PathBuf,
+    process::{self, Stdio},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use ansi_term::Color;
+use blrs::{search::VersionSearchQuery, BLRSConfig};
+use indicatif::HumanBytes;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{error_writing, CommandError, IoErrorOrigin},
+    resolving::build_query_string,
+    run::{launch_command, select_build},
+};
+
+/// A frame, or an inclusive range of frames, like `5` or `1-10`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frames {
+    pub start: i32,
+    pub end: i32,
+}
+
+impl Frames {
+    /// How many frames the range holds.
+    pub fn len(&self) -> u64 {
+        (self.end - self.start) as u64 + 1
+    }
+}
+
+impl FromStr for Frames {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<i32>()
+                .map_err(|_| format!["expected a frame like 5 or a range like 1-10, got {:?}", s])
+        };
+
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        match start <= end {
+            true => Ok(Self { start, end }),
+            false => Err(format!["the range {:?} ends before it starts", s]),
+        }
+    }
+}
+
+pub struct BenchOptions {
+    pub blend: PathBuf,
+    /// Renders the file's own frame range if not given.
+    pub frames: Option<Frames>,
+    pub runs: usize,
+}
+
+/// How a build did across its runs. Failed runs are not timed.
+struct BenchResult {
+    build: String,
+    times: Vec<Duration>,
+    peak_memory: Option<u64>,
+    /// Why the first failed run failed, if one did.
+    failure: Option<String>,
+}
+
+impl BenchResult {
+    fn mean(&self) -> Option<Duration> {
+        (!self.times.is_empty())
+            .then(|| self.times.iter().sum::<Duration>() / self.times.len() as u32)
+    }
+}
+
+/// Waits for the child, returning its exit code and its peak resident memory in bytes.
+#[cfg(unix)]
+fn wait_with_peak_memory(child: process::Child) -> std::io::Result<(Option<i32>, Option<u64>)> {
+    let mut status = 0;
+    // SAFETY: rusage is plain data, for which all zeroes is a valid value
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: the pid is a child that has not been waited for, and both pointers outlive the call
+        let pid = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut usage) };
+        if pid >= 0 {
+            break;
+        }
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+
+    // Linux reports kilobytes, macOS bytes
+    let unit = match cfg!(target_os = "macos") {
+        true => 1,
+        false => 1024,
+    };
+    let code = libc::WIFEXITED(status).then(|| libc::WEXITSTATUS(status));
+    Ok((code, Some(usage.ru_maxrss as u64 * unit)))
+}
+
+#[cfg(not(unix))]
+fn wait_with_peak_memory(mut child: process::Child) -> std::io::Result<(Option<i32>, Option<u64>)> {
+    Ok((child.wait()?.code(), None))
+}
+
+/// Renders the blendfile with each build, `runs` times each, and prints how they compare.
+/// The first build is the baseline the others are compared to.
+pub fn bench(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    queries: Vec<VersionSearchQuery>,
+    opts: &BenchOptions,
+) -> Result<(), CommandError> {
+    let blend = std::path::absolute(&opts.blend).unwrap_or(opts.blend.clone());
+    let builds = queries
+        .iter()
+        .map(|q| select_build(cfg, q, false))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Renders go somewhere disposable, so the file's own output is never overwritten
+    let output_dir = std::env::temp_dir().join(format!["blrs-bench-{}", Uuid::new_v4()]);
+    std::fs::create_dir_all(&output_dir).map_err(|e| error_writing(output_dir.clone(), e))?;
+
+    let mut results = vec![];
+    for (build, nick) in &builds {
+        let mut result = BenchResult {
+            build: build_query_string(&build.info.basic, nick),
+            times: vec![],
+            peak_memory: None,
+            failure: None,
+        };
+
+        for run in 1..=opts.runs {
+            info!["Rendering with {} ({}/{})", result.build, run, opts.runs];
+            let mut command = launch_command(cli_cfg, build, nick, Some(blend.clone()))?;
+            command
+                .arg("-b")
+                .arg("-o")
+                .arg(output_dir.join("frame_####"));
+            match opts.frames {
+                Some(Frames { start, end }) => {
+                    command.args(["-s", &start.to_string(), "-e", &end.to_string()])
+                }
+                None => &mut command,
+            }
+            .arg("-a")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+            let start = Instant::now();
+            let child = command
+                .spawn()
+                .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+            let (code, peak_memory) = wait_with_peak_memory(child)
+                .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+            let elapsed = start.elapsed();
+
+            if code != Some(0) {
+                let failure = match code {
+                    Some(code) => format!["exit code {}", code],
+                    None => "killed".to_string(),
+                };
+                error!["{} failed to render: {}", result.build, failure];
+                result.failure = Some(failure);
+                break;
+            }
+            result.times.push(elapsed);
+            result.peak_memory = result.peak_memory.max(peak_memory);
+        }
+        results.push(result);
+    }
+
+    if let Err(e) = std::fs::remove_dir_all(&output_dir) {
+        error!["Failed to remove {}: {}", output_dir.display(), e];
+    }
+
+    print_table(&results);
+
+    match results.iter().filter(|r| r.failure.is_some()).count() {
+        0 => Ok(()),
+        n => Err(CommandError::FailedBenchmarks(n)),
+    }
+}
+
+fn print_table(results: &[BenchResult]) {
+    let seconds = |d: Duration| format!["{:.2}s", d.as_secs_f64()];
+    let baseline = results.first().and_then(BenchResult::mean);
+    let width = results.iter().map(|r| r.build.len()).max().unwrap_or(0);
+
+    println![
+        "\n{:<width$}  {:>9}  {:>9}  {:>12}  vs first",
+        "build", "mean", "fastest", "peak memory"
+    ];
+    for (idx, result) in results.iter().enumerate() {
+        let (mean, fastest) = match (result.mean(), result.times.iter().min()) {
+            (Some(mean), Some(fastest)) => (seconds(mean), seconds(*fastest)),
+            _ => ("-".to_string(), "-".to_string()),
+        };
+        let memory = result
+            .peak_memory
+            .map(|m| HumanBytes(m).to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let comparison = match (&result.failure, result.mean(), baseline) {
+            (Some(failure), _, _) => Color::Red.paint(format!["failed, {}", failure]),
+            _ if idx == 0 => Color::White.dimmed().paint("baseline".to_string()),
+            (None, Some(mean), Some(baseline)) => {
+                let change = (mean.as_secs_f64() / baseline.as_secs_f64() - 1.0) * 100.0;
+                let text = format!["{:+.1}%", change];
+                // Small differences are likely noise
+                match change {
+                    c if c > 5.0 => Color::Red.paint(text),
+                    c if c < -5.0 => Color::Green.paint(text),
+                    _ => Color::White.dimmed().paint(text),
+                }
+            }
+            _ => Color::White.dimmed().paint("-".to_string()),
+        };
+
+        println![
+            "{:<width$}  {:>9}  {:>9}  {:>12}  {}",
+            result.build, mean, fastest, memory, comparison
+        ];
+    }
+}
@@ -0,0 +1,324 @@
+use std::path::{Path, PathBuf};
+
+use blrs::BLRSConfig;
+use indicatif::HumanBytes;
+use log::{error, info};
+
+use crate::{
+    archive_cache, deletion, dirs,
+    errs::{error_reading, unknown_repo, CommandError},
+    launch_history::LaunchHistory,
+    repo_cache,
+    resolving::{confirm, walk_layout},
+};
+
+use super::{fetcher::listing_path, http_cache::Validators, info::dir_size, pull::ArchiveFormat};
+
+/// What `clean` deletes. Installed builds are never touched, `prune` and `rm` remove those.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CleanTarget {
+    /// The archives kept in the download cache after their builds were extracted.
+    Archives,
+    /// The cached build lists of the repos. They are downloaded again by the next fetch or pull.
+    RepoCache,
+    /// Archives, partial downloads, and staging folders left behind by cancelled or failed pulls and installs.
+    Temp,
+    /// The history of launched builds, used by `recent` and `run --open-last`.
+    History,
+}
+
+impl CleanTarget {
+    pub const ALL: [CleanTarget; 4] = [
+        CleanTarget::Archives,
+        CleanTarget::RepoCache,
+        CleanTarget::Temp,
+        CleanTarget::History,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            CleanTarget::Archives => "Cached archives",
+            CleanTarget::RepoCache => "Cached build lists",
+            CleanTarget::Temp => "Leftover downloads",
+            CleanTarget::History => "Launch history",
+        }
+    }
+
+    fn files(&self, cfg: &BLRSConfig) -> Result<Vec<(PathBuf, u64)>, CommandError> {
+        match self {
+            CleanTarget::Archives => Ok(archive_cache::archives()),
+            CleanTarget::RepoCache => cached_build_lists(cfg),
+            CleanTarget::Temp => find_leftovers(cfg),
+            CleanTarget::History => Ok(with_sizes([LaunchHistory::path()])),
+        }
+    }
+}
+
+/// The files that exist, with their sizes.
+fn with_sizes(paths: impl IntoIterator<Item = PathBuf>) -> Vec<(PathBuf, u64)> {
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let m = path.metadata().ok().filter(|m| m.is_file())?;
+            Some((path, m.len()))
+        })
+        .collect()
+}
+
+/// The cached build list of every repo, the validators and listings saved next to them, and the parsed repos.
+fn cached_build_lists(cfg: &BLRSConfig) -> Result<Vec<(PathBuf, u64)>, CommandError> {
+    let folder = &cfg.paths.remote_repos;
+    if !folder.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files = with_sizes(
+        folder
+            .read_dir()
+            .map_err(|e| error_reading(folder.clone(), e))?
+            .flatten()
+            .map(|item| item.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == "json" || ext == "validators" || ext == "listed")
+                    || path.ends_with(repo_cache::CACHE_FILE)
+            }),
+    );
+    files.sort();
+    Ok(files)
+}
+
+/// Whether a file in a repo folder was left behind by a pull. Finished pulls delete their archive,
+/// so any archive or partial download still there belongs to a pull that was cancelled or failed.
+fn is_leftover(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "part") || ArchiveFormat::from_path(path).is_some()
+}
+
+/// Whether a folder in a repo folder is where a pull or install extracted a build before moving it into place,
+/// or where a reinstalled build was moved aside. They are only left behind when blrs was killed, or exited on
+/// a second Ctrl+C.
+fn is_staging(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            [".pulling-", ".installing-", ".replacing-"]
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+        })
+}
+
+/// Finds the leftover downloads and staging folders in a repo folder, with their sizes.
+fn leftovers_in(folder: &Path) -> Result<Vec<(PathBuf, u64)>, CommandError> {
+    if !folder.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut leftovers = vec![];
+    for item in folder
+        .read_dir()
+        .map_err(|e| error_reading(folder.to_path_buf(), e))?
+    {
+        let Ok(item) = item else { continue };
+        let path = item.path();
+        match item.metadata() {
+            Ok(m) if m.is_file() && is_leftover(&path) => leftovers.push((path, m.len())),
+            Ok(m) if m.is_dir() && is_staging(&path) => {
+                let size = dir_size(&path).unwrap_or_default();
+                leftovers.push((path, size));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(leftovers)
+}
+
+/// Finds the leftover downloads and staging folders in every repo folder, and the partial downloads in the download folder, with their sizes.
+pub(super) fn find_leftovers(cfg: &BLRSConfig) -> Result<Vec<(PathBuf, u64)>, CommandError> {
+    // Archives in the download folder are the archive cache, which trims itself
+    let mut leftovers: Vec<_> = leftovers_in(&dirs::archive_dir())?
+        .into_iter()
+        .filter(|(path, _)| path.extension().is_some_and(|ext| ext == "part"))
+        .collect();
+    if !cfg.paths.library.exists() {
+        return Ok(leftovers);
+    }
+
+    for repo in cfg
+        .paths
+        .library
+        .read_dir()
+        .map_err(|e| error_reading(cfg.paths.library.clone(), e))?
+    {
+        let repo = repo.map_err(|e| error_reading(cfg.paths.library.clone(), e))?;
+        if repo.file_type().is_ok_and(|t| t.is_dir()) {
+            leftovers.extend(leftovers_in(&repo.path())?);
+            // Pulls stage builds next to where they go, which nested library layouts put deeper
+            for folder in walk_layout(&repo.path()).0 {
+                leftovers.extend(leftovers_in(&folder)?);
+            }
+        }
+    }
+
+    leftovers.sort();
+    Ok(leftovers)
+}
+
+fn print_files(files: &[(PathBuf, u64)]) {
+    files.iter().for_each(|(path, size)| {
+        println![
+            "  {}  {}",
+            ansi_term::Color::Purple.paint(HumanBytes(*size).to_string()),
+            path.display()
+        ];
+    });
+}
+
+fn freed_label() -> ansi_term::ANSIString<'static> {
+    ansi_term::Color::Green
+        .bold()
+        .paint(match deletion::uses_trash() {
+            true => "Moved to the trash:",
+            false => "Freed",
+        })
+}
+
+/// Removes the files according to the deletion policy, returning how many bytes are gone.
+/// Every file is attempted before the first error is returned.
+fn remove_files(files: &[(PathBuf, u64)]) -> (u64, Result<(), CommandError>) {
+    let results: Vec<_> = files
+        .iter()
+        .map(|(path, _)| {
+            deletion::remove(path)
+                .map(|_| ())
+                .inspect_err(|e| error!["Failed to delete {}: {}", path.display(), e])
+        })
+        .collect();
+
+    let freed: u64 = files
+        .iter()
+        .filter(|(path, _)| !path.exists())
+        .map(|(_, size)| size)
+        .sum();
+
+    (
+        freed,
+        results.into_iter().find(|r| r.is_err()).unwrap_or(Ok(())),
+    )
+}
+
+/// Lists the files, asks for confirmation, and removes them according to the deletion policy.
+fn delete_files(files: &[(PathBuf, u64)], dry_run: bool) -> Result<(), CommandError> {
+    let total: u64 = files.iter().map(|(_, size)| size).sum();
+    println!["{} file(s) will be deleted:", files.len()];
+    print_files(files);
+    println!["Total: {}", HumanBytes(total)];
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if !confirm("Delete these files?", false) {
+        return Err(CommandError::Cancelled);
+    }
+
+    let (freed, result) = remove_files(files);
+    println!["{} {}", freed_label(), HumanBytes(freed)];
+    result
+}
+
+/// Deletes the files of each target, reporting how much space every one of them took.
+/// Without targets, only the leftover downloads of cancelled or failed pulls are deleted.
+pub fn clean(cfg: &BLRSConfig, targets: &[CleanTarget], dry_run: bool) -> Result<(), CommandError> {
+    let mut targets = match targets.is_empty() {
+        true => vec![CleanTarget::Temp],
+        false => targets.to_vec(),
+    };
+    targets.sort();
+    targets.dedup();
+
+    let mut found = vec![];
+    for target in targets {
+        let files = target.files(cfg)?;
+        if files.is_empty() {
+            info!["{}: nothing to delete", target.label()];
+        } else {
+            found.push((target, files));
+        }
+    }
+    if found.is_empty() {
+        return Ok(());
+    }
+
+    for (target, files) in &found {
+        let size: u64 = files.iter().map(|(_, size)| size).sum();
+        println![
+            "{}: {} file(s), {}",
+            ansi_term::Style::new().bold().paint(target.label()),
+            files.len(),
+            HumanBytes(size)
+        ];
+        print_files(files);
+    }
+    let total: u64 = found
+        .iter()
+        .flat_map(|(_, files)| files)
+        .map(|(_, size)| size)
+        .sum();
+    println!["Total: {}", HumanBytes(total)];
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if !confirm("Delete these files?", false) {
+        return Err(CommandError::Cancelled);
+    }
+
+    let mut result = Ok(());
+    let mut freed_total = 0;
+    for (target, files) in &found {
+        let (freed, r) = remove_files(files);
+        println![
+            "{}: {} {}",
+            target.label(),
+            freed_label(),
+            HumanBytes(freed)
+        ];
+        freed_total += freed;
+        result = result.and(r);
+    }
+    if found.len() > 1 {
+        println!["Total: {} {}", freed_label(), HumanBytes(freed_total)];
+    }
+
+    result
+}
+
+/// Deletes a repo's cached build list and its leftover downloads, so the next fetch starts clean.
+/// Installed builds are left alone.
+pub fn purge_cache(cfg: &BLRSConfig, name: &str) -> Result<(), CommandError> {
+    let repo = cfg
+        .repos
+        .iter()
+        .find(|r| r.nickname == name || r.repo_id == name)
+        .ok_or_else(|| unknown_repo(cfg, name))?;
+
+    let mut files = leftovers_in(&cfg.paths.path_to_repo(repo))?;
+    let cache = cfg.paths.remote_repos.join(repo.repo_id.clone() + ".json");
+    for path in [Validators::path(&cache), listing_path(&cache)] {
+        if let Ok(m) = path.metadata() {
+            files.insert(0, (path, m.len()));
+        }
+    }
+    if let Ok(m) = cache.metadata() {
+        files.insert(0, (cache, m.len()));
+    }
+
+    if files.is_empty() {
+        info!["{} has no cached build list or downloads", repo.nickname];
+        return Ok(());
+    }
+
+    delete_files(&files, false)
+}
@@ -0,0 +1,274 @@
+use std::{path::PathBuf, process};
+
+use blrs::{search::VersionSearchQuery, BLRSConfig};
+use log::{error, info};
+use serde::Serialize;
+use toml::Value;
+
+use crate::{
+    cli_config::CliConfig,
+    dirs,
+    errs::{error_reading, error_renaming, error_writing, CommandError, IoErrorOrigin},
+    resolving::{build_query_string, confirm},
+    run::select_build,
+    tasks::ConfigTask,
+};
+
+/// Changes to the launch settings of a single build. `None` leaves a setting untouched.
+#[derive(Debug, Clone, Default)]
+pub struct BuildSettings {
+    pub args: Option<Vec<String>>,
+    pub env: Vec<(String, String)>,
+    pub exe: Option<String>,
+    pub name: Option<String>,
+    pub clear: bool,
+}
+
+impl BuildSettings {
+    fn is_empty(&self) -> bool {
+        self.args.is_none()
+            && self.env.is_empty()
+            && self.exe.is_none()
+            && self.name.is_none()
+            && !self.clear
+    }
+}
+
+/// Shows or updates the custom launch settings of an installed build.
+pub fn configure_build(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    query: VersionSearchQuery,
+    settings: BuildSettings,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let (mut build, nick) = select_build(cfg, &query, false)?;
+    let key = build_query_string(&build.info.basic, &nick);
+
+    if settings.is_empty() {
+        println!["{}", key];
+        println!["  name: {:?}", build.info.custom_name];
+        println!["  exe:  {:?}", build.info.custom_exe];
+        println!["  env:  {:?}", build.info.custom_env];
+        println!["  args: {:?}", cli_cfg.build_args.get(&key)];
+        return Ok(vec![]);
+    }
+
+    let mut tasks = vec![];
+    if settings.clear {
+        build.info.custom_name = None;
+        build.info.custom_exe = None;
+        build.info.custom_env = None;
+        tasks.push(ConfigTask::SetBuildArgs(key.clone(), None));
+    }
+    if let Some(name) = settings.name {
+        build.info.custom_name = Some(name).filter(|n| !n.is_empty());
+    }
+    if let Some(exe) = settings.exe {
+        build.info.custom_exe = Some(exe).filter(|e| !e.is_empty());
+    }
+    if !settings.env.is_empty() {
+        build
+            .info
+            .custom_env
+            .get_or_insert_with(Default::default)
+            .extend(settings.env);
+    }
+    if let Some(args) = settings.args {
+        let args: Vec<String> = args.into_iter().filter(|a| !a.is_empty()).collect();
+        tasks.push(ConfigTask::SetBuildArgs(
+            key.clone(),
+            (!args.is_empty()).then_some(args),
+        ));
+    }
+
+    build
+        .write()
+        .map_err(|e| error_writing(build.folder.clone(), e))?;
+    info!["Updated the launch settings of {}", key];
+
+    Ok(tasks)
+}
+
+/// Where the blrs config is saved.
+pub fn config_path() -> PathBuf {
+    dirs::config_dir().join("config.toml")
+}
+
+fn to_value<T: Serialize>(t: &T) -> Value {
+    Value::try_from(t).expect("configs can always be represented as TOML")
+}
+
+/// Splits a key into the config it belongs to and the path inside it.
+/// Keys starting with `cli.` address the blrs-cli settings.
+fn split_key(key: &str) -> (bool, Vec<&str>) {
+    match key.strip_prefix("cli.") {
+        Some(rest) => (true, rest.split('.').collect()),
+        None => (false, key.split('.').collect()),
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |v, key| v.get(key))
+}
+
+/// Parses a value the way it would be written in the TOML file, falling back to a plain string.
+fn parse_value(s: &str) -> Value {
+    toml::from_str::<toml::Table>(&format!["v = {}", s])
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| Value::String(s.to_string()))
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Table(t) => toml::to_string_pretty(t).unwrap_or_default(),
+        v => v.to_string(),
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::Table(t) => t.iter().for_each(|(k, v)| {
+            let key = match prefix {
+                "" => k.clone(),
+                p => format!["{p}.{k}"],
+            };
+            flatten(&key, v, out)
+        }),
+        v => out.push((prefix.to_string(), v.to_string())),
+    }
+}
+
+/// Prints every setting as `key = value`.
+pub fn list(cfg: &BLRSConfig, cli_cfg: &CliConfig) {
+    let mut entries = vec![];
+    flatten("", &to_value(cfg), &mut entries);
+    flatten("cli", &to_value(cli_cfg), &mut entries);
+
+    entries
+        .into_iter()
+        .for_each(|(k, v)| println!["{} = {}", k, v]);
+}
+
+pub fn get(cfg: &BLRSConfig, cli_cfg: &CliConfig, key: &str) -> Result<(), CommandError> {
+    let (is_cli, path) = split_key(key);
+    let root = match is_cli {
+        true => to_value(cli_cfg),
+        false => to_value(cfg),
+    };
+
+    match lookup(&root, &path) {
+        Some(value) => {
+            println!["{}", format_value(value)];
+            Ok(())
+        }
+        None => Err(CommandError::UnknownConfigKey(key.to_string())),
+    }
+}
+
+fn set_in(root: &mut Value, path: &[&str], value: Value) -> bool {
+    let Some((last, parents)) = path.split_last() else {
+        return false;
+    };
+    let parent = parents.iter().try_fold(root, |v, key| v.get_mut(key));
+
+    match parent {
+        Some(Value::Table(t)) => {
+            t.insert(last.to_string(), value);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Changes a single setting. The changed config is validated before it is saved.
+pub fn set(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    key: &str,
+    value: &str,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let (is_cli, path) = split_key(key);
+    let mut root = match is_cli {
+        true => to_value(cli_cfg),
+        false => to_value(cfg),
+    };
+
+    if !set_in(&mut root, &path, parse_value(value)) {
+        return Err(CommandError::UnknownConfigKey(key.to_string()));
+    }
+
+    let invalid =
+        |e: toml::de::Error| CommandError::InvalidConfig(format!["{key}: {}", e.message()]);
+    let task = match is_cli {
+        true => {
+            let new: CliConfig = root.try_into().map_err(invalid)?;
+            // Unknown keys are silently dropped when deserializing, so make sure it stuck
+            lookup(&to_value(&new), &path)
+                .ok_or_else(|| CommandError::UnknownConfigKey(key.to_string()))?;
+            ConfigTask::SetCliConfig(Box::new(new))
+        }
+        false => {
+            let new: BLRSConfig = root.try_into().map_err(invalid)?;
+            lookup(&to_value(&new), &path)
+                .ok_or_else(|| CommandError::UnknownConfigKey(key.to_string()))?;
+            ConfigTask::SetConfig(Box::new(new))
+        }
+    };
+
+    info!["Set {} to {}", key, value];
+    Ok(vec![task])
+}
+
+fn editor() -> process::Command {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| match cfg!(windows) {
+            true => "notepad".to_string(),
+            false => "vi".to_string(),
+        });
+
+    // Editors are often configured with arguments, like `code --wait`
+    let mut parts = editor.split_whitespace();
+    let mut command = process::Command::new(parts.next().unwrap_or("vi"));
+    command.args(parts);
+    command
+}
+
+/// Opens the config in an editor, and only saves it if it can still be loaded.
+pub fn edit(cfg: &BLRSConfig) -> Result<(), CommandError> {
+    let path = config_path();
+    let draft = path.with_extension("toml.draft");
+
+    std::fs::create_dir_all(dirs::config_dir())
+        .map_err(|e| error_writing(dirs::config_dir().to_path_buf(), e))?;
+    let current = match path.exists() {
+        true => std::fs::read_to_string(&path).map_err(|e| error_reading(path.clone(), e))?,
+        false => toml::to_string_pretty(cfg).unwrap_or_default(),
+    };
+    std::fs::write(&draft, current).map_err(|e| error_writing(draft.clone(), e))?;
+
+    loop {
+        editor()
+            .arg(&draft)
+            .status()
+            .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+
+        match BLRSConfig::default_figment(Some(draft.clone())).extract::<BLRSConfig>() {
+            Ok(_) => break,
+            Err(e) => {
+                error!["The edited config is invalid: {}", e];
+                if !confirm("Edit it again?", true) {
+                    let _ = std::fs::remove_file(&draft);
+                    return Err(CommandError::InvalidConfig(e.to_string()));
+                }
+            }
+        }
+    }
+
+    std::fs::rename(&draft, &path).map_err(|e| error_renaming(draft, path.clone(), e))?;
+    info!["Saved {}", path.display()];
+
+    Ok(())
+}
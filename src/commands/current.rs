@@ -0,0 +1,166 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use blrs::{search::VersionSearchQuery, BLRSConfig, LocalBuild};
+use log::{info, warn};
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{error_writing, CommandError, IoErrorOrigin},
+    resolving::build_query_string,
+    run::{launch_command, select_build},
+};
+
+/// The link that always points at the build selected with `blrs use`.
+///
+/// It lives next to the library instead of inside it, so it is not mistaken for a repo.
+pub fn current_link(cfg: &BLRSConfig) -> PathBuf {
+    let library = &cfg.paths.library;
+    library.parent().unwrap_or(library).join("current")
+}
+
+/// Points the `current` link at a build, or shows where it points if no query is given.
+pub fn use_build(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    query: Option<VersionSearchQuery>,
+    shim_dir: Option<PathBuf>,
+) -> Result<(), CommandError> {
+    let link = current_link(cfg);
+
+    let Some(query) = query else {
+        match fs::read_link(&link) {
+            Ok(target) => println!["{}", target.display()],
+            Err(_) => info!["No build is in use"],
+        }
+        return Ok(());
+    };
+
+    let (build, nick) = select_build(cfg, &query, false)?;
+
+    remove_link(&link)?;
+    create_link(&build.folder, &link)?;
+    info![
+        "{} now points to {}",
+        link.display(),
+        build_query_string(&build.info.basic, &nick)
+    ];
+
+    if let Some(dir) = shim_dir {
+        let shim = write_shim(cli_cfg, &build, &nick, &link, &dir)?;
+        info!["Installed a launcher at {}", shim.display()];
+    }
+
+    Ok(())
+}
+
+/// Removes the `current` link if it points at the given build folder. Called before a build is deleted.
+pub fn unlink_if_current(cfg: &BLRSConfig, folder: &Path) {
+    let link = current_link(cfg);
+    if fs::read_link(&link).is_ok_and(|target| target == folder) {
+        match remove_link(&link) {
+            Ok(()) => warn!["The build in use was removed. Select another one with `blrs use`"],
+            Err(e) => warn!["Failed to remove {}: {}", link.display(), e],
+        }
+    }
+}
+
+fn remove_link(link: &Path) -> Result<(), CommandError> {
+    let Ok(meta) = fs::symlink_metadata(link) else {
+        return Ok(());
+    };
+    if !meta.file_type().is_symlink() {
+        return Err(error_writing(
+            link.to_path_buf(),
+            std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "refusing to replace something that is not a link",
+            ),
+        ));
+    }
+
+    // Junctions are directories on Windows, symlinks are files everywhere else
+    #[cfg(windows)]
+    let result = fs::remove_dir(link);
+    #[cfg(not(windows))]
+    let result = fs::remove_file(link);
+
+    result.map_err(|e| CommandError::IoError(IoErrorOrigin::DeletingObject(link.to_path_buf()), e))
+}
+
+#[cfg(not(windows))]
+fn create_link(target: &Path, link: &Path) -> Result<(), CommandError> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent).map_err(|e| error_writing(parent.to_path_buf(), e))?;
+    }
+    std::os::unix::fs::symlink(target, link).map_err(|e| error_writing(link.to_path_buf(), e))
+}
+
+#[cfg(windows)]
+fn create_link(target: &Path, link: &Path) -> Result<(), CommandError> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent).map_err(|e| error_writing(parent.to_path_buf(), e))?;
+    }
+    // Junctions do not need administrator rights, unlike directory symlinks
+    let status = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(link)
+        .arg(target)
+        .output()
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+
+    match status.status.success() {
+        true => Ok(()),
+        false => Err(error_writing(
+            link.to_path_buf(),
+            std::io::Error::other(String::from_utf8_lossy(&status.stderr).to_string()),
+        )),
+    }
+}
+
+/// Writes a `blender` launcher script into `dir` that runs the executable through the `current` link.
+fn write_shim(
+    cli_cfg: &CliConfig,
+    build: &LocalBuild,
+    nick: &str,
+    link: &Path,
+    dir: &Path,
+) -> Result<PathBuf, CommandError> {
+    let command = launch_command(cli_cfg, build, nick, None)?;
+    let program = PathBuf::from(command.get_program());
+    let exe = match program.strip_prefix(&build.folder) {
+        Ok(relative) => link.join(relative),
+        Err(_) => program,
+    };
+
+    fs::create_dir_all(dir).map_err(|e| error_writing(dir.to_path_buf(), e))?;
+
+    #[cfg(windows)]
+    let (shim, script) = (
+        dir.join("blender.cmd"),
+        format!["@echo off\r\n\"{}\" %*\r\n", exe.display()],
+    );
+    #[cfg(not(windows))]
+    let (shim, script) = (
+        dir.join("blender"),
+        format![
+            "#!/bin/sh\n# Generated by blrs. Launches the build selected with `blrs use`.\nexec \"{}\" \"$@\"\n",
+            exe.display()
+        ],
+    );
+
+    fs::write(&shim, script).map_err(|e| error_writing(shim.clone(), e))?;
+
+    #[cfg(not(windows))]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&shim, fs::Permissions::from_mode(0o755))
+            .map_err(|e| error_writing(shim.clone(), e))?;
+    }
+
+    Ok(shim)
+}
@@ -0,0 +1,37 @@
+use blrs::{search::VersionSearchQuery, BLRSConfig};
+use log::info;
+
+use crate::{
+    cli_config::CliConfig, errs::CommandError, resolving::build_query_string, run::select_build,
+    tasks::ConfigTask,
+};
+
+/// Shows, sets, or clears the build that is launched when no build is specified.
+pub fn set_default(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    query: Option<VersionSearchQuery>,
+    unset: bool,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    if unset {
+        info!["Cleared the default build"];
+        return Ok(vec![ConfigTask::SetDefaultBuild(None)]);
+    }
+
+    match query {
+        None => {
+            match &cli_cfg.default_build {
+                Some(default) => println!["{}", default],
+                None => info!["No default build has been set"],
+            }
+            Ok(vec![])
+        }
+        Some(query) => {
+            let (build, nick) = select_build(cfg, &query, false)?;
+            let default = build_query_string(&build.info.basic, &nick);
+
+            info!["Set the default build to {}", default];
+            Ok(vec![ConfigTask::SetDefaultBuild(Some(default))])
+        }
+    }
+}
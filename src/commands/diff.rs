@@ -0,0 +1,133 @@
+use std::path::Path;
+
+use ansi_term::Color;
+use blrs::{search::VersionSearchQuery, BLRSConfig, BasicBuildInfo};
+use log::info;
+
+use crate::{
+    errs::CommandError,
+    integrity::{self, FileManifest},
+    resolving::build_query_string,
+};
+
+use super::info::{describable_builds, find_described, Described};
+
+/// The manifest saved when the build was pulled, or a freshly generated one for builds that have none.
+fn manifest_of(folder: &Path) -> Result<FileManifest, CommandError> {
+    match integrity::read(folder)? {
+        Some(manifest) => Ok(manifest),
+        None => {
+            info!["{} has no manifest, hashing its files", folder.display()];
+            integrity::generate(folder)
+        }
+    }
+}
+
+fn print_field(key: &str, a: String, b: String) {
+    let key = Color::White.dimmed().paint(format!["{:>12}", key]);
+    match a == b {
+        true => println!["{} {}", key, a],
+        false => println![
+            "{} {} -> {}",
+            key,
+            Color::Red.paint(a),
+            Color::Green.paint(b)
+        ],
+    }
+}
+
+fn print_builds(a: (&BasicBuildInfo, &str), b: (&BasicBuildInfo, &str)) {
+    let (va, vb) = (a.0.version(), b.0.version());
+    print_field("repo:", a.1.to_string(), b.1.to_string());
+    print_field(
+        "version:",
+        format!["{}.{}.{}", va.major, va.minor, va.patch],
+        format!["{}.{}.{}", vb.major, vb.minor, vb.patch],
+    );
+    print_field("branch:", va.pre.to_string(), vb.pre.to_string());
+    print_field("hash:", va.build.to_string(), vb.build.to_string());
+    print_field(
+        "date:",
+        a.0.commit_dt.to_string(),
+        b.0.commit_dt.to_string(),
+    );
+
+    let elapsed = b.0.commit_dt - a.0.commit_dt;
+    if !elapsed.is_zero() {
+        println![
+            "{:>12} {} {}",
+            "",
+            humantime::format_duration(elapsed.abs().to_std().unwrap_or_default()),
+            match elapsed > chrono::TimeDelta::zero() {
+                true => "newer",
+                false => "older",
+            }
+        ];
+    }
+}
+
+/// Compares the versions of two builds and, if both are installed, the files in their folders.
+pub fn diff(
+    cfg: &BLRSConfig,
+    first: VersionSearchQuery,
+    second: VersionSearchQuery,
+    list_files: bool,
+) -> Result<(), CommandError> {
+    let builds = describable_builds(cfg)?;
+    let (a, nick_a) = find_described(&builds, &first)?;
+    let (b, nick_b) = find_described(&builds, &second)?;
+
+    println![
+        "{} -> {}",
+        Color::Red
+            .bold()
+            .paint(build_query_string(a.as_ref(), &nick_a)),
+        Color::Green
+            .bold()
+            .paint(build_query_string(b.as_ref(), &nick_b))
+    ];
+    print_builds((a.as_ref(), &nick_a), (b.as_ref(), &nick_b));
+
+    let (Described::Installed(a), Described::Installed(b)) = (&a, &b) else {
+        println![
+            "{} only compared when both builds are installed",
+            Color::White.dimmed().paint(format!["{:>12}", "files:"])
+        ];
+        return Ok(());
+    };
+
+    let differences = integrity::diff(&manifest_of(&a.folder)?, &manifest_of(&b.folder)?);
+    println![
+        "{} {} changed, {} added, {} removed",
+        Color::White.dimmed().paint(format!["{:>12}", "files:"]),
+        differences.modified.len(),
+        differences.extra.len(),
+        differences.missing.len()
+    ];
+
+    if list_files {
+        let mut lines: Vec<_> = differences
+            .modified
+            .iter()
+            .map(|f| (f, Color::Yellow.paint("~")))
+            .chain(
+                differences
+                    .extra
+                    .iter()
+                    .map(|f| (f, Color::Green.paint("+"))),
+            )
+            .chain(
+                differences
+                    .missing
+                    .iter()
+                    .map(|f| (f, Color::Red.paint("-"))),
+            )
+            .collect();
+        lines.sort_by_key(|(f, _)| *f);
+        lines
+            .into_iter()
+            .for_each(|(f, marker)| println!["{:>12} {} {}", "", marker, f]);
+    }
+
+    Ok(())
+}
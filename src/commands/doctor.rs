@@ -0,0 +1,265 @@
+use std::path::Path;
+
+use ansi_term::Color;
+use blrs::{fetching::build_repository::BuildRepo, BLRSConfig};
+use chrono::TimeDelta;
+use futures::future::join_all;
+use indicatif::HumanBytes;
+use uuid::Uuid;
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{CommandError, IoErrorOrigin},
+    repo_auth::client_for,
+    repo_cache::read_repos,
+};
+
+use super::{clean::find_leftovers, config_path, fetcher::cache_age, ls::find_problems};
+
+/// Build lists older than this are reported, as they likely miss new builds.
+const OLD_CACHE: TimeDelta = TimeDelta::days(7);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Health {
+    Ok,
+    Warning,
+    Failure,
+}
+
+struct Check {
+    name: &'static str,
+    health: Health,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            health: Health::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warning(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            health: Health::Warning,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn failure(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            health: Health::Failure,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn print(&self) {
+        let status = match self.health {
+            Health::Ok => Color::Green.paint(format!["{:<4}", "ok"]),
+            Health::Warning => Color::Yellow.paint(format!["{:<4}", "warn"]),
+            Health::Failure => Color::Red.bold().paint(format!["{:<4}", "fail"]),
+        };
+        println!["{} {:<8} {}", status, self.name, self.detail];
+        if let Some(fix) = &self.fix {
+            println!["{:<13} {}", "", Color::White.dimmed().paint(fix)];
+        }
+    }
+}
+
+fn check_config_file(path: &Path) -> Check {
+    if !path.exists() {
+        return Check::ok(
+            "config",
+            format!["{} does not exist, using the defaults", path.display()],
+        );
+    }
+
+    match std::fs::read_to_string(path).map(|data| toml::from_str::<toml::Table>(&data)) {
+        Ok(Ok(_)) => Check::ok("config", format!["{} parses", path.display()]),
+        Ok(Err(e)) => Check::failure(
+            "config",
+            format!["{} is not valid TOML: {}", path.display(), e.message()],
+            "fix the file with `blrs config edit`, or remove it to start over",
+        ),
+        Err(e) => Check::failure(
+            "config",
+            format!["{} could not be read: {}", path.display(), e],
+            "check the permissions of the file",
+        ),
+    }
+}
+
+fn check_library(library: &Path) -> Check {
+    if !library.exists() {
+        return Check::failure(
+            "library",
+            format!["{} does not exist", library.display()],
+            "create it, or point `paths.library` at an existing folder",
+        );
+    }
+
+    let probe = library.join(format![".blrs-doctor-{}", Uuid::new_v4()]);
+    match std::fs::write(&probe, b"") {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::ok("library", format!["{} is writable", library.display()])
+        }
+        Err(e) => Check::failure(
+            "library",
+            format!["{} is not writable: {}", library.display(), e],
+            "check the permissions of the folder",
+        ),
+    }
+}
+
+fn check_cache(cfg: &BLRSConfig, repo: &BuildRepo) -> Check {
+    let fix = format!["run `blrs fetch --repo {}`", repo.nickname];
+    match cache_age(cfg, repo) {
+        None => Check::warning(
+            "cache",
+            format!["{} has never been fetched", repo.nickname],
+            fix,
+        ),
+        Some(age) if age > OLD_CACHE => Check::warning(
+            "cache",
+            format![
+                "{} was fetched {} ago",
+                repo.nickname,
+                humantime::format_duration(age.to_std().unwrap_or_default())
+            ],
+            fix,
+        ),
+        Some(_) => Check::ok("cache", format!["{} is up to date", repo.nickname]),
+    }
+}
+
+async fn check_network(cfg: &BLRSConfig, cli_cfg: &CliConfig, repo: &BuildRepo) -> Check {
+    let url = repo.url();
+    let client = match client_for(cfg, &cli_cfg.repo_auth, repo, &url) {
+        Ok(client) => client,
+        Err(e) => {
+            return Check::failure(
+                "network",
+                format!["no client could be made for {}: {}", repo.nickname, e],
+                "check the proxy and credentials of the repo",
+            )
+        }
+    };
+
+    match client.head(url.clone()).send().await {
+        Ok(r) if r.status().is_success() || r.status().is_redirection() => {
+            Check::ok("network", format!["{} is reachable", url])
+        }
+        Ok(r) if r.status().as_u16() == 401 || r.status().as_u16() == 403 => Check::warning(
+            "network",
+            format!["{} refused the request ({})", url, r.status()],
+            format![
+                "check the credentials with `blrs repos set-auth {}`",
+                repo.nickname
+            ],
+        ),
+        Ok(r) => Check::warning(
+            "network",
+            format!["{} responded with {}", url, r.status()],
+            "the host may be down, try again later",
+        ),
+        Err(e) => Check::failure(
+            "network",
+            format!["{} is unreachable: {}", url, e],
+            "check your connection, or set a proxy with --proxy or `cli.network.proxy`",
+        ),
+    }
+}
+
+fn check_leftovers(cfg: &BLRSConfig) -> Check {
+    match find_leftovers(cfg) {
+        Ok(leftovers) if leftovers.is_empty() => Check::ok("files", "no leftover downloads"),
+        Ok(leftovers) => Check::warning(
+            "files",
+            format![
+                "{} leftover download(s) take up {}",
+                leftovers.len(),
+                HumanBytes(leftovers.iter().map(|(_, size)| size).sum())
+            ],
+            "run `blrs clean` to delete them",
+        ),
+        Err(e) => Check::failure(
+            "files",
+            format!["the library could not be searched: {}", e],
+            "check the permissions of the library",
+        ),
+    }
+}
+
+fn check_builds(cfg: &BLRSConfig) -> Vec<Check> {
+    let repos = match read_repos(cfg.repos.clone(), &cfg.paths, false) {
+        Ok(repos) => repos,
+        Err(e) => {
+            return vec![Check::failure(
+                "builds",
+                CommandError::IoError(IoErrorOrigin::ReadingRepos, e).to_string(),
+                "check the permissions of the library",
+            )]
+        }
+    };
+
+    let problems = find_problems(cfg, &repos);
+    if problems.is_empty() {
+        return vec![Check::ok("builds", "every build info could be read")];
+    }
+    problems
+        .into_iter()
+        .map(|p| {
+            let location = p
+                .path
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| format!["repo {}", p.repo]);
+            Check::failure("builds", format!["{}: {}", location, p.cause], p.fix)
+        })
+        .collect()
+}
+
+/// Checks the config, library, build lists, network, and installed builds, and suggests fixes for any problems.
+/// Fails if any check fails. Warnings alone do not.
+pub async fn doctor(cfg: &BLRSConfig, cli_cfg: &CliConfig) -> Result<(), CommandError> {
+    let mut checks = vec![
+        check_config_file(&config_path()),
+        check_config_file(&CliConfig::path()),
+        check_library(&cfg.paths.library),
+    ];
+    checks.extend(cfg.repos.iter().map(|repo| check_cache(cfg, repo)));
+    checks.extend(
+        join_all(
+            cfg.repos
+                .iter()
+                .map(|repo| check_network(cfg, cli_cfg, repo)),
+        )
+        .await,
+    );
+    checks.push(check_leftovers(cfg));
+    checks.extend(check_builds(cfg));
+
+    checks.iter().for_each(Check::print);
+
+    let count = |health| checks.iter().filter(|c| c.health == health).count();
+    let (warnings, failures) = (count(Health::Warning), count(Health::Failure));
+    println![
+        "\n{} passed, {} warning(s), {} failed",
+        count(Health::Ok),
+        warnings,
+        failures
+    ];
+
+    match failures {
+        0 => Ok(()),
+        failed => Err(CommandError::Unhealthy(failed)),
+    }
+}
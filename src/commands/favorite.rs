@@ -0,0 +1,38 @@
+use blrs::{search::VersionSearchQuery, BLRSConfig};
+use log::info;
+
+use crate::{
+    errs::{error_writing, CommandError},
+    resolving::build_query_string,
+    run::select_build,
+};
+
+/// Marks or unmarks an installed build as a favorite.
+pub fn set_favorite(
+    cfg: &BLRSConfig,
+    query: VersionSearchQuery,
+    favorited: bool,
+) -> Result<(), CommandError> {
+    let (mut build, nick) = select_build(cfg, &query, false)?;
+    let name = build_query_string(&build.info.basic, &nick);
+
+    if build.info.is_favorited == favorited {
+        info!["{} is already {}", name, status(favorited)];
+        return Ok(());
+    }
+
+    build.info.is_favorited = favorited;
+    build
+        .write()
+        .map_err(|e| error_writing(build.folder.clone(), e))?;
+    info!["{} is now {}", name, status(favorited)];
+
+    Ok(())
+}
+
+fn status(favorited: bool) -> &'static str {
+    match favorited {
+        true => "a favorite",
+        false => "not a favorite",
+    }
+}
@@ -1,82 +1,532 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
+use ansi_term::Color;
 use async_std::io::WriteExt;
 use blrs::{
+    config::FETCH_INTERVAL,
     fetching::{
-        build_repository::{fetch_repo, FetchError},
+        build_repository::{fetch_repo, BuildRepo, FetchError, RepoType},
         build_schemas::BlenderBuildSchema,
     },
-    BLRSConfig,
+    repos::{BuildEntry, RepoEntry},
+    BLRSConfig, BasicBuildInfo,
 };
 use futures::future::{join_all, try_join_all};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use reqwest::Url;
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{unknown_repo, CommandError, IoErrorOrigin},
+    events::{emit, Event},
+    provenance,
+    repo_auth::client_for,
+    repo_cache::{self, read_repos},
+    resolving::installed_builds,
+    tasks::ConfigTask,
+};
+
+use super::{
+    http_cache::{self, Freshness, Validators},
+    index_repo::fetch_index,
+    net_failure::{self, with_backoff, NetworkFailure},
+    rate_limit::{self, RateLimited},
+};
+
+/// Whether the cached build lists are missing or older than the fetch interval.
+pub fn cache_is_stale(cfg: &BLRSConfig) -> bool {
+    let expired = cfg
+        .history
+        .last_time_checked
+        .is_none_or(|checked| checked + FETCH_INTERVAL < chrono::Utc::now());
+    let missing = cfg.repos.iter().any(|repo| {
+        !cfg.paths
+            .remote_repos
+            .join(repo.repo_id.clone() + ".json")
+            .exists()
+    });
+
+    expired || missing
+}
+
+/// How long ago the repo's build list was fetched, or `None` if it never was.
+pub fn cache_age(cfg: &BLRSConfig, repo: &BuildRepo) -> Option<chrono::TimeDelta> {
+    let modified = cfg
+        .paths
+        .remote_repos
+        .join(repo.repo_id.clone() + ".json")
+        .metadata()
+        .and_then(|m| m.modified())
+        .ok()?;
+
+    Some(chrono::Utc::now() - chrono::DateTime::<chrono::Utc>::from(modified))
+}
+
+/// The registered repos whose cached build list exists but cannot be parsed, like after an
+/// interrupted write, with what is wrong with it.
+pub fn corrupt_caches(cfg: &BLRSConfig) -> Vec<(BuildRepo, PathBuf, String)> {
+    repo_cache::unparsable(&cfg.repos, &cfg.paths)
+        .into_iter()
+        .filter_map(|(repo, e)| {
+            let path = cfg.paths.remote_repos.join(repo.repo_id.clone() + ".json");
+            path.exists().then_some((repo, path, e))
+        })
+        .collect()
+}
+
+/// Fetches the repos with corrupt build lists again if `cli.refetch_corrupt` is set,
+/// and fails with `CorruptCache` for the first one otherwise.
+pub async fn repair_caches(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+) -> Result<Option<ConfigTask>, CommandError> {
+    let corrupt = corrupt_caches(cfg);
+    let Some((repo, path, e)) = corrupt.first() else {
+        return Ok(None);
+    };
+    if !cli_cfg.refetch_corrupt {
+        return Err(CommandError::CorruptCache(
+            repo.nickname.clone(),
+            path.clone(),
+            e.clone(),
+        ));
+    }
+
+    let repos: Vec<BuildRepo> = corrupt
+        .into_iter()
+        .map(|(repo, _, e)| {
+            warn![
+                "The build list of {} is corrupt ({}), fetching it again",
+                repo.nickname, e
+            ];
+            repo
+        })
+        .collect();
+    fetch(
+        cfg,
+        cli_cfg,
+        &repos,
+        true,
+        false,
+        StalePolicy::default(),
+        false,
+    )
+    .await
+    .map(|(task, _)| Some(task))
+    .map_err(|e| CommandError::IoError(IoErrorOrigin::Fetching, e))
+}
 
-use crate::tasks::ConfigTask;
+/// Finds the registered repos with the given nicknames or ids. Every repo is selected if no names are given.
+pub fn select_repos(cfg: &BLRSConfig, names: &[String]) -> Result<Vec<BuildRepo>, CommandError> {
+    if names.is_empty() {
+        return Ok(cfg.repos.clone());
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            cfg.repos
+                .iter()
+                .find(|r| &r.nickname == name || &r.repo_id == name)
+                .cloned()
+                .ok_or_else(|| unknown_repo(cfg, name))
+        })
+        .collect()
+}
+
+/// The URLs installed builds were pulled from, as recorded in their provenance.
+fn installed_urls(cfg: &BLRSConfig) -> Result<HashSet<String>, std::io::Error> {
+    let builds = installed_builds(cfg).map_err(|e| std::io::Error::other(e.to_string()))?;
+    Ok(builds
+        .iter()
+        .filter_map(|(b, _)| provenance::read(&b.folder).ok().flatten())
+        .map(|p| p.url)
+        .collect())
+}
+
+/// Adds the cached builds that the repo no longer lists to the fetched ones, so installed builds keep their listing.
+/// With `keep`, only the stale builds whose URL is in it are kept, and the rest are pruned.
+/// Without it every stale build is kept.
+fn reconcile(
+    repo: &str,
+    cache: &Path,
+    mut builds: Vec<BlenderBuildSchema>,
+    keep: Option<&HashSet<String>>,
+) -> Vec<BlenderBuildSchema> {
+    let Some(cached) = std::fs::read(cache)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Vec<BlenderBuildSchema>>(&data).ok())
+    else {
+        return builds;
+    };
+
+    let listed: HashSet<String> = builds.iter().map(|b| b.link.clone()).collect();
+    let stale: Vec<_> = cached
+        .into_iter()
+        .filter(|b| !listed.contains(&b.link))
+        .collect();
+    let total = stale.len();
+    let kept: Vec<_> = match keep {
+        Some(keep) => stale
+            .into_iter()
+            .filter(|b| keep.contains(&b.link))
+            .collect(),
+        None => stale,
+    };
+
+    if keep.is_some() && total > 0 {
+        info![
+            "Pruned {} build(s) that {} no longer lists, kept {} installed one(s)",
+            total - kept.len(),
+            repo,
+            kept.len()
+        ];
+    } else if total > 0 {
+        debug!["Keeping {} build(s) that {} no longer lists", total, repo];
+    }
+    builds.extend(kept);
+    builds
+}
+
+/// What happens to the cached builds that a repo stopped listing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StalePolicy {
+    /// Drop them when the listing changed, except those of installed builds.
+    #[default]
+    KeepInstalled,
+    /// Like `KeepInstalled`, but also when the repo reports that its listing has not changed.
+    Prune,
+    /// Keep all of them.
+    KeepAll,
+}
+
+/// How a repo's listing changed compared to the cached one, counted by download URL.
+#[derive(Debug)]
+pub struct FetchSummary {
+    pub repo: String,
+    pub new: usize,
+    /// Builds the repo stopped listing. Only the installed ones stay in the cache, unless stale builds are kept.
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// The download URLs in a cached build list. Empty if there is none yet.
+fn cached_links(cache: &Path) -> HashSet<String> {
+    std::fs::read(cache)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Vec<BlenderBuildSchema>>(&data).ok())
+        .map(|builds| builds.into_iter().map(|b| b.link).collect())
+        .unwrap_or_default()
+}
+
+/// Where the download URLs the repo listed in the last fetch are kept, next to its cached build list.
+/// The cache also holds the stale builds that were kept, which were already counted as removed.
+pub(super) fn listing_path(cache: &Path) -> PathBuf {
+    cache.with_extension("listed")
+}
+
+/// The download URLs the repo listed in the last fetch. Caches written before they were saved fall back to the cached builds.
+fn last_listing(cache: &Path) -> HashSet<String> {
+    std::fs::read(listing_path(cache))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_else(|| cached_links(cache))
+}
+
+fn save_listing(cache: &Path, listed: &[String]) -> std::io::Result<()> {
+    std::fs::write(listing_path(cache), serde_json::to_string(listed).unwrap())
+}
+
+impl FetchSummary {
+    fn new(repo: &str, cached: &HashSet<String>, builds: &[BlenderBuildSchema]) -> Self {
+        let listed: HashSet<&str> = builds.iter().map(|b| b.link.as_str()).collect();
+        let unchanged = cached
+            .iter()
+            .filter(|link| listed.contains(link.as_str()))
+            .count();
+
+        Self {
+            repo: repo.to_string(),
+            new: listed.len() - unchanged,
+            removed: cached.len() - unchanged,
+            unchanged,
+        }
+    }
+}
+
+/// Prints a table of what every fetched repo gained and lost, with the newest build it lists.
+pub fn print_summary(cfg: &BLRSConfig, summaries: &[FetchSummary]) {
+    if summaries.is_empty() {
+        return;
+    }
+
+    let repos: Vec<BuildRepo> = cfg
+        .repos
+        .iter()
+        .filter(|r| summaries.iter().any(|s| s.repo == r.nickname))
+        .cloned()
+        .collect();
+    let newest: HashMap<String, BasicBuildInfo> = read_repos(repos, &cfg.paths, false)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| match r {
+            RepoEntry::Registered(repo, entries) => {
+                let newest = entries
+                    .into_iter()
+                    .filter_map(|entry| match entry {
+                        BuildEntry::NotInstalled(variants) => Some(variants.basic),
+                        BuildEntry::Installed(_, build) => Some(build.info.basic),
+                        BuildEntry::Errored(_, _) => None,
+                    })
+                    .max_by_key(|b| b.commit_dt)?;
+                Some((repo.nickname, newest))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let width = summaries.iter().map(|s| s.repo.len()).max().unwrap_or(0);
+    println![
+        "\n{:<width$}  {:>5}  {:>7}  {:>9}  newest",
+        "repo", "new", "removed", "unchanged"
+    ];
+    for summary in summaries {
+        let new = format!["{:>5}", summary.new];
+        let newest = newest
+            .get(&summary.repo)
+            .map(|b| {
+                format![
+                    "{} ({})",
+                    b.version(),
+                    b.commit_dt.with_timezone(&chrono::Local).format("%Y-%m-%d")
+                ]
+            })
+            .unwrap_or_else(|| "-".to_string());
+        println![
+            "{:<width$}  {}  {:>7}  {:>9}  {}",
+            summary.repo,
+            match summary.new {
+                0 => Color::White.dimmed().paint(new),
+                _ => Color::Green.bold().paint(new),
+            },
+            summary.removed,
+            summary.unchanged,
+            newest
+        ];
+    }
+}
 
 /// Fetches from the builder's repo
+///
+/// The asset patterns and credentials of the repos are taken from `cli_cfg`.
+/// What happens to the builds the repo stopped listing is decided by `stale`.
+/// GitHub repos fail with `RateLimited` when the API quota is used up, unless `wait_for_ratelimit` is set.
+/// Returns how the listing of every repo changed.
 pub async fn fetch(
     cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    repos: &[BuildRepo],
     parallel: bool,
     ignore_errors: bool,
-) -> Result<ConfigTask, std::io::Error> {
+    stale: StalePolicy,
+    wait_for_ratelimit: bool,
+) -> Result<(ConfigTask, Vec<FetchSummary>), std::io::Error> {
     let repos_folder = &cfg.paths.remote_repos.clone();
     // Ensure the repos folder exists
     let _ = std::fs::create_dir_all(repos_folder);
+    let keep = match stale {
+        StalePolicy::KeepAll => None,
+        _ => Some(installed_urls(cfg)?),
+    };
+    let keep = keep.as_ref();
 
-    let actions = cfg
-        .repos
+    let actions = repos
         .iter()
         .map(|repo| async {
-            let url = repo.url();
-            let client = cfg
-                .client_builder(url.domain().is_some_and(|h| h.contains("api.github.com")))
-                .build()
-                .unwrap();
+            let index = cli_cfg.index_repos.get(&repo.repo_id);
+            // Index repos are fetched from the URL as it was given
+            let url = match index {
+                Some(_) => Url::parse(&repo.url).map_err(std::io::Error::other)?,
+                None => repo.url(),
+            };
+            let client = client_for(cfg, &cli_cfg.repo_auth, repo, &url)
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
 
-            info!["Fetching from {}", url];
-            let r = fetch_repo(client, repo.clone()).await;
+            let github = matches!(repo.repo_type, RepoType::GithubAPI);
+            if github {
+                match rate_limit::check(&client).await {
+                    Some(limit) if limit.is_exhausted() && wait_for_ratelimit => {
+                        rate_limit::wait_for_reset(&limit).await
+                    }
+                    Some(limit) if limit.is_exhausted() => {
+                        return Err(RateLimited(limit.reset).into())
+                    }
+                    _ => {}
+                }
+            }
 
+            info!["Fetching from {}", url];
+            emit(Event::FetchStarted {
+                repo: &repo.nickname,
+                url: url.as_str(),
+            });
             let filename = repos_folder.join(repo.repo_id.clone() + ".json");
+            let cached = last_listing(&filename);
+
+            // Pruning rewrites the cache even if the listing is the same
+            let asset_pattern = cli_cfg.asset_patterns.get(&repo.repo_id);
+            let previous = match stale == StalePolicy::Prune || cached.is_empty() {
+                true => None,
+                false => Validators::load(&filename, asset_pattern),
+            };
+            let validators =
+                match http_cache::check(&client, &url, previous.as_ref(), asset_pattern).await {
+                    Freshness::NotModified => {
+                        info!["{} has not changed since the last fetch", repo.nickname];
+                        emit(Event::FetchFinished {
+                            repo: &repo.nickname,
+                            path: &filename,
+                        });
+                        return Ok(FetchSummary {
+                            repo: repo.nickname.clone(),
+                            new: 0,
+                            removed: 0,
+                            unchanged: cached.len(),
+                        });
+                    }
+                    Freshness::Modified(validators) => validators,
+                };
+
+            let fetch_builds = || {
+                with_backoff(&repo.nickname, || async {
+                    match index {
+                        Some(template) => fetch_index(&client, &url, template).await,
+                        None => fetch_repo(client.clone(), repo.clone()).await,
+                    }
+                })
+            };
+            let mut r = fetch_builds().await;
+
+            if github {
+                match rate_limit::check(&client).await {
+                    // The quota running out mid-fetch is the likely cause of the failure
+                    Some(limit) if r.is_err() && limit.is_exhausted() && wait_for_ratelimit => {
+                        warn!["Fetching from {} failed: {:?}", repo.nickname, r.err()];
+                        rate_limit::wait_for_reset(&limit).await;
+                        r = fetch_builds().await;
+                    }
+                    Some(limit) if r.is_err() && limit.is_exhausted() => {
+                        error!["Failed fetching from {}: {:?}", repo.nickname, r.err()];
+                        return Err(RateLimited(limit.reset).into());
+                    }
+                    Some(limit) => info![
+                        "{} of {} GitHub API requests left, resetting at {}",
+                        limit.remaining,
+                        limit.limit,
+                        limit.reset.with_timezone(&chrono::Local).format("%H:%M")
+                    ],
+                    None => {}
+                }
+            }
+
+            let r = r.map(|builds| {
+                let builds = match cli_cfg.asset_patterns.get(&repo.repo_id) {
+                    Some(pattern) => filter_assets(builds, pattern),
+                    None => builds,
+                };
+                let summary = FetchSummary::new(&repo.nickname, &cached, &builds);
+                let listed: Vec<String> = builds.iter().map(|b| b.link.clone()).collect();
+                (
+                    reconcile(&repo.nickname, &filename, builds, keep),
+                    summary,
+                    listed,
+                )
+            });
+
+            let (r, summary, listed) = match r {
+                Ok((builds, summary, listed)) => (Ok(builds), Some(summary), listed),
+                Err(e) => (Err(e), None, vec![]),
+            };
+            _process_result(&repo.nickname, filename.clone(), r).await?;
+            if let Err(e) = save_listing(&filename, &listed) {
+                warn!["Failed to save the listing of {}: {}", repo.nickname, e];
+            }
+            if let Err(e) = validators.save(&filename) {
+                warn!["Failed to save the validators of {}: {}", repo.nickname, e];
+            }
 
-            _process_result(filename, r).await
+            Ok::<_, std::io::Error>(summary.expect("the fetch succeeded"))
         })
         .collect::<Vec<_>>();
 
-    let mut result = Ok(ConfigTask::UpdateLastTimeChecked);
-    if parallel {
+    let summaries = if parallel {
         if ignore_errors {
             join_all(actions.into_iter())
                 .await
                 .into_iter()
-                .map(|r| match r {
-                    Ok(_) => Ok(ConfigTask::UpdateLastTimeChecked),
-                    Err(e) => Err(e),
-                })
-                .find(Result::is_err)
-                .unwrap_or(result)
+                .collect::<Result<Vec<_>, _>>()?
         } else {
-            try_join_all(actions.into_iter())
-                .await
-                .map(|_| ConfigTask::UpdateLastTimeChecked)
+            try_join_all(actions.into_iter()).await?
         }
     } else {
+        let mut summaries = vec![];
+        let mut result = Ok(());
         for action in actions.into_iter() {
-            let r = action.await.map(|_| ConfigTask::UpdateLastTimeChecked);
-
-            if r.is_err() {
-                result = r;
+            match action.await {
+                Ok(summary) => summaries.push(summary),
+                Err(e) => {
+                    result = Err(e);
 
-                if ignore_errors {
-                    break;
+                    if ignore_errors {
+                        break;
+                    }
                 }
             }
         }
 
-        result
+        result.map(|_| summaries)?
+    };
+
+    Ok((ConfigTask::UpdateLastTimeChecked, summaries))
+}
+
+/// Turns a failed fetch into the error reported for it, so rate limits, timeouts, and network failures
+/// get their own exit codes and messages.
+pub fn fetch_error(e: std::io::Error) -> CommandError {
+    if let Some(failure) = net_failure::failure_of(&e) {
+        return failure.to_command_error();
+    }
+    match (rate_limit::reset_of(&e), e.kind()) {
+        (Some(reset), _) => CommandError::RateLimited(reset),
+        (None, std::io::ErrorKind::TimedOut) => CommandError::TimedOut(e.to_string()),
+        _ => CommandError::IoError(IoErrorOrigin::Fetching, e),
     }
 }
 
+/// Keeps the builds whose file name matches the glob, so releases with many assets only list the relevant ones.
+fn filter_assets(builds: Vec<BlenderBuildSchema>, pattern: &str) -> Vec<BlenderBuildSchema> {
+    let pattern = match glob::Pattern::new(pattern) {
+        Ok(p) => p,
+        Err(e) => {
+            warn!["Ignoring invalid asset pattern {:?}: {}", pattern, e];
+            return builds;
+        }
+    };
+
+    builds
+        .into_iter()
+        .filter(|b| {
+            let name = b.link.rsplit('/').next().unwrap_or(&b.link);
+            pattern.matches(name)
+        })
+        .collect()
+}
+
 async fn _process_result(
+    repo: &str,
     filename: PathBuf,
     r: Result<Vec<BlenderBuildSchema>, FetchError>,
 ) -> Result<(), std::io::Error> {
@@ -86,21 +536,48 @@ async fn _process_result(
 
             debug!["Saving builds to database..."];
 
-            {
-                let mut file = async_std::fs::File::create(&filename).await?;
+            // Written next to the cache and renamed over it, so an interrupted fetch never leaves half a file
+            let partial = filename.with_extension("json.part");
+            let written = async {
+                let mut file = async_std::fs::File::create(&partial).await?;
 
                 let data = serde_json::to_string(&builds).unwrap();
                 file.write_all(data.as_bytes()).await?;
-                info!["Saved cache to {}", filename.to_str().unwrap()];
+                file.sync_all().await?;
+                async_std::fs::rename(&partial, &filename).await
             }
+            .await;
+            if let Err(e) = written {
+                let _ = async_std::fs::remove_file(&partial).await;
+                return Err(e);
+            }
+            info!["Saved cache to {}", filename.to_str().unwrap()];
+
+            emit(Event::FetchFinished {
+                repo,
+                path: &filename,
+            });
 
             Ok(())
         }
         Err(e) => {
             error!["Failed fetching from builder: {:?}", e];
+            emit(Event::Error {
+                message: format!["Failed fetching from {}: {:?}", repo, e],
+                path: None,
+            });
 
+            if let FetchError::Reqwest(e) = &e {
+                if let Some(failure) = NetworkFailure::of(e, repo) {
+                    return Err(failure.into());
+                }
+            }
             match e {
                 FetchError::IoError(error) => Err(error),
+                FetchError::Reqwest(e) if e.is_timeout() => Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!["{} stopped responding", repo],
+                )),
                 e => Err(std::io::Error::new(
                     std::io::ErrorKind::Other,
                     format!["Error: {e:?}"],
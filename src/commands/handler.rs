@@ -0,0 +1,357 @@
+use std::path::PathBuf;
+
+use log::info;
+
+use crate::errs::{CommandError, IoErrorOrigin};
+
+/// Runs a helper program, treating a non-zero exit as an error.
+#[cfg(any(windows, target_os = "macos"))]
+pub(super) fn run_helper(command: &mut std::process::Command) -> Result<(), CommandError> {
+    let status = command
+        .status()
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+
+    match status.success() {
+        true => Ok(()),
+        false => Err(CommandError::IoError(
+            IoErrorOrigin::CommandExecution,
+            std::io::Error::other(format!["{:?} failed with {}", command, status]),
+        )),
+    }
+}
+
+/// Quotes one argument of a desktop entry's `Exec=` key, as the Desktop Entry spec asks for.
+/// Inside quotes `"`, `` ` ``, `$` and `\` are escaped, a literal `%` is doubled so it is not
+/// taken for a field code, and the backslashes are escaped once more as the value is a string.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(super) fn exec_arg(arg: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => quoted.extend(['\\', c]),
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted.replace('\\', "\\\\")
+}
+
+fn blrs_exe() -> Result<PathBuf, CommandError> {
+    std::env::current_exe().map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))
+}
+
+/// Makes blrs the handler of `.blend` files, so opening one launches it through `blrs run file`.
+/// Removes the registration instead if `unregister` is set.
+pub fn register_handler(unregister: bool) -> Result<(), CommandError> {
+    let exe = blrs_exe()?;
+
+    match unregister {
+        false => {
+            platform::register(&exe)?;
+            info!["blrs now opens .blend files"];
+        }
+        true => {
+            platform::unregister()?;
+            info!["blrs no longer opens .blend files"];
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    use crate::errs::{error_reading, error_writing, CommandError};
+
+    use super::exec_arg;
+
+    const DESKTOP_FILE: &str = "blrs-blend.desktop";
+    const MIME_TYPE: &str = "application/x-blender";
+    const DEFAULTS_SECTION: &str = "[Default Applications]";
+
+    fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+        std::env::var_os(var).map(PathBuf::from).unwrap_or_else(|| {
+            PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(fallback)
+        })
+    }
+
+    fn desktop_file() -> PathBuf {
+        xdg_dir("XDG_DATA_HOME", ".local/share")
+            .join("applications")
+            .join(DESKTOP_FILE)
+    }
+
+    fn mimeapps() -> PathBuf {
+        xdg_dir("XDG_CONFIG_HOME", ".config").join("mimeapps.list")
+    }
+
+    /// Makes blrs the default handler of the mime type, or removes it if `register` is unset.
+    /// Only the default applications section is touched, and the handlers of other programs are kept after blrs.
+    fn update_defaults(content: &str, register: bool) -> String {
+        let key = format!["{}=", MIME_TYPE];
+        let mut in_defaults = false;
+        let mut others: Vec<&str> = vec![];
+        let mut lines: Vec<String> = content
+            .lines()
+            .filter_map(|line| {
+                if line.trim_start().starts_with('[') {
+                    in_defaults = line.trim() == DEFAULTS_SECTION;
+                }
+                let Some(handlers) = line.strip_prefix(&key).filter(|_| in_defaults) else {
+                    return Some(line.to_string());
+                };
+
+                let handlers: Vec<&str> = handlers.split(';').filter(|h| !h.is_empty()).collect();
+                let rest: Vec<&str> = handlers
+                    .iter()
+                    .copied()
+                    .filter(|h| *h != DESKTOP_FILE)
+                    .collect();
+                match (register, rest.len() < handlers.len()) {
+                    // Added again right below the section header, with blrs first
+                    (true, _) => {
+                        others.extend(rest);
+                        None
+                    }
+                    (false, true) => {
+                        (!rest.is_empty()).then(|| format!["{}{};", key, rest.join(";")])
+                    }
+                    // Another program is the handler
+                    (false, false) => Some(line.to_string()),
+                }
+            })
+            .collect();
+
+        if register {
+            let entry = std::iter::once(DESKTOP_FILE)
+                .chain(others)
+                .fold(key.clone(), |entry, h| entry + h + ";");
+            match lines.iter().position(|l| l.trim() == DEFAULTS_SECTION) {
+                Some(idx) => lines.insert(idx + 1, entry),
+                None => {
+                    lines.push(DEFAULTS_SECTION.to_string());
+                    lines.push(entry);
+                }
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    fn write_defaults(register: bool) -> Result<(), CommandError> {
+        let path = mimeapps();
+        let content = match path.exists() {
+            true => std::fs::read_to_string(&path).map_err(|e| error_reading(path.clone(), e))?,
+            false => String::new(),
+        };
+        let updated = update_defaults(&content, register);
+        if updated.trim_end() == content.trim_end() {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| error_writing(parent.to_path_buf(), e))?;
+        }
+        std::fs::write(&path, updated).map_err(|e| error_writing(path, e))
+    }
+
+    pub fn register(exe: &Path) -> Result<(), CommandError> {
+        let path = desktop_file();
+        let entry = format![
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=Blender (blrs)\n\
+             Comment=Open blendfiles with the matching Blender version\n\
+             Exec={} run file %f\n\
+             Icon=blender\n\
+             Terminal=false\n\
+             NoDisplay=true\n\
+             MimeType={};\n",
+            exec_arg(&exe.display().to_string()),
+            MIME_TYPE
+        ];
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| error_writing(parent.to_path_buf(), e))?;
+        }
+        std::fs::write(&path, entry).map_err(|e| error_writing(path.clone(), e))?;
+
+        write_defaults(true)
+    }
+
+    pub fn unregister() -> Result<(), CommandError> {
+        let path = desktop_file();
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|e| error_writing(path, e))?;
+        }
+
+        write_defaults(false)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const MIMEAPPS: &str = "[Added Associations]\n\
+                                application/x-blender=blender.desktop;\n\
+                                [Default Applications]\n\
+                                text/plain=org.gnome.TextEditor.desktop\n\
+                                application/x-blender=blender.desktop;\n";
+
+        #[test]
+        fn registering_only_changes_the_default_applications() {
+            let updated = update_defaults(MIMEAPPS, true);
+            assert_eq!(
+                updated,
+                "[Added Associations]\n\
+                 application/x-blender=blender.desktop;\n\
+                 [Default Applications]\n\
+                 application/x-blender=blrs-blend.desktop;blender.desktop;\n\
+                 text/plain=org.gnome.TextEditor.desktop\n"
+            );
+            assert_eq!(update_defaults(&updated, false).lines().count(), 5);
+        }
+
+        #[test]
+        fn unregistering_keeps_other_handlers() {
+            assert_eq!(update_defaults(MIMEAPPS, false), MIMEAPPS);
+        }
+
+        #[test]
+        fn exec_args_are_escaped() {
+            assert_eq!(
+                exec_arg("/home/a user/50% \"blrs\""),
+                r#""/home/a user/50%% \\"blrs\\"""#
+            );
+            assert_eq!(exec_arg(r"C:\$x"), r#""C:\\\\\\$x""#);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::{path::Path, process};
+
+    use crate::errs::CommandError;
+
+    use super::run_helper;
+
+    const PROG_ID: &str = "blrs.blend";
+    const CLASSES: &str = r"HKCU\Software\Classes";
+
+    fn reg_set(key: &str, value: &str) -> Result<(), CommandError> {
+        run_helper(process::Command::new("reg").args(["add", key, "/ve", "/d", value, "/f"]))
+    }
+
+    pub fn register(exe: &Path) -> Result<(), CommandError> {
+        reg_set(&format![r"{}\.blend", CLASSES], PROG_ID)?;
+        reg_set(&format![r"{}\{}", CLASSES, PROG_ID], "Blender file (blrs)")?;
+        reg_set(
+            &format![r"{}\{}\shell\open\command", CLASSES, PROG_ID],
+            &format![r#""{}" run file "%1""#, exe.display()],
+        )
+    }
+
+    pub fn unregister() -> Result<(), CommandError> {
+        // .blend itself may have belonged to another program before, so only our ProgID is removed
+        run_helper(process::Command::new("reg").args([
+            "delete",
+            &format![r"{}\{}", CLASSES, PROG_ID],
+            "/f",
+        ]))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::{
+        path::{Path, PathBuf},
+        process,
+    };
+
+    use blrs::config::PROJECT_DIRS;
+
+    use crate::errs::{error_writing, CommandError};
+
+    use super::run_helper;
+
+    const BUNDLE_ID: &str = "com.blrs.blend-handler";
+    const CONTENT_TYPE: &str = "org.blenderfoundation.blender.file";
+    const LSREGISTER: &str = "/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister";
+
+    /// Launch Services only hands files to app bundles, so a small AppleScript app forwards them to blrs.
+    fn app_path() -> PathBuf {
+        PROJECT_DIRS.data_local_dir().join("Blender (blrs).app")
+    }
+
+    pub fn register(exe: &Path) -> Result<(), CommandError> {
+        let app = app_path();
+        let script = format![
+            "on open theFiles\n\
+             \trepeat with f in theFiles\n\
+             \t\tdo shell script quoted form of \"{}\" & \" run file \" & quoted form of POSIX path of f & \" > /dev/null 2>&1 &\"\n\
+             \tend repeat\n\
+             end open\n",
+            exe.display()
+        ];
+        let script_path = PROJECT_DIRS
+            .data_local_dir()
+            .join("blend-handler.applescript");
+        std::fs::create_dir_all(PROJECT_DIRS.data_local_dir())
+            .map_err(|e| error_writing(PROJECT_DIRS.data_local_dir().to_path_buf(), e))?;
+        std::fs::write(&script_path, script).map_err(|e| error_writing(script_path.clone(), e))?;
+
+        run_helper(
+            process::Command::new("osacompile")
+                .arg("-o")
+                .arg(&app)
+                .arg(&script_path),
+        )?;
+
+        let plist = app.join("Contents/Info.plist");
+        run_helper(
+            process::Command::new("plutil")
+                .args(["-replace", "CFBundleIdentifier", "-string", BUNDLE_ID])
+                .arg(&plist),
+        )?;
+        run_helper(
+            process::Command::new("plutil")
+                .args([
+                    "-replace",
+                    "CFBundleDocumentTypes",
+                    "-json",
+                    &format![
+                        r#"[{{"CFBundleTypeRole":"Viewer","LSItemContentTypes":["{}"]}}]"#,
+                        CONTENT_TYPE
+                    ],
+                ])
+                .arg(&plist),
+        )?;
+
+        run_helper(process::Command::new(LSREGISTER).arg("-f").arg(&app))?;
+
+        run_helper(process::Command::new("defaults").args([
+            "write",
+            "com.apple.LaunchServices/com.apple.launchservices.secure",
+            "LSHandlers",
+            "-array-add",
+            &format![
+                "{{LSHandlerContentType={};LSHandlerRoleAll={};}}",
+                CONTENT_TYPE, BUNDLE_ID
+            ],
+        ]))
+    }
+
+    /// The LSHandlers entry is left behind, but has no effect once the bundle is unregistered.
+    pub fn unregister() -> Result<(), CommandError> {
+        let app = app_path();
+        if app.exists() {
+            run_helper(process::Command::new(LSREGISTER).arg("-u").arg(&app))?;
+            std::fs::remove_dir_all(&app).map_err(|e| error_writing(app, e))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{
+    errs::{error_reading, error_writing, CommandError},
+    launch_history::LaunchHistory,
+};
+
+/// Prints the most recent launches, newest first, or forgets all of them if `clear` is set.
+pub fn show_history(limit: usize, clear: bool) -> Result<(), CommandError> {
+    if clear {
+        LaunchHistory::default()
+            .save()
+            .map_err(|e| error_writing(LaunchHistory::path(), e))?;
+        log::info!["Cleared the launch history"];
+        return Ok(());
+    }
+
+    let history = LaunchHistory::load().map_err(|e| error_reading(LaunchHistory::path(), e))?;
+    if history.launches.is_empty() {
+        log::info!["Nothing has been launched yet"];
+        return Ok(());
+    }
+
+    for launch in history.launches.iter().rev().take(limit) {
+        let file = launch
+            .file
+            .as_ref()
+            .map(|f| format![" {}", f.display()])
+            .unwrap_or_default();
+        let crash = launch
+            .crash
+            .as_ref()
+            .map(|c| {
+                ansi_term::Color::Red
+                    .paint(format![" (crashed: {})", c.reason])
+                    .to_string()
+            })
+            .unwrap_or_default();
+        println![
+            "{} {}{}{}",
+            ansi_term::Color::White
+                .dimmed()
+                .paint(launch.time.format("%Y-%m-%d %H:%M").to_string()),
+            ansi_term::Color::Cyan.paint(&launch.build),
+            file,
+            crash
+        ];
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use reqwest::{
+    header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Client, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+
+/// The `ETag` and `Last-Modified` headers a repo's listing was served with. They are sent back on
+/// the next fetch, so a server can answer 304 Not Modified instead of sending the listing again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// The asset pattern the cached listing was filtered with. A new pattern needs the full listing.
+    asset_pattern: Option<String>,
+}
+
+pub enum Freshness {
+    /// The cached listing is still current.
+    NotModified,
+    /// The listing has to be fetched. The validators are saved once the new listing is.
+    Modified(Validators),
+}
+
+impl Validators {
+    /// Where the validators of a cached build list are kept, next to it.
+    pub fn path(cache: &Path) -> PathBuf {
+        cache.with_extension("validators")
+    }
+
+    /// Reads the validators of a cached build list, unless they were saved for another asset pattern.
+    pub fn load(cache: &Path, asset_pattern: Option<&String>) -> Option<Self> {
+        let data = std::fs::read(Self::path(cache)).ok()?;
+        serde_json::from_slice::<Self>(&data)
+            .inspect_err(|e| {
+                debug![
+                    "Ignoring unreadable validators of {}: {}",
+                    cache.display(),
+                    e
+                ]
+            })
+            .ok()
+            .filter(|v| v.asset_pattern.as_ref() == asset_pattern)
+    }
+
+    pub fn save(&self, cache: &Path) -> Result<(), std::io::Error> {
+        // Without either header, the server cannot answer conditional requests anyway
+        if self.etag.is_none() && self.last_modified.is_none() {
+            return Ok(());
+        }
+        std::fs::write(Self::path(cache), serde_json::to_string(self).unwrap())
+    }
+
+    fn from_headers(headers: &HeaderMap, asset_pattern: Option<&String>) -> Self {
+        let header = |name| Some(headers.get(name)?.to_str().ok()?.to_string());
+
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            asset_pattern: asset_pattern.cloned(),
+        }
+    }
+}
+
+/// Asks the server with a conditional HEAD request whether the listing changed since `previous` was saved.
+/// A failed request is treated as a change, so the listing is fetched as usual.
+pub async fn check(
+    client: &Client,
+    url: &Url,
+    previous: Option<&Validators>,
+    asset_pattern: Option<&String>,
+) -> Freshness {
+    let mut request = client.head(url.clone());
+    if let Some(previous) = previous {
+        if let Some(etag) = &previous.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &previous.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, modified);
+        }
+    }
+
+    match request.send().await {
+        Ok(r) if r.status() == StatusCode::NOT_MODIFIED && previous.is_some() => {
+            Freshness::NotModified
+        }
+        Ok(r) => Freshness::Modified(Validators::from_headers(r.headers(), asset_pattern)),
+        Err(e) => {
+            debug!["HEAD {} failed: {:?}", url, e];
+            Freshness::Modified(Validators {
+                asset_pattern: asset_pattern.cloned(),
+                ..Default::default()
+            })
+        }
+    }
+}
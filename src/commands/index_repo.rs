@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+
+use blrs::fetching::{build_repository::FetchError, build_schemas::BlenderBuildSchema};
+use chrono::{NaiveDate, Utc};
+use log::debug;
+use regex::Regex;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// The capture groups an index pattern can use. Only `version` is required.
+const GROUPS: [&str; 6] = ["version", "branch", "hash", "platform", "arch", "date"];
+
+/// How the listing of a repo that is a plain directory index or JSON document is turned into builds,
+/// for build servers that do not speak Blender's builder API. See `repos add-index`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexTemplate {
+    /// Matched against the file name of every link in an HTML index, or every string in a JSON document.
+    /// Its named groups fill in the build: `version` like `4.2.1`, and optionally `branch`, `hash`,
+    /// `platform`, `arch`, and `date` like `2024-06-01` or `20240601`.
+    pub pattern: String,
+    /// The branch of builds whose file name has no `branch` group.
+    #[serde(default = "default_branch")]
+    pub branch: String,
+}
+
+fn default_branch() -> String {
+    "stable".to_string()
+}
+
+impl IndexTemplate {
+    /// Checks that the pattern compiles and captures the version.
+    pub fn validate(&self) -> Result<Regex, String> {
+        let re = Regex::new(&self.pattern).map_err(|e| e.to_string())?;
+        match re.capture_names().flatten().any(|name| name == "version") {
+            true => Ok(re),
+            false => Err(format![
+                "the pattern {:?} has no `version` group, like (?<version>\\d+\\.\\d+\\.\\d+)",
+                self.pattern
+            ]),
+        }
+    }
+}
+
+/// Every string in a JSON document, wherever it is nested.
+fn json_strings(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(s) => out.push(s.clone()),
+        Value::Array(items) => items.iter().for_each(|v| json_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| json_strings(v, out)),
+        _ => {}
+    }
+}
+
+/// The link targets of an HTML page, like the ones in a web server's directory listing.
+fn html_links(body: &str) -> Vec<String> {
+    let href = Regex::new(r#"(?i)href\s*=\s*["']([^"']+)["']"#).unwrap();
+    href.captures_iter(body)
+        .map(|c| c[1].replace("&amp;", "&"))
+        .collect()
+}
+
+fn parse_date(s: &str) -> Option<chrono::DateTime<Utc>> {
+    ["%Y-%m-%d", "%Y%m%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(s, format).ok())
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
+/// Turns one matching link into a build, in the format of Blender's builder API that the library reads.
+fn build_from_link(url: &Url, template: &IndexTemplate, re: &Regex) -> Option<Value> {
+    let file_name = url.path_segments()?.next_back()?.to_string();
+    let captures = re.captures(&file_name)?;
+    let group = |name: &str| captures.name(name).map(|m| m.as_str().to_string());
+
+    let version = group("version")?;
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_string())
+        .unwrap_or_default();
+    // Without a date, builds are ordered by their version alone
+    let mtime = group("date")
+        .and_then(|d| parse_date(&d))
+        .map(|d| d.timestamp())
+        .unwrap_or_default();
+
+    Some(json!({
+        "app": "Blender",
+        "url": url.as_str(),
+        "link": url.as_str(),
+        "version": version,
+        "branch": group("branch").unwrap_or_else(|| template.branch.clone()),
+        "patch": null,
+        "hash": group("hash").unwrap_or_default(),
+        "platform": group("platform").unwrap_or_default(),
+        "architecture": group("arch").unwrap_or_default(),
+        "file_mtime": mtime,
+        "file_name": file_name,
+        "file_size": 0,
+        "file_extension": extension,
+        "release_cycle": group("branch").unwrap_or_else(|| template.branch.clone()),
+    }))
+}
+
+/// Downloads the index at `url` and lists the files matching the template's pattern as builds.
+/// The index is read as JSON if it parses as JSON, and as an HTML page otherwise.
+pub async fn fetch_index(
+    client: &Client,
+    url: &Url,
+    template: &IndexTemplate,
+) -> Result<Vec<BlenderBuildSchema>, FetchError> {
+    let invalid = |e: String| FetchError::IoError(std::io::Error::other(e));
+    let re = template.validate().map_err(invalid)?;
+
+    let body = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(FetchError::Reqwest)?
+        .text()
+        .await
+        .map_err(FetchError::Reqwest)?;
+
+    let candidates = match serde_json::from_str::<Value>(&body) {
+        Ok(value) => {
+            let mut strings = vec![];
+            json_strings(&value, &mut strings);
+            strings
+        }
+        Err(_) => html_links(&body),
+    };
+
+    let mut seen = HashSet::new();
+    let builds: Vec<Value> = candidates
+        .iter()
+        .filter_map(|link| url.join(link).ok())
+        .filter(|link| seen.insert(link.to_string()))
+        .filter_map(|link| build_from_link(&link, template, &re))
+        .collect();
+    debug![
+        "{} of {} links in {} matched the pattern",
+        builds.len(),
+        candidates.len(),
+        url
+    ];
+
+    serde_json::from_value(Value::Array(builds)).map_err(|e| {
+        invalid(format![
+            "the builds found in {} could not be read as build lists: {}",
+            url, e
+        ])
+    })
+}
+
+/// The named groups of a pattern that an index template does not know, which are likely typos.
+pub fn unknown_groups(re: &Regex) -> Vec<String> {
+    re.capture_names()
+        .flatten()
+        .filter(|name| !GROUPS.contains(name))
+        .map(str::to_string)
+        .collect()
+}
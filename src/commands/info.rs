@@ -0,0 +1,281 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use ansi_term::Color;
+use blrs::{
+    fetching::build_repository::BuildRepo,
+    repos::{BuildEntry, RepoEntry, Variants},
+    search::VersionSearchQuery,
+    BLRSConfig, BasicBuildInfo, LocalBuild, RemoteBuild,
+};
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use indicatif::HumanBytes;
+use serde::Serialize;
+
+use crate::{
+    errs::{CommandError, IoErrorOrigin},
+    network::client_builder,
+    provenance::{self, Provenance},
+    repo_cache::read_repos,
+    resolving::{build_query_string, find_matches, resolve_match},
+};
+
+/// A build that can be described, whether or not it is installed.
+#[derive(Clone)]
+pub(super) enum Described {
+    Installed(LocalBuild),
+    Remote(Variants<RemoteBuild>),
+}
+
+impl AsRef<BasicBuildInfo> for Described {
+    fn as_ref(&self) -> &BasicBuildInfo {
+        match self {
+            Described::Installed(lb) => &lb.info.basic,
+            Described::Remote(variants) => &variants.basic,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct VariantDetails {
+    target: String,
+    url: String,
+    /// The size of the archive in bytes, if the server reports it.
+    archive_size: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct BuildDetails {
+    query: String,
+    repo: String,
+    version: String,
+    branch: String,
+    build_hash: String,
+    commit_dt: DateTime<Utc>,
+    installed: bool,
+    favorited: bool,
+    path: Option<PathBuf>,
+    /// The size of the installed build in bytes.
+    disk_usage: Option<u64>,
+    custom_name: Option<String>,
+    custom_exe: Option<String>,
+    custom_env: Option<HashMap<String, String>>,
+    /// Where the build was pulled from, if that was recorded.
+    provenance: Option<Provenance>,
+    variants: Vec<VariantDetails>,
+}
+
+/// Every installed or remote build in the library and the cached build lists, with the nickname of its repo.
+pub(super) fn describable_builds(
+    cfg: &BLRSConfig,
+) -> Result<Vec<(Described, String)>, CommandError> {
+    Ok(read_repos(cfg.repos.clone(), &cfg.paths, false)
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
+        .into_iter()
+        .flat_map(|r| match r {
+            RepoEntry::Registered(BuildRepo { nickname, .. }, vec)
+            | RepoEntry::Unknown(nickname, vec) => vec
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    BuildEntry::Installed(_, lb) => Some(Described::Installed(lb)),
+                    BuildEntry::NotInstalled(variants) => Some(Described::Remote(variants)),
+                    BuildEntry::Errored(_, _) => None,
+                })
+                .map(|d| (d, nickname.clone()))
+                .collect(),
+            RepoEntry::Error(_, _) => vec![],
+        })
+        .collect())
+}
+
+/// Finds the single build a query points to, asking which one is meant if there are several.
+pub(super) fn find_described(
+    builds: &[(Described, String)],
+    query: &VersionSearchQuery,
+) -> Result<(Described, String), CommandError> {
+    let matches: Vec<_> = find_matches(builds, query).into_iter().cloned().collect();
+    if matches.is_empty() {
+        return Err(CommandError::QueryResultEmpty(query.to_string()));
+    }
+
+    resolve_match(
+        &matches,
+        &format!["Multiple matches for query {query}! select a build"],
+    )
+    .cloned()
+    .ok_or(CommandError::InvalidInput)
+}
+
+/// Prints everything known about a single installed or remote build.
+pub async fn show_info(
+    cfg: &BLRSConfig,
+    query: VersionSearchQuery,
+    json: bool,
+) -> Result<(), CommandError> {
+    let builds = describable_builds(cfg)?;
+    let (described, repo) = find_described(&builds, &query)?;
+
+    let details = describe(cfg, &described, &repo).await?;
+
+    if json {
+        println!["{}", serde_json::to_string_pretty(&details).unwrap()];
+    } else {
+        print_details(&details);
+    }
+
+    Ok(())
+}
+
+async fn describe(
+    cfg: &BLRSConfig,
+    described: &Described,
+    repo: &str,
+) -> Result<BuildDetails, CommandError> {
+    let basic = described.as_ref();
+    let version = basic.version();
+
+    let mut details = BuildDetails {
+        query: build_query_string(basic, repo),
+        repo: repo.to_string(),
+        version: format!["{}.{}.{}", version.major, version.minor, version.patch],
+        branch: version.pre.to_string(),
+        build_hash: version.build.to_string(),
+        commit_dt: basic.commit_dt,
+        installed: false,
+        favorited: false,
+        path: None,
+        disk_usage: None,
+        custom_name: None,
+        custom_exe: None,
+        custom_env: None,
+        provenance: None,
+        variants: vec![],
+    };
+
+    match described {
+        Described::Installed(lb) => {
+            details.installed = true;
+            details.favorited = lb.info.is_favorited;
+            details.path = Some(lb.folder.clone());
+            details.disk_usage = dir_size(&lb.folder).ok();
+            details.custom_name = lb.info.custom_name.clone();
+            details.custom_exe = lb.info.custom_exe.clone();
+            details.custom_env = lb.info.custom_env.clone();
+            details.provenance = provenance::read(&lb.folder)?;
+        }
+        Described::Remote(variants) => {
+            let client = client_builder(cfg, false)?
+                .build()
+                .map_err(CommandError::ReqwestError)?;
+            details.variants = join_all(variants.v.iter().map(|variant| {
+                let client = client.clone();
+                async move {
+                    let url = variant.b.url();
+                    // Not every server answers HEAD requests, so a missing size is not an error
+                    let archive_size = client
+                        .head(url.clone())
+                        .send()
+                        .await
+                        .ok()
+                        .filter(|r| r.status().is_success())
+                        .and_then(|r| r.content_length());
+
+                    VariantDetails {
+                        target: variant.to_string(),
+                        url: url.to_string(),
+                        archive_size,
+                    }
+                }
+            }))
+            .await;
+        }
+    }
+
+    Ok(details)
+}
+
+fn print_details(d: &BuildDetails) {
+    let key = |k: &str| Color::White.dimmed().paint(format!["{:>12}", k]);
+    let or_none = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+
+    println!["{}", Color::Green.bold().paint(&d.query)];
+    println!["{} {}", key("repo:"), d.repo];
+    println!["{} {}", key("version:"), d.version];
+    println!["{} {}", key("branch:"), d.branch];
+    println!["{} {}", key("hash:"), d.build_hash];
+    println!["{} {}", key("date:"), d.commit_dt];
+
+    if d.installed {
+        println![
+            "{} {}{}",
+            key("installed:"),
+            Color::Cyan.paint("yes"),
+            match d.favorited {
+                true => format![" {}", Color::Yellow.paint("★")],
+                false => String::new(),
+            }
+        ];
+        if let Some(path) = &d.path {
+            println!["{} {}", key("path:"), path.display()];
+        }
+        if let Some(size) = d.disk_usage {
+            println!["{} {}", key("disk usage:"), HumanBytes(size)];
+        }
+        println!["{} {}", key("name:"), or_none(&d.custom_name)];
+        println!["{} {}", key("exe:"), or_none(&d.custom_exe)];
+        match &d.custom_env {
+            Some(env) if !env.is_empty() => {
+                let mut env: Vec<_> = env.iter().collect();
+                env.sort();
+                println!["{}", key("env:")];
+                env.into_iter()
+                    .for_each(|(k, v)| println!["{:>12} {}={}", "", k, v]);
+            }
+            _ => println!["{} -", key("env:")],
+        }
+        match &d.provenance {
+            Some(p) => {
+                println!["{} {}", key("source:"), p.repo];
+                println!["{} {}", key("url:"), p.url];
+                println!["{} {}", key("archive:"), p.archive];
+                println!["{} {}", key("sha256:"), or_none(&p.sha256)];
+                println!["{} {}", key("pulled:"), p.installed_at];
+            }
+            None => println!["{} not recorded", key("source:")],
+        }
+    } else {
+        println!["{} no", key("installed:")];
+        println!["{}", key("variants:")];
+        d.variants.iter().for_each(|v| {
+            println![
+                "{:>12} {} {}",
+                "",
+                v.target,
+                Color::White.dimmed().paint(format![
+                    "{} ({})",
+                    v.url,
+                    v.archive_size
+                        .map(|s| HumanBytes(s).to_string())
+                        .unwrap_or_else(|| "unknown size".to_string())
+                ])
+            ]
+        });
+    }
+}
+
+/// The total size of the files in a folder, not following symlinks.
+pub fn dir_size(path: &Path) -> Result<u64, std::io::Error> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = entry.path().symlink_metadata()?;
+        total += match meta.is_dir() {
+            true => dir_size(&entry.path())?,
+            false => meta.len(),
+        };
+    }
+    Ok(total)
+}
@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+
+use blrs::{info::launching::OSLaunchTarget, BLRSConfig, LocalBuild};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    cancel,
+    cli_config::CliConfig,
+    errs::{error_reading, error_renaming, error_writing, CommandError},
+    events::{emit, Event},
+    hooks::{self, Hook},
+    integrity,
+    resolving::folder_name,
+};
+
+use super::{
+    addons::link_into,
+    pull::{extract_file, ArchiveFormat},
+};
+
+/// The repo folder builds are installed into when no repo is given.
+const LOCAL_REPO: &str = "local";
+
+/// The name of the repo builds from outside blrs are put in, and its folder in the library.
+/// A registered repo is named by its nickname, even when it was given by its id, like `pull` names it.
+/// The repo does not have to be registered.
+pub(super) fn repo_folder(cfg: &BLRSConfig, repo: Option<String>) -> (String, PathBuf) {
+    let repo = repo.unwrap_or_else(|| LOCAL_REPO.to_string());
+    match cfg
+        .repos
+        .iter()
+        .find(|r| r.nickname == repo || r.repo_id == repo)
+    {
+        Some(registered) => (
+            registered.nickname.clone(),
+            cfg.paths.path_to_repo(registered),
+        ),
+        None => {
+            let path = cfg.paths.library.join(&repo);
+            (repo, path)
+        }
+    }
+}
+
+/// Copies a folder's contents into `destination`, keeping symlinks as they are.
+pub(super) fn copy_dir(source: &Path, destination: &Path) -> Result<(), CommandError> {
+    std::fs::create_dir_all(destination).map_err(|e| error_writing(destination.into(), e))?;
+
+    for item in source
+        .read_dir()
+        .map_err(|e| error_reading(source.into(), e))?
+    {
+        let item = item.map_err(|e| error_reading(source.into(), e))?;
+        let (from, to) = (item.path(), destination.join(item.file_name()));
+        let file_type = item
+            .file_type()
+            .map_err(|e| error_reading(from.clone(), e))?;
+
+        if file_type.is_dir() {
+            copy_dir(&from, &to)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            {
+                let target = from
+                    .read_link()
+                    .map_err(|e| error_reading(from.clone(), e))?;
+                std::os::unix::fs::symlink(&target, &to)
+                    .map_err(|e| error_writing(to.clone(), e))?;
+            }
+            // Creating symlinks needs extra privileges on Windows, so their targets are copied
+            #[cfg(windows)]
+            std::fs::copy(&from, &to).map_err(|e| error_writing(to.clone(), e))?;
+        } else {
+            std::fs::copy(&from, &to).map_err(|e| error_writing(to.clone(), e))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts the build at `source` into `staging` and reads its info from the executable.
+async fn stage(source: &Path, staging: &Path) -> Result<LocalBuild, CommandError> {
+    if source.is_dir() {
+        info!["Copying {}", source.display()];
+        copy_dir(source, staging)?;
+    } else {
+        let ppb = ProgressBar::new(0);
+        ppb.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} [{bar:40.cyan/red}] {bytes}/{total_bytes} {msg:.green}",
+            )
+            .unwrap()
+            .progress_chars("#|-"),
+        );
+        ppb.set_message(format!["Extracting {}", source.display()]);
+        extract_file(&ppb, source, staging).await?;
+        ppb.finish_and_clear();
+    }
+
+    let exe = staging.join(OSLaunchTarget::try_default().unwrap().exe_name());
+    if !exe.exists() {
+        return Err(CommandError::BrokenArchive(
+            source.to_path_buf(),
+            "it does not contain a Blender executable at its top level",
+        ));
+    }
+
+    info!["Reading the version of {}", exe.display()];
+    LocalBuild::generate_from_exe(&exe).map_err(|e| error_reading(exe, e))
+}
+
+/// Installs a build from an archive or folder on disk, so builds that were not pulled can be used too.
+pub async fn install(
+    cfg: &BLRSConfig,
+    source: PathBuf,
+    repo: Option<String>,
+    name: Option<String>,
+    cli_cfg: &CliConfig,
+) -> Result<(), CommandError> {
+    cancel::handle_ctrl_c();
+    if !source.exists() {
+        return Err(error_reading(
+            source,
+            std::io::Error::from(std::io::ErrorKind::NotFound),
+        ));
+    }
+    if source.is_file()
+        && matches!(
+            ArchiveFormat::from_path(&source),
+            None | Some(ArchiveFormat::Dmg)
+        )
+    {
+        return Err(CommandError::UnsupportedFileFormat(
+            source.display().to_string(),
+        ));
+    }
+
+    let (repo, repo_path) = repo_folder(cfg, repo);
+
+    // Staged next to its destination, so moving it into place is a rename
+    let staging = repo_path.join(format![".installing-{}", Uuid::new_v4()]);
+    let staged = stage(&source, &staging).await;
+    let mut build = match staged {
+        Ok(build) => build,
+        Err(e) => {
+            if let Err(e) = std::fs::remove_dir_all(&staging) {
+                warn!["Failed to clean up {}: {}", staging.display(), e];
+            }
+            return Err(e);
+        }
+    };
+
+    let destination = repo_path.join(folder_name(&build.info.basic, &repo));
+    if destination.exists() {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(error_writing(
+            destination,
+            std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "this version is already installed",
+            ),
+        ));
+    }
+    // Nested library layouts put builds in folders that may not exist yet
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| error_writing(parent.to_path_buf(), e))?;
+    }
+    std::fs::rename(&staging, &destination)
+        .map_err(|e| error_renaming(staging, destination.clone(), e))?;
+
+    if cli_cfg.record_manifests {
+        integrity::write(&destination)?;
+    }
+    build.folder = destination.clone();
+    build.info.custom_name = name;
+    build
+        .write()
+        .map_err(|e| error_writing(destination.clone(), e))?;
+
+    emit(Event::BuildInstalled {
+        version: build.info.basic.ver.to_string(),
+        path: &destination,
+    });
+    println![
+        "{} {}/{} to {}",
+        ansi_term::Color::Green.bold().paint("Installed"),
+        repo,
+        build.info.basic.ver,
+        destination.display()
+    ];
+    if let Err(e) = link_into(&build, &cli_cfg.addons) {
+        warn![
+            "Failed to link the addons into {}: {}",
+            destination.display(),
+            e
+        ];
+    }
+    hooks::run_after(Hook::PostPull, &build, &repo, &[]);
+
+    Ok(())
+}
@@ -0,0 +1,233 @@
+use std::{io::Write, path::PathBuf};
+
+use blrs::{
+    fetching::build_repository::BuildRepo,
+    repos::{BuildEntry, RepoEntry},
+    BasicBuildInfo,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{repo_formatting::DiskUsage, resolving::build_query_string};
+
+/// Bumped whenever a field is renamed or removed. New fields may be added without bumping it.
+const SCHEMA_VERSION: u32 = 1;
+
+/// What `ls` prints in its serialized formats. The builds are converted into this instead of
+/// printing blrs' own types, so scripts do not break when those change.
+#[derive(Debug, Serialize)]
+pub struct Listing {
+    pub schema_version: u32,
+    pub repos: Vec<RepoListing>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepoListing {
+    /// The nickname of the repo, or the name of its folder if it is not registered.
+    pub name: String,
+    /// Whether the repo is in the config. Unregistered repos only contain installed builds.
+    pub registered: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Why the repo could not be read. It has no builds if this is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub builds: Vec<BuildListing>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuildListing {
+    /// `major.minor.patch`, without the branch or hash.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_time: Option<DateTime<Utc>>,
+    /// A version matcher that finds exactly this build.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    pub installed: bool,
+    pub favorite: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// The disk usage in bytes. Only present with `--usage`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// The downloads available for a build that is not installed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<VariantListing>,
+    /// Why the build could not be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One line of the ndjson format: a build along with the repo it is in.
+/// Repos that could not be read get a line with only `repo` and `repo_error`.
+#[derive(Debug, Serialize)]
+struct BuildLine<'a> {
+    schema_version: u32,
+    repo: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repo_error: Option<String>,
+    #[serde(flatten)]
+    build: Option<BuildListing>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VariantListing {
+    pub target: String,
+    pub url: String,
+}
+
+impl BuildListing {
+    fn describe(basic: &BasicBuildInfo, nick: &str) -> Self {
+        let version = basic.version();
+        let non_empty = |s: String| (!s.is_empty()).then_some(s);
+
+        Self {
+            version: Some(format![
+                "{}.{}.{}",
+                version.major, version.minor, version.patch
+            ]),
+            branch: non_empty(version.pre.to_string()),
+            hash: non_empty(version.build.to_string()),
+            commit_time: Some(basic.commit_dt),
+            query: Some(build_query_string(basic, nick)),
+            installed: false,
+            favorite: false,
+            path: None,
+            size: None,
+            variants: vec![],
+            error: None,
+        }
+    }
+
+    fn new(entry: &BuildEntry, nick: &str, usage: &DiskUsage) -> Self {
+        match entry {
+            BuildEntry::NotInstalled(variants) => Self {
+                variants: variants
+                    .v
+                    .iter()
+                    .map(|v| VariantListing {
+                        target: v.to_string(),
+                        url: v.b.link.clone(),
+                    })
+                    .collect(),
+                ..Self::describe(&variants.basic, nick)
+            },
+            BuildEntry::Installed(_, lb) => Self {
+                installed: true,
+                favorite: lb.info.is_favorited,
+                path: Some(lb.folder.clone()),
+                size: usage.get(&lb.folder).copied(),
+                ..Self::describe(&lb.info.basic, nick)
+            },
+            BuildEntry::Errored(e, path) => Self {
+                version: None,
+                branch: None,
+                hash: None,
+                commit_time: None,
+                query: None,
+                installed: false,
+                favorite: false,
+                path: path.clone(),
+                size: None,
+                variants: vec![],
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+impl Listing {
+    pub fn new(repos: &[RepoEntry], usage: &DiskUsage) -> Self {
+        let builds = |vec: &[BuildEntry], nick: &str| {
+            vec.iter()
+                .map(|entry| BuildListing::new(entry, nick, usage))
+                .collect()
+        };
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            repos: repos
+                .iter()
+                .map(|repo| match repo {
+                    RepoEntry::Registered(
+                        BuildRepo {
+                            repo_id,
+                            url,
+                            nickname,
+                            repo_type: _,
+                        },
+                        vec,
+                    ) => RepoListing {
+                        name: nickname.clone(),
+                        registered: true,
+                        id: Some(repo_id.clone()),
+                        url: Some(url.clone()),
+                        error: None,
+                        builds: builds(vec, nickname),
+                    },
+                    RepoEntry::Unknown(name, vec) => RepoListing {
+                        name: name.clone(),
+                        registered: false,
+                        id: None,
+                        url: None,
+                        error: None,
+                        builds: builds(vec, name),
+                    },
+                    RepoEntry::Error(name, e) => RepoListing {
+                        name: name.clone(),
+                        registered: false,
+                        id: None,
+                        url: None,
+                        error: Some(e.to_string()),
+                        builds: vec![],
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Writes every build as its own line of JSON as it is converted, so consumers can start reading
+/// before the whole listing is done and nothing holds all of it in memory.
+pub fn write_ndjson(
+    repos: &[RepoEntry],
+    usage: &DiskUsage,
+    mut out: impl Write,
+) -> Result<(), std::io::Error> {
+    let mut write_line = |line: &BuildLine| {
+        serde_json::to_writer(&mut out, line)?;
+        out.write_all(b"\n")
+    };
+
+    for repo in repos {
+        match repo {
+            RepoEntry::Registered(BuildRepo { nickname: name, .. }, vec)
+            | RepoEntry::Unknown(name, vec) => {
+                for entry in vec {
+                    write_line(&BuildLine {
+                        schema_version: SCHEMA_VERSION,
+                        repo: name,
+                        repo_error: None,
+                        build: Some(BuildListing::new(entry, name, usage)),
+                    })?;
+                }
+            }
+            RepoEntry::Error(name, e) => write_line(&BuildLine {
+                schema_version: SCHEMA_VERSION,
+                repo: name,
+                repo_error: Some(e.to_string()),
+                build: None,
+            })?,
+        }
+    }
+
+    out.flush()
+}
@@ -1,18 +1,38 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
 use blrs::{
-    build_targets::{filter_repos_by_target, get_target_setup},
     fetching::build_repository::BuildRepo,
-    repos::{read_repos, BuildEntry, RepoEntry},
+    info::build_info::BasicBuildInfo,
+    repos::{BuildEntry, RepoEntry},
+    search::VersionSearchQuery,
     BLRSConfig,
 };
+use chrono::{DateTime, TimeDelta, Utc};
 use clap::ValueEnum;
-use log::{debug, error};
+use indicatif::HumanBytes;
+use log::{debug, error, warn};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     errs::{error_writing, CommandError, IoErrorOrigin},
-    repo_formatting::{RepoEntryTreeConstructor, SortFormat},
+    repo_cache::{read_repos_within, Window},
+    repo_formatting::{DiskUsage, RepoEntryTreeConstructor, SortFormat},
+    resolving::{build_query_string, find_matches},
+    target,
 };
 
+use super::{
+    fetcher::{cache_age, corrupt_caches},
+    info::dir_size,
+    listing::{write_ndjson, Listing},
+};
+
+/// Build lists older than this get a hint to fetch them again.
+const STALE_AFTER: TimeDelta = TimeDelta::days(1);
+
 #[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize)]
 pub enum LsFormat {
     /// A visual tree. Good for human interpretation, but not easily parsed.
@@ -24,20 +44,362 @@ pub enum LsFormat {
     Json,
     /// Json but indented by 2 spaces to make it more human readable.
     PrettyJson,
+    /// Newline-delimited JSON, one build and its repo per line, written as it is produced.
+    Ndjson,
+    /// The same schema as json, as TOML.
+    Toml,
+    /// The same schema as json, as YAML.
+    Yaml,
+    /// Column-aligned table, one build per row.
+    Table,
+    /// Comma separated values with a header row.
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum LsColumn {
+    Version,
+    Branch,
+    Hash,
+    Date,
+    Repo,
+    Installed,
+    Path,
+    /// The disk usage of installed builds in bytes. Not shown unless requested or `--usage` is given.
+    Size,
+}
+
+impl LsColumn {
+    pub const ALL: [LsColumn; 7] = [
+        LsColumn::Repo,
+        LsColumn::Version,
+        LsColumn::Branch,
+        LsColumn::Hash,
+        LsColumn::Date,
+        LsColumn::Installed,
+        LsColumn::Path,
+    ];
+
+    fn header(&self) -> &'static str {
+        match self {
+            LsColumn::Version => "version",
+            LsColumn::Branch => "branch",
+            LsColumn::Hash => "hash",
+            LsColumn::Date => "date",
+            LsColumn::Repo => "repo",
+            LsColumn::Installed => "installed",
+            LsColumn::Path => "path",
+            LsColumn::Size => "size",
+        }
+    }
+
+    fn value(&self, repo: &str, entry: &BuildEntry, usage: &DiskUsage) -> String {
+        let (basic, folder) = match entry {
+            BuildEntry::NotInstalled(variants) => (&variants.basic, None),
+            BuildEntry::Installed(_, local_build) => {
+                (&local_build.info.basic, Some(&local_build.folder))
+            }
+            BuildEntry::Errored(_, path) => {
+                return match self {
+                    LsColumn::Repo => repo.to_string(),
+                    LsColumn::Installed => "error".to_string(),
+                    LsColumn::Path => path
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default(),
+                    _ => String::new(),
+                }
+            }
+        };
+        let version = basic.version();
+
+        match self {
+            LsColumn::Version => {
+                format!["{}.{}.{}", version.major, version.minor, version.patch]
+            }
+            LsColumn::Branch => version.pre.to_string(),
+            LsColumn::Hash => version.build.to_string(),
+            LsColumn::Date => basic.commit_dt.to_rfc3339(),
+            LsColumn::Repo => repo.to_string(),
+            LsColumn::Installed => folder.is_some().to_string(),
+            LsColumn::Path => folder.map(|f| f.display().to_string()).unwrap_or_default(),
+            LsColumn::Size => folder
+                .and_then(|f| usage.get(f))
+                .map(|size| size.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// How `list_builds` should collect and present builds.
+#[derive(Debug, Clone, Default)]
+pub struct LsOptions {
+    pub format: LsFormat,
+    pub sort_format: SortFormat,
+    pub installed_only: bool,
+    /// Only show builds that are not installed.
+    pub remote_only: bool,
+    pub show_variants: bool,
+    pub all_builds: bool,
+    /// Only show favorited builds.
+    pub favorites_only: bool,
+    /// The columns shown by the table and CSV formats. Empty means all of them.
+    pub columns: Vec<LsColumn>,
+    /// Calculate and show how much disk space installed builds use.
+    pub usage: bool,
+    /// Nest builds under their version series and branch in the tree format.
+    pub group: bool,
+    /// With `group`, only expand the groups that contain builds matching this query.
+    pub expand: Option<VersionSearchQuery>,
+    /// Only show builds matching this query.
+    pub query: Option<VersionSearchQuery>,
+    /// Only list the builds and repos that could not be read.
+    pub problems: bool,
+    /// Only show builds committed after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only show the last this many builds of each repo, in the sort order.
+    pub limit: Option<usize>,
+}
+
+/// A build or repo that could not be read, as listed by `ls --problems`.
+#[derive(Debug, Serialize)]
+pub(super) struct Problem {
+    pub(super) repo: String,
+    pub(super) path: Option<PathBuf>,
+    pub(super) cause: String,
+    pub(super) fix: String,
+}
+
+/// Collects every errored repo and build, with a suggestion for how to fix it.
+pub(super) fn find_problems(cfg: &BLRSConfig, repos: &[RepoEntry]) -> Vec<Problem> {
+    let is_registered = |name: &str| {
+        cfg.repos
+            .iter()
+            .any(|r| r.nickname == name || r.repo_id == name)
+    };
+
+    repos
+        .iter()
+        .flat_map(|repo| match repo {
+            RepoEntry::Error(name, e) => vec![Problem {
+                repo: name.clone(),
+                path: None,
+                cause: e.to_string(),
+                fix: match is_registered(name) {
+                    true => format!["run `blrs fetch --repo {}` to download its build list again", name],
+                    false => format![
+                        "check that {} is readable, or remove it",
+                        cfg.paths.library.join(name).display()
+                    ],
+                },
+            }],
+            RepoEntry::Registered(BuildRepo { nickname: name, .. }, vec)
+            | RepoEntry::Unknown(name, vec) => vec
+                .iter()
+                .filter_map(|entry| match entry {
+                    BuildEntry::Errored(e, path) => Some(Problem {
+                        repo: name.clone(),
+                        path: path.clone(),
+                        cause: e.to_string(),
+                        fix: match path {
+                            Some(_) => "run `blrs verify` to regenerate its build info, or remove the folder".to_string(),
+                            None => format!["run `blrs fetch --repo {}` to download its build list again", name],
+                        },
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn print_problems(problems: &[Problem], format: LsFormat) {
+    match format {
+        LsFormat::Json => println!["{}", serde_json::to_string(problems).unwrap()],
+        LsFormat::PrettyJson => println!["{}", serde_json::to_string_pretty(problems).unwrap()],
+        LsFormat::Ndjson => problems
+            .iter()
+            .for_each(|p| println!["{}", serde_json::to_string(p).unwrap()]),
+        LsFormat::Yaml => println!["{}", serde_yaml::to_string(problems).unwrap().trim_end()],
+        _ if problems.is_empty() => println!["No problems found"],
+        _ => {
+            println![
+                "{}",
+                ansi_term::Color::Red
+                    .bold()
+                    .paint(format!["{} problem(s) found:", problems.len()])
+            ];
+            problems.iter().for_each(|p| {
+                let location = p
+                    .path
+                    .as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| format!["repo {}", p.repo]);
+                println!["  {}", ansi_term::Color::Red.paint(location)];
+                println!["    {}", p.cause];
+                println!["    {} {}", ansi_term::Color::Green.paint("fix:"), p.fix];
+            });
+        }
+    }
+}
+
+fn format_age(age: TimeDelta) -> String {
+    match (age.num_days(), age.num_hours(), age.num_minutes()) {
+        (0, 0, 0) => "just now".to_string(),
+        (0, 0, m) => format!["{} minute(s) ago", m],
+        (0, h, _) => format!["{} hour(s) ago", h],
+        (d, _, _) => format!["{} day(s) ago", d],
+    }
+}
+
+/// Describes how old a repo's cached build list is, suggesting a fetch if it is stale.
+fn staleness(cfg: &BLRSConfig, repo: &BuildRepo) -> String {
+    let text = match cache_age(cfg, repo) {
+        Some(age) if age < STALE_AFTER => {
+            return ansi_term::Color::White
+                .dimmed()
+                .paint(format!["fetched {}", format_age(age)])
+                .to_string()
+        }
+        Some(age) => format!["fetched {} - run blrs fetch", format_age(age)],
+        None => "never fetched - run blrs fetch".to_string(),
+    };
+    ansi_term::Color::Yellow.paint(text).to_string()
+}
+
+fn repo_nickname(repo: &RepoEntry) -> &str {
+    match repo {
+        RepoEntry::Registered(BuildRepo { nickname, .. }, _)
+        | RepoEntry::Unknown(nickname, _)
+        | RepoEntry::Error(nickname, _) => nickname,
+    }
+}
+
+fn entry_basic(entry: &BuildEntry) -> Option<&BasicBuildInfo> {
+    match entry {
+        BuildEntry::NotInstalled(variants) => Some(&variants.basic),
+        BuildEntry::Installed(_, lb) => Some(&lb.info.basic),
+        BuildEntry::Errored(_, _) => None,
+    }
+}
+
+/// The query strings of every listed build that matches the query.
+fn matching_builds(repos: &[RepoEntry], query: &VersionSearchQuery) -> HashSet<String> {
+    let builds: Vec<(BasicBuildInfo, String)> = repos
+        .iter()
+        .flat_map(|repo| {
+            let nick = repo_nickname(repo);
+            let entries = match repo {
+                RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => vec.as_slice(),
+                RepoEntry::Error(_, _) => &[],
+            };
+            entries
+                .iter()
+                .filter_map(move |e| entry_basic(e).map(|b| (b.clone(), nick.to_string())))
+        })
+        .collect();
+
+    find_matches(&builds, query)
+        .into_iter()
+        .map(|(b, nick)| build_query_string(b, nick))
+        .collect()
+}
+
+/// Measures the folder of every installed build.
+fn calculate_usage(repos: &[RepoEntry]) -> DiskUsage {
+    repos
+        .iter()
+        .flat_map(|repo| match repo {
+            RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => vec.as_slice(),
+            RepoEntry::Error(_, _) => &[],
+        })
+        .filter_map(|entry| match entry {
+            BuildEntry::Installed(_, lb) => dir_size(&lb.folder)
+                .inspect_err(|e| debug!["Failed to measure {}: {}", lb.folder.display(), e])
+                .ok()
+                .map(|size| (lb.folder.clone(), size)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn build_rows(repos: &[RepoEntry], columns: &[LsColumn], usage: &DiskUsage) -> Vec<Vec<String>> {
+    repos
+        .iter()
+        .flat_map(|repo| match repo {
+            RepoEntry::Registered(BuildRepo { nickname, .. }, vec)
+            | RepoEntry::Unknown(nickname, vec) => vec
+                .iter()
+                .map(|entry| {
+                    columns
+                        .iter()
+                        .map(|c| c.value(nickname, entry, usage))
+                        .collect()
+                })
+                .collect(),
+            RepoEntry::Error(_, _) => vec![],
+        })
+        .collect()
+}
+
+fn print_table(columns: &[LsColumn], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.header().len()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, w)| format!["{:<w$}", cell, w = w])
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println![
+        "{}",
+        ansi_term::Style::new()
+            .bold()
+            .paint(format_row(columns.iter().map(|c| c.header()).collect()))
+    ];
+    for row in rows {
+        println!["{}", format_row(row.iter().map(String::as_str).collect())];
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!["\"{}\"", s.replace('"', "\"\"")]
+    } else {
+        s.to_string()
+    }
+}
+
+fn print_csv(columns: &[LsColumn], rows: &[Vec<String>]) {
+    let header: Vec<_> = columns.iter().map(|c| csv_field(c.header())).collect();
+    println!["{}", header.join(",")];
+    for row in rows {
+        let row: Vec<_> = row.iter().map(|c| csv_field(c)).collect();
+        println!["{}", row.join(",")];
+    }
 }
 
-fn gather_and_filter_repos(
+pub fn gather_and_filter_repos(
     cfg: &BLRSConfig,
     installed_only: bool,
     all_builds: bool,
     sort_format: Option<SortFormat>,
+    window: &Window,
 ) -> Result<Vec<RepoEntry>, std::io::Error> {
-    let mut repos = read_repos(cfg.repos.clone(), &cfg.paths, installed_only)?;
+    let mut repos = read_repos_within(cfg.repos.clone(), &cfg.paths, installed_only, window)?;
     debug!("Finished reading repos");
     repos = if !all_builds {
-        let target = get_target_setup().unwrap();
-        debug!["filtering list of builds by the target: {:?}", target];
-        filter_repos_by_target(repos, Some(target))
+        target::filter_repos(repos)
     } else {
         repos
     };
@@ -58,20 +420,135 @@ fn gather_and_filter_repos(
     Ok(repos)
 }
 
+/// Keeps the last `limit` builds of every repo. Returns how many builds of each repo were dropped.
+fn limit_builds(repos: &mut [RepoEntry], limit: usize) -> HashMap<String, usize> {
+    let mut hidden = HashMap::new();
+    repos.iter_mut().for_each(|repo| match repo {
+        RepoEntry::Registered(BuildRepo { nickname, .. }, vec)
+        | RepoEntry::Unknown(nickname, vec) => {
+            let excess = vec.len().saturating_sub(limit);
+            if excess > 0 {
+                vec.drain(..excess);
+                hidden.insert(nickname.clone(), excess);
+            }
+        }
+        RepoEntry::Error(_, _) => {}
+    });
+
+    hidden
+}
+
 pub fn list_builds(
     cfg: &BLRSConfig,
-    ls_format: LsFormat,
-    sort_format: SortFormat,
-    installed_only: bool,
-    show_variants: bool,
-    all_builds: bool,
+    options: LsOptions,
+    default_build: Option<&str>,
 ) -> Result<(), CommandError> {
+    let LsOptions {
+        format: ls_format,
+        sort_format,
+        installed_only,
+        remote_only,
+        show_variants,
+        all_builds,
+        favorites_only,
+        columns,
+        usage,
+        group,
+        expand,
+        query,
+        problems,
+        since,
+        limit,
+    } = options;
+
     std::fs::create_dir_all(&cfg.paths.library)
         .inspect_err(|e| error!("Failed to create library path: {:?}", e))
         .map_err(|e| error_writing(cfg.paths.library.clone(), e))?;
 
-    let mut all_repos = gather_and_filter_repos(cfg, installed_only, all_builds, Some(sort_format))
-        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?;
+    if !installed_only {
+        for (repo, path, e) in corrupt_caches(cfg) {
+            warn![
+                "The cached build list of {} at {} is corrupt ({}), so its builds are missing. Run `blrs fetch --repo {}` to download it again",
+                repo.nickname,
+                path.display(),
+                e,
+                repo.nickname
+            ];
+        }
+    }
+
+    // Builds outside of --since and --limit are never read out of the cache. Queries are matched against
+    // every build, so the limit can only be applied while reading when there is none
+    let window = match problems {
+        true => Window::default(),
+        false => Window {
+            since,
+            limit: limit
+                .filter(|_| query.is_none())
+                .map(|limit| (limit, sort_format)),
+            all_targets: all_builds,
+        },
+    };
+    let mut all_repos = gather_and_filter_repos(
+        cfg,
+        installed_only || favorites_only,
+        // Problems are not tied to a platform
+        all_builds || problems,
+        Some(sort_format),
+        &window,
+    )
+    .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?;
+
+    if problems {
+        print_problems(&find_problems(cfg, &all_repos), ls_format);
+        return Ok(());
+    }
+
+    if favorites_only {
+        all_repos.iter_mut().for_each(|repo| match repo {
+            RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => vec.retain(
+                |entry| matches!(entry, BuildEntry::Installed(_, lb) if lb.info.is_favorited),
+            ),
+            RepoEntry::Error(_, _) => {}
+        });
+    }
+
+    if let Some(query) = &query {
+        let matching = matching_builds(&all_repos, query);
+        all_repos.iter_mut().for_each(|repo| match repo {
+            RepoEntry::Registered(BuildRepo { nickname, .. }, vec)
+            | RepoEntry::Unknown(nickname, vec) => vec.retain(|entry| {
+                entry_basic(entry)
+                    .is_some_and(|b| matching.contains(&build_query_string(b, nickname)))
+            }),
+            RepoEntry::Error(_, _) => {}
+        });
+    }
+
+    if remote_only {
+        all_repos.iter_mut().for_each(|repo| match repo {
+            RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => {
+                vec.retain(|entry| matches!(entry, BuildEntry::NotInstalled(_)))
+            }
+            RepoEntry::Error(_, _) => {}
+        });
+    }
+
+    if let Some(since) = since {
+        all_repos.iter_mut().for_each(|repo| match repo {
+            RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => {
+                vec.retain(|entry| entry_basic(entry).is_none_or(|b| b.commit_dt >= since))
+            }
+            RepoEntry::Error(_, _) => {}
+        });
+    }
+
+    if favorites_only || remote_only || query.is_some() || since.is_some() {
+        all_repos.retain(|r| match r {
+            RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => !vec.is_empty(),
+            RepoEntry::Error(_, _) => true,
+        });
+    }
 
     all_repos.sort_by_cached_key(|r| match r {
         RepoEntry::Registered(
@@ -87,12 +564,69 @@ pub fn list_builds(
         | RepoEntry::Unknown(nickname, _) => nickname.clone(),
     });
 
+    // Installed builds past the limit are dropped before anything is measured or rendered
+    let hidden = match limit {
+        Some(limit) => limit_builds(&mut all_repos, limit),
+        None => HashMap::new(),
+    };
+
+    let needs_usage = usage || columns.contains(&LsColumn::Size);
+    let disk_usage: DiskUsage = match needs_usage {
+        true => calculate_usage(&all_repos),
+        false => DiskUsage::new(),
+    };
+
     match ls_format {
-        LsFormat::Tree => all_repos.into_iter().for_each(|repo_entry| {
-            let tree = RepoEntryTreeConstructor(&repo_entry).to_tree(show_variants);
+        LsFormat::Tree => {
+            if let Some(default) = default_build {
+                println![
+                    "{} {}",
+                    ansi_term::Color::White.dimmed().paint("Default build:"),
+                    ansi_term::Color::Cyan.paint(default)
+                ];
+            }
+
+            let usage_ref = usage.then_some(&disk_usage);
+            let expanded = expand.as_ref().map(|q| matching_builds(&all_repos, q));
+            all_repos.iter().for_each(|repo_entry| {
+                let constructor = RepoEntryTreeConstructor(repo_entry, usage_ref);
+                let mut tree = match (group, &expanded) {
+                    (false, _) => constructor.to_tree(show_variants),
+                    (true, None) => constructor.to_grouped_tree(show_variants, None),
+                    (true, Some(expanded)) => {
+                        let nick = repo_nickname(repo_entry);
+                        let expand = |e: &BuildEntry| {
+                            entry_basic(e)
+                                .is_some_and(|b| expanded.contains(&build_query_string(b, nick)))
+                        };
+                        constructor.to_grouped_tree(show_variants, Some(&expand))
+                    }
+                };
+                // Installed builds do not depend on the build lists
+                if let (RepoEntry::Registered(repo, _), false) = (repo_entry, installed_only) {
+                    tree.root = format!["{} {}", tree.root, staleness(cfg, repo)];
+                }
+                if let Some(count) = hidden.get(repo_nickname(repo_entry)) {
+                    tree.push(
+                        ansi_term::Color::White
+                            .dimmed()
+                            .paint(format!["{} older build(s) not shown, see --limit", count])
+                            .to_string(),
+                    );
+                }
+
+                println!["{}", tree];
+            });
 
-            println!["{}", tree];
-        }),
+            if usage {
+                println![
+                    "{} {}",
+                    ansi_term::Color::White.dimmed().paint("Total disk usage:"),
+                    ansi_term::Color::Purple
+                        .paint(HumanBytes(disk_usage.values().sum()).to_string())
+                ];
+            }
+        }
         LsFormat::Paths => {
             all_repos.into_iter().for_each(|repo| match repo {
                 RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => {
@@ -105,13 +639,131 @@ pub fn list_builds(
                 RepoEntry::Error(_, _) => {}
             });
         }
-        LsFormat::Json => {
-            println!["{}", serde_json::to_string(&all_repos).unwrap()];
+        LsFormat::Ndjson => {
+            let stdout = std::io::stdout().lock();
+            match write_ndjson(&all_repos, &disk_usage, std::io::BufWriter::new(stdout)) {
+                // The reader stopped early, like `head`
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+                r => r.map_err(|e| CommandError::IoError(IoErrorOrigin::Terminal, e))?,
+            }
         }
-        LsFormat::PrettyJson => {
-            println!["{}", serde_json::to_string_pretty(&all_repos).unwrap()];
+        LsFormat::Json | LsFormat::PrettyJson | LsFormat::Toml | LsFormat::Yaml => {
+            let listing = Listing::new(&all_repos, &disk_usage);
+            let data = match ls_format {
+                LsFormat::Json => serde_json::to_string(&listing).unwrap(),
+                LsFormat::PrettyJson => serde_json::to_string_pretty(&listing).unwrap(),
+                LsFormat::Toml => toml::to_string_pretty(&listing).unwrap(),
+                _ => serde_yaml::to_string(&listing).unwrap(),
+            };
+            println!["{}", data.trim_end()];
+        }
+        LsFormat::Table | LsFormat::Csv => {
+            let mut columns = match columns.is_empty() {
+                true => LsColumn::ALL.to_vec(),
+                false => columns,
+            };
+            if usage && !columns.contains(&LsColumn::Size) {
+                columns.push(LsColumn::Size);
+            }
+            let rows = build_rows(&all_repos, &columns, &disk_usage);
+            match ls_format {
+                LsFormat::Table => print_table(&columns, &rows),
+                _ => print_csv(&columns, &rows),
+            }
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{ListedBuild, TempLibrary};
+
+    use super::*;
+
+    /// How many builds the generated build list has, about as many as a few years of daily builds.
+    const BUILDS: usize = 5000;
+
+    /// A library whose only repo has a cached build list of `BUILDS` builds, one a day.
+    fn generated_library() -> TempLibrary {
+        let builds: Vec<serde_json::Value> = (0..BUILDS)
+            .map(|i| {
+                let version = format!["4.{}.{}", i / 100, i % 100];
+                let hash = format!["{:012x}", i];
+                let mut build = ListedBuild {
+                    version: &version,
+                    hash: &hash,
+                    ..Default::default()
+                }
+                .json();
+                build["file_mtime"] = serde_json::json!(1_700_000_000 + i * 86400);
+                build
+            })
+            .collect();
+
+        TempLibrary::with_builds(&builds)
+    }
+
+    fn remote_versions(repos: &[RepoEntry]) -> Vec<String> {
+        repos
+            .iter()
+            .flat_map(|r| match r {
+                RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => vec.as_slice(),
+                RepoEntry::Error(_, _) => &[],
+            })
+            .filter_map(|entry| match entry {
+                BuildEntry::NotInstalled(variants) => Some(variants.basic.version().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_the_limited_builds_are_read() {
+        let library = generated_library();
+        let window = Window {
+            since: None,
+            limit: Some((20, SortFormat::Version)),
+            all_targets: true,
+        };
+
+        // The first read parses the build list, the second one reads it from the parsed cache
+        for _ in 0..2 {
+            let repos = gather_and_filter_repos(
+                &library.cfg,
+                false,
+                true,
+                Some(SortFormat::Version),
+                &window,
+            )
+            .unwrap();
+            let versions = remote_versions(&repos);
+
+            assert_eq!(versions.len(), 20);
+            assert_eq!(versions.first().map(String::as_str), Some("4.49.80"));
+            assert_eq!(versions.last().map(String::as_str), Some("4.49.99"));
+        }
+    }
+
+    #[test]
+    fn builds_before_since_are_not_read() {
+        let library = generated_library();
+        let window = Window {
+            since: DateTime::from_timestamp(1_700_000_000 + (BUILDS as i64 - 3) * 86400, 0),
+            limit: None,
+            all_targets: true,
+        };
+
+        let repos = gather_and_filter_repos(
+            &library.cfg,
+            false,
+            true,
+            Some(SortFormat::Version),
+            &window,
+        )
+        .unwrap();
+
+        assert_eq!(remote_versions(&repos), ["4.49.97", "4.49.98", "4.49.99"]);
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use blrs::{search::VersionSearchQuery, BLRSConfig};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{error_reading, error_writing, CommandError},
+    resolving::{build_query_string, foreign_target, installed_builds},
+    tasks::ConfigTask,
+};
+
+use super::pull::{pull_builds, PullOptions};
+
+/// A list of installed builds that can be recreated on another machine.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub builds: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub repo: String,
+    pub version: String,
+    pub branch: String,
+    pub build_hash: String,
+    pub commit_dt: DateTime<Utc>,
+    /// The query that finds this exact build again.
+    pub query: String,
+}
+
+/// Writes a manifest of every installed build to `path`, or to stdout if no path is given.
+pub fn export(cfg: &BLRSConfig, path: Option<&Path>) -> Result<(), CommandError> {
+    let mut builds: Vec<ManifestEntry> = installed_builds(cfg)?
+        .into_iter()
+        // Builds for other platforms cannot be pulled by their version alone
+        .filter(|(b, _)| foreign_target(&b.info.basic).is_none())
+        .map(|(b, repo)| {
+            let basic = &b.info.basic;
+            let version = basic.version();
+            ManifestEntry {
+                query: build_query_string(basic, &repo),
+                version: format!["{}.{}.{}", version.major, version.minor, version.patch],
+                branch: version.pre.to_string(),
+                build_hash: version.build.to_string(),
+                commit_dt: basic.commit_dt,
+                repo,
+            }
+        })
+        .collect();
+    builds.sort_by(|a, b| (&a.repo, a.commit_dt).cmp(&(&b.repo, b.commit_dt)));
+
+    let data = serde_json::to_string_pretty(&Manifest { builds }).unwrap();
+    match path {
+        Some(path) => {
+            std::fs::write(path, data).map_err(|e| error_writing(path.to_path_buf(), e))?;
+            info!["Saved the manifest to {}", path.display()];
+        }
+        None => println!["{}", data],
+    }
+
+    Ok(())
+}
+
+/// Pulls every build in the manifest that is not installed yet.
+pub async fn import(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    path: &Path,
+    opts: &PullOptions,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let data = std::fs::read_to_string(path).map_err(|e| error_reading(path.to_path_buf(), e))?;
+    let manifest: Manifest = serde_json::from_str(&data).map_err(|e| {
+        error_reading(
+            path.to_path_buf(),
+            std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        )
+    })?;
+
+    let installed: Vec<String> = installed_builds(cfg)?
+        .iter()
+        .map(|(b, repo)| build_query_string(&b.info.basic, repo))
+        .collect();
+
+    let queries: Vec<VersionSearchQuery> = manifest
+        .builds
+        .iter()
+        .filter(|entry| !installed.contains(&entry.query))
+        .filter_map(
+            |entry| match VersionSearchQuery::try_from(entry.query.as_str()) {
+                Ok(q) => Some(q),
+                Err(e) => {
+                    warn![
+                        "Skipping {:?}, it is not a valid query: {:?}",
+                        entry.query, e
+                    ];
+                    None
+                }
+            },
+        )
+        .collect();
+
+    info![
+        "{} of {} builds in the manifest are missing",
+        queries.len(),
+        manifest.builds.len()
+    ];
+    if queries.is_empty() {
+        return Ok(vec![]);
+    }
+
+    pull_builds(cfg, cli_cfg, queries, opts).await
+}
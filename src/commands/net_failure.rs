@@ -0,0 +1,263 @@
+use std::{error::Error, future::Future, time::Duration};
+
+use blrs::fetching::build_repository::FetchError;
+use log::warn;
+use reqwest::StatusCode;
+
+use crate::errs::CommandError;
+
+/// How many times fetching a repo is retried after a failure that may not happen again.
+const RETRIES: u32 = 3;
+/// The wait before the first retry. It doubles with every retry.
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The network failures that are reported on their own, as each is fixed differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The host name could not be resolved.
+    Dns,
+    /// The TLS handshake failed, like for an invalid certificate.
+    Tls,
+    /// No connection could be made, or making one timed out.
+    Connect,
+    /// The server answered with a 5xx status.
+    Server(StatusCode),
+}
+
+impl FailureKind {
+    /// Whether retrying can help. A failing handshake fails the same way every time,
+    /// while resolvers, connections, and servers have blips.
+    fn is_transient(self) -> bool {
+        !matches!(self, FailureKind::Tls)
+    }
+}
+
+/// Carried through `fetch`'s IO errors like `RateLimited`, so the command can report it as its own `CommandError`.
+#[derive(Debug)]
+pub struct NetworkFailure {
+    pub host: String,
+    pub kind: FailureKind,
+    pub detail: String,
+}
+
+impl std::fmt::Display for NetworkFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write![
+            f,
+            "{:?} failure from {}: {}",
+            self.kind, self.host, self.detail
+        ]
+    }
+}
+
+impl Error for NetworkFailure {}
+
+impl From<NetworkFailure> for std::io::Error {
+    fn from(e: NetworkFailure) -> Self {
+        std::io::Error::other(e)
+    }
+}
+
+impl NetworkFailure {
+    /// Classifies a failed request. `None` if it is not one of the failures reported on their own.
+    pub fn of(e: &reqwest::Error, fallback_host: &str) -> Option<Self> {
+        let kind = classify(e)?;
+        Some(Self {
+            host: e
+                .url()
+                .and_then(|url| url.host_str())
+                .unwrap_or(fallback_host)
+                .to_string(),
+            kind,
+            detail: root_cause(e),
+        })
+    }
+
+    pub fn to_command_error(&self) -> CommandError {
+        let (host, detail) = (self.host.clone(), self.detail.clone());
+        match self.kind {
+            FailureKind::Dns => CommandError::DnsFailure { host, detail },
+            FailureKind::Tls => CommandError::TlsFailure { host, detail },
+            FailureKind::Connect => CommandError::ConnectFailure { host, detail },
+            FailureKind::Server(status) => CommandError::ServerError { host, status },
+        }
+    }
+}
+
+/// The innermost error of the chain, which is the one that says what went wrong.
+fn root_cause(e: &reqwest::Error) -> String {
+    let mut cause: &dyn Error = e;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+/// reqwest does not expose DNS and TLS failures directly, so they are told apart by the errors they wrap.
+fn classify(e: &reqwest::Error) -> Option<FailureKind> {
+    if let Some(status) = e.status().filter(|s| s.is_server_error()) {
+        return Some(FailureKind::Server(status));
+    }
+    if !e.is_connect() {
+        return None;
+    }
+
+    let mut chain = String::new();
+    let mut cause: Option<&dyn Error> = Some(e);
+    while let Some(c) = cause {
+        chain.push_str(&c.to_string().to_lowercase());
+        chain.push('\n');
+        cause = c.source();
+    }
+
+    if chain.contains("dns error") || chain.contains("failed to lookup address") {
+        Some(FailureKind::Dns)
+    } else if ["certificate", "tls", "ssl", "handshake"]
+        .iter()
+        .any(|s| chain.contains(s))
+    {
+        Some(FailureKind::Tls)
+    } else {
+        Some(FailureKind::Connect)
+    }
+}
+
+/// The network failure an error from `fetch` came from, if it did.
+pub fn failure_of(e: &std::io::Error) -> Option<&NetworkFailure> {
+    e.get_ref()?.downcast_ref::<NetworkFailure>()
+}
+
+/// Runs a fetch, retrying with exponential backoff while it fails in a way that may not happen again.
+pub async fn with_backoff<T, F, Fut>(repo: &str, fetch: F) -> Result<T, FetchError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, FetchError>>,
+{
+    retry(repo, FIRST_BACKOFF, fetch).await
+}
+
+/// Like `with_backoff`, waiting `first_backoff` before the first retry.
+async fn retry<T, F, Fut>(
+    repo: &str,
+    first_backoff: Duration,
+    mut fetch: F,
+) -> Result<T, FetchError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, FetchError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match fetch().await {
+            Err(FetchError::Reqwest(e)) if attempt < RETRIES && is_transient(&e) => {
+                let wait = first_backoff * 2u32.pow(attempt);
+                warn![
+                    "Fetching {} failed: {}. Retrying in {}s ({}/{})",
+                    repo,
+                    root_cause(&e),
+                    wait.as_secs(),
+                    attempt + 1,
+                    RETRIES
+                ];
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(e: &reqwest::Error) -> bool {
+    match classify(e) {
+        Some(kind) => kind.is_transient(),
+        None => e.is_timeout(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    use super::*;
+
+    /// A local server answering every connection with `response`, whatever was asked.
+    fn serve(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                // The request is read first, so the client does not see the connection reset while sending it
+                let _ = stream.read(&mut [0; 4096]);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        address
+    }
+
+    /// An address nothing listens on.
+    fn closed_port() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    async fn failed_request(url: &str) -> reqwest::Error {
+        match reqwest::get(url).await {
+            Ok(response) => response.error_for_status().unwrap_err(),
+            Err(e) => e,
+        }
+    }
+
+    #[tokio::test]
+    async fn failures_are_classified() {
+        let refused = failed_request(&format!["http://{}/", closed_port()]).await;
+        assert_eq!(classify(&refused), Some(FailureKind::Connect));
+
+        let unresolvable = failed_request("http://blrs-test.invalid/").await;
+        assert_eq!(classify(&unresolvable), Some(FailureKind::Dns));
+
+        // A server that does not speak TLS fails the handshake
+        let plain = serve("HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+        let handshake = failed_request(&format!["https://{}/", plain]).await;
+        assert_eq!(classify(&handshake), Some(FailureKind::Tls));
+
+        let unavailable = serve("HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n");
+        let server = failed_request(&format!["http://{}/", unavailable]).await;
+        assert_eq!(
+            classify(&server),
+            Some(FailureKind::Server(StatusCode::SERVICE_UNAVAILABLE))
+        );
+
+        let not_found = serve("HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n");
+        let missing = failed_request(&format!["http://{}/", not_found]).await;
+        assert_eq!(classify(&missing), None);
+    }
+
+    /// How many times `retry` fetched from `url` before giving up.
+    async fn attempts(url: &str) -> u32 {
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), FetchError> = retry("test", Duration::ZERO, || async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(FetchError::Reqwest(failed_request(url).await))
+        })
+        .await;
+
+        assert!(result.is_err());
+        attempts.into_inner()
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let url = format!["http://{}/", closed_port()];
+        assert_eq!(attempts(&url).await, RETRIES + 1);
+    }
+
+    #[tokio::test]
+    async fn tls_failures_are_not_retried() {
+        let plain = serve("HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+        assert_eq!(attempts(&format!["https://{}/", plain]).await, 1);
+    }
+}
@@ -0,0 +1,47 @@
+use log::info;
+
+use crate::{
+    errs::{error_reading, error_writing, CommandError, IoErrorOrigin},
+    pin::{Pin, PIN_FILE},
+    resolving::parse_query,
+};
+
+/// Shows, sets, or removes the build pinned for the current folder.
+pub fn pin(query: Option<String>, remove: bool) -> Result<(), CommandError> {
+    let cwd =
+        std::env::current_dir().map_err(|e| CommandError::IoError(IoErrorOrigin::Terminal, e))?;
+
+    if remove {
+        let path = cwd.join(PIN_FILE);
+        if !path.exists() {
+            info!["{} has no pinned build", cwd.display()];
+            return Ok(());
+        }
+        std::fs::remove_file(&path).map_err(|e| error_writing(path, e))?;
+        info!["Unpinned {}", cwd.display()];
+        return Ok(());
+    }
+
+    match query {
+        None => match Pin::find(&cwd) {
+            Some(path) => {
+                let pin = Pin::read(&path).map_err(|e| error_reading(path.clone(), e))?;
+                println!["{} (from {})", pin.build, path.display()];
+            }
+            None => info!["No build is pinned for {}", cwd.display()],
+        },
+        Some(query) => {
+            parse_query(&query)?;
+
+            let path = cwd.join(PIN_FILE);
+            Pin {
+                build: query.clone(),
+            }
+            .write(&path)
+            .map_err(|e| error_writing(path, e))?;
+            info!["Pinned {} to {}", cwd.display(), query];
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use blrs::{search::VersionSearchQuery, BLRSConfig, LocalBuild};
+use chrono::{TimeDelta, Utc};
+use log::info;
+
+use crate::{
+    errs::CommandError,
+    resolving::{build_branch, build_query_string, confirm, find_matches, installed_builds},
+};
+
+use super::rm::delete_builds;
+
+/// Decides which installed builds are old enough to be removed.
+#[derive(Debug, Clone, Copy)]
+pub struct PrunePolicy {
+    /// Remove builds whose commit is older than this many days.
+    pub older_than: Option<i64>,
+    /// Keep only this many of the newest builds for every repo and branch.
+    pub keep: Option<usize>,
+}
+
+impl PrunePolicy {
+    /// Returns the builds that violate the policy. Favorited builds are never returned.
+    fn select(&self, builds: Vec<(LocalBuild, String)>) -> Vec<(LocalBuild, String)> {
+        let cutoff = self
+            .older_than
+            .map(|days| Utc::now() - TimeDelta::days(days));
+
+        let mut branches: HashMap<(String, String), Vec<(LocalBuild, String)>> = HashMap::new();
+        builds
+            .into_iter()
+            .filter(|(b, _)| !b.info.is_favorited)
+            .for_each(|(b, nick)| {
+                // Builds that do not record their branch, like adopted ones, go by their release cycle
+                let branch = build_branch(&b.info.basic)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| b.info.basic.version().pre.to_string());
+                branches
+                    .entry((nick.clone(), branch))
+                    .or_default()
+                    .push((b, nick));
+            });
+
+        branches
+            .into_values()
+            .flat_map(|mut builds| {
+                // Newest first
+                builds.sort_by_key(|(b, _)| std::cmp::Reverse(b.info.basic.commit_dt));
+
+                builds
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(idx, (b, _))| {
+                        self.keep.is_some_and(|keep| *idx >= keep)
+                            || cutoff.is_some_and(|cutoff| b.info.basic.commit_dt < cutoff)
+                    })
+                    .map(|(_, entry)| entry)
+            })
+            .collect()
+    }
+}
+
+pub fn prune_builds(
+    cfg: &BLRSConfig,
+    queries: Vec<VersionSearchQuery>,
+    policy: PrunePolicy,
+    dry_run: bool,
+) -> Result<(), CommandError> {
+    if policy.older_than.is_none() && policy.keep.is_none() {
+        return Err(CommandError::NotEnoughInput);
+    }
+
+    let mut builds = installed_builds(cfg)?;
+    if !queries.is_empty() {
+        builds = queries
+            .iter()
+            .flat_map(|query| find_matches(&builds, query))
+            .cloned()
+            .collect();
+        // Queries can overlap
+        builds.sort_by(|(a, _), (b, _)| a.folder.cmp(&b.folder));
+        builds.dedup_by(|(a, _), (b, _)| a.folder == b.folder);
+    }
+
+    let mut pruned = policy.select(builds);
+    pruned.sort_by_key(|(b, _)| (b.info.basic.commit_dt, b.info.basic.version().clone()));
+
+    if pruned.is_empty() {
+        info!["No builds need to be pruned"];
+        return Ok(());
+    }
+
+    println!["{} build(s) will be removed:", pruned.len()];
+    pruned.iter().for_each(|(b, nick)| {
+        println![
+            "  {}  {}  {}",
+            build_query_string(&b.info.basic, nick),
+            b.info.basic.commit_dt,
+            ansi_term::Color::White
+                .dimmed()
+                .paint(b.folder.display().to_string())
+        ];
+    });
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if !confirm("Remove these builds?", false) {
+        return Err(CommandError::Cancelled);
+    }
+
+    delete_builds(cfg, pruned.iter().map(|(b, _)| b))
+}
@@ -2,43 +2,199 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, LazyLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::LazyLock;
+use std::time::Duration;
 
 use blrs::build_targets::get_target_setup;
 use blrs::info::build_info::LocalBuildInfo;
-use blrs::search::{BInfoMatcher, VersionSearchQuery};
+use blrs::search::VersionSearchQuery;
 use blrs::LocalBuild;
 use blrs::{
     fetching::{build_repository::BuildRepo, fetcher::FetchStreamerState},
-    repos::{read_repos, BuildEntry, RepoEntry, Variants},
+    repos::{BuildEntry, RepoEntry, Variants},
     BLRSConfig, BasicBuildInfo, RemoteBuild,
 };
 
-use futures::AsyncWriteExt;
-use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
-use log::{error, info, warn};
-use reqwest::{Client, Url};
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use futures::future::{join_all, try_join_all};
+use futures::{AsyncSeekExt, AsyncWriteExt};
+use indicatif::{
+    HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle,
+};
+use log::{debug, error, info, warn};
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::{Client, StatusCode, Url};
+use sevenz_rust::{Password, SevenZReader};
 use tar::Archive;
 use uuid::Uuid;
 use xz::read::XzDecoder;
-use zip::ZipArchive;
+use zip::{read::ZipFile, result::ZipError, ZipArchive};
+
+use crate::archive_cache;
+use crate::cancel;
+use crate::cli_config::CliConfig;
+use crate::deletion;
+use crate::dirs;
+use crate::errs::{
+    error_reading, error_renaming, error_writing, request_error, CommandError, IoErrorOrigin,
+};
+use crate::events::{emit, Event, ProgressMilestones};
+use crate::hooks::{self, Hook};
+use crate::integrity;
+use crate::network::client_builder;
+use crate::notify::notify;
+use crate::provenance::{self, Provenance};
+use crate::repo_auth::client_for;
+use crate::repo_cache::read_repos;
+use crate::resolving::{
+    confirm, find_matches, folder_name, installed_builds, resolve_match, resolve_variant,
+    with_target, VariantPick, VariantPreference,
+};
+use crate::target;
+
+use crate::tasks::ConfigTask;
+
+use super::addons::link_into;
+use super::fetcher::{cache_is_stale, fetch, repair_caches, StalePolicy};
+use super::scheduler::{parse_rate, DownloadScheduler, RateLimiter};
+
+/// Shared by every download, so the limit applies to their combined speed.
+static RATE_LIMITER: LazyLock<RateLimiter> = LazyLock::new(RateLimiter::new);
+
+/// Roughly how much larger an extracted build is than its archive.
+const EXTRACTION_RATIO: u64 = 3;
+
+/// Options that change how builds are resolved and downloaded.
+#[derive(Debug, Clone)]
+pub struct PullOptions {
+    /// Do not filter variants by the current platform.
+    pub all_platforms: bool,
+    /// The number of concurrent connections used to download a single file.
+    pub connections: usize,
+    /// Do not draw progress bars. Progress is still reported through events.
+    pub hide_progress: bool,
+    /// The number of builds downloaded at the same time.
+    pub jobs: usize,
+    /// The minimum time between requests to the same host.
+    pub host_delay: Duration,
+    /// How many times a download is retried when the host rate limits us.
+    pub retries: u32,
+    /// Whether the build lists are fetched before searching them.
+    pub fetch: FetchPolicy,
+    /// Extract tarballs while they are downloaded instead of saving the archive first.
+    pub stream: bool,
+    /// Only print what would be downloaded, going by the cached build lists.
+    pub dry_run: bool,
+    /// The combined download speed limit in bytes per second. Falls back to `cli.limit_rate`.
+    pub limit_rate: Option<u64>,
+    /// Picks between variants that fit the platform. Falls back to `cli.variant_preferences`.
+    pub prefer_variant: Vec<VariantPreference>,
+    /// Downloads this variant, whatever the platform is, instead of choosing one.
+    pub variant: Option<VariantPick>,
+    /// Pull even if the library seems to be too small for the builds.
+    pub force: bool,
+    /// Pull builds that are already installed again, replacing them.
+    pub reinstall: bool,
+    /// Record a manifest of the extracted files for `verify --deep`. Falls back to `cli.record_manifests`.
+    pub record_manifest: bool,
+}
 
-use crate::errs::{error_reading, error_renaming, error_writing, CommandError, IoErrorOrigin};
-use crate::resolving::{resolve_match, resolve_variant};
+/// Decides whether `pull` refreshes the cached build lists first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FetchPolicy {
+    /// Fetch when the cache is missing or older than the fetch interval.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
 
-pub static CANCELLED: LazyLock<Arc<AtomicBool>> =
-    LazyLock::new(|| Arc::new(AtomicBool::new(false)));
+impl Default for PullOptions {
+    fn default() -> Self {
+        Self {
+            all_platforms: false,
+            connections: 1,
+            hide_progress: false,
+            jobs: 3,
+            host_delay: Duration::from_millis(500),
+            retries: 3,
+            fetch: FetchPolicy::Auto,
+            stream: false,
+            dry_run: false,
+            limit_rate: None,
+            prefer_variant: vec![],
+            variant: None,
+            force: false,
+            reinstall: false,
+            record_manifest: false,
+        }
+    }
+}
 
 pub async fn pull_builds(
     cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
     queries: Vec<VersionSearchQuery>,
-    all_platforms: bool,
-) -> Result<(), CommandError> {
+    opts: &PullOptions,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    cancel::handle_ctrl_c();
     std::fs::create_dir_all(&cfg.paths.library)
         .inspect_err(|e| error!("Failed to create library path: {:?}", e))
         .map_err(|e| error_writing(cfg.paths.library.clone(), e))?;
 
+    let limit_rate = match (opts.limit_rate, &cli_cfg.limit_rate) {
+        (Some(rate), _) => Some(rate),
+        (None, Some(rate)) => Some(parse_rate(rate).map_err(CommandError::InvalidConfig)?),
+        (None, None) => None,
+    };
+    RATE_LIMITER.set_rate(limit_rate);
+    let cache_size = match cli_cfg.archive_cache.enabled {
+        true => archive_cache::parse_size(&cli_cfg.archive_cache.max_size)
+            .map_err(CommandError::InvalidConfig)?,
+        false => 0,
+    };
+    let archive_dir = dirs::archive_dir();
+    std::fs::create_dir_all(&archive_dir).map_err(|e| error_writing(archive_dir, e))?;
+
+    let mut tasks = vec![];
+    // A dry run goes by the cached build lists instead of fetching them
+    let should_fetch = !opts.dry_run
+        && match opts.fetch {
+            FetchPolicy::Auto => cache_is_stale(cfg),
+            FetchPolicy::Always => true,
+            FetchPolicy::Never => false,
+        };
+    if should_fetch {
+        info!["Refreshing the build lists before searching them"];
+        match fetch(
+            cfg,
+            cli_cfg,
+            &cfg.repos,
+            true,
+            true,
+            StalePolicy::default(),
+            false,
+        )
+        .await
+        {
+            Ok((task, _)) => tasks.push(task),
+            // A stale cache is still better than nothing
+            Err(e) if opts.fetch == FetchPolicy::Auto => {
+                warn![
+                    "Failed to refresh the build lists, using the cached ones: {}",
+                    e
+                ]
+            }
+            Err(e) => return Err(CommandError::IoError(IoErrorOrigin::Fetching, e)),
+        }
+    }
+    // A truncated build list would otherwise only show up as builds missing from it
+    if !opts.dry_run {
+        tasks.extend(repair_caches(cfg, cli_cfg).await?);
+    }
+
     let repos: Vec<_> = read_repos(cfg.repos.clone(), &cfg.paths, false)
         .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
         .into_iter()
@@ -60,18 +216,21 @@ pub async fn pull_builds(
         })
         .collect();
 
-    let mut map = build_map(&repos, all_platforms);
+    let mut map = build_map(&repos, opts.all_platforms || opts.variant.is_some());
+    // Builds without the variant would only fail once they are chosen
+    if let Some(pick) = &opts.variant {
+        map.retain(|_, (variants, _)| variants.v.iter().any(|v| pick.matches(v)));
+    }
 
     let builds: Vec<(BasicBuildInfo, String)> = map
         .iter()
         .map(|(b, (_, r))| (b.clone(), r.nickname.clone()))
         .collect();
 
-    let matcher = BInfoMatcher::new(&builds);
     let matches: Vec<(&VersionSearchQuery, Vec<(BasicBuildInfo, String)>)> = {
         queries
             .iter()
-            .map(|q| (q, matcher.find_all(q).into_iter().cloned().collect()))
+            .map(|q| (q, find_matches(&builds, q).into_iter().cloned().collect()))
             .collect()
     };
 
@@ -84,6 +243,11 @@ pub async fn pull_builds(
         return Err(CommandError::QueryResultEmpty(empty_matches.join(", ")));
     }
 
+    let preferences = match opts.prefer_variant.is_empty() {
+        true => &cli_cfg.variant_preferences,
+        false => &opts.prefer_variant,
+    };
+
     // Get builds selected to download
     let choices = matches
         .into_iter()
@@ -93,7 +257,7 @@ pub async fn pull_builds(
                 &binfos.into_iter().collect::<Vec<_>>(),
                 &format!["Multiple matches for query {q}! select a build to download"],
             )
-            .cloned()
+            .map(|(b, _)| b.clone())
         })
         // Get variants of the chosen builds
         .map(|info: BasicBuildInfo| {
@@ -103,36 +267,45 @@ pub async fn pull_builds(
                 "Selected build {}/{} for installation",
                 remove.1.nickname, info.ver
             ];
+            emit(Event::BuildSelected {
+                repo: &remove.1.nickname,
+                version: info.ver.to_string(),
+            });
 
             remove
         })
         // Check if the variants were larger than 1. If so, perform conflict resolution
         .filter_map(|(variants, repo): (Variants<_>, &BuildRepo)| {
-            resolve_variant(variants, all_platforms).map(|build| (build, repo))
-        });
-
-    // ? Progress bar styling
-    let pb = MultiProgress::new();
-    let template =
-        "{spinner:.green} [{elapsed_precise} (ETA {eta})] [{bar:40.cyan/red}] {bytes}/{total_bytes} {msg:.green}";
-    let pbstyle = ProgressStyle::with_template(template)
-        .unwrap()
-        .with_key(
-            "eta",
-            |state: &ProgressState, w: &mut dyn std::fmt::Write| {
-                write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
-            },
-        )
-        .progress_chars("#|-");
-
-    // Setup Ctrl+C handler, if possible
-    let _ = ctrlc::set_handler(|| {
-        CANCELLED.store(true, Ordering::Release);
-    });
+            let native: Vec<String> = variants
+                .clone()
+                .filter_target(get_target_setup().unwrap())
+                .v
+                .iter()
+                .map(|variant| variant.to_string())
+                .collect();
+
+            resolve_variant(
+                variants,
+                opts.all_platforms,
+                preferences,
+                opts.variant.as_ref(),
+            )
+            .map(|variant| {
+                variant.map(|variant| {
+                    // Builds for other platforms are kept apart from the native one
+                    let target = variant.to_string();
+                    let foreign = (!native.contains(&target)).then(|| target.clone());
+                    (variant.b, target, foreign, repo)
+                })
+            })
+            .transpose()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let setups: Vec<_> = choices
+    let mirror_client = client_builder(cfg, false)?.build().map_err(request_error)?;
+    let plans: Vec<(PullTarget, String)> = choices
         .into_iter()
-        .map(|(remote_build, repo)| {
+        .map(|(remote_build, variant, foreign_target, repo)| {
             let url = remote_build.url();
             let extension = remote_build.file_extension.clone().unwrap_or_default();
             let filename = PathBuf::from(url.path())
@@ -148,43 +321,325 @@ pub async fn pull_builds(
 
             let repo_path = cfg.paths.path_to_repo(repo);
 
-            let completed_filepath = repo_path.join(&filename);
+            let completed_filepath =
+                archive_cache::path_for(&url, &remote_build.basic.commit_dt, Path::new(&filename));
             let temporary_filepath = completed_filepath.with_extension(extension + ".part");
-            let destination = repo_path.join(remote_build.basic.version().to_string());
+            let basic = match &foreign_target {
+                Some(target) => with_target(&remote_build.basic, target),
+                None => remote_build.basic,
+            };
+            let destination = repo_path.join(folder_name(&basic, &repo.nickname));
+            let client = client_for(cfg, &cli_cfg.repo_auth, repo, &url)?;
+            let mirrors = cli_cfg
+                .mirrors
+                .get(&repo.repo_id)
+                .map(|templates| mirror_urls(templates, &url))
+                .unwrap_or_default();
+
+            let target = PullTarget {
+                url,
+                client,
+                mirrors,
+                mirror_client: mirror_client.clone(),
+                basic,
+                repo_id: repo.repo_id.clone(),
+                temporary_filepath,
+                completed_filepath,
+                destination,
+            };
+            Ok((target, format!["{}/{}", repo.nickname, variant]))
+        })
+        .collect::<Result<_, CommandError>>()?;
+
+    // Pulling an installed build again would replace it, so that has to be asked for
+    let installed = installed_builds(cfg)?;
+    let plans: Vec<(PullTarget, String)> = plans
+        .into_iter()
+        .filter(|(target, _)| {
+            let Some((build, nick)) = installed.iter().find(|(b, _)| {
+                b.folder == target.destination
+                    || (b.info.basic == target.basic
+                        && b.folder.parent() == target.destination.parent())
+            }) else {
+                return true;
+            };
+
+            match opts.reinstall {
+                true => info!["Reinstalling {}/{}", nick, build.info.basic.ver],
+                false => info![
+                    "{}/{} is already installed at {}, skipping it. Pass --reinstall to pull it again",
+                    nick,
+                    build.info.basic.ver,
+                    build.folder.display()
+                ],
+            }
+            opts.reinstall
+        })
+        .collect();
+    if plans.is_empty() {
+        return Ok(tasks);
+    }
+
+    if opts.dry_run {
+        print_plan(&plans).await;
+        return Ok(tasks);
+    }
+    check_disk_space(&plans, opts.stream, opts.force).await?;
+
+    // ? Progress bar styling
+    let pb = match opts.hide_progress {
+        true => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        false => MultiProgress::new(),
+    };
+    let template =
+        "{spinner:.green} [{elapsed_precise} (ETA {eta})] [{bar:40.cyan/red}] {bytes}/{total_bytes} {msg:.green}";
+    let pbstyle = ProgressStyle::with_template(template)
+        .unwrap()
+        .with_key(
+            "eta",
+            |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
+            },
+        )
+        .progress_chars("#|-");
+
+    let scheduler = DownloadScheduler::new(opts.jobs, opts.host_delay, opts.retries);
+
+    let setups: Vec<_> = plans
+        .into_iter()
+        .map(|(target, variant)| {
+            let temporary_filepath = target.temporary_filepath.clone();
+            let completed_filepath = target.completed_filepath.clone();
+            let label = format!["{} {}", target.basic.version(), variant];
 
             let ppb = pb.add(ProgressBar::new(0));
             ppb.set_style(pbstyle.clone());
             (
-                process_build(
-                    ppb,
-                    cfg,
-                    url,
-                    remote_build.basic,
-                    temporary_filepath.clone(),
-                    completed_filepath.clone(),
-                    destination,
-                ),
+                process_build(ppb, opts, &scheduler, target, cli_cfg),
                 temporary_filepath,
                 completed_filepath,
+                label,
             )
         })
         .collect();
 
     let targets: Vec<(PathBuf, PathBuf)> = setups
         .iter()
-        .map(|(_, temp, finished)| (temp.clone(), finished.clone()))
+        .map(|(_, temp, finished, _)| (temp.clone(), finished.clone()))
+        .collect();
+    let labels: Vec<String> = setups
+        .iter()
+        .map(|(_, _, _, label)| label.clone())
         .collect();
     let result: Vec<Result<(), CommandError>> =
-        futures::future::join_all(setups.into_iter().map(|(fut, _, _)| fut))
+        futures::future::join_all(setups.into_iter().map(|(fut, _, _, _)| fut))
             .await
             .into_iter()
             .collect();
 
+    result
+        .iter()
+        .zip(&targets)
+        .for_each(|(result, (_, finished))| {
+            if let Err(e) = result {
+                emit(Event::Error {
+                    message: e.to_string(),
+                    path: Some(finished),
+                });
+            }
+        });
+
+    print_summary(&labels, &result);
+
+    let total = result.len();
+    let failed = result.iter().filter(|r| r.is_err()).count();
+    let cancelled = result
+        .iter()
+        .any(|r| matches!(r, Err(CommandError::Cancelled)));
+
     prompt_deletions(result, targets);
+    archive_cache::trim(cache_size);
+
+    match (failed, cancelled) {
+        (0, _) => Ok(tasks),
+        (_, true) => Err(CommandError::Cancelled),
+        (failed, false) => Err(CommandError::FailedPulls { failed, total }),
+    }
+}
 
+/// Prints the outcome of every build, so failures are not lost between the progress bars.
+fn print_summary(labels: &[String], result: &[Result<(), CommandError>]) {
+    if result.len() < 2 {
+        return;
+    }
+
+    let width = labels.iter().map(|l| l.len()).max().unwrap_or_default();
+    for (label, result) in labels.iter().zip(result) {
+        let status = match result {
+            Ok(_) => ansi_term::Color::Green.paint("installed".to_string()),
+            Err(CommandError::Cancelled) => ansi_term::Color::Yellow.paint("cancelled".to_string()),
+            Err(e) => ansi_term::Color::Red.paint(format!["failed: {}", e]),
+        };
+        println!["{:<width$}  {}", label, status];
+    }
+}
+
+/// Asks the host for the size of the file at `url` without downloading it. None if it does not report one.
+pub(super) async fn head_size(client: &Client, url: &Url) -> Option<u64> {
+    match client.head(url.clone()).send().await {
+        Ok(r) if r.status().is_success() => r.content_length(),
+        Ok(r) => {
+            debug!["HEAD {} returned {}", url, r.status()];
+            None
+        }
+        Err(e) => {
+            debug!["HEAD {} failed: {:?}", url, e];
+            None
+        }
+    }
+}
+
+/// Checks that the filesystems the archives and builds are written to have room for them.
+/// Archives whose size the host does not report are left out of the estimate.
+async fn check_disk_space(
+    plans: &[(PullTarget, String)],
+    stream: bool,
+    force: bool,
+) -> Result<(), CommandError> {
+    let sizes = join_all(
+        plans
+            .iter()
+            .map(|(target, _)| head_size(&target.client, &target.url)),
+    )
+    .await;
+    let mut needs = vec![];
+    for ((target, _), size) in plans.iter().zip(sizes) {
+        let Some(size) = size else {
+            continue;
+        };
+        // The archive is kept until its extraction finishes
+        if !stream && !target.completed_filepath.exists() {
+            needs.push((existing_ancestor(&target.completed_filepath), size));
+        }
+        needs.push((
+            existing_ancestor(&target.destination),
+            size * EXTRACTION_RATIO,
+        ));
+    }
+
+    for (dir, needed) in by_filesystem(needs) {
+        check_space(&dir, needed, force)?;
+    }
     Ok(())
 }
 
+/// The closest folder to `path` that exists already, as the free space of a folder that is
+/// yet to be made is that of the filesystem it will be made in.
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .skip(1)
+        .find(|p| p.is_dir())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+/// Adds up the bytes written to folders on the same filesystem, as they share its free space.
+fn by_filesystem(needs: Vec<(PathBuf, u64)>) -> Vec<(PathBuf, u64)> {
+    let mut merged: Vec<(PathBuf, u64)> = vec![];
+    for (dir, needed) in needs {
+        match merged.iter_mut().find(|(d, _)| same_filesystem(d, &dir)) {
+            Some((_, total)) => *total += needed,
+            None => merged.push((dir, needed)),
+        }
+    }
+    merged
+}
+
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => a == b,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    // Filesystems are told apart by their drive
+    a.components().next() == b.components().next()
+}
+
+/// Asks before pulling if `dir` has less than `needed` bytes free.
+fn check_space(dir: &Path, needed: u64, force: bool) -> Result<(), CommandError> {
+    if needed == 0 {
+        return Ok(());
+    }
+    let available = match fs2::available_space(dir) {
+        Ok(available) => available,
+        Err(e) => {
+            warn!["Failed to check the free space in {}: {}", dir.display(), e];
+            return Ok(());
+        }
+    };
+    debug![
+        "{} needed in {}, {} available",
+        HumanBytes(needed),
+        dir.display(),
+        HumanBytes(available)
+    ];
+    if needed <= available {
+        return Ok(());
+    }
+
+    warn![
+        "The builds need about {}, but only {} is free in {}",
+        HumanBytes(needed),
+        HumanBytes(available),
+        dir.display()
+    ];
+    if force || confirm("Pull anyway?", false) {
+        return Ok(());
+    }
+    Err(CommandError::NotEnoughSpace {
+        path: dir.to_path_buf(),
+        needed,
+        available,
+    })
+}
+
+/// Prints what `pull` would download without downloading anything.
+/// The sizes are requested from the hosts, and are left out if they do not report one.
+async fn print_plan(plans: &[(PullTarget, String)]) {
+    let mut total = 0;
+    for (target, variant) in plans {
+        let size = head_size(&target.client, &target.url).await;
+        total += size.unwrap_or_default();
+
+        let existing = match target.completed_filepath.exists() {
+            true => " (already downloaded)",
+            false => "",
+        };
+        println![
+            "{} {}\n  size: {}{}\n  url: {}\n  into: {}",
+            target.basic.version(),
+            ansi_term::Color::White.dimmed().paint(variant),
+            size.map(|s| HumanBytes(s).to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            existing,
+            target.url,
+            target.destination.display(),
+        ];
+    }
+
+    println![
+        "{} build(s) would be pulled, {} in total",
+        plans.len(),
+        HumanBytes(total)
+    ];
+}
+
 fn build_map(
     repos: &[(BuildRepo, Vec<Variants<RemoteBuild>>)],
     all_platforms: bool,
@@ -208,12 +663,10 @@ fn build_map(
 
     // Filter out build variants that do not coencide with our system
     if !all_platforms {
-        let target = get_target_setup().unwrap();
-
         let h: HashMap<_, _> = m
             .into_iter()
             .filter_map(|(key, (variants, repos))| {
-                let filtered = variants.filter_target(target);
+                let filtered = target::filter_variants(variants);
                 match filtered.v.len() {
                     0 => None,
                     _ => Some((key, (filtered, repos))),
@@ -225,40 +678,250 @@ fn build_map(
     m
 }
 
-async fn process_build(
-    ppb: ProgressBar,
-    cfg: &BLRSConfig,
+/// A remote build that has been chosen for download, and where it will end up.
+#[derive(Debug, Clone)]
+struct PullTarget {
     url: Url,
+    /// A client with the credentials of the build's repo.
+    client: Client,
+    /// Tried in order when downloading from `url` fails.
+    mirrors: Vec<Url>,
+    /// A client without credentials, as mirrors are not the repo's host.
+    mirror_client: Client,
     basic: BasicBuildInfo,
+    /// The id of the repo the build is listed in.
+    repo_id: String,
+    /// Where the archive is written while it is being downloaded.
     temporary_filepath: PathBuf,
+    /// Where the archive is moved once the download is complete.
     completed_filepath: PathBuf,
+    /// The folder the build is extracted into.
     destination: PathBuf,
+}
+
+/// Fills in the mirror URL templates of a repo for one download.
+/// `{path}` is replaced by the path of the original URL, and `{file}` by its file name.
+fn mirror_urls(templates: &[String], url: &Url) -> Vec<Url> {
+    let path = url.path().trim_start_matches('/');
+    let file = path.rsplit('/').next().unwrap_or(path);
+
+    templates
+        .iter()
+        .filter_map(|template| {
+            let mirror = template.replace("{path}", path).replace("{file}", file);
+            Url::parse(&mirror)
+                .inspect_err(|e| warn!["Ignoring invalid mirror {:?}: {}", mirror, e])
+                .ok()
+        })
+        .collect()
+}
+
+async fn process_build(
+    ppb: ProgressBar,
+    opts: &PullOptions,
+    scheduler: &DownloadScheduler,
+    target: PullTarget,
+    cli_cfg: &CliConfig,
 ) -> Result<(), CommandError> {
-    if !completed_filepath.exists() {
-        let client = cfg
-            .client_builder(url.domain().is_some_and(|h| h.contains("api.github.com")))
-            .build()
-            .unwrap();
+    let PullTarget {
+        url,
+        client,
+        mirrors,
+        mirror_client,
+        basic,
+        repo_id,
+        temporary_filepath,
+        completed_filepath,
+        destination,
+    } = target;
+
+    // Tarballs can be unpacked as they arrive. Anything else needs the whole archive on disk
+    let stream_format = ArchiveFormat::from_path(&completed_filepath)
+        .filter(|format| opts.stream && format.is_tarball() && !completed_filepath.exists());
+
+    // Extracted next to its destination and renamed into place once complete,
+    // so a failed or cancelled pull never leaves a partial build behind
+    let staging = destination.with_file_name(format![".pulling-{}", Uuid::new_v4()]);
+    let staged = async {
+        if !completed_filepath.exists() {
+            let download = |url: Url, client: Client| {
+                let (ppb, temporary_filepath, completed_filepath, staging) =
+                    (&ppb, &temporary_filepath, &completed_filepath, &staging);
+                async move {
+                    ppb.set_message(format!["Queued {}", url]);
+                    let url_str = url.to_string();
+
+                    scheduler
+                        .run(&url, || async {
+                            ppb.set_message(format!["Downloading file {}", url]);
+                            ppb.set_position(0);
+                            emit(Event::DownloadStarted {
+                                url: &url_str,
+                                path: completed_filepath,
+                            });
+
+                            if let Some(format) = stream_format {
+                                download_and_extract(
+                                    ppb,
+                                    client.clone(),
+                                    url.clone(),
+                                    format,
+                                    staging,
+                                )
+                                .await
+                            } else if opts.connections > 1 {
+                                download_file_segmented(
+                                    ppb,
+                                    client.clone(),
+                                    url.clone(),
+                                    temporary_filepath,
+                                    completed_filepath,
+                                    opts.connections,
+                                )
+                                .await
+                            } else {
+                                download_file(
+                                    ppb,
+                                    client.clone(),
+                                    url.clone(),
+                                    temporary_filepath,
+                                    completed_filepath,
+                                )
+                                .await
+                            }
+                        })
+                        .await?;
+
+                    emit(Event::DownloadFinished {
+                        url: &url_str,
+                        path: completed_filepath,
+                    });
+                    Ok(())
+                }
+            };
+
+            let mut result = download(url.clone(), client).await;
+            let mut source = &url;
+            for mirror in &mirrors {
+                match result {
+                    Ok(()) | Err(CommandError::Cancelled) => break,
+                    Err(e) => {
+                        warn![
+                            "Downloading from {} failed, trying {}: {}",
+                            source, mirror, e
+                        ];
+                        source = mirror;
+                        result = download(mirror.clone(), mirror_client.clone()).await;
+                        if result.is_ok() {
+                            info!["Downloaded {} from the mirror {}", url, mirror];
+                        }
+                    }
+                }
+            }
+            result?;
+        }
 
-        ppb.set_message(format!["Downloading file {}", url]);
+        // Extract file
+        if stream_format.is_none() {
+            ppb.set_message(format!["Extracting file {}", completed_filepath.display()]);
+            let success = extract_file(&ppb, &completed_filepath, &staging).await?;
+            if !success {
+                return Err(CommandError::UnsupportedFileFormat(
+                    completed_filepath
+                        .extension()
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = staged {
+        if staging.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&staging) {
+                warn!["Failed to clean up {}: {}", staging.display(), e];
+            }
+        }
+        return Err(e);
+    }
 
-        download_file(&ppb, client, url, &temporary_filepath, &completed_filepath).await?;
+    // Only a build being reinstalled, or the leftovers of an older, interrupted pull, can be in the way.
+    // It is moved aside until the new build is in place, so a failed swap can put it back
+    let replaced = match destination.exists() {
+        true => {
+            debug!["Replacing {}", destination.display()];
+            let aside = destination.with_file_name(format![".replacing-{}", Uuid::new_v4()]);
+            std::fs::rename(&destination, &aside)
+                .map_err(|e| error_renaming(destination.clone(), aside.clone(), e))?;
+            Some(aside)
+        }
+        false => None,
+    };
+    if let Err(e) = std::fs::rename(&staging, &destination) {
+        if let Some(aside) = &replaced {
+            if let Err(e) = std::fs::rename(aside, &destination) {
+                warn![
+                    "Failed to restore {} from {}: {}",
+                    destination.display(),
+                    aside.display(),
+                    e
+                ];
+            }
+        }
+        return Err(error_renaming(staging, destination.clone(), e));
+    }
+    if let Some(aside) = replaced {
+        if let Err(e) = deletion::remove(&aside) {
+            warn![
+                "Failed to remove the replaced build {}: {}",
+                aside.display(),
+                e
+            ];
+        }
     }
+    emit(Event::ExtractionFinished {
+        archive: &completed_filepath,
+        destination: &destination,
+    });
 
-    // Extract file
-    ppb.set_message(format!["Extracting file {}", completed_filepath.display()]);
-    let success = extract_file(&ppb, &completed_filepath, &destination).await?;
-    if !success {
-        return Err(CommandError::UnsupportedFileFormat(
-            completed_filepath
-                .extension()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .into(),
-        ));
+    // Recorded before the build info is written, so `verify --deep` can tell if the build changes later
+    if opts.record_manifest || cli_cfg.record_manifests {
+        ppb.set_message("Hashing the extracted files");
+        let destination = destination.clone();
+        tokio::task::spawn_blocking(move || integrity::write(&destination))
+            .await
+            .unwrap()?;
     }
 
+    let sha256 = match stream_format {
+        Some(_) => None,
+        None => {
+            ppb.set_message("Hashing the archive");
+            let archive = completed_filepath.clone();
+            tokio::task::spawn_blocking(move || integrity::hash_file(&archive))
+                .await
+                .unwrap()
+                .map(Some)
+                .map_err(|e| error_reading(completed_filepath.clone(), e))?
+        }
+    };
+    provenance::write(
+        &destination,
+        &Provenance {
+            repo: repo_id,
+            url: url.to_string(),
+            archive: completed_filepath
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            sha256,
+            installed_at: chrono::Utc::now(),
+        },
+    )?;
+
     ppb.set_message("Generating the build info");
     ppb.set_position(0);
     ppb.set_length(1);
@@ -276,12 +939,39 @@ async fn process_build(
 
     lb.write()
         .map_err(|e| error_writing(destination.clone(), e))?;
+    emit(Event::BuildInstalled {
+        version: lb.info.basic.ver.to_string(),
+        path: &destination,
+    });
+    notify(
+        &format!["Blender {} downloaded and ready", lb.info.basic.ver],
+        &destination.display().to_string(),
+    );
+
+    // Kept for pulling the build again, until the cache is trimmed at the end of the pull
+    if stream_format.is_none() {
+        archive_cache::touch(&completed_filepath);
+    }
 
-    // Delete archive file
+    ppb.set_message("Linking the addons");
+    if let Err(e) = link_into(&lb, &cli_cfg.addons) {
+        ppb.suspend(|| {
+            warn![
+                "Failed to link the addons into {}: {}",
+                lb.folder.display(),
+                e
+            ]
+        });
+    }
 
-    ppb.set_message("Deleting temp file");
-    if trash::delete(&completed_filepath).is_err() {
-        std::fs::remove_file(completed_filepath).map_err(|e| error_writing(destination, e))?;
+    ppb.set_message("Running the post-pull hooks");
+    {
+        let ppb = ppb.clone();
+        tokio::task::spawn_blocking(move || {
+            ppb.suspend(|| hooks::run_after(Hook::PostPull, &lb, &hooks::repo_of(&lb), &[]))
+        })
+        .await
+        .unwrap();
     }
 
     ppb.finish();
@@ -304,6 +994,8 @@ async fn download_file(
         .await
         .map_err(|e| error_writing(temporary_filepath.into(), e))?;
 
+    let url_str = url.to_string();
+    let milestones = ProgressMilestones::default();
     let mut state = FetchStreamerState::new(client, url);
 
     let mut length = None;
@@ -325,7 +1017,9 @@ async fn download_file(
                 }
                 {}
 
+                RATE_LIMITER.consume(last_chunk.len() as u64).await;
                 ppb.inc(last_chunk.len() as u64);
+                milestones.update(&url_str, ppb.position(), length.unwrap_or_default());
 
                 file.write_all(last_chunk)
                     .await
@@ -357,7 +1051,7 @@ async fn download_file(
             }
         }
 
-        if CANCELLED.load(Ordering::Acquire) {
+        if cancel::is_cancelled() {
             drop(state);
             drop(file);
 
@@ -367,13 +1061,224 @@ async fn download_file(
 
     // Moved out of the loop to gain ownership of the error
     if let FetchStreamerState::Err(error) = state {
-        Err(CommandError::ReqwestError(error))
+        Err(request_error(error))
     } else {
         Ok(())
     }
 }
 
-async fn extract_file<P>(
+/// Downloads a file over several connections at once, each one fetching a separate byte range.
+///
+/// Falls back to a regular download if the server does not support range requests.
+async fn download_file_segmented(
+    ppb: &ProgressBar,
+    client: Client,
+    url: Url,
+    temporary_filepath: &Path,
+    completed_filepath: &Path,
+    connections: usize,
+) -> Result<(), CommandError> {
+    let head = client
+        .head(url.clone())
+        .send()
+        .await
+        .map_err(request_error)?;
+
+    let accepts_ranges = head
+        .headers()
+        .get(ACCEPT_RANGES)
+        .is_some_and(|v| v.as_bytes() == b"bytes");
+    // The body of a HEAD response is empty, so the header has to be read directly
+    let length = head
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let length = match (accepts_ranges, length) {
+        (true, Some(length)) if length > 0 => length,
+        _ => {
+            debug![
+                "{} does not support range requests, downloading normally",
+                url
+            ];
+            return download_file(ppb, client, url, temporary_filepath, completed_filepath).await;
+        }
+    };
+
+    ppb.set_length(length);
+
+    std::fs::create_dir_all(temporary_filepath.parent().unwrap())
+        .map_err(|e| error_writing(temporary_filepath.parent().unwrap().into(), e))?;
+
+    // Allocate the whole file up front so every segment can write to its own offset
+    std::fs::File::create(temporary_filepath)
+        .and_then(|f| f.set_len(length))
+        .map_err(|e| error_writing(temporary_filepath.into(), e))?;
+
+    let milestones = ProgressMilestones::default();
+    let segment_size = length.div_ceil(connections as u64);
+    let segments = (0..connections as u64)
+        .map(|i| (i * segment_size, ((i + 1) * segment_size).min(length)))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| {
+            download_segment(
+                ppb,
+                &milestones,
+                client.clone(),
+                url.clone(),
+                temporary_filepath,
+                start,
+                end - 1,
+            )
+        });
+
+    match try_join_all(segments).await {
+        Ok(_) => {}
+        Err(SegmentError::Failed(e)) => return Err(e),
+        Err(SegmentError::WrongRange(range)) => {
+            warn![
+                "{} sent the range {:?} instead of the one asked for, downloading it again in one piece",
+                url, range
+            ];
+            ppb.set_position(0);
+            return download_file(ppb, client, url, temporary_filepath, completed_filepath).await;
+        }
+    }
+
+    async_std::fs::rename(&temporary_filepath, &completed_filepath)
+        .await
+        .map_err(|e| error_renaming(temporary_filepath.into(), completed_filepath.into(), e))
+}
+
+/// Why a segment of a file could not be downloaded.
+enum SegmentError {
+    /// The server sent another range than the one asked for, or did not say which.
+    /// Writing it would corrupt the file.
+    WrongRange(Option<String>),
+    Failed(CommandError),
+}
+
+impl From<CommandError> for SegmentError {
+    fn from(e: CommandError) -> Self {
+        Self::Failed(e)
+    }
+}
+
+/// Whether a `Content-Range` header like `bytes 0-499/1234` covers exactly the inclusive range `start..=end`.
+fn covers_range(content_range: &str, start: u64, end: u64) -> bool {
+    content_range
+        .strip_prefix("bytes ")
+        .and_then(|range| range.split_once('/'))
+        .and_then(|(range, _)| range.split_once('-'))
+        .is_some_and(|(first, last)| {
+            first.trim().parse() == Ok(start) && last.trim().parse() == Ok(end)
+        })
+}
+
+/// Downloads the inclusive byte range `start..=end` of a file into the same range of `filepath`.
+async fn download_segment(
+    ppb: &ProgressBar,
+    milestones: &ProgressMilestones,
+    client: Client,
+    url: Url,
+    filepath: &Path,
+    start: u64,
+    end: u64,
+) -> Result<(), SegmentError> {
+    let url_str = url.to_string();
+    let mut response = client
+        .get(url)
+        .header(RANGE, format!["bytes={}-{}", start, end])
+        .send()
+        .await
+        .map_err(request_error)?;
+
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(CommandError::ReturnCode(response.status()).into());
+    }
+    let content_range = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok());
+    if !content_range.is_some_and(|range| covers_range(range, start, end)) {
+        return Err(SegmentError::WrongRange(content_range.map(str::to_string)));
+    }
+
+    let mut file = async_std::fs::OpenOptions::new()
+        .write(true)
+        .open(filepath)
+        .await
+        .map_err(|e| error_writing(filepath.into(), e))?;
+    file.seek(std::io::SeekFrom::Start(start))
+        .await
+        .map_err(|e| error_writing(filepath.into(), e))?;
+
+    while let Some(chunk) = response.chunk().await.map_err(request_error)? {
+        RATE_LIMITER.consume(chunk.len() as u64).await;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| error_writing(filepath.into(), e))?;
+        ppb.inc(chunk.len() as u64);
+        milestones.update(&url_str, ppb.position(), ppb.length().unwrap_or_default());
+
+        cancel::check()?;
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| error_writing(filepath.into(), e).into())
+}
+
+/// The archive formats builds can be distributed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ArchiveFormat {
+    TarXz,
+    TarGz,
+    TarBz2,
+    Zip,
+    SevenZip,
+    Dmg,
+}
+
+impl ArchiveFormat {
+    /// Detects the format from the whole file name, so compound extensions like `.tar.gz` are recognized.
+    pub(super) fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+
+        [
+            (".tar.xz", Self::TarXz),
+            (".txz", Self::TarXz),
+            (".tar.gz", Self::TarGz),
+            (".tgz", Self::TarGz),
+            (".tar.bz2", Self::TarBz2),
+            (".tbz2", Self::TarBz2),
+            // Plain .xz files have always been treated as tarballs
+            (".xz", Self::TarXz),
+            (".zip", Self::Zip),
+            (".7z", Self::SevenZip),
+            (".dmg", Self::Dmg),
+        ]
+        .into_iter()
+        .find_map(|(suffix, format)| name.ends_with(suffix).then_some(format))
+    }
+
+    fn is_tarball(&self) -> bool {
+        matches!(self, Self::TarXz | Self::TarGz | Self::TarBz2)
+    }
+
+    /// Wraps a reader of the compressed archive in the matching decoder.
+    fn tar_decoder<R: Read + 'static>(&self, reader: R) -> Option<Box<dyn Read>> {
+        match self {
+            Self::TarXz => Some(Box::new(XzDecoder::new(reader))),
+            Self::TarGz => Some(Box::new(GzDecoder::new(reader))),
+            Self::TarBz2 => Some(Box::new(BzDecoder::new(reader))),
+            _ => None,
+        }
+    }
+}
+
+pub(super) async fn extract_file<P>(
     ppb: &ProgressBar,
     filepath: P,
     destination: P,
@@ -383,126 +1288,399 @@ where
 {
     let filepath = filepath.as_ref();
     let destination = destination.as_ref();
-    match filepath.extension().unwrap().to_str().unwrap() {
-        "xz" => {
-            let total_size = filepath.metadata().unwrap().len();
-            ppb.set_length(total_size);
-            ppb.set_position(0);
+    // What a tarball unpacks to is only known once it is read, so its progress is the compressed bytes read
+    let open = || {
+        let file = File::open(filepath).map_err(|e| error_reading(filepath.into(), e))?;
+        ppb.set_length(file.metadata().map(|m| m.len()).unwrap_or_default());
+        ppb.set_position(0);
+        Ok::<_, CommandError>(ProgressReader { inner: file, ppb })
+    };
 
-            let file = XzDecoder::new(
-                File::open(filepath).map_err(|e| error_reading(filepath.into(), e))?,
-            );
-            let mut archive = Archive::new(file);
-
-            for entry in archive
-                .entries()
-                .map_err(|e| error_reading(filepath.into(), e))?
-            {
-                match entry {
-                    Ok(mut entry) => {
-                        let unpacked_size = entry.size();
-
-                        // Skip the root folder
-                        let pth: PathBuf = destination.join(
-                            entry
-                                .path()
-                                .unwrap()
-                                .components()
-                                .skip(1)
-                                .collect::<PathBuf>(),
-                        );
-
-                        let parent_path = pth.parent().unwrap();
-                        async_std::fs::create_dir_all(parent_path)
-                            .await
-                            .map_err(|e| error_writing(parent_path.into(), e))?;
-                        entry.unpack(&pth).map_err(|e| error_writing(pth, e))?;
-
-                        ppb.inc(unpacked_size);
-                    }
-                    Err(e) => {
-                        return Err(CommandError::IoError(
-                            IoErrorOrigin::WritingObject(filepath.into()),
-                            e,
-                        ))
-                    }
-                }
+    match ArchiveFormat::from_path(filepath) {
+        Some(ArchiveFormat::TarXz) => {
+            extract_tar(XzDecoder::new(open()?), filepath, destination).await
+        }
+        Some(ArchiveFormat::TarGz) => {
+            extract_tar(GzDecoder::new(open()?), filepath, destination).await
+        }
+        Some(ArchiveFormat::TarBz2) => {
+            extract_tar(BzDecoder::new(open()?), filepath, destination).await
+        }
+        Some(ArchiveFormat::Zip) => extract_zip(ppb, filepath, destination).await,
+        Some(ArchiveFormat::SevenZip) => extract_7z(ppb, filepath, destination),
+        // TODO:
+        Some(ArchiveFormat::Dmg) => {
+            println!["DETECTED DMG FILE {:?}", filepath];
+            todo!();
+        }
+        None => Err(CommandError::UnsupportedFileFormat(
+            filepath
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        )),
+    }
+}
 
-                if CANCELLED.load(Ordering::Acquire) {
-                    return Err(CommandError::Cancelled);
-                }
-            }
+/// The size of the buffer extracted files are written through.
+const WRITE_BUFFER: usize = 256 * 1024;
+
+/// Advances a progress bar by every byte written through it.
+struct ProgressWriter<'a, W> {
+    inner: W,
+    ppb: &'a ProgressBar,
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.ppb.inc(written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-            Ok(true)
+/// Advances a progress bar by every byte read through it.
+struct ProgressReader<'a, R> {
+    inner: R,
+    ppb: &'a ProgressBar,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.ppb.inc(read as u64);
+        Ok(read)
+    }
+}
+
+fn zip_error(filepath: &Path, e: ZipError) -> CommandError {
+    match e {
+        ZipError::Io(error) => error_reading(filepath.to_path_buf(), error),
+        ZipError::InvalidArchive(e) | ZipError::UnsupportedArchive(e) => {
+            CommandError::BrokenArchive(filepath.to_path_buf(), e)
         }
-        // TODO:
-        "zip" => {
-            let mut archive = ZipArchive::new(
-                File::open(filepath).map_err(|e| error_reading(filepath.into(), e))?,
-            )
-            .map_err(|e| match e {
-                zip::result::ZipError::Io(error) => error_reading(filepath.to_path_buf(), error),
-                zip::result::ZipError::InvalidArchive(e)
-                | zip::result::ZipError::UnsupportedArchive(e) => {
-                    CommandError::BrokenArchive(filepath.to_path_buf(), e)
-                }
-                zip::result::ZipError::FileNotFound => todo!(),
-                zip::result::ZipError::InvalidPassword => todo!(),
-                _ => todo!(),
-            })?;
-
-            let total_size = archive
-                .decompressed_size()
-                .map(|n| n as u64)
-                .unwrap_or_else(|| filepath.metadata().unwrap().len());
-            ppb.set_length(total_size);
-            ppb.set_position(0);
+        _ => CommandError::BrokenArchive(filepath.to_path_buf(), "the archive could not be read"),
+    }
+}
 
-            for name in archive.file_names().map(str::to_string).collect::<Vec<_>>() {
-                let mut file = archive.by_name(&name).unwrap();
+fn open_zip(filepath: &Path) -> Result<ZipArchive<File>, CommandError> {
+    let file = File::open(filepath).map_err(|e| error_reading(filepath.into(), e))?;
+    ZipArchive::new(file).map_err(|e| zip_error(filepath, e))
+}
+
+/// Where an entry of a zip archive ends up, without the archive's root folder.
+fn zip_entry_path(entry: &ZipFile, destination: &Path) -> PathBuf {
+    let name = entry.enclosed_name().unwrap_or(entry.mangled_name());
+    destination.join(name.components().skip(1).collect::<PathBuf>())
+}
+
+/// Extracts a zip archive off the async runtime, writing several entries at once.
+async fn extract_zip(
+    ppb: &ProgressBar,
+    filepath: &Path,
+    destination: &Path,
+) -> Result<bool, CommandError> {
+    let (ppb, filepath, destination) = (
+        ppb.clone(),
+        filepath.to_path_buf(),
+        destination.to_path_buf(),
+    );
+
+    tokio::task::spawn_blocking(move || unpack_zip(&ppb, &filepath, &destination))
+        .await
+        .unwrap()
+        .map(|_| true)
+}
+
+fn unpack_zip(ppb: &ProgressBar, filepath: &Path, destination: &Path) -> Result<(), CommandError> {
+    let mut archive = open_zip(filepath)?;
+
+    let total_size = archive
+        .decompressed_size()
+        .map(|n| n as u64)
+        .unwrap_or_else(|| filepath.metadata().map(|m| m.len()).unwrap_or_default());
+    ppb.set_length(total_size);
+    ppb.set_position(0);
+
+    // Folders are created up front, so the files can be written in any order
+    let mut files = vec![];
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(index)
+            .map_err(|e| zip_error(filepath, e))?;
+        let path = zip_entry_path(&entry, destination);
+        let folder = match entry.is_dir() {
+            true => path.as_path(),
+            false => {
+                files.push(index);
+                path.parent().unwrap_or(destination)
+            }
+        };
+        std::fs::create_dir_all(folder).map_err(|e| error_writing(folder.to_path_buf(), e))?;
+    }
+
+    // Every worker reads through its own handle, taking the next file until none are left
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(files.len().max(1));
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<(), CommandError> {
+                    let mut archive = open_zip(filepath)?;
+                    while let Some(&index) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        cancel::check()?;
+                        if failed.load(Ordering::Acquire) {
+                            return Ok(());
+                        }
+
+                        unpack_zip_entry(ppb, &mut archive, index, filepath, destination)
+                            .inspect_err(|_| failed.store(true, Ordering::Release))?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Result<Vec<_>, _>>()
+    })?;
 
-                let file_path = file.enclosed_name().unwrap_or(file.mangled_name());
+    Ok(())
+}
 
+fn unpack_zip_entry(
+    ppb: &ProgressBar,
+    archive: &mut ZipArchive<File>,
+    index: usize,
+    filepath: &Path,
+    destination: &Path,
+) -> Result<(), CommandError> {
+    let mut entry = archive
+        .by_index(index)
+        .map_err(|e| zip_error(filepath, e))?;
+    let path = zip_entry_path(&entry, destination);
+
+    let file = File::create(&path).map_err(|e| error_writing(path.clone(), e))?;
+    let mut writer =
+        std::io::BufWriter::with_capacity(WRITE_BUFFER, ProgressWriter { inner: file, ppb });
+    std::io::copy(&mut entry, &mut writer)
+        .and_then(|_| writer.flush())
+        .map_err(|e| error_writing(path.clone(), e))?;
+
+    #[cfg(unix)]
+    if let Some(mode) = entry.unix_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))
+            .map_err(|e| error_writing(path, e))?;
+    }
+
+    Ok(())
+}
+
+/// Extracts a tarball read through the given decoder.
+async fn extract_tar<R: Read>(
+    decoder: R,
+    filepath: &Path,
+    destination: &Path,
+) -> Result<bool, CommandError> {
+    unpack_tar(decoder, filepath, destination)?;
+
+    Ok(true)
+}
+
+/// Unpacks every entry of a tarball into `destination`, without the archive's root folder.
+/// `source` is only used to describe errors.
+fn unpack_tar<R: Read>(decoder: R, source: &Path, destination: &Path) -> Result<(), CommandError> {
+    let mut archive = Archive::new(decoder);
+
+    for entry in archive
+        .entries()
+        .map_err(|e| error_reading(source.into(), e))?
+    {
+        match entry {
+            Ok(mut entry) => {
                 // Skip the root folder
-                let pth: PathBuf =
-                    destination.join(file_path.components().skip(1).collect::<PathBuf>());
+                let pth: PathBuf = destination.join(
+                    entry
+                        .path()
+                        .unwrap()
+                        .components()
+                        .skip(1)
+                        .collect::<PathBuf>(),
+                );
 
                 let parent_path = pth.parent().unwrap();
-                let _ = async_std::fs::create_dir_all(parent_path).await;
-                if file.is_dir() {
-                    async_std::fs::create_dir_all(&pth)
-                        .await
-                        .map_err(|e| error_writing(pth.clone(), e))?;
-                } else {
-                    {
-                        let mut extracted_file = std::fs::File::create(&pth)
-                            .map_err(|e| error_writing(pth.clone(), e))?;
-
-                        let mut v = Vec::with_capacity(file.size() as usize);
-                        file.read_to_end(&mut v)
-                            .map_err(|e| error_writing(pth.clone(), e))?;
-                        extracted_file
-                            .write_all(&v)
-                            .map_err(|e| error_writing(pth, e))?;
-                    }
-                }
+                std::fs::create_dir_all(parent_path)
+                    .map_err(|e| error_writing(parent_path.into(), e))?;
+                entry.unpack(&pth).map_err(|e| error_writing(pth, e))?;
+            }
+            Err(e) => {
+                return Err(CommandError::IoError(
+                    IoErrorOrigin::WritingObject(source.into()),
+                    e,
+                ))
+            }
+        }
 
-                ppb.inc(file.size());
+        cancel::check()?;
+    }
 
-                if CANCELLED.load(Ordering::Acquire) {
-                    return Err(CommandError::Cancelled);
+    Ok(())
+}
+
+/// Reads the chunks of a response body as the download task receives them.
+struct ChunkReader {
+    rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
                 }
+                // The sender is gone, so the body has ended
+                None => return Ok(0),
             }
-
-            Ok(true)
         }
-        // TODO:
-        "dmg" => {
-            println!["DETECTED DMG FILE {:?}", filepath];
-            todo!();
+
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Downloads a tarball and unpacks it on a blocking thread as the chunks arrive, so the archive
+/// is never written to disk.
+async fn download_and_extract(
+    ppb: &ProgressBar,
+    client: Client,
+    url: Url,
+    format: ArchiveFormat,
+    destination: &Path,
+) -> Result<(), CommandError> {
+    let mut response = client
+        .get(url.clone())
+        .send()
+        .await
+        .map_err(request_error)?;
+    if !response.status().is_success() {
+        return Err(CommandError::ReturnCode(response.status()));
+    }
+    if let Some(length) = response.content_length() {
+        ppb.set_length(length);
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel(64);
+    let reader = ChunkReader {
+        rx,
+        chunk: vec![],
+        pos: 0,
+    };
+    let unpacker = {
+        let destination = destination.to_path_buf();
+        let source = PathBuf::from(url.path());
+        tokio::task::spawn_blocking(move || {
+            let decoder = format.tar_decoder(reader).unwrap();
+            unpack_tar(decoder, &source, &destination)
+        })
+    };
+
+    let url_str = url.to_string();
+    let milestones = ProgressMilestones::default();
+    let streamed: Result<(), CommandError> = async {
+        while let Some(chunk) = response.chunk().await.map_err(request_error)? {
+            RATE_LIMITER.consume(chunk.len() as u64).await;
+            ppb.inc(chunk.len() as u64);
+            milestones.update(&url_str, ppb.position(), ppb.length().unwrap_or_default());
+
+            // The unpacker only hangs up early if it failed, and its error is reported below
+            if tx.send(chunk.to_vec()).await.is_err() {
+                break;
+            }
+            cancel::check()?;
         }
-        ext => Err(CommandError::UnsupportedFileFormat(ext.to_string())),
+        Ok(())
     }
+    .await;
+    drop(tx);
+
+    let unpacked = unpacker
+        .await
+        .unwrap_or_else(|e| Err(error_writing(destination.into(), std::io::Error::other(e))));
+
+    let result = streamed.and(unpacked);
+    if result.is_err() {
+        // Do not leave a half extracted build behind
+        let _ = std::fs::remove_dir_all(destination);
+    }
+    result
+}
+
+fn extract_7z(
+    ppb: &ProgressBar,
+    filepath: &Path,
+    destination: &Path,
+) -> Result<bool, CommandError> {
+    let convert_err = |e: sevenz_rust::Error| match e {
+        sevenz_rust::Error::Io(e, _) => error_writing(destination.into(), e),
+        e => {
+            error!["Failed to read 7z archive: {:?}", e];
+            CommandError::BrokenArchive(filepath.into(), "invalid 7z archive")
+        }
+    };
+
+    let mut reader = SevenZReader::open(filepath, Password::empty()).map_err(convert_err)?;
+
+    let total_size = reader.archive().files.iter().map(|f| f.size()).sum();
+    ppb.set_length(total_size);
+    ppb.set_position(0);
+
+    reader
+        .for_each_entries(|entry, data| {
+            // Skip the root folder
+            let pth: PathBuf = destination.join(
+                Path::new(entry.name())
+                    .components()
+                    .skip(1)
+                    .collect::<PathBuf>(),
+            );
+
+            if entry.is_directory() {
+                std::fs::create_dir_all(&pth)?;
+            } else {
+                if let Some(parent_path) = pth.parent() {
+                    std::fs::create_dir_all(parent_path)?;
+                }
+                let mut extracted_file = File::create(&pth)?;
+                std::io::copy(data, &mut extracted_file)?;
+            }
+
+            ppb.inc(entry.size());
+
+            // Returning false stops the iteration
+            Ok(!cancel::is_cancelled())
+        })
+        .map_err(convert_err)?;
+
+    cancel::check()?;
+
+    Ok(true)
 }
 
 /// Prompt the user to delete files after cancellation of pulling
@@ -517,17 +1695,11 @@ fn prompt_deletions(result: Vec<Result<(), CommandError>>, targets: Vec<(PathBuf
                         "Cancelled during downloading of {}. Do you wish to delete it?",
                         temp.display()
                     ];
-                    let inquiry = inquire::Confirm::new(&s).with_default(false);
-                    match inquiry.prompt_skippable() {
-                        Ok(Some(true)) => {
-                            info!["Deleting {:?}...", temp];
-
-                            match std::fs::remove_file(&temp) {
-                                Ok(_) => info!["Success."],
-                                Err(e) => warn!["Failed to delete {:?}! {:?}", temp, e],
-                            }
+                    if confirm(&s, false) {
+                        match deletion::remove(&temp) {
+                            Ok(_) => info!["Success."],
+                            Err(e) => warn!["Failed to delete {:?}! {:?}", temp, e],
                         }
-                        Ok(_) | Err(_) => todo!(),
                     }
                 }
 
@@ -536,17 +1708,11 @@ fn prompt_deletions(result: Vec<Result<(), CommandError>>, targets: Vec<(PathBuf
                         "Cancelled during extraction of {}. Do you wish to delete it?",
                         temp.display()
                     ];
-                    let inquiry = inquire::Confirm::new(&s).with_default(false);
-                    match inquiry.prompt_skippable() {
-                        Ok(Some(true)) => {
-                            info!["Deleting {:?}...", finished];
-
-                            match std::fs::remove_file(&finished) {
-                                Ok(_) => info!["Success."],
-                                Err(e) => warn!["Failed to delete {:?}! {:?}", finished, e],
-                            }
+                    if confirm(&s, false) {
+                        match deletion::remove(&finished) {
+                            Ok(_) => info!["Success."],
+                            Err(e) => warn!["Failed to delete {:?}! {:?}", finished, e],
                         }
-                        Ok(_) | Err(_) => todo!(),
                     }
                 }
             }
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use reqwest::{header::HeaderMap, Client};
+
+/// Reports the quota of the client making the request, without counting against it.
+const RATE_LIMIT_URL: &str = "https://api.github.com/rate_limit";
+
+/// GitHub's API quota, as reported by the `X-RateLimit-*` headers.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    pub reset: DateTime<Utc>,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let number = |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+
+        Some(Self {
+            limit: number("x-ratelimit-limit")?,
+            remaining: number("x-ratelimit-remaining")?,
+            reset: DateTime::from_timestamp(number("x-ratelimit-reset")? as i64, 0)?,
+        })
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == 0 && self.reset > Utc::now()
+    }
+}
+
+/// Asks GitHub how much of the client's quota is left. Failing to find out is not an error.
+pub async fn check(client: &Client) -> Option<RateLimit> {
+    match client.get(RATE_LIMIT_URL).send().await {
+        Ok(response) => RateLimit::from_headers(response.headers()),
+        Err(e) => {
+            debug!["Failed to check GitHub's rate limit: {}", e];
+            None
+        }
+    }
+}
+
+/// Sleeps until the quota resets, with a second to spare for clock differences.
+pub async fn wait_for_reset(limit: &RateLimit) {
+    let wait = Duration::from_secs(
+        (limit.reset - Utc::now())
+            .to_std()
+            .unwrap_or_default()
+            .as_secs()
+            + 1,
+    );
+    info![
+        "GitHub's rate limit is used up, waiting {} until it resets",
+        humantime::format_duration(wait)
+    ];
+    tokio::time::sleep(wait).await;
+}
+
+/// Carried through `fetch`'s IO errors, so the command can report it as `CommandError::RateLimited`.
+#[derive(Debug)]
+pub struct RateLimited(pub DateTime<Utc>);
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write![f, "GitHub's rate limit is used up until {}", self.0]
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+impl From<RateLimited> for std::io::Error {
+    fn from(e: RateLimited) -> Self {
+        std::io::Error::other(e)
+    }
+}
+
+/// The reset time of an error that came from an exhausted rate limit.
+pub fn reset_of(e: &std::io::Error) -> Option<DateTime<Utc>> {
+    e.get_ref()?.downcast_ref::<RateLimited>().map(|r| r.0)
+}
@@ -0,0 +1,96 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use ansi_term::Color;
+use blrs::BLRSConfig;
+use chrono::{DateTime, Utc};
+use log::info;
+
+use crate::{
+    cli_config::CliConfig,
+    commands::{BlenderArgs, LaunchOutputArgs, RunCommand},
+    errs::{error_reading, CommandError},
+    launch_history::LaunchHistory,
+    prompt::prompter,
+    resolving::is_interactive,
+    run,
+};
+
+struct RecentFile {
+    path: PathBuf,
+    /// The build the file was last opened with.
+    build: String,
+    time: DateTime<Utc>,
+}
+
+/// Every file in the launch history, newest first, each listed once with its latest launch.
+fn recent_files(history: LaunchHistory) -> Vec<RecentFile> {
+    let mut seen = HashSet::new();
+    history
+        .launches
+        .into_iter()
+        .rev()
+        .filter_map(|launch| {
+            let path = launch.file?;
+            seen.insert(path.clone()).then_some(RecentFile {
+                path,
+                build: launch.build,
+                time: launch.time,
+            })
+        })
+        .collect()
+}
+
+/// Lists the blendfiles opened most recently. When interactive and `list` is not set,
+/// one of them is chosen with a fuzzy search and opened again.
+pub fn recent(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    limit: usize,
+    list: bool,
+    output: &LaunchOutputArgs,
+) -> Result<(), CommandError> {
+    let history = LaunchHistory::load().map_err(|e| error_reading(LaunchHistory::path(), e))?;
+    let mut files = recent_files(history);
+    files.truncate(limit);
+    if files.is_empty() {
+        info!["No files have been opened yet"];
+        return Ok(());
+    }
+
+    if list || !is_interactive() {
+        for file in &files {
+            let missing = match file.path.exists() {
+                true => String::new(),
+                false => format![" {}", Color::Red.paint("(missing)")],
+            };
+            println![
+                "{} {}{} {}",
+                Color::White
+                    .dimmed()
+                    .paint(file.time.format("%Y-%m-%d %H:%M").to_string()),
+                file.path.display(),
+                missing,
+                Color::Cyan.paint(&file.build)
+            ];
+        }
+        return Ok(());
+    }
+
+    // Files that were moved or deleted cannot be reopened
+    files.retain(|file| file.path.exists());
+    let choices: Vec<String> = files
+        .iter()
+        .map(|file| file.path.display().to_string())
+        .collect();
+    let Some(choice) = prompter().select("Choose a file to open", choices, 0) else {
+        return Ok(());
+    };
+
+    let cmd = RunCommand::File {
+        paths: vec![PathBuf::from(choice)],
+        together: false,
+        prefer: None,
+        blender: BlenderArgs::default(),
+    };
+    run::run(cfg, cli_cfg, cmd, output, false).map(|_| ())
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Mutex},
+};
+
+use blrs::{
+    info::read_blendfile_header,
+    search::{OrdPlacement, VersionSearchQuery},
+    BLRSConfig, LocalBuild,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+use regex::Regex;
+use semver::Version;
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{exit_codes, CommandError, IoErrorOrigin},
+    resolving::{build_query_string, parse_query},
+    run::{launch_command, select_build, select_nearest_build, FallbackPolicy},
+};
+
+use super::bench::Frames;
+
+/// How many lines of Blender's output are shown when a render fails.
+const FAILURE_LINES: usize = 20;
+
+/// Picks the build matching the file's version, like `run file` does.
+fn build_for_file(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    file: &Path,
+) -> Result<(LocalBuild, String), CommandError> {
+    let version = read_blendfile_header(file)
+        .map(|header| {
+            let ver = header.version();
+            Version::new(ver.major, ver.minor, 0)
+        })
+        .inspect_err(|e| warn!["Failed to read the version of {:?}: {:?}", file, e])
+        .ok();
+
+    match version {
+        Some(ver) => {
+            let query = VersionSearchQuery {
+                major: OrdPlacement::Exact(ver.major),
+                minor: OrdPlacement::Exact(ver.minor),
+                ..Default::default()
+            };
+            match cli_cfg.file_fallback {
+                FallbackPolicy::Ask => select_build(cfg, &query, false),
+                prefer => select_nearest_build(cfg, &query, &ver, prefer),
+            }
+        }
+        None => select_build(cfg, &VersionSearchQuery::default(), false),
+    }
+}
+
+fn progress_bar(frames: Option<Frames>) -> ProgressBar {
+    match frames {
+        Some(frames) => {
+            let pb = ProgressBar::new(frames.len());
+            pb.set_style(
+                ProgressStyle::with_template(
+                    "{spinner:.green} [{bar:40.cyan/red}] {pos}/{len} frames {elapsed} {msg}",
+                )
+                .unwrap()
+                .progress_chars("#|-"),
+            );
+            pb
+        }
+        // The file's own frame range is not known without opening it
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::with_template("{spinner:.green} {pos} frames {elapsed} {msg}")
+                    .unwrap(),
+            );
+            pb
+        }
+    }
+}
+
+/// Renders a blendfile in the background, showing Blender's progress as a progress bar.
+/// Fails with Blender's exit code if it does not exit cleanly.
+pub fn render(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    file: PathBuf,
+    build: Option<String>,
+    frames: Option<Frames>,
+    output: Option<String>,
+) -> Result<(), CommandError> {
+    let file = std::path::absolute(&file).unwrap_or(file);
+    let (build, nick) = match build {
+        Some(query) => select_build(cfg, &parse_query(&query)?, false)?,
+        None => build_for_file(cfg, cli_cfg, &file)?,
+    };
+    info![
+        "Rendering {} with {}",
+        file.display(),
+        build_query_string(&build.info.basic, &nick)
+    ];
+
+    // Blender reads its options in order, so the output and frames come before -a or -f
+    let mut command = launch_command(cli_cfg, &build, &nick, Some(file))?;
+    command.arg("-b");
+    if let Some(output) = &output {
+        command.args(["-o", output]);
+    }
+    match frames {
+        Some(frames) if frames.start == frames.end => {
+            command.args(["-f", &frames.start.to_string()]);
+        }
+        Some(frames) => {
+            command
+                .args(["-s", &frames.start.to_string()])
+                .args(["-e", &frames.end.to_string()])
+                .arg("-a");
+        }
+        None => {
+            command.arg("-a");
+        }
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!["Running command {:?}", command];
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+
+    let frame = Regex::new(r"^Fra:(-?\d+)\b").unwrap();
+    let saved = Regex::new(r"^Saved: '(.+)'").unwrap();
+    let pb = progress_bar(frames);
+    // Shared with the thread reading stderr, where Blender reports most of its errors
+    let recent = Arc::new(Mutex::new(VecDeque::with_capacity(FAILURE_LINES)));
+    let remember = |recent: &Mutex<VecDeque<String>>, line: String| {
+        let mut recent = recent.lock().unwrap();
+        if recent.len() == FAILURE_LINES {
+            recent.pop_front();
+        }
+        recent.push_back(line);
+    };
+    let stderr_reader = child.stderr.take().map(|stderr| {
+        let recent = recent.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                remember(&recent, line);
+            }
+        })
+    });
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if let Some(c) = frame.captures(&line) {
+                // Like "Fra:12 Mem:... | Time:00:01.23 | ... | Rendering 10 / 64 samples"
+                let status = line.rsplit('|').next().unwrap_or_default().trim();
+                pb.set_message(format!["frame {}: {}", &c[1], status]);
+            } else if let Some(c) = saved.captures(&line) {
+                pb.inc(1);
+                pb.println(format!["Saved {}", &c[1]]);
+            }
+
+            remember(&recent, line);
+        }
+    }
+    let status = child
+        .wait()
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+    if let Some(reader) = stderr_reader {
+        let _ = reader.join();
+    }
+    pb.finish_and_clear();
+
+    if status.success() {
+        info!["Rendered {} frame(s)", pb.position()];
+        return Ok(());
+    }
+
+    for line in recent.lock().unwrap().iter() {
+        eprintln!["{}", line];
+    }
+    // Crashes on Windows exit with codes like 0xC0000005, which are negative and cannot be passed on
+    Err(CommandError::RenderFailed(
+        status.to_string(),
+        status
+            .code()
+            .filter(|code| (1..=255).contains(code))
+            .unwrap_or(exit_codes::FAILURE),
+    ))
+}
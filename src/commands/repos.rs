@@ -0,0 +1,351 @@
+use blrs::{
+    fetching::{
+        authentication::GithubAuthentication,
+        build_repository::{BuildRepo, RepoType},
+    },
+    BLRSConfig,
+};
+use log::{info, warn};
+use reqwest::Url;
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{unknown_repo, CommandError},
+    repo_auth::{RepoAuth, Secret},
+    tasks::ConfigTask,
+};
+
+use super::{
+    index_repo::{unknown_groups, IndexTemplate},
+    RepoAuthArgs,
+};
+
+/// A repo blrs-cli knows how to fetch, that is only registered once it is enabled with `repos enable`.
+struct BuiltinRepo {
+    nickname: &'static str,
+    repo_id: &'static str,
+    url: &'static str,
+    description: &'static str,
+}
+
+const BUILTIN_REPOS: [BuiltinRepo; 1] = [BuiltinRepo {
+    nickname: "experimental",
+    repo_id: "builder.blender.org.experimental",
+    url: "https://builder.blender.org/download/experimental/?format=json&v=1",
+    description: "builds of Blender's feature branches, like temp-sculpt-dyntopo",
+}];
+
+fn is_registered(cfg: &BLRSConfig, builtin: &BuiltinRepo) -> bool {
+    cfg.repos
+        .iter()
+        .any(|r| r.repo_id == builtin.repo_id || r.url == builtin.url)
+}
+
+/// Prints every registered repo, along with the asset pattern of GitHub repos and the pattern of index repos.
+pub fn list(cfg: &BLRSConfig, cli_cfg: &CliConfig) {
+    for repo in &cfg.repos {
+        let pattern = match (
+            cli_cfg.asset_patterns.get(&repo.repo_id),
+            cli_cfg.index_repos.get(&repo.repo_id),
+        ) {
+            (Some(p), _) => format![" assets: {}", p],
+            (None, Some(index)) => format![" index: {}", index.pattern],
+            (None, None) => String::new(),
+        };
+        let auth = match cli_cfg.repo_auth.contains_key(&repo.repo_id) {
+            true => " (authenticated)",
+            false => "",
+        };
+
+        println![
+            "{} {}{}{}",
+            ansi_term::Color::Green.paint(&repo.nickname),
+            ansi_term::Color::White.dimmed().paint(format![
+                "{} ({:?}) {}",
+                repo.repo_id, repo.repo_type, repo.url
+            ]),
+            pattern,
+            auth
+        ];
+    }
+
+    for builtin in BUILTIN_REPOS.iter().filter(|b| !is_registered(cfg, b)) {
+        println![
+            "{} {}",
+            ansi_term::Color::White.dimmed().paint(builtin.nickname),
+            ansi_term::Color::White.dimmed().paint(format![
+                "{} (not enabled, see `blrs repos enable {}`)",
+                builtin.description, builtin.nickname
+            ]),
+        ];
+    }
+}
+
+/// Registers one of the built-in repos, like `experimental`.
+pub fn enable(cfg: &BLRSConfig, name: &str) -> Result<Vec<ConfigTask>, CommandError> {
+    let Some(builtin) = BUILTIN_REPOS.iter().find(|b| b.nickname == name) else {
+        let known: Vec<&str> = BUILTIN_REPOS.iter().map(|b| b.nickname).collect();
+        return Err(CommandError::InvalidConfig(format![
+            "there is no built-in repo named {:?}. The built-in repos are: {}",
+            name,
+            known.join(", ")
+        ]));
+    };
+    if is_registered(cfg, builtin) {
+        info!["{} is already enabled", builtin.nickname];
+        return Ok(vec![]);
+    }
+    if let Some(existing) = cfg.repos.iter().find(|r| r.nickname == builtin.nickname) {
+        return Err(CommandError::InvalidConfig(format![
+            "a repo named {:?} ({}) is already registered",
+            existing.nickname, existing.repo_id
+        ]));
+    }
+
+    let mut new_cfg = cfg.clone();
+    new_cfg.repos.push(BuildRepo {
+        repo_id: builtin.repo_id.to_string(),
+        url: builtin.url.to_string(),
+        nickname: builtin.nickname.to_string(),
+        repo_type: RepoType::Blender,
+    });
+    info![
+        "Enabled {}. Run `blrs fetch --repo {}` to list its builds",
+        builtin.nickname, builtin.nickname
+    ];
+
+    Ok(vec![ConfigTask::SetConfig(Box::new(new_cfg))])
+}
+
+/// Registers a GitHub repo whose releases are treated as builds.
+///
+/// `spec` is written as `owner/name`. If `asset_pattern` is given, only release assets
+/// whose file name matches the glob are kept when fetching.
+pub fn add_github(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    spec: &str,
+    nickname: Option<String>,
+    asset_pattern: Option<String>,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let (owner, name) = match spec.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+            (owner, name)
+        }
+        _ => {
+            return Err(CommandError::InvalidConfig(format![
+                "{:?} is not a GitHub repo, expected owner/name",
+                spec
+            ]))
+        }
+    };
+
+    if let Some(pattern) = &asset_pattern {
+        glob::Pattern::new(pattern).map_err(|e| {
+            CommandError::InvalidConfig(format!["invalid asset pattern {:?}: {}", pattern, e])
+        })?;
+    }
+
+    let repo = BuildRepo {
+        repo_id: format!["github-{}-{}", owner, name],
+        url: format!["https://api.github.com/repos/{}/{}/releases", owner, name],
+        nickname: nickname.unwrap_or_else(|| name.to_string()),
+        repo_type: RepoType::GithubAPI,
+    };
+
+    if let Some(existing) = cfg
+        .repos
+        .iter()
+        .find(|r| r.repo_id == repo.repo_id || r.nickname == repo.nickname)
+    {
+        return Err(CommandError::InvalidConfig(format![
+            "a repo named {:?} ({}) is already registered",
+            existing.nickname, existing.repo_id
+        ]));
+    }
+
+    if cfg.gh_auth.is_none() {
+        info!["No GitHub token is saved. Private repos need one, see `blrs repos auth`"];
+    }
+    info!["Registered {} as {}", spec, repo.nickname];
+
+    let mut new_cli_cfg = cli_cfg.clone();
+    match asset_pattern {
+        Some(pattern) => new_cli_cfg
+            .asset_patterns
+            .insert(repo.repo_id.clone(), pattern),
+        None => new_cli_cfg.asset_patterns.remove(&repo.repo_id),
+    };
+
+    let mut new_cfg = cfg.clone();
+    new_cfg.repos.push(repo);
+
+    Ok(vec![
+        ConfigTask::SetConfig(Box::new(new_cfg)),
+        ConfigTask::SetCliConfig(Box::new(new_cli_cfg)),
+    ])
+}
+
+/// Registers a directory index or JSON document as a repo, whose builds are found with `template`.
+pub fn add_index(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    url: &str,
+    nickname: String,
+    template: IndexTemplate,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let parsed = Url::parse(url)
+        .map_err(|e| CommandError::InvalidConfig(format!["{:?} is not a URL: {}", url, e]))?;
+    let re = template
+        .validate()
+        .map_err(|e| CommandError::InvalidConfig(format!["invalid index pattern: {}", e]))?;
+    for group in unknown_groups(&re) {
+        warn![
+            "The pattern's group {:?} is not used, see `blrs repos add-index --help`",
+            group
+        ];
+    }
+
+    let repo = BuildRepo {
+        repo_id: format!["index-{}", nickname],
+        url: parsed.to_string(),
+        nickname,
+        // Not used for fetching, as index repos are parsed by blrs-cli itself
+        repo_type: RepoType::Blender,
+    };
+
+    if let Some(existing) = cfg
+        .repos
+        .iter()
+        .find(|r| r.repo_id == repo.repo_id || r.nickname == repo.nickname)
+    {
+        return Err(CommandError::InvalidConfig(format![
+            "a repo named {:?} ({}) is already registered",
+            existing.nickname, existing.repo_id
+        ]));
+    }
+    info!["Registered {} as {}", url, repo.nickname];
+
+    let mut new_cli_cfg = cli_cfg.clone();
+    new_cli_cfg
+        .index_repos
+        .insert(repo.repo_id.clone(), template);
+
+    let mut new_cfg = cfg.clone();
+    new_cfg.repos.push(repo);
+
+    Ok(vec![
+        ConfigTask::SetConfig(Box::new(new_cfg)),
+        ConfigTask::SetCliConfig(Box::new(new_cli_cfg)),
+    ])
+}
+
+/// Unregisters a repo by its nickname or id, and removes its cached build list.
+/// Builds installed from the repo are kept.
+pub fn remove(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    name: &str,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let Some(idx) = cfg
+        .repos
+        .iter()
+        .position(|r| r.nickname == name || r.repo_id == name)
+    else {
+        return Err(unknown_repo(cfg, name));
+    };
+
+    let mut new_cfg = cfg.clone();
+    let repo = new_cfg.repos.remove(idx);
+
+    let cache = cfg.paths.remote_repos.join(repo.repo_id.clone() + ".json");
+    if cache.exists() {
+        if let Err(e) = std::fs::remove_file(&cache) {
+            warn![
+                "Failed to remove the cached build list {}: {}",
+                cache.display(),
+                e
+            ];
+        }
+    }
+    info!["Removed {} ({})", repo.nickname, repo.repo_id];
+
+    let mut new_cli_cfg = cli_cfg.clone();
+    new_cli_cfg.asset_patterns.remove(&repo.repo_id);
+    new_cli_cfg.index_repos.remove(&repo.repo_id);
+    new_cli_cfg.repo_auth.remove(&repo.repo_id);
+
+    Ok(vec![
+        ConfigTask::SetConfig(Box::new(new_cfg)),
+        ConfigTask::SetCliConfig(Box::new(new_cli_cfg)),
+    ])
+}
+
+/// Sets or clears the credentials of a repo.
+pub fn set_auth(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    name: &str,
+    args: RepoAuthArgs,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let repo = cfg
+        .repos
+        .iter()
+        .find(|r| r.nickname == name || r.repo_id == name)
+        .ok_or_else(|| unknown_repo(cfg, name))?;
+
+    let password = args
+        .password
+        .map(Secret::Plain)
+        .or(args.password_env.map(Secret::Env));
+    let token = args
+        .token
+        .map(Secret::Plain)
+        .or(args.token_env.map(Secret::Env));
+
+    let auth = match (args.clear, token, args.user, password) {
+        (true, _, _, _) => None,
+        (false, Some(token), None, None) => Some(RepoAuth::Bearer { token }),
+        (false, None, Some(user), Some(password)) => Some(RepoAuth::Basic { user, password }),
+        (false, None, Some(_), None) => {
+            return Err(CommandError::InvalidConfig(
+                "basic auth needs --password or --password-env".to_string(),
+            ))
+        }
+        _ => return Err(CommandError::NotEnoughInput),
+    };
+
+    let mut new_cli_cfg = cli_cfg.clone();
+    match auth {
+        Some(auth) => {
+            if matches!(
+                &auth,
+                RepoAuth::Bearer {
+                    token: Secret::Plain(_)
+                } | RepoAuth::Basic {
+                    password: Secret::Plain(_),
+                    ..
+                }
+            ) {
+                warn!["The secret is not encrypted and is readable by anyone with access to your config"];
+            }
+            info!["Saved credentials for {}", repo.nickname];
+            new_cli_cfg.repo_auth.insert(repo.repo_id.clone(), auth);
+        }
+        None => {
+            info!["Removed the credentials of {}", repo.nickname];
+            new_cli_cfg.repo_auth.remove(&repo.repo_id);
+        }
+    }
+
+    Ok(vec![ConfigTask::SetCliConfig(Box::new(new_cli_cfg))])
+}
+
+/// Saves the GitHub credentials used when fetching from GitHub repos.
+pub fn auth(user: String, token: String) -> Vec<ConfigTask> {
+    warn!["The token is not encrypted and is readable by anyone with access to your config"];
+    vec![ConfigTask::UpdateGHAuth(GithubAuthentication {
+        user,
+        token,
+    })]
+}
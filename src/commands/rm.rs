@@ -1,122 +1,285 @@
-use std::collections::HashMap;
-
-use blrs::{
-    fetching::build_repository::BuildRepo,
-    repos::read_repos,
-    search::{BInfoMatcher, VersionSearchQuery},
-    BLRSConfig, LocalBuild,
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
 };
+
+use blrs::{search::VersionSearchQuery, BLRSConfig, LocalBuild};
+use indicatif::HumanBytes;
 use log::{error, info};
 
 use crate::{
-    errs::{error_writing, CommandError},
-    resolving::get_choice_map,
+    cancel, deletion,
+    errs::{error_reading, error_writing, unknown_repo, CommandError},
+    events::{emit, json_output, Event},
+    hooks::{self, Hook},
+    prompt::prompter,
+    resolving::{
+        assume_yes, confirm, find_matches, get_choice_map, installed_builds, is_interactive,
+        walk_layout,
+    },
 };
 
+use super::{current::unlink_if_current, info::dir_size, install::repo_folder};
+
 pub fn remove_builds(
     cfg: &BLRSConfig,
     queries: Vec<VersionSearchQuery>,
-    no_trash: bool,
+    all_matching: bool,
 ) -> Result<(), CommandError> {
+    cancel::handle_ctrl_c();
     std::fs::create_dir_all(&cfg.paths.library)
         .inspect_err(|e| error!("Failed to create library path: {:?}", e))
         .map_err(|e| error_writing(cfg.paths.library.clone(), e))?;
 
-    let local_builds: Vec<_> = read_repos(cfg.repos.clone(), &cfg.paths, false)
-        .map_err(|e| CommandError::IoError(crate::errs::IoErrorOrigin::ReadingRepos, e))?
-        .into_iter()
-        .filter_map(|r| match r {
-            blrs::repos::RepoEntry::Registered(
-                BuildRepo {
-                    repo_id: _,
-                    url: _,
-                    nickname,
-                    repo_type: _,
-                },
-                vec,
-            )
-            | blrs::repos::RepoEntry::Unknown(nickname, vec) => {
-                let collect: Vec<blrs::LocalBuild> = vec
-                    .into_iter()
-                    .filter_map(|entry| match entry {
-                        blrs::repos::BuildEntry::Installed(_, local_build) => Some(local_build),
-                        _ => None,
-                    })
-                    .collect();
-                (!collect.is_empty()).then_some((collect, nickname))
-            }
-            _ => None,
-        })
-        .flat_map(|v| v.0.into_iter().map(move |b| (b, v.1.clone())))
-        .collect();
-
-    let matcher = BInfoMatcher::new(&local_builds);
+    let local_builds = installed_builds(cfg)?;
 
     let matched_builds: Vec<(LocalBuild, _)> = queries
         .into_iter()
-        .flat_map(|query| matcher.find_all(&query))
+        .flat_map(|query| find_matches(&local_builds, &query))
         .cloned()
         .collect();
 
-    let choice_map: HashMap<String, &LocalBuild> = get_choice_map(&matched_builds);
-
-    println!["{:#?}", choice_map];
-
-    let inquiry = inquire::MultiSelect::new(
-        "Choose which builds you want to uninstall",
-        choice_map.keys().cloned().collect(),
-    );
-
-    match inquiry.prompt() {
-        Ok(v) => {
-            let chosen_builds: Vec<_> = v
-                .into_iter()
-                .map(|choice| choice_map.get(&choice).unwrap())
-                .collect();
-
-            if !no_trash {
-                chosen_builds
-                    .into_iter()
-                    .map(|build| {
-                        info!["Trashing {}", build.folder.display()];
-                        trash::delete(&build.folder)
-                            .inspect(|_| info!["Success."])
-                            .map_err(|e| {
-                                error!["Failure. {}", e];
-                                CommandError::TrashError(build.folder.clone(), e)
-                            })
-                    })
-                    .collect::<Vec<_>>() // Generate all the results before checking if any failed
-                    .into_iter()
-                    .find(|r| r.is_err())
-                    .unwrap_or(Ok(()))
-            } else {
-                chosen_builds
-                    .into_iter()
-                    .map(|build| {
-                        info!["Deleting {}", build.folder.display()];
-                        std::fs::remove_dir_all(&build.folder)
-                            .inspect(|_| info!["Success."])
-                            .map_err(|e| {
-                                error!["Failure. {}", e];
-                                CommandError::IoError(
-                                    crate::errs::IoErrorOrigin::DeletingObject(
-                                        build.folder.clone(),
-                                    ),
-                                    e,
-                                )
-                            })
-                    })
-                    .collect::<Vec<_>>() // Generate all the results before checking if any failed
-                    .into_iter()
-                    .find(|r| r.is_err())
-                    .unwrap_or(Ok(()))
-            }
+    let choice_map: HashMap<String, &(LocalBuild, String)> = get_choice_map(&matched_builds);
+
+    if !json_output() {
+        println!["{:#?}", choice_map];
+    }
+
+    if choice_map.is_empty() {
+        info!["No installed builds match the given queries"];
+        return Ok(());
+    }
+
+    let choices: Vec<String> = choice_map.keys().cloned().collect();
+    let selected = if all_matching && is_interactive() {
+        let prompt = format!["Remove all {} matching builds?", choices.len()];
+        if !confirm(&prompt, false) {
+            return Ok(());
+        }
+        choices
+    } else if !is_interactive() {
+        if !assume_yes() {
+            return Err(CommandError::PromptRequired(
+                "Removing builds requires confirmation. Pass --yes to remove every match".into(),
+            ));
         }
-        Err(e) => {
-            println!["{:?}", e];
+        choices
+    } else {
+        match prompter().multi_select("Choose which builds you want to uninstall", choices) {
+            Some(v) => v,
+            None => return Ok(()),
+        }
+    };
+
+    let chosen_builds: Vec<_> = selected
+        .into_iter()
+        .map(|choice| &choice_map.get(&choice).unwrap().0)
+        .collect();
 
-            Ok(())
+    let sizes: Vec<(PathBuf, u64)> = chosen_builds
+        .iter()
+        .map(|b| (b.folder.clone(), dir_size(&b.folder).unwrap_or_default()))
+        .collect();
+
+    let result = delete_builds(cfg, chosen_builds.iter().copied());
+
+    print_removed(sizes);
+
+    result
+}
+
+/// Prints what was removed and how much space it took. Failed deletions leave their folder behind,
+/// so only what is actually gone is counted.
+fn print_removed(sizes: Vec<(PathBuf, u64)>) {
+    let removed: Vec<_> = sizes.into_iter().filter(|(p, _)| !p.exists()).collect();
+    if json_output() || removed.is_empty() {
+        return;
+    }
+
+    removed
+        .iter()
+        .for_each(|(p, size)| println!["Removed {} ({})", p.display(), HumanBytes(*size)]);
+    println![
+        "{} {} build(s), {} {}",
+        ansi_term::Color::Green.bold().paint("Removed"),
+        removed.len(),
+        HumanBytes(removed.iter().map(|(_, size)| size).sum()),
+        match deletion::uses_trash() {
+            true => "moved to the trash",
+            false => "freed",
         }
+    ];
+}
+
+/// Removes installed build folders without matching them by query, so builds whose build info
+/// is too broken to be read can still be removed. The folders have to be builds in the library.
+pub fn remove_folders(cfg: &BLRSConfig, paths: Vec<PathBuf>) -> Result<(), CommandError> {
+    let library = cfg
+        .paths
+        .library
+        .canonicalize()
+        .map_err(|e| error_reading(cfg.paths.library.clone(), e))?;
+
+    let folders = paths
+        .into_iter()
+        .map(|path| {
+            let folder = resolve_parent(&path).map_err(|e| error_reading(path.clone(), e))?;
+            // Builds are kept in <library>/<repo>/<build>, or nested deeper by the library layout.
+            // Anything above is more than one build, and anything below is part of one
+            let repo_folder = match folder.strip_prefix(&library) {
+                Ok(relative) => relative.components().next().map(|c| library.join(c)),
+                Err(_) => None,
+            };
+            match repo_folder {
+                Some(repo_folder) if walk_layout(&repo_folder).1.contains(&folder) => Ok(folder),
+                _ => Err(CommandError::NotABuildFolder(path)),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let prompt = format!["Remove {} folder(s)?", folders.len()];
+    remove_confirmed(cfg, folders, &prompt)
+}
+
+/// Resolves `path` like `canonicalize`, except for its last component, so a build linked into the
+/// library by `adopt --link` is found where it is in the library rather than where it points to.
+fn resolve_parent(path: &Path) -> std::io::Result<PathBuf> {
+    let absolute = std::path::absolute(path)?;
+    let folder = match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => parent.canonicalize()?.join(name),
+        _ => absolute.canonicalize()?,
+    };
+    folder.symlink_metadata().map(|_| folder)
+}
+
+/// Removes every installed build of a repo, including the ones whose build info cannot be read.
+pub fn remove_repo(cfg: &BLRSConfig, repo: String) -> Result<(), CommandError> {
+    let (repo, path) = repo_folder(cfg, Some(repo));
+    if !path.is_dir() {
+        return match cfg
+            .repos
+            .iter()
+            .any(|r| r.nickname == repo || r.repo_id == repo)
+        {
+            true => {
+                info!["{} has no installed builds", repo];
+                Ok(())
+            }
+            false => Err(unknown_repo(cfg, &repo)),
+        };
+    }
+
+    let mut folders: Vec<PathBuf> = path
+        .read_dir()
+        .map_err(|e| error_reading(path.clone(), e))?
+        .flatten()
+        .map(|item| item.path())
+        // Hidden folders are pulls that are still being extracted
+        .filter(|p| {
+            p.is_dir()
+                && !p
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    folders.sort();
+
+    if folders.is_empty() {
+        info!["{} has no installed builds", repo];
+        return Ok(());
+    }
+    if !json_output() {
+        folders.iter().for_each(|f| println!["{}", f.display()]);
+    }
+
+    let prompt = format![
+        "Remove all {} installed build(s) of {}?",
+        folders.len(),
+        repo
+    ];
+    remove_confirmed(cfg, folders, &prompt)
+}
+
+/// Asks once before removing the folders, or requires --yes when prompting is disabled.
+fn remove_confirmed(
+    cfg: &BLRSConfig,
+    folders: Vec<PathBuf>,
+    prompt: &str,
+) -> Result<(), CommandError> {
+    cancel::handle_ctrl_c();
+    if !is_interactive() && !assume_yes() {
+        return Err(CommandError::PromptRequired(
+            "Removing builds requires confirmation. Pass --yes to remove them".into(),
+        ));
     }
+    if !confirm(prompt, false) {
+        return Ok(());
+    }
+
+    let sizes: Vec<(PathBuf, u64)> = folders
+        .iter()
+        .map(|f| (f.clone(), dir_size(f).unwrap_or_default()))
+        .collect();
+
+    let result = folders
+        .iter()
+        .map(|folder| {
+            cancel::check()?;
+            // The build info is only needed for the hooks, which are skipped if it is broken
+            let build = LocalBuild::read(folder).ok();
+            delete_folder(cfg, folder, build.as_ref())
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .find(|r| r.is_err())
+        .unwrap_or(Ok(()));
+
+    print_removed(sizes);
+
+    result
+}
+
+/// Removes the folders of the given builds according to the deletion policy.
+/// Every build is attempted before the first error is returned, unless Ctrl+C is pressed.
+pub fn delete_builds<'a>(
+    cfg: &BLRSConfig,
+    builds: impl IntoIterator<Item = &'a LocalBuild>,
+) -> Result<(), CommandError> {
+    builds
+        .into_iter()
+        .map(|build| {
+            cancel::check()?;
+            delete_folder(cfg, &build.folder, Some(build))
+        })
+        .collect::<Vec<_>>() // Generate all the results before checking if any failed
+        .into_iter()
+        .find(|r| r.is_err())
+        .unwrap_or(Ok(()))
+}
+
+fn delete_folder(
+    cfg: &BLRSConfig,
+    folder: &Path,
+    build: Option<&LocalBuild>,
+) -> Result<(), CommandError> {
+    unlink_if_current(cfg, folder);
+    deletion::remove(folder)
+        .inspect(|trashed| {
+            info!["Success."];
+            emit(Event::BuildRemoved {
+                path: folder,
+                trashed: *trashed,
+            });
+            if let Some(build) = build {
+                hooks::run_after(Hook::PostRm, build, &hooks::repo_of(build), &[]);
+            }
+        })
+        .map(|_| ())
+        .inspect_err(|e| {
+            error!["Failure. {}", e];
+            emit(Event::Error {
+                message: e.to_string(),
+                path: Some(folder),
+            });
+        })
 }
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::warn;
+use reqwest::{StatusCode, Url};
+use tokio::{
+    sync::{Mutex, Semaphore, SemaphorePermit},
+    time::{sleep, sleep_until, Instant},
+};
+
+use crate::errs::CommandError;
+
+/// The longest time to wait between retries of a rate limited request.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Limits how many downloads run at once and how often each host is contacted.
+#[derive(Debug)]
+pub struct DownloadScheduler {
+    slots: Semaphore,
+    host_delay: Duration,
+    retries: u32,
+    /// The earliest time the next request may be sent to each host.
+    next_request: Mutex<HashMap<String, Instant>>,
+}
+
+impl DownloadScheduler {
+    pub fn new(jobs: usize, host_delay: Duration, retries: u32) -> Self {
+        Self {
+            slots: Semaphore::new(jobs.max(1)),
+            host_delay,
+            retries,
+            next_request: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for a free download slot, then for the host's delay to pass.
+    /// The slot is released when the returned permit is dropped.
+    pub async fn acquire(&self, url: &Url) -> SemaphorePermit<'_> {
+        let permit = self
+            .slots
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+
+        let host = url.host_str().unwrap_or_default().to_string();
+        let slot = {
+            let mut next_request = self.next_request.lock().await;
+            let now = Instant::now();
+            let slot = next_request.get(&host).map_or(now, |&t| t.max(now));
+            next_request.insert(host, slot + self.host_delay);
+            slot
+        };
+        sleep_until(slot).await;
+
+        permit
+    }
+
+    /// Runs a download in a slot, retrying with exponential backoff if the host responds with 429
+    /// or the download fails in a way that may not happen again.
+    pub async fn run<F, Fut>(&self, url: &Url, mut download: F) -> Result<(), CommandError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<(), CommandError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = {
+                let _permit = self.acquire(url).await;
+                download().await
+            };
+
+            match result {
+                Err(CommandError::ReturnCode(StatusCode::TOO_MANY_REQUESTS))
+                    if attempt < self.retries =>
+                {
+                    let wait = self.backoff(attempt);
+                    warn![
+                        "{} is rate limiting us, retrying in {:.0}s ({}/{})",
+                        url.host_str().unwrap_or_default(),
+                        wait.as_secs_f64(),
+                        attempt + 1,
+                        self.retries
+                    ];
+                    sleep(wait).await;
+                    attempt += 1;
+                }
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    let wait = self.backoff(attempt);
+                    warn![
+                        "Downloading {} failed: {}. Retrying in {:.0}s ({}/{})",
+                        url,
+                        e,
+                        wait.as_secs_f64(),
+                        attempt + 1,
+                        self.retries
+                    ];
+                    sleep(wait).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let base = self.host_delay.max(Duration::from_secs(1));
+        base.saturating_mul(2u32.saturating_pow(attempt + 1))
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// Limits the combined download speed of every running download with a token bucket.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Bytes per second. 0 means unlimited.
+    rate: AtomicU64,
+    /// The tokens available, and when they were last refilled. Tokens go negative when a
+    /// download takes more than is available, and the download waits until they are paid back.
+    bucket: std::sync::Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self {
+            rate: AtomicU64::new(0),
+            bucket: std::sync::Mutex::new((0.0, Instant::now())),
+        }
+    }
+
+    pub fn set_rate(&self, rate: Option<u64>) {
+        self.rate.store(rate.unwrap_or_default(), Ordering::Release);
+    }
+
+    /// Takes `bytes` from the bucket, waiting if the downloads are going faster than the limit.
+    pub async fn consume(&self, bytes: u64) {
+        let rate = self.rate.load(Ordering::Acquire);
+        if rate == 0 {
+            return;
+        }
+
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let (tokens, last) = &mut *bucket;
+            let now = Instant::now();
+            // Allow bursts of up to a second's worth of data
+            *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate as f64)
+                .min(rate as f64)
+                - bytes as f64;
+            *last = now;
+
+            (*tokens < 0.0).then(|| Duration::from_secs_f64(-*tokens / rate as f64))
+        };
+
+        if let Some(wait) = wait {
+            sleep(wait).await;
+        }
+    }
+}
+
+/// Parses a rate like `500K`, `5M`, or `1.5MB/s` into bytes per second. Suffixes are powers of 1024.
+pub fn parse_rate(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim().trim_end_matches("/s");
+    let trimmed = trimmed.strip_suffix(['B', 'b']).unwrap_or(trimmed);
+
+    let (number, multiplier) = match trimmed.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let multiplier: u64 = match c.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                _ => return Err(format!["unknown unit {:?} in {:?}", c, s]),
+            };
+            (&trimmed[..i], multiplier)
+        }
+        _ => (trimmed, 1),
+    };
+
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!["expected a rate like 5M, got {:?}", s])?;
+    match number > 0.0 {
+        true => Ok((number * multiplier as f64) as u64),
+        false => Err("the rate must be above 0".to_string()),
+    }
+}
+
+/// Whether an error is likely to go away if the download is tried again.
+fn is_transient(e: &CommandError) -> bool {
+    match e {
+        CommandError::ReqwestError(_) | CommandError::TimedOut(_) => true,
+        CommandError::ReturnCode(code) => code.is_server_error(),
+        CommandError::IoError(_, e) => matches!(
+            e.kind(),
+            std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::TimedOut
+                | std::io::ErrorKind::UnexpectedEof
+                | std::io::ErrorKind::Interrupted
+        ),
+        _ => false,
+    }
+}
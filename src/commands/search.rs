@@ -0,0 +1,158 @@
+use ansi_term::Color;
+use blrs::{
+    fetching::build_repository::BuildRepo,
+    repos::{BuildEntry, RepoEntry, Variants},
+    BLRSConfig, RemoteBuild,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use log::info;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    errs::{CommandError, IoErrorOrigin},
+    repo_cache::read_repos,
+    resolving::{build_query_string, VariantPreference},
+};
+
+/// What a build in the cached build lists has to match to be listed by `search`.
+#[derive(Debug, Default)]
+pub struct SearchFilter {
+    /// A glob matched against the version, like `4.3*`.
+    pub version: Option<glob::Pattern>,
+    pub branch: Option<Regex>,
+    /// Every variant preference has to match at least one variant of the build.
+    pub variants: Vec<VariantPreference>,
+    pub since: Option<DateTime<Utc>>,
+    pub repos: Vec<String>,
+}
+
+impl SearchFilter {
+    fn matches(&self, nick: &str, build: &Variants<RemoteBuild>) -> bool {
+        let version = build.basic.version();
+        let short = format!["{}.{}.{}", version.major, version.minor, version.patch];
+
+        (self.repos.is_empty() || self.repos.iter().any(|r| r == nick))
+            && self
+                .version
+                .as_ref()
+                .is_none_or(|p| p.matches(&short) || p.matches(&version.to_string()))
+            && self
+                .branch
+                .as_ref()
+                .is_none_or(|r| r.is_match(version.pre.as_str()))
+            && self
+                .since
+                .is_none_or(|since| build.basic.commit_dt >= since)
+            && self
+                .variants
+                .iter()
+                .all(|pref| build.v.iter().any(|variant| pref.matches(variant)))
+    }
+}
+
+/// Checks that a `--branch` regex compiles. It is kept as a string so commands stay serializable.
+pub fn validate_regex(s: &str) -> Result<String, String> {
+    Regex::new(s)
+        .map(|_| s.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Checks that a version glob is valid.
+pub fn validate_glob(s: &str) -> Result<String, String> {
+    glob::Pattern::new(s)
+        .map(|_| s.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Parses a date like `2024-06-01`, or a duration like `2w` that is counted back from now.
+pub fn parse_since(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+
+    humantime::parse_duration(s)
+        .ok()
+        .and_then(|d| chrono::TimeDelta::from_std(d).ok())
+        .map(|d| Utc::now() - d)
+        .ok_or_else(|| {
+            format![
+                "expected a date like 2024-06-01 or a duration like 2w, got {:?}",
+                s
+            ]
+        })
+}
+
+#[derive(Debug, Serialize)]
+struct SearchResult {
+    query: String,
+    repo: String,
+    version: String,
+    branch: String,
+    commit_dt: DateTime<Utc>,
+    targets: Vec<String>,
+}
+
+/// Lists the builds in the cached build lists that match every given filter, newest first.
+/// Installed builds are not searched, as `ls` already shows them.
+pub fn search(cfg: &BLRSConfig, filter: SearchFilter, json: bool) -> Result<(), CommandError> {
+    let mut results: Vec<SearchResult> = read_repos(cfg.repos.clone(), &cfg.paths, false)
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
+        .into_iter()
+        .filter_map(|r| match r {
+            RepoEntry::Registered(BuildRepo { nickname, .. }, entries) => Some((nickname, entries)),
+            _ => None,
+        })
+        .flat_map(|(nick, entries)| {
+            entries
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    BuildEntry::NotInstalled(variants) => Some(variants),
+                    _ => None,
+                })
+                .filter(|variants| filter.matches(&nick, variants))
+                .map(|variants| {
+                    let version = variants.basic.version();
+                    SearchResult {
+                        query: build_query_string(&variants.basic, &nick),
+                        repo: nick.clone(),
+                        version: format!["{}.{}.{}", version.major, version.minor, version.patch],
+                        branch: version.pre.to_string(),
+                        commit_dt: variants.basic.commit_dt,
+                        targets: variants.v.iter().map(|v| v.to_string()).collect(),
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    results.sort_by_key(|r| std::cmp::Reverse(r.commit_dt));
+
+    if json {
+        println!["{}", serde_json::to_string_pretty(&results).unwrap()];
+        return Ok(());
+    }
+    if results.is_empty() {
+        info!["No builds in the cached build lists match"];
+        return Ok(());
+    }
+
+    let width = |f: fn(&SearchResult) -> usize| results.iter().map(f).max().unwrap_or(0);
+    let (repo_w, version_w, branch_w) = (
+        width(|r| r.repo.len()),
+        width(|r| r.version.len()),
+        width(|r| r.branch.len()),
+    );
+    results.iter().for_each(|r| {
+        println![
+            "{} {} {:<branch_w$} {} {}",
+            Color::Cyan.paint(format!["{:<repo_w$}", r.repo]),
+            Color::Green.paint(format!["{:<version_w$}", r.version]),
+            r.branch,
+            r.commit_dt.format("%Y-%m-%d %H:%M"),
+            Color::White.dimmed().paint(r.targets.join(", "))
+        ];
+    });
+    println!["{} build(s) found", results.len()];
+
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use std::{
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use blrs::BLRSConfig;
+use flate2::read::GzDecoder;
+use log::{debug, info};
+use reqwest::Client;
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use xz::read::XzDecoder;
+use zip::ZipArchive;
+
+use crate::{
+    errs::{error_renaming, error_writing, CommandError},
+    network::client_builder,
+    resolving::confirm,
+};
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/zeptofine/blrs-cli/releases/latest";
+/// The extensions of the release archives, in order of preference.
+const ARCHIVE_EXTENSIONS: [&str; 3] = [".zip", ".tar.gz", ".tar.xz"];
+/// Every release archive is published with a file holding its SHA-256, named like the archive plus this.
+const CHECKSUM_EXTENSION: &str = ".sha256sum";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn version(&self) -> Option<Version> {
+        Version::parse(self.tag_name.trim_start_matches('v')).ok()
+    }
+
+    /// The archive built for this platform, matched by the target triple in its name.
+    fn archive(&self) -> Option<&Asset> {
+        let os = match std::env::consts::OS {
+            "macos" => "apple-darwin",
+            os => os,
+        };
+        let arch = std::env::consts::ARCH;
+
+        ARCHIVE_EXTENSIONS.iter().find_map(|ext| {
+            self.assets
+                .iter()
+                .find(|a| a.name.contains(arch) && a.name.contains(os) && a.name.ends_with(ext))
+        })
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+async fn download(client: &Client, url: &str) -> Result<Vec<u8>, CommandError> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(CommandError::ReqwestError)?;
+    if !response.status().is_success() {
+        return Err(CommandError::ReturnCode(response.status()));
+    }
+
+    response
+        .bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(CommandError::ReqwestError)
+}
+
+/// Finds the executable in a release archive and reads it.
+fn unpack_executable(name: &str, data: &[u8]) -> Result<Vec<u8>, CommandError> {
+    let exe_name = format!["blrs{}", std::env::consts::EXE_SUFFIX];
+    let broken = |reason| CommandError::BrokenArchive(name.into(), reason);
+    let is_exe = |path: &Path| path.file_name().is_some_and(|n| *n == *exe_name);
+    let mut exe = vec![];
+
+    if name.ends_with(".zip") {
+        let mut archive = ZipArchive::new(Cursor::new(data))
+            .map_err(|_| broken("the archive could not be read"))?;
+        for idx in 0..archive.len() {
+            let mut entry = archive
+                .by_index(idx)
+                .map_err(|_| broken("the archive could not be read"))?;
+            if entry.enclosed_name().is_some_and(|p| is_exe(&p)) {
+                entry
+                    .read_to_end(&mut exe)
+                    .map_err(|_| broken("the executable could not be read"))?;
+                return Ok(exe);
+            }
+        }
+    } else {
+        let reader: Box<dyn Read> = match name.ends_with(".tar.xz") {
+            true => Box::new(XzDecoder::new(data)),
+            false => Box::new(GzDecoder::new(data)),
+        };
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive
+            .entries()
+            .map_err(|_| broken("the archive could not be read"))?
+        {
+            let mut entry = entry.map_err(|_| broken("the archive could not be read"))?;
+            if entry.path().is_ok_and(|p| is_exe(&p)) {
+                entry
+                    .read_to_end(&mut exe)
+                    .map_err(|_| broken("the executable could not be read"))?;
+                return Ok(exe);
+            }
+        }
+    }
+
+    Err(broken("it does not contain a blrs executable"))
+}
+
+/// Swaps the running executable for the new one. The new file is written next to it first,
+/// so the rename at the end either fully happens or not at all.
+fn replace_executable(exe: &[u8]) -> Result<(), CommandError> {
+    let current = std::env::current_exe().map_err(|e| {
+        CommandError::SelfUpdate(format!["the running executable could not be found: {}", e])
+    })?;
+    let staged = current.with_extension("new");
+    std::fs::write(&staged, exe).map_err(|e| error_writing(staged.clone(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| error_writing(staged.clone(), e))?;
+    }
+
+    // Windows does not allow replacing a running executable, but it does allow renaming it
+    #[cfg(windows)]
+    let old = {
+        let old = current.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(&current, &old)
+            .map_err(|e| error_renaming(current.clone(), old.clone(), e))?;
+        old
+    };
+
+    if let Err(e) = std::fs::rename(&staged, &current) {
+        // Put the running executable back, so blrs can still be started
+        #[cfg(windows)]
+        if let Err(e) = std::fs::rename(&old, &current) {
+            log::warn![
+                "Failed to restore {} from {}: {}",
+                current.display(),
+                old.display(),
+                e
+            ];
+        }
+        let _ = std::fs::remove_file(&staged);
+        return Err(error_renaming(staged, current, e));
+    }
+
+    Ok(())
+}
+
+/// Checks the GitHub releases for a newer blrs and installs it over the running executable.
+pub async fn self_update(cfg: &BLRSConfig, check: bool) -> Result<(), CommandError> {
+    let client = client_builder(cfg, true)?
+        .build()
+        .map_err(CommandError::ReqwestError)?;
+
+    let release: Release = serde_json::from_slice(&download(&client, LATEST_RELEASE).await?)
+        .map_err(|e| CommandError::SelfUpdate(format!["the release could not be read: {}", e]))?;
+
+    let current = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
+    let latest = release.version().ok_or_else(|| {
+        CommandError::SelfUpdate(format![
+            "the latest release has an invalid version {:?}",
+            release.tag_name
+        ])
+    })?;
+    if latest <= current {
+        info!["blrs {} is the newest version", current];
+        return Ok(());
+    }
+
+    println!["blrs {} is available, you have {}", latest, current];
+    if check {
+        return Ok(());
+    }
+
+    let archive = release.archive().ok_or_else(|| {
+        CommandError::SelfUpdate(format![
+            "release {} has no build for {}-{}",
+            release.tag_name,
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        ])
+    })?;
+    let checksum = release
+        .asset(&(archive.name.clone() + CHECKSUM_EXTENSION))
+        .ok_or_else(|| {
+            CommandError::SelfUpdate(format!["{} has no published checksum", archive.name])
+        })?;
+
+    if !confirm(&format!["Update blrs to {}?", latest], true) {
+        return Err(CommandError::Cancelled);
+    }
+
+    info!["Downloading {}", archive.name];
+    let data = download(&client, &archive.browser_download_url).await?;
+    let expected =
+        String::from_utf8_lossy(&download(&client, &checksum.browser_download_url).await?)
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+    let actual = format!["{:x}", Sha256::digest(&data)];
+    debug!["Expected checksum {}, got {}", expected, actual];
+    if expected != actual {
+        return Err(CommandError::ChecksumMismatch {
+            name: archive.name.clone(),
+            expected,
+            actual,
+        });
+    }
+
+    let exe = unpack_executable(&archive.name, &data)?;
+    replace_executable(&exe)?;
+
+    println![
+        "{} blrs {}",
+        ansi_term::Color::Green.bold().paint("Updated to"),
+        latest
+    ];
+    Ok(())
+}
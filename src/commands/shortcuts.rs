@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+
+use blrs::{BLRSConfig, LocalBuild};
+use log::info;
+
+use crate::{
+    errs::{error_reading, error_writing, CommandError, IoErrorOrigin},
+    resolving::{build_query_string, foreign_target, installed_builds},
+};
+
+/// A launcher entry for one installed build.
+struct Shortcut {
+    /// The name shown in the launcher.
+    title: String,
+    /// The query passed to `blrs run build`, so the build's configured arguments apply.
+    query: String,
+    folder: PathBuf,
+}
+
+impl Shortcut {
+    fn new(build: &LocalBuild, nick: &str) -> Self {
+        let name = build
+            .info
+            .custom_name
+            .clone()
+            .unwrap_or_else(|| build.info.basic.ver.to_string());
+
+        Self {
+            title: format!["Blender {} ({})", name, nick],
+            query: build_query_string(&build.info.basic, nick),
+            folder: build.folder.clone(),
+        }
+    }
+}
+
+/// Creates a launcher entry for every installed build and removes the entries of builds that are gone.
+pub fn sync(cfg: &BLRSConfig) -> Result<(), CommandError> {
+    let exe = std::env::current_exe()
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+    let dir = platform::shortcut_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| error_writing(dir.clone(), e))?;
+
+    let wanted: Vec<(PathBuf, Shortcut)> = installed_builds(cfg)?
+        .into_iter()
+        // Builds for other platforms cannot be launched here
+        .filter(|(b, _)| foreign_target(&b.info.basic).is_none())
+        .map(|(b, nick)| {
+            let shortcut = Shortcut::new(&b, &nick);
+            (
+                dir.join(platform::file_name(&b, &nick, &shortcut)),
+                shortcut,
+            )
+        })
+        .collect();
+
+    let mut removed = 0;
+    for path in platform::owned_entries(&dir).map_err(|e| error_reading(dir.clone(), e))? {
+        if !wanted.iter().any(|(p, _)| *p == path) {
+            info!["Removing {}", path.display()];
+            std::fs::remove_file(&path).map_err(|e| error_writing(path.clone(), e))?;
+            removed += 1;
+        }
+    }
+
+    let mut added = 0;
+    for (path, shortcut) in &wanted {
+        if path.symlink_metadata().is_err() {
+            info!["Adding {}", shortcut.title];
+            added += 1;
+        }
+        platform::write(path, shortcut, &exe)?;
+    }
+
+    info![
+        "{} shortcut(s) added, {} removed, {} in total",
+        added,
+        removed,
+        wanted.len()
+    ];
+    Ok(())
+}
+
+/// Lists the files in `dir` with the given extension.
+fn entries_with_extension(dir: &Path, extension: &str) -> std::io::Result<Vec<PathBuf>> {
+    Ok(dir
+        .read_dir()?
+        .filter_map(Result::ok)
+        .map(|item| item.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+        .collect())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    use blrs::LocalBuild;
+
+    use crate::{
+        commands::handler::exec_arg,
+        errs::{error_writing, CommandError},
+        run::safe_name,
+    };
+
+    use super::{entries_with_extension, Shortcut};
+
+    /// Desktop files are all kept in one folder, so blrs only touches the ones starting with this.
+    const PREFIX: &str = "blrs-build-";
+
+    pub fn shortcut_dir() -> PathBuf {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".local/share")
+            })
+            .join("applications")
+    }
+
+    pub fn file_name(build: &LocalBuild, nick: &str, _: &Shortcut) -> String {
+        format!["{}{}.desktop", PREFIX, safe_name(build, nick)]
+    }
+
+    pub fn owned_entries(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        Ok(entries_with_extension(dir, "desktop")?
+            .into_iter()
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with(PREFIX))
+            })
+            .collect())
+    }
+
+    pub fn write(path: &Path, shortcut: &Shortcut, exe: &Path) -> Result<(), CommandError> {
+        let entry = format![
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name={}\n\
+             Comment=Launched through blrs\n\
+             Exec={} run build {}\n\
+             Icon={}\n\
+             Terminal=false\n\
+             Categories=Graphics;3DGraphics;\n",
+            shortcut.title,
+            exec_arg(&exe.display().to_string()),
+            exec_arg(&shortcut.query),
+            shortcut.folder.join("blender.svg").display()
+        ];
+
+        std::fs::write(path, entry).map_err(|e| error_writing(path.to_path_buf(), e))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::{
+        path::{Path, PathBuf},
+        process,
+    };
+
+    use blrs::LocalBuild;
+
+    use crate::{commands::handler::run_helper, errs::CommandError};
+
+    use super::{entries_with_extension, Shortcut};
+
+    /// Everything in this Start Menu folder belongs to blrs.
+    pub fn shortcut_dir() -> PathBuf {
+        PathBuf::from(std::env::var_os("APPDATA").unwrap_or_default())
+            .join(r"Microsoft\Windows\Start Menu\Programs\Blender (blrs)")
+    }
+
+    pub fn file_name(_: &LocalBuild, _: &str, shortcut: &Shortcut) -> String {
+        format![
+            "{}.lnk",
+            shortcut
+                .title
+                .replace(['\\', '/', ':', '*', '?', '"', '<', '>', '|'], "_")
+        ]
+    }
+
+    pub fn owned_entries(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        entries_with_extension(dir, "lnk")
+    }
+
+    /// Quotes a string for PowerShell, where only single quotes need escaping.
+    fn quote(s: &str) -> String {
+        format!["'{}'", s.replace('\'', "''")]
+    }
+
+    /// Shortcuts can only be made through COM, which PowerShell exposes without extra dependencies.
+    pub fn write(path: &Path, shortcut: &Shortcut, exe: &Path) -> Result<(), CommandError> {
+        let script = format![
+            "$s = (New-Object -ComObject WScript.Shell).CreateShortcut({}); \
+             $s.TargetPath = {}; $s.Arguments = {}; $s.IconLocation = {}; \
+             $s.WorkingDirectory = {}; $s.Save()",
+            quote(&path.display().to_string()),
+            quote(&exe.display().to_string()),
+            quote(&format![r#"run build "{}""#, shortcut.query]),
+            quote(&shortcut.folder.join("blender.exe").display().to_string()),
+            quote(&shortcut.folder.display().to_string()),
+        ];
+
+        run_helper(
+            process::Command::new("powershell")
+                .args(["-NoProfile", "-NonInteractive", "-Command"])
+                .arg(script),
+        )
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    use blrs::LocalBuild;
+
+    use crate::errs::{error_writing, CommandError};
+
+    use super::{entries_with_extension, Shortcut};
+
+    /// Everything in this folder belongs to blrs. Spotlight and Launchpad pick up apps in ~/Applications.
+    pub fn shortcut_dir() -> PathBuf {
+        PathBuf::from(std::env::var_os("HOME").unwrap_or_default())
+            .join("Applications")
+            .join("Blender (blrs)")
+    }
+
+    pub fn file_name(_: &LocalBuild, _: &str, shortcut: &Shortcut) -> String {
+        format!["{}.app", shortcut.title.replace(['/', ':'], "_")]
+    }
+
+    pub fn owned_entries(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        entries_with_extension(dir, "app")
+    }
+
+    /// Links to the build's own app bundle, so it launches directly rather than through blrs.
+    pub fn write(path: &Path, shortcut: &Shortcut, _: &Path) -> Result<(), CommandError> {
+        if path.symlink_metadata().is_ok() {
+            std::fs::remove_file(path).map_err(|e| error_writing(path.to_path_buf(), e))?;
+        }
+        std::os::unix::fs::symlink(shortcut.folder.join("Blender.app"), path)
+            .map_err(|e| error_writing(path.to_path_buf(), e))
+    }
+}
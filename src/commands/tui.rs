@@ -0,0 +1,570 @@
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{
+        atomic::Ordering,
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use blrs::{
+    fetching::build_repository::BuildRepo,
+    repos::{BuildEntry, RepoEntry},
+    search::VersionSearchQuery,
+    BLRSConfig, BasicBuildInfo, LocalBuild,
+};
+use log::LevelFilter;
+use ratatui::{
+    crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, Gauge, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{error_writing, CommandError, IoErrorOrigin},
+    events::{set_listener, Event},
+    repo_cache::Window,
+    repo_formatting::SortFormat,
+    resolving::{build_query_string, NON_INTERACTIVE},
+    run::launch_command,
+};
+
+use super::{
+    ls::gather_and_filter_repos,
+    pull::{pull_builds, FetchPolicy, PullOptions},
+    rm::delete_builds,
+};
+
+const HELP: &str =
+    "↑/↓ move  / filter  enter install/launch  d remove  f favorite  r reload  q quit";
+
+/// A download in progress, as reported by pull events.
+#[derive(Debug, Clone, Default)]
+struct Download {
+    downloaded: u64,
+    total: u64,
+    finished: bool,
+}
+
+type Downloads = Arc<Mutex<HashMap<String, Download>>>;
+
+fn track_download(downloads: &Downloads, event: &Event) {
+    let mut downloads = downloads.lock().unwrap();
+    match event {
+        Event::DownloadStarted { url, .. } => {
+            downloads.insert(url.to_string(), Download::default());
+        }
+        Event::DownloadProgress {
+            url,
+            downloaded,
+            total,
+            ..
+        } => {
+            let d = downloads.entry(url.to_string()).or_default();
+            d.downloaded = *downloaded;
+            d.total = *total;
+        }
+        Event::DownloadFinished { url, .. } => {
+            downloads.entry(url.to_string()).or_default().finished = true;
+        }
+        _ => {}
+    }
+}
+
+struct Item {
+    repo: String,
+    entry: BuildEntry,
+}
+
+impl Item {
+    fn basic(&self) -> Option<&BasicBuildInfo> {
+        match &self.entry {
+            BuildEntry::NotInstalled(variants) => Some(&variants.basic),
+            BuildEntry::Installed(_, local_build) => Some(&local_build.info.basic),
+            BuildEntry::Errored(_, _) => None,
+        }
+    }
+
+    fn query(&self) -> Option<String> {
+        self.basic().map(|b| build_query_string(b, &self.repo))
+    }
+
+    fn local(&self) -> Option<&LocalBuild> {
+        match &self.entry {
+            BuildEntry::Installed(_, local_build) => Some(local_build),
+            _ => None,
+        }
+    }
+
+    fn to_row(&self) -> Row<'static> {
+        let (version, date) = match self.basic() {
+            Some(b) => (
+                VersionSearchQuery::from(b.clone())
+                    .with_commit_dt(None)
+                    .to_string(),
+                b.commit_dt.format("%Y-%m-%d %H:%M").to_string(),
+            ),
+            None => (String::new(), String::new()),
+        };
+        let (state, style) = match &self.entry {
+            BuildEntry::Installed(_, lb) if lb.info.is_favorited => {
+                ("★ installed".to_string(), Style::new().fg(Color::Yellow))
+            }
+            BuildEntry::Installed(_, _) => ("installed".to_string(), Style::new().fg(Color::Cyan)),
+            BuildEntry::NotInstalled(variants) => {
+                (format!["{} variants", variants.v.len()], Style::new().dim())
+            }
+            BuildEntry::Errored(e, path) => (
+                format!["error at {:?}: {:?}", path, e],
+                Style::new().fg(Color::Red),
+            ),
+        };
+
+        Row::new([self.repo.clone(), version, date, state]).style(style)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    Filter,
+    ConfirmRemove,
+}
+
+struct JobResult {
+    query: String,
+    result: Result<(), String>,
+}
+
+struct App<'a> {
+    cfg: &'a BLRSConfig,
+    cli_cfg: &'a CliConfig,
+    all_builds: bool,
+    items: Vec<Item>,
+    /// Indices into `items` that match the filter.
+    visible: Vec<usize>,
+    table: TableState,
+    filter: String,
+    mode: Mode,
+    status: String,
+    running_jobs: usize,
+    downloads: Downloads,
+    job_tx: Sender<JobResult>,
+    job_rx: Receiver<JobResult>,
+}
+
+impl<'a> App<'a> {
+    fn new(
+        cfg: &'a BLRSConfig,
+        cli_cfg: &'a CliConfig,
+        all_builds: bool,
+        downloads: Downloads,
+    ) -> Self {
+        let (job_tx, job_rx) = mpsc::channel();
+        Self {
+            cfg,
+            cli_cfg,
+            all_builds,
+            items: vec![],
+            visible: vec![],
+            table: TableState::default(),
+            filter: String::new(),
+            mode: Mode::Browse,
+            status: String::new(),
+            running_jobs: 0,
+            downloads,
+            job_tx,
+            job_rx,
+        }
+    }
+
+    fn reload(&mut self) -> Result<(), CommandError> {
+        let mut repos = gather_and_filter_repos(
+            self.cfg,
+            false,
+            self.all_builds,
+            Some(SortFormat::Version),
+            &Window::default(),
+        )
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?;
+        repos.sort_by_cached_key(|r| match r {
+            RepoEntry::Registered(BuildRepo { nickname, .. }, _)
+            | RepoEntry::Error(nickname, _)
+            | RepoEntry::Unknown(nickname, _) => nickname.clone(),
+        });
+
+        self.items = repos
+            .into_iter()
+            .flat_map(|repo| match repo {
+                RepoEntry::Registered(BuildRepo { nickname: repo, .. }, builds)
+                | RepoEntry::Unknown(repo, builds) => builds
+                    .into_iter()
+                    .rev()
+                    .map(|entry| Item {
+                        repo: repo.clone(),
+                        entry,
+                    })
+                    .collect(),
+                RepoEntry::Error(_, _) => vec![],
+            })
+            .collect();
+        self.apply_filter();
+
+        Ok(())
+    }
+
+    fn apply_filter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.visible = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                filter.is_empty()
+                    || item
+                        .query()
+                        .is_some_and(|q| q.to_lowercase().contains(&filter))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+
+        let selected = match self.visible.len() {
+            0 => None,
+            n => Some(self.table.selected().unwrap_or(0).min(n - 1)),
+        };
+        self.table.select(selected);
+    }
+
+    fn selected(&self) -> Option<&Item> {
+        self.table
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .map(|&idx| &self.items[idx])
+    }
+
+    /// Returns false when the TUI should close.
+    fn handle_key(&mut self, code: KeyCode) -> Result<bool, CommandError> {
+        match self.mode {
+            Mode::Filter => match code {
+                KeyCode::Enter => self.mode = Mode::Browse,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.mode = Mode::Browse;
+                    self.apply_filter();
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.apply_filter();
+                }
+                KeyCode::Char(c) => {
+                    self.filter.push(c);
+                    self.apply_filter();
+                }
+                _ => {}
+            },
+            Mode::ConfirmRemove => {
+                self.mode = Mode::Browse;
+                match code {
+                    KeyCode::Char('y') => self.remove_selected()?,
+                    _ => self.status = "Removal cancelled".to_string(),
+                }
+            }
+            Mode::Browse => match code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+                KeyCode::Home | KeyCode::Char('g') => self.table.select_first(),
+                KeyCode::End | KeyCode::Char('G') => self.table.select_last(),
+                KeyCode::Char('/') => self.mode = Mode::Filter,
+                KeyCode::Char('r') => {
+                    self.reload()?;
+                    self.status = "Reloaded builds".to_string();
+                }
+                KeyCode::Enter => match self.selected().map(|i| i.local().is_some()) {
+                    Some(true) => self.launch_selected()?,
+                    Some(false) => self.install_selected(),
+                    None => {}
+                },
+                KeyCode::Char('i') => self.install_selected(),
+                KeyCode::Char('l') => self.launch_selected()?,
+                KeyCode::Char('f') => self.toggle_favorite()?,
+                KeyCode::Char('d') => {
+                    if let Some(query) = self
+                        .selected()
+                        .filter(|i| i.local().is_some())
+                        .and_then(Item::query)
+                    {
+                        self.status = format!["Remove {}? (y/n)", query];
+                        self.mode = Mode::ConfirmRemove;
+                    }
+                }
+                _ => {}
+            },
+        }
+
+        Ok(true)
+    }
+
+    fn install_selected(&mut self) {
+        let Some(item) = self.selected() else { return };
+        if item.local().is_some() {
+            self.status = "That build is already installed".to_string();
+            return;
+        }
+        let Some(query) = item.query() else { return };
+        let Ok(q) = VersionSearchQuery::try_from(query.as_str()) else {
+            return;
+        };
+
+        let cfg = self.cfg.clone();
+        let cli_cfg = self.cli_cfg.clone();
+        let tx = self.job_tx.clone();
+        self.running_jobs += 1;
+        self.status = format!["Installing {}", query];
+
+        std::thread::spawn(move || {
+            // The list shown is read from the cache, so fetching here would not change what was picked
+            let opts = PullOptions {
+                hide_progress: true,
+                fetch: FetchPolicy::Never,
+                ..Default::default()
+            };
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .enable_io()
+                .build()
+                .expect("failed to create runtime");
+
+            let result = rt
+                .block_on(pull_builds(&cfg, &cli_cfg, vec![q], &opts))
+                .map(|_| ())
+                .map_err(|e| e.to_string());
+            let _ = tx.send(JobResult { query, result });
+        });
+    }
+
+    fn launch_selected(&mut self) -> Result<(), CommandError> {
+        let Some(item) = self.selected() else {
+            return Ok(());
+        };
+        let Some(build) = item.local() else {
+            self.status = "Install the build before launching it".to_string();
+            return Ok(());
+        };
+
+        let mut command = launch_command(self.cli_cfg, build, &item.repo, None)?;
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+
+        self.status = format!["Launched {}", item.query().unwrap_or_default()];
+        Ok(())
+    }
+
+    fn toggle_favorite(&mut self) -> Result<(), CommandError> {
+        let Some(idx) = self
+            .table
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .copied()
+        else {
+            return Ok(());
+        };
+        let BuildEntry::Installed(_, build) = &mut self.items[idx].entry else {
+            self.status = "Only installed builds can be favorited".to_string();
+            return Ok(());
+        };
+
+        build.info.is_favorited = !build.info.is_favorited;
+        build
+            .write()
+            .map_err(|e| error_writing(build.folder.clone(), e))?;
+
+        self.status = match build.info.is_favorited {
+            true => "Added to favorites".to_string(),
+            false => "Removed from favorites".to_string(),
+        };
+        Ok(())
+    }
+
+    fn remove_selected(&mut self) -> Result<(), CommandError> {
+        let Some(item) = self.selected() else {
+            return Ok(());
+        };
+        let Some(build) = item.local() else {
+            return Ok(());
+        };
+
+        let query = item.query().unwrap_or_default();
+        match delete_builds(self.cfg, [build]) {
+            Ok(()) => self.status = format!["Removed {}", query],
+            Err(e) => self.status = format!["Failed to remove {}: {}", query, e],
+        }
+        self.reload()
+    }
+
+    fn poll_jobs(&mut self) -> Result<(), CommandError> {
+        let mut finished = false;
+        while let Ok(JobResult { query, result }) = self.job_rx.try_recv() {
+            self.running_jobs -= 1;
+            finished = true;
+            self.status = match result {
+                Ok(()) => format!["Installed {}", query],
+                Err(e) => format!["Failed to install {}: {}", query, e],
+            };
+        }
+
+        if finished {
+            if self.running_jobs == 0 {
+                self.downloads.lock().unwrap().clear();
+            }
+            self.reload()?;
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let downloads: Vec<(String, Download)> = {
+            let mut downloads: Vec<_> = self
+                .downloads
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(url, d)| {
+                    let name = url.rsplit('/').next().unwrap_or(url).to_string();
+                    (name, d.clone())
+                })
+                .collect();
+            downloads.sort_by(|a, b| a.0.cmp(&b.0));
+            downloads
+        };
+
+        let [header_area, table_area, downloads_area, status_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(downloads.len() as u16),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let header = match (self.mode, self.filter.is_empty()) {
+            (Mode::Filter, _) => Line::from(format!["/{}", self.filter]).bold(),
+            (_, false) => Line::from(format!["filter: {}  |  {}", self.filter, HELP]).dim(),
+            (_, true) => Line::from(HELP).dim(),
+        };
+        frame.render_widget(Paragraph::new(header), header_area);
+
+        let rows: Vec<Row> = self
+            .visible
+            .iter()
+            .map(|&idx| self.items[idx].to_row())
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(16),
+                Constraint::Length(32),
+                Constraint::Length(17),
+                Constraint::Min(10),
+            ],
+        )
+        .header(Row::new(["repo", "version", "date", "state"]).bold())
+        .block(Block::bordered().title(format![" blrs - {} builds ", self.visible.len()]))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let gauge_areas =
+            Layout::vertical(downloads.iter().map(|_| Constraint::Length(1))).split(downloads_area);
+        for ((name, d), area) in downloads.iter().zip(gauge_areas.iter()) {
+            let ratio = match (d.finished, d.total) {
+                (true, _) => 1.0,
+                (false, 0) => 0.0,
+                (false, total) => (d.downloaded as f64 / total as f64).min(1.0),
+            };
+            let label = match d.finished {
+                true => format!["{} - extracting", name],
+                false => format!["{} - {:.0}%", name, ratio * 100.0],
+            };
+            frame.render_widget(
+                Gauge::default()
+                    .gauge_style(Style::new().fg(Color::Cyan))
+                    .ratio(ratio)
+                    .label(label),
+                *area,
+            );
+        }
+
+        let status = match self.running_jobs {
+            0 => self.status.clone(),
+            n => format!["[{} running] {}", n, self.status],
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+}
+
+fn terminal_error(e: std::io::Error) -> CommandError {
+    CommandError::IoError(IoErrorOrigin::Terminal, e)
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), CommandError> {
+    app.reload()?;
+
+    loop {
+        app.poll_jobs()?;
+        terminal
+            .draw(|frame| app.draw(frame))
+            .map_err(terminal_error)?;
+
+        if !event::poll(Duration::from_millis(200)).map_err(terminal_error)? {
+            continue;
+        }
+        match event::read().map_err(terminal_error)? {
+            TermEvent::Key(key) if key.kind == KeyEventKind::Press => {
+                match app.handle_key(key.code) {
+                    Ok(true) => {}
+                    Ok(false) => return Ok(()),
+                    // Failed actions are reported without leaving the interface
+                    Err(e) => app.status = e.to_string(),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Opens a full-screen browser for installing, removing, favoriting, and launching builds.
+pub fn run_tui(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    all_builds: bool,
+) -> Result<(), CommandError> {
+    let downloads: Downloads = Arc::default();
+    {
+        let downloads = downloads.clone();
+        set_listener(move |event| track_download(&downloads, event));
+    }
+
+    // Logs and prompts would draw over the interface
+    let log_level = log::max_level();
+    log::set_max_level(LevelFilter::Off);
+    let was_non_interactive = NON_INTERACTIVE.swap(true, Ordering::AcqRel);
+
+    let mut terminal = ratatui::init();
+    let mut app = App::new(cfg, cli_cfg, all_builds, downloads);
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+
+    NON_INTERACTIVE.store(was_non_interactive, Ordering::Release);
+    log::set_max_level(log_level);
+
+    result
+}
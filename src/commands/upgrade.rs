@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use blrs::{
+    build_targets::get_target_setup,
+    repos::{BuildEntry, RepoEntry},
+    search::VersionSearchQuery,
+    BLRSConfig, BasicBuildInfo, LocalBuild,
+};
+use log::{info, warn};
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{CommandError, IoErrorOrigin},
+    repo_cache::read_repos,
+    resolving::{build_query_string, find_matches, foreign_target, installed_builds},
+    tasks::ConfigTask,
+};
+
+use super::{
+    fetcher::{cache_is_stale, fetch, StalePolicy},
+    pull::{pull_builds, FetchPolicy, PullOptions},
+    rm::delete_builds,
+};
+
+/// Builds that belong to the same repo, branch, and `major.minor` version replace each other.
+type Series = (String, u64, u64, String);
+
+fn series(b: &BasicBuildInfo, nick: &str) -> Series {
+    let v = b.version();
+    (nick.to_string(), v.major, v.minor, v.pre.to_string())
+}
+
+/// An installed series and the build that supersedes it.
+struct Upgrade {
+    nick: String,
+    installed: Vec<LocalBuild>,
+    newest: BasicBuildInfo,
+}
+
+/// The builds in the cached build lists that can be installed on this platform, with the nickname of their repo.
+fn available_builds(cfg: &BLRSConfig) -> Result<Vec<(BasicBuildInfo, String)>, CommandError> {
+    let target = get_target_setup().unwrap();
+
+    Ok(read_repos(cfg.repos.clone(), &cfg.paths, false)
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
+        .into_iter()
+        .filter_map(|r| match r {
+            RepoEntry::Registered(repo, entries) => Some((repo.nickname, entries)),
+            _ => None,
+        })
+        .flat_map(|(nick, entries)| {
+            entries.into_iter().filter_map(move |entry| match entry {
+                BuildEntry::NotInstalled(variants)
+                    if !variants.clone().filter_target(target).v.is_empty() =>
+                {
+                    Some((variants.basic, nick.clone()))
+                }
+                _ => None,
+            })
+        })
+        .collect())
+}
+
+/// Pairs every series of installed builds with the newest available build of that series, if it is newer.
+fn find_upgrades(
+    installed: Vec<(LocalBuild, String)>,
+    available: Vec<(BasicBuildInfo, String)>,
+) -> Vec<Upgrade> {
+    let mut newest: HashMap<Series, BasicBuildInfo> = HashMap::new();
+    available.into_iter().for_each(|(b, nick)| {
+        let entry = newest.entry(series(&b, &nick)).or_insert_with(|| b.clone());
+        if b.commit_dt > entry.commit_dt {
+            *entry = b;
+        }
+    });
+
+    let mut groups: HashMap<Series, Vec<LocalBuild>> = HashMap::new();
+    installed.into_iter().for_each(|(b, nick)| {
+        groups
+            .entry(series(&b.info.basic, &nick))
+            .or_default()
+            .push(b);
+    });
+
+    let mut upgrades: Vec<Upgrade> = groups
+        .into_iter()
+        .filter_map(|(key, installed)| {
+            let current = installed.iter().map(|b| b.info.basic.commit_dt).max()?;
+            let newest = newest.remove(&key).filter(|b| b.commit_dt > current)?;
+            Some(Upgrade {
+                nick: key.0,
+                installed,
+                newest,
+            })
+        })
+        .collect();
+    upgrades.sort_by(|a, b| (&a.nick, a.newest.version()).cmp(&(&b.nick, b.newest.version())));
+    upgrades
+}
+
+/// Pulls the newest build of every installed branch and series that has a newer one available.
+pub async fn upgrade(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    queries: Vec<VersionSearchQuery>,
+    remove_old: bool,
+    dry_run: bool,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let mut tasks = vec![];
+    if cache_is_stale(cfg) {
+        info!["Refreshing the build lists before looking for upgrades"];
+        match fetch(
+            cfg,
+            cli_cfg,
+            &cfg.repos,
+            true,
+            true,
+            StalePolicy::default(),
+            false,
+        )
+        .await
+        {
+            Ok((task, _)) => tasks.push(task),
+            Err(e) => warn![
+                "Failed to refresh the build lists, using the cached ones: {}",
+                e
+            ],
+        }
+    }
+
+    let mut installed: Vec<_> = installed_builds(cfg)?
+        .into_iter()
+        // Builds installed for another platform would be replaced by a native one
+        .filter(|(b, _)| foreign_target(&b.info.basic).is_none())
+        .collect();
+    if !queries.is_empty() {
+        installed = queries
+            .iter()
+            .flat_map(|query| find_matches(&installed, query))
+            .cloned()
+            .collect();
+        // Queries can overlap
+        installed.sort_by(|(a, _), (b, _)| a.folder.cmp(&b.folder));
+        installed.dedup_by(|(a, _), (b, _)| a.folder == b.folder);
+    }
+
+    let upgrades = find_upgrades(installed, available_builds(cfg)?);
+    if upgrades.is_empty() {
+        info!["Every installed build is up to date"];
+        return Ok(tasks);
+    }
+
+    println!["{} upgrade(s) available:", upgrades.len()];
+    upgrades.iter().for_each(|u| {
+        let current = u.installed.iter().max_by_key(|b| b.info.basic.commit_dt);
+        println![
+            "  {}  {} -> {}",
+            u.nick,
+            current
+                .map(|b| b.info.basic.ver.to_string())
+                .unwrap_or_default(),
+            ansi_term::Color::Green.paint(u.newest.ver.to_string())
+        ];
+    });
+
+    if dry_run {
+        return Ok(tasks);
+    }
+
+    let queries = upgrades
+        .iter()
+        .map(|u| {
+            let s = build_query_string(&u.newest, &u.nick);
+            VersionSearchQuery::try_from(s.as_str())
+                .map_err(|e| CommandError::CouldNotParseQuery(s, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let opts = PullOptions {
+        fetch: FetchPolicy::Never,
+        ..Default::default()
+    };
+    tasks.extend(pull_builds(cfg, cli_cfg, queries, &opts).await?);
+
+    // Pulls skip builds whose variant prompt was escaped, so only the upgrades that were installed count
+    let now_installed = installed_builds(cfg)?;
+    let (done, skipped): (Vec<&Upgrade>, Vec<&Upgrade>) = upgrades.iter().partition(|u| {
+        now_installed
+            .iter()
+            .any(|(b, nick)| *nick == u.nick && b.info.basic.version() == u.newest.version())
+    });
+    skipped.iter().for_each(|u| {
+        warn![
+            "{} was not installed, keeping the builds it would replace",
+            build_query_string(&u.newest, &u.nick)
+        ]
+    });
+
+    println![
+        "{} {} build(s)",
+        ansi_term::Color::Green.bold().paint("Upgraded"),
+        done.len()
+    ];
+
+    if remove_old {
+        // Favorites are kept, the same as with `prune`
+        let old: Vec<&LocalBuild> = done
+            .iter()
+            .flat_map(|u| u.installed.iter())
+            .filter(|b| !b.info.is_favorited)
+            .collect();
+        if !old.is_empty() {
+            delete_builds(cfg, old.iter().copied())?;
+            println!["Removed {} superseded build(s)", old.len()];
+        }
+    }
+
+    Ok(tasks)
+}
@@ -0,0 +1,138 @@
+use ansi_term::Color;
+use blrs::{
+    build_targets::get_target_setup,
+    fetching::build_repository::BuildRepo,
+    repos::{BuildEntry, RepoEntry, Variants},
+    search::VersionSearchQuery,
+    BLRSConfig, BasicBuildInfo, RemoteBuild,
+};
+use futures::future::join_all;
+use indicatif::HumanBytes;
+use serde::Serialize;
+
+use crate::{
+    cli_config::CliConfig,
+    errs::{CommandError, IoErrorOrigin},
+    repo_auth::client_for,
+    repo_cache::read_repos,
+    resolving::{build_query_string, find_matches, resolve_match},
+};
+
+use super::pull::head_size;
+
+#[derive(Debug, Serialize)]
+struct VariantRow {
+    target: String,
+    extension: String,
+    /// As reported by the host. None if it does not report one.
+    size: Option<u64>,
+    url: String,
+    /// Whether the variant runs on this system, so it is pulled without `--all-platforms`.
+    native: bool,
+}
+
+/// Lists every variant of a remote build, with its file type, download size, and URL.
+/// If the query matches several builds, one is chosen the same way `pull` chooses.
+pub async fn variants(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    query: VersionSearchQuery,
+    json: bool,
+) -> Result<(), CommandError> {
+    let entries: Vec<(Variants<RemoteBuild>, BuildRepo)> =
+        read_repos(cfg.repos.clone(), &cfg.paths, false)
+            .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
+            .into_iter()
+            .filter_map(|r| match r {
+                RepoEntry::Registered(repo, entries) => Some((repo, entries)),
+                _ => None,
+            })
+            .flat_map(|(repo, entries)| {
+                entries.into_iter().filter_map(move |entry| match entry {
+                    BuildEntry::NotInstalled(variants) => Some((variants, repo.clone())),
+                    _ => None,
+                })
+            })
+            .collect();
+
+    let builds: Vec<(BasicBuildInfo, String)> = entries
+        .iter()
+        .map(|(variants, repo)| (variants.basic.clone(), repo.nickname.clone()))
+        .collect();
+    let matches: Vec<_> = find_matches(&builds, &query).into_iter().cloned().collect();
+    if matches.is_empty() {
+        return Err(CommandError::QueryResultEmpty(query.to_string()));
+    }
+    let Some((basic, nick)) = resolve_match(
+        &matches,
+        &format!["Multiple matches for query {query}! select a build"],
+    ) else {
+        return Ok(());
+    };
+    let (variants, repo) = entries
+        .iter()
+        .find(|(variants, repo)| variants.basic == *basic && repo.nickname == *nick)
+        .unwrap();
+
+    let native: Vec<String> = variants
+        .clone()
+        .filter_target(get_target_setup().unwrap())
+        .v
+        .iter()
+        .map(|variant| variant.to_string())
+        .collect();
+
+    let rows: Vec<VariantRow> = join_all(variants.v.iter().map(|variant| async {
+        let url = variant.b.url();
+        let size = match client_for(cfg, &cli_cfg.repo_auth, repo, &url) {
+            Ok(client) => head_size(&client, &url).await,
+            Err(_) => None,
+        };
+        let target = variant.to_string();
+
+        VariantRow {
+            native: native.contains(&target),
+            target,
+            extension: variant.b.file_extension.clone().unwrap_or_default(),
+            size,
+            url: url.to_string(),
+        }
+    }))
+    .await;
+
+    if json {
+        println!["{}", serde_json::to_string_pretty(&rows).unwrap()];
+        return Ok(());
+    }
+
+    println!["{}", Color::Green.paint(build_query_string(basic, nick))];
+    let width = |f: fn(&VariantRow) -> usize| rows.iter().map(f).max().unwrap_or(0);
+    let (target_w, extension_w) = (width(|r| r.target.len()), width(|r| r.extension.len()));
+    for row in &rows {
+        let size = row
+            .size
+            .map(|s| HumanBytes(s).to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let target = format!["{:<target_w$}", row.target];
+        println![
+            "  {} {:<extension_w$} {:>10}  {}",
+            match row.native {
+                true => Color::Cyan.bold().paint(target),
+                false => Color::Cyan.paint(target),
+            },
+            row.extension,
+            size,
+            Color::White.dimmed().paint(&row.url)
+        ];
+    }
+    if native.len() < rows.len() {
+        println![
+            "{}",
+            Color::White
+                .dimmed()
+                .paint("Variants for other systems are only pulled with --all-platforms")
+        ];
+    }
+
+    Ok(())
+}
@@ -1,16 +1,55 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
 
+use ansi_term::Color;
 use blrs::{info::launching::OSLaunchTarget, BLRSConfig, LocalBuild};
-use log::{debug, error, info};
+use futures::{stream, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, error, info, warn};
 
-use crate::errs::{error_reading, CommandError};
+use crate::{
+    cancel,
+    errs::{error_reading, CommandError},
+    events::{emit, json_output, Event},
+    integrity,
+    resolving::walk_layout,
+};
 
-#[inline]
-fn is_dir_or_link_to_dir(p: &Path) -> bool {
-    p.is_dir() || p.read_link().is_ok_and(|p| p.is_dir())
+#[cfg(unix)]
+fn is_executable(p: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    p.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
 }
 
-pub fn verify(cfg: &BLRSConfig, repos: Option<Vec<String>>) -> Result<(), CommandError> {
+#[cfg(not(unix))]
+fn is_executable(p: &Path) -> bool {
+    p.is_file()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    Repaired,
+    Broken,
+}
+
+/// Verifies the installed builds of the given repos, or all of them, probing up to `jobs` builds at once.
+///
+/// Executables are only run if `launch` is set, or to regenerate a build info that cannot be read.
+/// A build whose executable takes longer than `timeout` to report its version is counted as broken.
+pub async fn verify(
+    cfg: &BLRSConfig,
+    repos: Option<Vec<String>>,
+    deep: bool,
+    launch: bool,
+    jobs: usize,
+    timeout: Option<Duration>,
+) -> Result<(), CommandError> {
+    cancel::handle_ctrl_c();
     let mut folders: Vec<PathBuf> = cfg
         .paths
         .library
@@ -32,49 +71,253 @@ pub fn verify(cfg: &BLRSConfig, repos: Option<Vec<String>>) -> Result<(), Comman
 
     debug!["Reading folders: {:?}", folders];
 
+    let mut build_folders: Vec<PathBuf> = vec![];
     for folder in folders {
-        let _: Vec<_> = folder
-            .read_dir().map_err(|e| error_reading(folder, e))?
-            .filter_map(|build_folder| {
-                let build_folder = build_folder.ok()?;
-                let path = build_folder.path();
-                if is_dir_or_link_to_dir(&build_folder.path()){
-
-                    match LocalBuild::read(&path) {
-                        Ok(build) => {
-                            debug!["Successfully read {:?}", build];
-
-                            Some(())
-                        }
-                        Err(e) => {
-                            error!["Failed to read build: {:?}\n Attempting to read the build for more info", e];
-                            let executable = path.join(OSLaunchTarget::try_default().unwrap().exe_name());
-                            match LocalBuild::generate_from_exe(&executable) {
-                                Ok(b) => {
-                                    debug!["{:?}", b];
-                                    info!["Success! Saving build..."];
-                                    let r = b.write();
-                                    info!["{:?}", r];
-
-                                    Some(())
-                                },
-                                Err(e) => {
-                                    println!{"Error: {:?}", e};
-                                    None
-                                },
-                            }
-
-
-
-                        }
-                    }
-                } else {
-                    debug!["Skipping file {:?}", build_folder];
-                    None
+        // Walked as deep as the library layout nests builds
+        folder
+            .read_dir()
+            .map_err(|e| error_reading(folder.clone(), e))?;
+        build_folders.extend(walk_layout(&folder).1);
+    }
+
+    let pb = ProgressBar::new(build_folders.len() as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.green} [{bar:40.cyan/red}] {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("#|-"),
+    );
+    let probing: Mutex<Vec<String>> = Mutex::new(vec![]);
+    let show_probing = |probing: &Vec<String>| pb.set_message(probing.join(", "));
+
+    // Builds already being probed are finished after Ctrl+C, but no new ones are started
+    let outcomes: Vec<Outcome> = stream::iter(build_folders)
+        .take_while(|_| std::future::ready(!cancel::is_cancelled()))
+        .map(|path| {
+            let (pb, probing) = (&pb, &probing);
+            async move {
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                {
+                    let mut probing = probing.lock().unwrap();
+                    probing.push(name.clone());
+                    show_probing(&probing);
                 }
-            })
-            .collect();
+
+                let outcome = probe(path, deep, launch, timeout).await;
+
+                let mut probing = probing.lock().unwrap();
+                probing.retain(|n| *n != name);
+                show_probing(&probing);
+                pb.inc(1);
+                outcome
+            }
+        })
+        .buffer_unordered(jobs.max(1))
+        .collect()
+        .await;
+    pb.finish_and_clear();
+    cancel::check()?;
+
+    let count = |o: Outcome| outcomes.iter().filter(|&&x| x == o).count();
+    let (ok, repaired, broken) = (
+        count(Outcome::Ok),
+        count(Outcome::Repaired),
+        count(Outcome::Broken),
+    );
+
+    if !json_output() {
+        println![
+            "{}, {}, {}",
+            Color::Green.paint(format!["{} ok", ok]),
+            Color::Yellow.paint(format!["{} repaired", repaired]),
+            match broken {
+                0 => Color::White.dimmed().paint("0 broken".to_string()),
+                n => Color::Red.bold().paint(format!["{} broken", n]),
+            }
+        ];
+    }
+
+    match broken {
+        0 => Ok(()),
+        n => Err(CommandError::BrokenBuilds(n)),
+    }
+}
+
+/// Verifies one build on a blocking thread, giving up on it after `timeout`.
+async fn probe(path: PathBuf, deep: bool, launch: bool, timeout: Option<Duration>) -> Outcome {
+    let task = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || verify_build(&path, launch))
+    };
+    let outcome = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, task).await {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                return broken(
+                    &path,
+                    format![
+                        "Blender did not report its version within {}, so it may hang. It is left running",
+                        humantime::format_duration(timeout)
+                    ],
+                )
+            }
+        },
+        None => task.await,
+    }
+    .unwrap_or(Outcome::Broken);
+
+    match outcome {
+        Outcome::Broken => Outcome::Broken,
+        // Hashing a large build takes a while legitimately, so it is not bound by the timeout
+        outcome if deep => tokio::task::spawn_blocking(move || verify_files(&path))
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or(outcome),
+        outcome => outcome,
+    }
+}
+
+fn default_exe(path: &Path) -> PathBuf {
+    path.join(OSLaunchTarget::try_default().unwrap().exe_name())
+}
+
+fn broken(path: &Path, message: String) -> Outcome {
+    error!["{}: {}", path.display(), message];
+    emit(Event::Error {
+        message,
+        path: Some(path),
+    });
+    Outcome::Broken
+}
+
+fn repaired(path: &Path, build: &LocalBuild) -> Outcome {
+    match build.write() {
+        Ok(_) => {
+            info!["Saved the repaired build info of {}", path.display()];
+            emit(Event::BuildVerified {
+                path,
+                repaired: true,
+            });
+            Outcome::Repaired
+        }
+        Err(e) => broken(path, format!["Failed to save the build info: {}", e]),
+    }
+}
+
+/// Checks that a build's info can be read, that its executable exists, and with `launch`, that the
+/// executable reports the recorded version. Regenerates the info from the executable if possible.
+fn verify_build(path: &Path, launch: bool) -> Outcome {
+    let mut build = match LocalBuild::read(path) {
+        Ok(build) => {
+            debug!["Successfully read {:?}", build];
+            build
+        }
+        Err(e) => {
+            error![
+                "Failed to read build: {:?}\n Attempting to read the build for more info",
+                e
+            ];
+            return match LocalBuild::generate_from_exe(&default_exe(path)) {
+                Ok(b) => {
+                    debug!["{:?}", b];
+                    repaired(path, &b)
+                }
+                Err(e) => broken(path, format!["Failed to generate the build info: {:?}", e]),
+            };
+        }
+    };
+
+    let exe = match &build.info.custom_exe {
+        Some(exe) => path.join(exe),
+        None => default_exe(path),
+    };
+    if !is_executable(&exe) {
+        return broken(
+            path,
+            format!["{} is missing or not executable", exe.display()],
+        );
+    }
+
+    if launch {
+        let reported = match LocalBuild::generate_from_exe(&exe) {
+            Ok(b) => b.info.basic,
+            Err(e) => return broken(path, format!["Failed to run {}: {:?}", exe.display(), e]),
+        };
+
+        let recorded = build.info.basic.version();
+        let actual = reported.version();
+        if (recorded.major, recorded.minor, recorded.patch)
+            != (actual.major, actual.minor, actual.patch)
+        {
+            warn![
+                "{} records version {} but the executable reports {}",
+                path.display(),
+                recorded,
+                actual
+            ];
+            build.info.basic = reported;
+            return repaired(path, &build);
+        }
+    }
+
+    emit(Event::BuildVerified {
+        path,
+        repaired: false,
+    });
+    Outcome::Ok
+}
+
+/// Compares the files of a build to the manifest recorded when it was installed.
+/// Returns `Some` if the build is broken.
+fn verify_files(path: &Path) -> Option<Outcome> {
+    let manifest = match integrity::read(path) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            warn![
+                "{} has no file manifest, so its files cannot be checked. Pull it with --record-manifest, \
+                 or set `record_manifests` to record one for every build",
+                path.display()
+            ];
+            return None;
+        }
+        Err(e) => {
+            return Some(broken(
+                path,
+                format!["Failed to read the file manifest: {}", e],
+            ))
+        }
+    };
+
+    let differences = match integrity::compare(path, &manifest) {
+        Ok(d) => d,
+        Err(e) => return Some(broken(path, format!["Failed to hash the files: {}", e])),
+    };
+    for file in &differences.extra {
+        warn!["{} extra: {}", path.display(), file];
+    }
+    if differences.is_intact() {
+        debug!["{} matches its file manifest", path.display()];
+        return None;
+    }
+
+    for (kind, files) in [
+        ("Modified", &differences.modified),
+        ("Missing", &differences.missing),
+    ] {
+        for file in files {
+            info!["{} {}: {}", path.display(), kind.to_lowercase(), file];
+        }
     }
 
-    Ok(())
+    Some(broken(
+        path,
+        format![
+            "{} modified, {} missing files",
+            differences.modified.len(),
+            differences.missing.len(),
+        ],
+    ))
 }
@@ -0,0 +1,194 @@
+use std::{collections::HashSet, time::Duration};
+
+use blrs::{
+    info::build_info::BasicBuildInfo,
+    repos::{BuildEntry, RepoEntry},
+    search::VersionSearchQuery,
+    BLRSConfig,
+};
+use log::{info, warn};
+
+use crate::{
+    cancel,
+    cli_config::CliConfig,
+    errs::{CommandError, IoErrorOrigin},
+    events::{emit, Event},
+    notify::notify,
+    repo_cache::read_repos,
+    resolving::{build_query_string, find_matches},
+    tasks::ConfigTask,
+};
+
+use super::{
+    fetcher::{fetch, StalePolicy},
+    pull::{pull_builds, FetchPolicy, PullOptions},
+};
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// How long to wait between checks.
+    pub interval: Duration,
+    /// New builds matching any of these are pulled as soon as they are found.
+    pub auto_pull: Vec<VersionSearchQuery>,
+    /// Check a single time and exit, for running from cron or a systemd timer.
+    pub once: bool,
+}
+
+/// Every build known to the cached build lists, installed or not, with the nickname of its repo.
+fn known_builds(cfg: &BLRSConfig) -> Result<Vec<(BasicBuildInfo, String)>, CommandError> {
+    Ok(read_repos(cfg.repos.clone(), &cfg.paths, false)
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
+        .into_iter()
+        .filter_map(|r| match r {
+            RepoEntry::Registered(repo, entries) => Some((repo.nickname, entries)),
+            _ => None,
+        })
+        .flat_map(|(nick, entries)| {
+            entries.into_iter().filter_map(move |entry| match entry {
+                BuildEntry::NotInstalled(variants) => Some((variants.basic, nick.clone())),
+                BuildEntry::Installed(_, build) => Some((build.info.basic, nick.clone())),
+                BuildEntry::Errored(_, _) => None,
+            })
+        })
+        .collect())
+}
+
+/// Fetches the repos, reports the builds that were not listed before, and pulls the ones matching `opts.auto_pull`.
+///
+/// Repos without cached builds are only seeded by their first fetch, as every build they list would be new.
+async fn check(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    opts: &WatchOptions,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    let known = known_builds(cfg)?;
+    let seeded: HashSet<&str> = known.iter().map(|(_, nick)| nick.as_str()).collect();
+    let before: HashSet<String> = known
+        .iter()
+        .map(|(b, nick)| build_query_string(b, nick))
+        .collect();
+
+    let mut tasks = vec![
+        cancel::or_cancelled(async {
+            fetch(
+                cfg,
+                cli_cfg,
+                &cfg.repos,
+                true,
+                true,
+                StalePolicy::default(),
+                false,
+            )
+            .await
+            .map(|(task, _)| task)
+            .map_err(|e| CommandError::IoError(IoErrorOrigin::Fetching, e))
+        })
+        .await?,
+    ];
+
+    let after = known_builds(cfg)?;
+    let mut unseeded: Vec<&str> = after
+        .iter()
+        .map(|(_, nick)| nick.as_str())
+        .filter(|nick| !seeded.contains(nick))
+        .collect();
+    unseeded.dedup();
+    for nick in unseeded {
+        info![
+            "{} had no cached builds, so its builds are only reported from the next check on",
+            nick
+        ];
+    }
+
+    let mut new: Vec<_> = after
+        .iter()
+        .filter(|(b, nick)| {
+            seeded.contains(nick.as_str()) && !before.contains(&build_query_string(b, nick))
+        })
+        .cloned()
+        .collect();
+    new.sort_by_key(|(b, _)| b.commit_dt);
+
+    if new.is_empty() {
+        info!["No new builds"];
+        return Ok(tasks);
+    }
+
+    info!["{} new build(s):", new.len()];
+    for (b, nick) in &new {
+        println!["{}", build_query_string(b, nick)];
+        emit(Event::BuildAvailable {
+            repo: nick,
+            version: b.version().to_string(),
+        });
+    }
+
+    notify(
+        &format!["{} new Blender build(s) available", new.len()],
+        &new.iter()
+            .map(|(b, nick)| build_query_string(b, nick))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+
+    let mut to_pull: Vec<String> = opts
+        .auto_pull
+        .iter()
+        .flat_map(|q| find_matches(&new, q))
+        .map(|(b, nick)| build_query_string(b, nick))
+        .collect();
+    to_pull.sort();
+    to_pull.dedup();
+
+    if !to_pull.is_empty() {
+        let queries = to_pull
+            .iter()
+            .map(|s| {
+                VersionSearchQuery::try_from(s.as_str())
+                    .map_err(|e| CommandError::CouldNotParseQuery(s.clone(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let pull_opts = PullOptions {
+            // The lists were just refreshed
+            fetch: FetchPolicy::Never,
+            ..Default::default()
+        };
+        tasks.extend(pull_builds(cfg, cli_cfg, queries, &pull_opts).await?);
+    }
+
+    Ok(tasks)
+}
+
+/// Checks for new builds every `opts.interval` until interrupted.
+pub async fn watch(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    opts: WatchOptions,
+) -> Result<Vec<ConfigTask>, CommandError> {
+    if opts.once {
+        return check(cfg, cli_cfg, &opts).await;
+    }
+
+    cancel::handle_ctrl_c();
+    let mut tasks = vec![];
+    loop {
+        match check(cfg, cli_cfg, &opts).await {
+            Ok(t) => tasks.extend(t),
+            Err(CommandError::Cancelled) => break,
+            // A failed check should not end the watch, the next one may succeed
+            Err(e) => warn!["Checking for new builds failed: {}", e],
+        }
+
+        info![
+            "Checking again in {}",
+            humantime::format_duration(opts.interval)
+        ];
+        tokio::select! {
+            _ = tokio::time::sleep(opts.interval) => {}
+            _ = cancel::cancelled() => break,
+        }
+    }
+
+    Ok(tasks)
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use blrs::{search::VersionSearchQuery, BLRSConfig};
+
+use crate::{
+    cli_config::CliConfig,
+    errs::CommandError,
+    run::{launch_command, select_build},
+};
+
+/// Prints the executable of the installed build matching the query, or its folder if `folder` is set.
+pub fn which(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    query: VersionSearchQuery,
+    folder: bool,
+) -> Result<(), CommandError> {
+    let (build, nick) = select_build(cfg, &query, false)?;
+
+    let path = match folder {
+        true => build.folder.clone(),
+        // The launch command already accounts for a custom executable
+        false => PathBuf::from(launch_command(cli_cfg, &build, &nick, None)?.get_program()),
+    };
+    println!["{}", path.display()];
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    thread::JoinHandle,
+};
+
+use ansi_term::Color;
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::errs::exit_codes;
+
+/// How many of the last lines of Blender's stderr are searched for crash signatures.
+const TAIL_LINES: usize = 200;
+
+/// Why a launched build stopped, as far as its exit status and stderr tell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Crash {
+    /// Like `EXCEPTION_ACCESS_VIOLATION` or `SIGSEGV`.
+    pub reason: String,
+    /// The address the crash happened at, if Blender printed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Where Blender writes the backtrace of the crash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crash_log: Option<PathBuf>,
+}
+
+/// Copies Blender's stderr to ours line by line, keeping the last lines for diagnosis.
+fn tee(stderr: impl Read) -> Vec<String> {
+    let mut tail = VecDeque::with_capacity(TAIL_LINES);
+    let mut out = std::io::stderr();
+    for line in BufReader::new(stderr).lines() {
+        let Ok(line) = line else { break };
+        let _ = writeln![out, "{}", line];
+        if tail.len() == TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+    tail.into()
+}
+
+/// The last lines of a log file Blender's output was written to.
+fn tail_of(path: &Path) -> Vec<String> {
+    let data = std::fs::read_to_string(path).unwrap_or_default();
+    let lines: Vec<&str> = data.lines().collect();
+    lines[lines.len().saturating_sub(TAIL_LINES)..]
+        .iter()
+        .map(|l| l.to_string())
+        .collect()
+}
+
+#[cfg(unix)]
+fn fatal_signal(status: &ExitStatus) -> Option<String> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let name = match status.signal()? {
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGILL => "SIGILL",
+        libc::SIGFPE => "SIGFPE",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Windows reports unhandled exceptions as the exit code of the process.
+#[cfg(windows)]
+fn fatal_signal(status: &ExitStatus) -> Option<String> {
+    let name = match status.code()? as u32 {
+        0xC000_0005 => "EXCEPTION_ACCESS_VIOLATION",
+        0xC000_001D => "EXCEPTION_ILLEGAL_INSTRUCTION",
+        0xC000_0094 => "EXCEPTION_INT_DIVIDE_BY_ZERO",
+        0xC000_00FD => "EXCEPTION_STACK_OVERFLOW",
+        0xC000_0409 => "STATUS_STACK_BUFFER_OVERRUN",
+        0x8000_0003 => "EXCEPTION_BREAKPOINT",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Looks for the crash signatures Blender's crash handler prints, like
+/// `Error   : EXCEPTION_ACCESS_VIOLATION`, `Address : 0x00007FF6...`, and `Writing: /tmp/blender.crash.txt`.
+fn diagnose(status: &ExitStatus, lines: &[String], blendfile: Option<&Path>) -> Option<Crash> {
+    let exception =
+        Regex::new(r"\b(EXCEPTION_[A-Z_]+|Segmentation fault|SIGSEGV|SIGABRT)").unwrap();
+    let address = Regex::new(r"(?i)\baddress\s*:\s*(0x[0-9a-f]+)").unwrap();
+    let writing = Regex::new(r"Writing: (.+\.crash\.txt)\s*$").unwrap();
+
+    let find = |re: &Regex| {
+        lines
+            .iter()
+            .rev()
+            .find_map(|line| re.captures(line).map(|c| c[1].to_string()))
+    };
+    let signature = find(&exception);
+    let crash_log = find(&writing).map(PathBuf::from);
+
+    let reason = signature
+        .or_else(|| fatal_signal(status))
+        .or_else(|| crash_log.as_ref().map(|_| "crash".to_string()))?;
+
+    Some(Crash {
+        reason,
+        address: find(&address),
+        crash_log: crash_log.or_else(|| Some(expected_crash_log(blendfile))),
+    })
+}
+
+/// Where Blender writes its crash log: the temporary folder, named after the open file.
+fn expected_crash_log(blendfile: Option<&Path>) -> PathBuf {
+    let name = blendfile
+        .and_then(|f| f.file_stem())
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "blender".to_string());
+    std::env::temp_dir().join(format!["{}.crash.txt", name])
+}
+
+fn report(status: &ExitStatus, crash: Option<&Crash>) {
+    let Some(crash) = crash else {
+        info!["Blender exited with {}", status];
+        return;
+    };
+
+    warn![
+        "{} ({})",
+        Color::Red.bold().paint("Blender crashed"),
+        crash.reason
+    ];
+    if let Some(address) = &crash.address {
+        warn!["The crash happened at {}", address];
+    }
+    match &crash.crash_log {
+        Some(log) if log.exists() => warn!["Blender's crash log: {}", log.display()],
+        Some(log) => warn![
+            "Blender's crash log should be at {}, but it was not written",
+            log.display()
+        ],
+        None => {}
+    }
+}
+
+/// Runs the command and waits for it, then reports how Blender exited. Its stderr is searched for
+/// crash signatures, either while it is passed through, or from `log_file` after Blender exited.
+pub fn wait(
+    command: &mut Command,
+    log_file: Option<&Path>,
+    blendfile: Option<&Path>,
+) -> std::io::Result<(usize, Option<Crash>)> {
+    if log_file.is_none() {
+        command.stderr(Stdio::piped());
+    }
+    let mut child = command.spawn()?;
+    let reader: Option<JoinHandle<Vec<String>>> = child
+        .stderr
+        .take()
+        .map(|stderr| std::thread::spawn(move || tee(stderr)));
+    let status = child.wait()?;
+
+    let lines = match (reader, log_file) {
+        (Some(reader), _) => reader.join().unwrap_or_default(),
+        (None, Some(path)) => tail_of(path),
+        (None, None) => vec![],
+    };
+    let crash = diagnose(&status, &lines, blendfile);
+    report(&status, crash.as_ref());
+
+    Ok((exit_code(&status, crash.is_some()) as usize, crash))
+}
+
+/// The code Blender exited with. Like shells report it, a build killed by a signal exits with 128 + the signal,
+/// and a crash is never reported as success, even when Blender's crash handler exited cleanly.
+fn exit_code(status: &ExitStatus, crashed: bool) -> i32 {
+    #[cfg(unix)]
+    let signal = {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal().map(|s| 128 + s)
+    };
+    #[cfg(not(unix))]
+    let signal = None;
+
+    match status.code().or(signal) {
+        Some(0) | None if crashed => exit_codes::FAILURE,
+        code => code.unwrap_or(exit_codes::FAILURE),
+    }
+}
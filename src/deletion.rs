@@ -0,0 +1,72 @@
+use std::{path::Path, sync::OnceLock};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errs::{CommandError, IoErrorOrigin},
+    resolving::confirm,
+};
+
+/// What happens to builds and files that blrs removes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeletionPolicy {
+    /// Send them to the trash, so they can be restored.
+    #[default]
+    Trash,
+    /// Delete them permanently.
+    Delete,
+    /// Ask once per command whether to use the trash.
+    Ask,
+}
+
+/// Deletion settings of blrs-cli.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeletionConfig {
+    pub policy: DeletionPolicy,
+}
+
+/// Set from `--no-trash` or `cli.deletion.policy` before any command runs.
+static POLICY: OnceLock<DeletionPolicy> = OnceLock::new();
+/// The answer to the `ask` policy, so a command removing several things only asks once.
+static USE_TRASH: OnceLock<bool> = OnceLock::new();
+
+pub fn set_policy(policy: DeletionPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+/// Whether removed things go to the trash, asking first if the policy says so.
+pub fn uses_trash() -> bool {
+    match POLICY.get().copied().unwrap_or_default() {
+        DeletionPolicy::Trash => true,
+        DeletionPolicy::Delete => false,
+        DeletionPolicy::Ask => *USE_TRASH.get_or_init(|| {
+            confirm(
+                "Send removed files to the trash? Otherwise they are deleted permanently",
+                true,
+            )
+        }),
+    }
+}
+
+/// Trashes or deletes a file or folder according to the deletion policy.
+/// Returns whether it was sent to the trash.
+pub fn remove(path: &Path) -> Result<bool, CommandError> {
+    if uses_trash() {
+        info!["Trashing {}", path.display()];
+        return trash::delete(path)
+            .map(|_| true)
+            .map_err(|e| CommandError::TrashError(path.to_path_buf(), e));
+    }
+
+    info!["Deleting {}", path.display()];
+    let result = match path.symlink_metadata() {
+        Ok(m) if m.is_dir() => std::fs::remove_dir_all(path),
+        _ => std::fs::remove_file(path),
+    };
+    result
+        .map(|_| false)
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::DeletingObject(path.to_path_buf()), e))
+}
@@ -0,0 +1,46 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use blrs::config::PROJECT_DIRS;
+
+/// A file next to the executable that turns on portable mode, as if `--portable` was given.
+const PORTABLE_MARKER: &str = "portable.marker";
+
+/// The folder holding everything in portable mode, which is the folder of the executable.
+/// Returns None unless `--portable` was given or the marker file exists.
+pub fn portable_root(forced: bool) -> Option<PathBuf> {
+    let root = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    (forced || root.join(PORTABLE_MARKER).exists()).then_some(root)
+}
+
+/// Set from `--config-dir` before any config is read.
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Set from `--download-dir` before any command runs.
+static DOWNLOAD_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_dirs(config_dir: Option<PathBuf>, download_dir: Option<PathBuf>) {
+    if let Some(dir) = config_dir {
+        let _ = CONFIG_DIR.set(dir);
+    }
+    if let Some(dir) = download_dir {
+        let _ = DOWNLOAD_DIR.set(dir);
+    }
+}
+
+/// The folder holding `config.toml` and `cli.toml`.
+pub fn config_dir() -> &'static Path {
+    CONFIG_DIR
+        .get()
+        .map(PathBuf::as_path)
+        .unwrap_or_else(|| PROJECT_DIRS.config_local_dir())
+}
+
+/// The folder archives are downloaded and cached in, shared by every repo.
+pub fn archive_dir() -> PathBuf {
+    DOWNLOAD_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| PROJECT_DIRS.cache_dir().join("archives"))
+}
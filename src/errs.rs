@@ -1,7 +1,10 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use blrs::{info::launching::ArgGenerationError, search::FromError};
+use blrs::{info::launching::ArgGenerationError, search::FromError, BLRSConfig};
+use chrono::{DateTime, Utc};
+use indicatif::HumanBytes;
 use reqwest::StatusCode;
+use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Debug)]
@@ -10,6 +13,7 @@ pub enum IoErrorOrigin {
     Fetching,
     ReadingRepos,
     CommandExecution,
+    Terminal,
     RenamingObject(PathBuf, PathBuf),
     ReadingObject(PathBuf),
     WritingObject(PathBuf),
@@ -37,8 +41,12 @@ pub enum CommandError {
     InvalidInput,
     #[error("No matches for Query(s) {0:?}")]
     QueryResultEmpty(String),
+    #[error("{0} has several variants matching {1}: {2}. Pick one with --filename-glob")]
+    AmbiguousVariant(String, String, String),
     #[error("No query has been given but is required")]
     MissingQuery,
+    #[error("{0}, but prompting is disabled. Refine the query or remove --non-interactive")]
+    PromptRequired(String),
     #[error("Insufficient time has passed since the last fetch. It is unlikely that new builds will be available, and to conserve requests these will be skipped.\nWait for {remaining}s")]
     FetchingTooFast { remaining: i64 },
     #[error("Error making a request: {0:?}")]
@@ -56,6 +64,105 @@ pub enum CommandError {
     IoError(IoErrorOrigin, std::io::Error),
     #[error("Broken archive {0:?}:  {1:?}")]
     BrokenArchive(PathBuf, &'static str),
+    #[error("Unknown config key {0:?}, see `blrs config list` for the available keys")]
+    UnknownConfigKey(String),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("No repo is registered as {0:?}. The registered repos are: {1}")]
+    UnknownRepo(String, String),
+    #[error("{0:?} is not the folder of an installed build in the library")]
+    NotABuildFolder(PathBuf),
+    #[error("No alias is named {0:?}, see `blrs alias list`")]
+    UnknownAlias(String),
+    #[error("{0:?} is not a linked addon, see `blrs addons list`")]
+    UnknownAddon(PathBuf),
+    #[error("No launch profile is named {0:?}. The profiles in `cli.profiles` are: {1}")]
+    UnknownProfile(String, String),
+    #[error(
+        "Not enough free space in {path:?}: about {} is needed, but only {} is free. Use --force to pull anyway",
+        HumanBytes(*needed),
+        HumanBytes(*available)
+    )]
+    NotEnoughSpace {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+    #[error("{0} build(s) could not be verified or repaired")]
+    BrokenBuilds(usize),
+    #[error("{0} build(s) failed to render the benchmark")]
+    FailedBenchmarks(usize),
+    #[error("{0} check(s) failed, see the suggested fixes above")]
+    Unhealthy(usize),
+    #[error("Credential store error: {0}")]
+    CredentialStore(String),
+    #[error("{failed} of {total} build(s) failed to pull")]
+    FailedPulls { failed: usize, total: usize },
+    #[error("The checksum of {name} does not match. Expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Could not update blrs: {0}")]
+    SelfUpdate(String),
+    #[error("Timed out: {0}")]
+    TimedOut(String),
+    #[error(
+        "GitHub's API rate limit is used up until {}. Authenticate with `blrs auth login` for a higher limit, or pass --wait-for-ratelimit",
+        .0.with_timezone(&chrono::Local).format("%H:%M:%S")
+    )]
+    RateLimited(DateTime<Utc>),
+    #[error("The cached build list of {0} at {1:?} is corrupt: {2}. Run `blrs fetch --repo {0}` to download it again")]
+    CorruptCache(String, PathBuf, String),
+    #[error("Could not resolve {host}: {detail}. Check the repo's URL with `blrs repos list`, and your network and DNS settings")]
+    DnsFailure { host: String, detail: String },
+    #[error("The secure connection to {host} failed: {detail}. Its certificate may be invalid or expired, the system clock may be wrong, or a proxy may be intercepting HTTPS")]
+    TlsFailure { host: String, detail: String },
+    #[error("Could not connect to {host}: {detail}. The server may be down, or blocked by a firewall or proxy")]
+    ConnectFailure { host: String, detail: String },
+    #[error("{host} kept answering with {status}. The server is having problems, try again later")]
+    ServerError { host: String, status: StatusCode },
+    #[error("Blender failed to render: {0}")]
+    RenderFailed(String, i32),
+    #[error("The {hook} hook {command:?} failed with {status}")]
+    HookFailed {
+        hook: &'static str,
+        command: String,
+        status: String,
+    },
+}
+
+/// The exit codes of blrs, so wrapper tools can tell failures apart without parsing messages.
+pub mod exit_codes {
+    /// Anything not covered below, including IO errors.
+    pub const FAILURE: i32 = 1;
+    /// The command line or the config is invalid.
+    pub const USAGE: i32 = 2;
+    /// No build matches a query, or choosing between matches needed a prompt.
+    pub const RESOLUTION: i32 = 3;
+    /// A request failed or the server returned an error.
+    pub const NETWORK: i32 = 4;
+    /// A downloaded archive could not be extracted.
+    pub const EXTRACTION: i32 = 5;
+    /// Installed builds failed verification, or their files no longer match their checksums.
+    pub const CHECKSUM: i32 = 6;
+    /// A request stalled for longer than `--timeout`, or the command ran past `--deadline`.
+    pub const TIMED_OUT: i32 = 124;
+    /// The user cancelled the command.
+    pub const CANCELLED: i32 = 130;
+}
+
+/// An error as written to stderr with `--output json`.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport<'a> {
+    pub kind: &'static str,
+    pub message: String,
+    pub paths: Vec<&'a Path>,
+    pub exit_code: i32,
+    /// The error code the OS gave for a failed IO operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_error: Option<i32>,
 }
 
 impl CommandError {
@@ -65,22 +172,129 @@ impl CommandError {
             | CommandError::MissingQuery
             | CommandError::NotEnoughInput
             | CommandError::InvalidInput
-            | CommandError::QueryResultEmpty(_)
-            | CommandError::FetchingTooFast { remaining: _ } => 2,
+            | CommandError::UnknownConfigKey(_)
+            | CommandError::InvalidConfig(_)
+            | CommandError::UnknownRepo(_, _)
+            | CommandError::UnknownAlias(_)
+            | CommandError::UnknownAddon(_)
+            | CommandError::NotABuildFolder(_)
+            | CommandError::UnknownProfile(_, _)
+            | CommandError::FetchingTooFast { remaining: _ } => exit_codes::USAGE,
+            CommandError::QueryResultEmpty(_)
+            | CommandError::PromptRequired(_)
+            | CommandError::AmbiguousVariant(_, _, _) => exit_codes::RESOLUTION,
             CommandError::ReturnCode(_)
-            | CommandError::UnsupportedFileFormat(_)
-            | CommandError::CouldNotGenerateParams(_)
-            | CommandError::BrokenArchive(_, _)
-            | CommandError::ReqwestError(_) => 1,
-            CommandError::IoError(_, error) => error.raw_os_error().unwrap_or(1),
-            CommandError::TrashError(_, error) => match error {
-                trash::Error::Os {
-                    code,
-                    description: _,
-                } => *code,
-                _ => 1,
+            | CommandError::ReqwestError(_)
+            | CommandError::RateLimited(_)
+            | CommandError::DnsFailure { .. }
+            | CommandError::TlsFailure { .. }
+            | CommandError::ConnectFailure { .. }
+            | CommandError::ServerError { .. } => exit_codes::NETWORK,
+            CommandError::UnsupportedFileFormat(_) | CommandError::BrokenArchive(_, _) => {
+                exit_codes::EXTRACTION
+            }
+            CommandError::BrokenBuilds(_) | CommandError::ChecksumMismatch { .. } => {
+                exit_codes::CHECKSUM
+            }
+            CommandError::CouldNotGenerateParams(_)
+            | CommandError::FailedPulls { .. }
+            | CommandError::CredentialStore(_)
+            | CommandError::SelfUpdate(_)
+            | CommandError::NotEnoughSpace { .. }
+            | CommandError::Unhealthy(_)
+            | CommandError::FailedBenchmarks(_)
+            | CommandError::HookFailed { .. }
+            | CommandError::CorruptCache(_, _, _)
+            | CommandError::IoError(_, _)
+            | CommandError::TrashError(_, _) => exit_codes::FAILURE,
+            // Scripts driving renders expect Blender's own exit code
+            CommandError::RenderFailed(_, code) => *code,
+            CommandError::TimedOut(_) => exit_codes::TIMED_OUT,
+            CommandError::Cancelled => exit_codes::CANCELLED,
+        }
+    }
+
+    /// A stable name for the kind of error, for tools that handle some errors differently.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CommandError::CouldNotParseQuery(_, _) => "could_not_parse_query",
+            CommandError::CouldNotGenerateParams(_) => "could_not_generate_params",
+            CommandError::NotEnoughInput => "not_enough_input",
+            CommandError::InvalidInput => "invalid_input",
+            CommandError::QueryResultEmpty(_) => "query_result_empty",
+            CommandError::AmbiguousVariant(_, _, _) => "ambiguous_variant",
+            CommandError::MissingQuery => "missing_query",
+            CommandError::PromptRequired(_) => "prompt_required",
+            CommandError::FetchingTooFast { .. } => "fetching_too_fast",
+            CommandError::ReqwestError(_) => "request",
+            CommandError::ReturnCode(_) => "return_code",
+            CommandError::UnsupportedFileFormat(_) => "unsupported_file_format",
+            CommandError::Cancelled => "cancelled",
+            CommandError::TrashError(_, _) => "trash",
+            CommandError::IoError(_, _) => "io",
+            CommandError::BrokenArchive(_, _) => "broken_archive",
+            CommandError::UnknownConfigKey(_) => "unknown_config_key",
+            CommandError::InvalidConfig(_) => "invalid_config",
+            CommandError::UnknownRepo(_, _) => "unknown_repo",
+            CommandError::UnknownAlias(_) => "unknown_alias",
+            CommandError::UnknownAddon(_) => "unknown_addon",
+            CommandError::NotABuildFolder(_) => "not_a_build_folder",
+            CommandError::UnknownProfile(_, _) => "unknown_profile",
+            CommandError::BrokenBuilds(_) => "broken_builds",
+            CommandError::Unhealthy(_) => "unhealthy",
+            CommandError::FailedBenchmarks(_) => "failed_benchmarks",
+            CommandError::CredentialStore(_) => "credential_store",
+            CommandError::FailedPulls { .. } => "failed_pulls",
+            CommandError::ChecksumMismatch { .. } => "checksum_mismatch",
+            CommandError::SelfUpdate(_) => "self_update",
+            CommandError::TimedOut(_) => "timed_out",
+            CommandError::RateLimited(_) => "rate_limited",
+            CommandError::NotEnoughSpace { .. } => "not_enough_space",
+            CommandError::HookFailed { .. } => "hook_failed",
+            CommandError::CorruptCache(_, _, _) => "corrupt_cache",
+            CommandError::RenderFailed(_, _) => "render_failed",
+            CommandError::DnsFailure { .. } => "dns_failure",
+            CommandError::TlsFailure { .. } => "tls_failure",
+            CommandError::ConnectFailure { .. } => "connect_failure",
+            CommandError::ServerError { .. } => "server_error",
+        }
+    }
+
+    /// The files and folders the error is about.
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            CommandError::IoError(origin, _) => match origin {
+                IoErrorOrigin::RenamingObject(from, to) => vec![from, to],
+                IoErrorOrigin::ReadingObject(p)
+                | IoErrorOrigin::WritingObject(p)
+                | IoErrorOrigin::DeletingObject(p) => vec![p],
+                _ => vec![],
             },
-            CommandError::Cancelled => 130,
+            CommandError::TrashError(p, _)
+            | CommandError::BrokenArchive(p, _)
+            | CommandError::CorruptCache(_, p, _)
+            | CommandError::NotABuildFolder(p)
+            | CommandError::NotEnoughSpace { path: p, .. } => vec![p],
+            _ => vec![],
+        }
+    }
+
+    pub fn report(&self) -> ErrorReport<'_> {
+        ErrorReport {
+            kind: self.kind(),
+            message: self.to_string(),
+            paths: self.paths(),
+            exit_code: self.exit_code(),
+            os_error: self.os_error(),
+        }
+    }
+
+    /// The OS error code behind an IO or trash error, which the exit code does not carry.
+    pub fn os_error(&self) -> Option<i32> {
+        match self {
+            CommandError::IoError(_, error) => error.raw_os_error(),
+            CommandError::TrashError(_, trash::Error::Os { code, .. }) => Some(*code),
+            _ => None,
         }
     }
 }
@@ -91,6 +305,22 @@ pub fn error_reading(p: PathBuf, e: std::io::Error) -> CommandError {
 pub fn error_writing(p: PathBuf, e: std::io::Error) -> CommandError {
     CommandError::IoError(IoErrorOrigin::WritingObject(p), e)
 }
+/// Tells requests that stalled past `--timeout` apart from other request errors.
+pub fn request_error(e: reqwest::Error) -> CommandError {
+    match e.is_timeout() {
+        true => CommandError::TimedOut(match e.url() {
+            Some(url) => format!["{} stopped responding", url],
+            None => "a request stopped responding".to_string(),
+        }),
+        false => CommandError::ReqwestError(e),
+    }
+}
 pub fn error_renaming(p: PathBuf, p2: PathBuf, e: std::io::Error) -> CommandError {
     CommandError::IoError(IoErrorOrigin::RenamingObject(p, p2), e)
 }
+
+/// An error for a name that matches no registered repo, listing the names that would.
+pub fn unknown_repo(cfg: &BLRSConfig, name: &str) -> CommandError {
+    let known: Vec<&str> = cfg.repos.iter().map(|r| r.nickname.as_str()).collect();
+    CommandError::UnknownRepo(name.to_string(), known.join(", "))
+}
@@ -0,0 +1,161 @@
+use std::{
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Set by `--output json`. Commands report what they do as JSON lines on stdout.
+pub static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Acquire)
+}
+
+type Listener = Box<dyn Fn(&Event) + Send + Sync>;
+
+/// Receives every emitted event, regardless of the output format. Used by the TUI to show progress.
+static LISTENER: OnceLock<Listener> = OnceLock::new();
+
+/// Installs the event listener. Only the first listener of the process is kept.
+pub fn set_listener(f: impl Fn(&Event) + Send + Sync + 'static) {
+    let _ = LISTENER.set(Box::new(f));
+}
+
+/// Set by `--progress-ipc`. Receives every event as a JSON line, for GUI frontends.
+static IPC: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Connects to the socket or pipe at `path` that events are written to.
+///
+/// On Unix this is a listening socket, or a FIFO. On Windows it is a named pipe like `\\.\pipe\blrs`.
+pub fn connect_ipc(path: &Path) -> Result<(), std::io::Error> {
+    #[cfg(unix)]
+    let sink: Box<dyn Write + Send> = match std::os::unix::net::UnixStream::connect(path) {
+        Ok(stream) => Box::new(stream),
+        // Not a socket, so it may be a FIFO
+        Err(_) => Box::new(std::fs::OpenOptions::new().write(true).open(path)?),
+    };
+    #[cfg(not(unix))]
+    let sink: Box<dyn Write + Send> = Box::new(std::fs::OpenOptions::new().write(true).open(path)?);
+
+    let _ = IPC.set(Mutex::new(sink));
+    Ok(())
+}
+
+fn is_observed() -> bool {
+    json_output() || LISTENER.get().is_some() || IPC.get().is_some()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum OutputFormat {
+    /// Human readable logs and progress bars.
+    #[default]
+    Text,
+    /// One JSON event per line on stdout. Logs and progress bars are still written to stderr.
+    Json,
+}
+
+/// Something that happened during a command, meant for wrapper tools to parse.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    FetchStarted {
+        repo: &'a str,
+        url: &'a str,
+    },
+    FetchFinished {
+        repo: &'a str,
+        path: &'a Path,
+    },
+    BuildSelected {
+        repo: &'a str,
+        version: String,
+    },
+    DownloadStarted {
+        url: &'a str,
+        path: &'a Path,
+    },
+    DownloadProgress {
+        url: &'a str,
+        downloaded: u64,
+        total: u64,
+        percent: u64,
+    },
+    DownloadFinished {
+        url: &'a str,
+        path: &'a Path,
+    },
+    ExtractionFinished {
+        archive: &'a Path,
+        destination: &'a Path,
+    },
+    BuildInstalled {
+        version: String,
+        path: &'a Path,
+    },
+    BuildAvailable {
+        repo: &'a str,
+        version: String,
+    },
+    BuildRemoved {
+        path: &'a Path,
+        trashed: bool,
+    },
+    BuildVerified {
+        path: &'a Path,
+        repaired: bool,
+    },
+    Error {
+        message: String,
+        path: Option<&'a Path>,
+    },
+}
+
+/// Writes the event to stdout if JSON output is enabled and to the IPC sink if there is one,
+/// and passes it to the listener if there is one.
+pub fn emit(event: Event) {
+    if json_output() {
+        println!["{}", serde_json::to_string(&event).unwrap()];
+    }
+    if let Some(listener) = LISTENER.get() {
+        listener(&event);
+    }
+    if let Some(ipc) = IPC.get() {
+        let mut sink = ipc.lock().unwrap();
+        // A frontend that went away should not interrupt the command
+        let _ =
+            writeln![sink, "{}", serde_json::to_string(&event).unwrap()].and_then(|_| sink.flush());
+    }
+}
+
+/// Reports download progress every 10%, so the output is not flooded with events.
+#[derive(Debug, Default)]
+pub struct ProgressMilestones {
+    last_percent: AtomicU64,
+}
+
+impl ProgressMilestones {
+    const STEP: u64 = 10;
+
+    pub fn update(&self, url: &str, downloaded: u64, total: u64) {
+        if !is_observed() || total == 0 {
+            return;
+        }
+
+        let percent = (downloaded * 100 / total).min(100) / Self::STEP * Self::STEP;
+        // Only the caller that moves the milestone forward reports it
+        if self.last_percent.fetch_max(percent, Ordering::AcqRel) < percent {
+            emit(Event::DownloadProgress {
+                url,
+                downloaded,
+                total,
+                percent,
+            });
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::{path::Path, process, sync::OnceLock};
+
+use blrs::LocalBuild;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errs::{CommandError, IoErrorOrigin},
+    resolving::{registered_nickname, repo_folder_name},
+};
+
+/// Commands run when something happens to a build, like `post-pull = ["~/bin/install-addons"]`.
+///
+/// Each command is run through the shell in the build's folder, with the build described by
+/// `BLRS_BUILD_PATH`, `BLRS_VERSION`, and `BLRS_REPO`, the nickname of its repo. `post-run` also gets `BLRS_EXIT_CODE`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Hooks {
+    /// After a build is pulled or installed.
+    pub post_pull: Vec<String>,
+    /// Before a build is launched. A failing command cancels the launch.
+    pub pre_run: Vec<String>,
+    /// After a launched build exits. Not run for detached launches.
+    pub post_run: Vec<String>,
+    /// After a build is removed. Its folder is gone by then, so these run in its repo's folder.
+    pub post_rm: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PostPull,
+    PreRun,
+    PostRun,
+    PostRm,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PostPull => "post-pull",
+            Hook::PreRun => "pre-run",
+            Hook::PostRun => "post-run",
+            Hook::PostRm => "post-rm",
+        }
+    }
+
+    fn commands(self, hooks: &Hooks) -> &[String] {
+        match self {
+            Hook::PostPull => &hooks.post_pull,
+            Hook::PreRun => &hooks.pre_run,
+            Hook::PostRun => &hooks.post_run,
+            Hook::PostRm => &hooks.post_rm,
+        }
+    }
+}
+
+/// Set from `cli.hooks` before any command runs.
+static HOOKS: OnceLock<Hooks> = OnceLock::new();
+
+pub fn set_hooks(hooks: Hooks) {
+    let _ = HOOKS.set(hooks);
+}
+
+fn shell(command: &str) -> process::Command {
+    #[cfg(windows)]
+    {
+        let mut shell = process::Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    }
+    #[cfg(not(windows))]
+    {
+        let mut shell = process::Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+/// Runs the commands of a hook for a build in `repo`, stopping at the first one that fails.
+pub fn run(
+    hook: Hook,
+    build: &LocalBuild,
+    repo: &str,
+    extra_env: &[(&str, String)],
+) -> Result<(), CommandError> {
+    let Some(commands) = HOOKS.get().map(|hooks| hook.commands(hooks)) else {
+        return Ok(());
+    };
+
+    let folder = &build.folder;
+    let cwd = match folder.is_dir() {
+        true => folder.as_path(),
+        false => folder.parent().unwrap_or(Path::new(".")),
+    };
+    for command in commands {
+        info!["Running {} hook {:?}", hook.name(), command];
+        let status = shell(command)
+            .current_dir(cwd)
+            .env("BLRS_HOOK", hook.name())
+            .env("BLRS_BUILD_PATH", folder)
+            .env("BLRS_VERSION", build.info.basic.ver.to_string())
+            .env("BLRS_REPO", repo)
+            .envs(extra_env.iter().map(|(k, v)| (k, v)))
+            .status()
+            .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))?;
+
+        if !status.success() {
+            return Err(CommandError::HookFailed {
+                hook: hook.name(),
+                command: command.clone(),
+                status: status.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a hook after the build already changed, so a failure only warns.
+pub fn run_after(hook: Hook, build: &LocalBuild, repo: &str, extra_env: &[(&str, String)]) {
+    if let Err(e) = run(hook, build, repo, extra_env) {
+        warn!["{}", e];
+    }
+}
+
+/// The repo an installed build belongs to, going by the repo folder it is in. Registered repos are named by
+/// their nickname, like the repo of a launched build is.
+pub fn repo_of(build: &LocalBuild) -> String {
+    let folder = repo_folder_name(&build.folder)
+        .or_else(|| {
+            build
+                .folder
+                .parent()
+                .and_then(Path::file_name)
+                .map(|n| n.to_string_lossy().to_string())
+        })
+        .unwrap_or_default();
+    registered_nickname(&folder).unwrap_or(folder)
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::errs::{error_reading, error_writing, CommandError};
+
+/// Where a build's manifest is stored, relative to its folder.
+pub const MANIFEST_FILE: &str = ".blrs-manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub size: u64,
+    /// The SHA-256 of the file's contents, or of the target of a symlink.
+    pub sha256: String,
+}
+
+/// The files of a build as they were right after it was extracted, keyed by their path relative to the build folder.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileManifest {
+    pub files: BTreeMap<String, FileRecord>,
+}
+
+/// How the contents of a build folder differ from its manifest.
+#[derive(Debug, Clone, Default)]
+pub struct Differences {
+    pub modified: Vec<String>,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl Differences {
+    /// Whether every file of the manifest is unchanged. Extra files do not count,
+    /// as Blender writes some next to itself while running, like `__pycache__` folders.
+    pub fn is_intact(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// The SHA-256 of a file's contents, as lowercase hex.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!["{:x}", hasher.finalize()])
+}
+
+fn record(path: &Path) -> io::Result<FileRecord> {
+    let metadata = path.symlink_metadata()?;
+    let sha256 = match metadata.is_symlink() {
+        true => format![
+            "{:x}",
+            Sha256::digest(path.read_link()?.to_string_lossy().as_bytes())
+        ],
+        false => hash_file(path)?,
+    };
+
+    Ok(FileRecord {
+        size: metadata.len(),
+        sha256,
+    })
+}
+
+/// Lists every file under the folder, relative to it. Symlinks are listed rather than followed.
+///
+/// Hidden files at the top of the folder are skipped, as that is where blrs keeps its own metadata.
+fn list_files(folder: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut files = vec![];
+    let mut dirs = vec![folder.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for item in dir.read_dir()? {
+            let item = item?;
+            let path = item.path();
+            if dir == folder && item.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            match item.file_type()?.is_dir() {
+                true => dirs.push(path),
+                false => {
+                    let relative = path
+                        .strip_prefix(folder)
+                        .unwrap()
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    files.push((relative, path));
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Hashes every file in a build folder.
+pub fn generate(folder: &Path) -> Result<FileManifest, CommandError> {
+    let files = list_files(folder)
+        .map_err(|e| error_reading(folder.to_path_buf(), e))?
+        .into_iter()
+        .map(|(relative, path)| {
+            record(&path)
+                .map(|r| (relative, r))
+                .map_err(|e| error_reading(path, e))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(FileManifest { files })
+}
+
+/// Hashes every file in a build folder and saves the result inside it.
+pub fn write(folder: &Path) -> Result<FileManifest, CommandError> {
+    let manifest = generate(folder)?;
+    let path = folder.join(MANIFEST_FILE);
+    let file = File::create(&path).map_err(|e| error_writing(path.clone(), e))?;
+    serde_json::to_writer(file, &manifest).map_err(|e| error_writing(path, e.into()))?;
+
+    Ok(manifest)
+}
+
+/// Reads the manifest saved in a build folder, if it has one.
+pub fn read(folder: &Path) -> Result<Option<FileManifest>, CommandError> {
+    let path = folder.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = File::open(&path).map_err(|e| error_reading(path.clone(), e))?;
+    serde_json::from_reader(file)
+        .map(Some)
+        .map_err(|e| error_reading(path, e.into()))
+}
+
+/// Compares two manifests. Files only in `old` are missing, files only in `new` are extra.
+pub fn diff(old: &FileManifest, new: &FileManifest) -> Differences {
+    let mut differences = Differences::default();
+
+    for (name, recorded) in &old.files {
+        match new.files.get(name) {
+            Some(actual) if actual != recorded => differences.modified.push(name.clone()),
+            Some(_) => {}
+            None => differences.missing.push(name.clone()),
+        }
+    }
+    differences.extra = new
+        .files
+        .keys()
+        .filter(|name| !old.files.contains_key(*name))
+        .cloned()
+        .collect();
+
+    differences
+}
+
+/// Re-hashes a build folder and compares it to its manifest.
+pub fn compare(folder: &Path, manifest: &FileManifest) -> Result<Differences, CommandError> {
+    Ok(diff(manifest, &generate(folder)?))
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use blrs::config::PROJECT_DIRS;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::crash_report::Crash;
+
+/// How many launches are remembered. Older ones are dropped.
+const MAX_LAUNCHES: usize = 100;
+
+/// A build that was launched by `run`, and the file it opened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Launch {
+    /// The query that finds the launched build again.
+    pub build: String,
+    pub file: Option<PathBuf>,
+    pub time: DateTime<Utc>,
+    /// How the build crashed, if it was launched with `--wait-and-report` and did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crash: Option<Crash>,
+}
+
+/// The most recent launches, oldest first. This is state rather than configuration,
+/// so it lives in the data folder instead of next to the config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchHistory {
+    pub launches: Vec<Launch>,
+}
+
+impl LaunchHistory {
+    pub fn path() -> PathBuf {
+        PROJECT_DIRS.data_local_dir().join("history.json")
+    }
+
+    /// Reads the history from disk. A missing file is treated as an empty history.
+    pub fn load() -> Result<Self, std::io::Error> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&data).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!["Failed to read {}: {}", path.display(), e],
+            )
+        })
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        std::fs::create_dir_all(PROJECT_DIRS.data_local_dir())?;
+        std::fs::write(Self::path(), serde_json::to_string(self).unwrap())
+    }
+
+    pub fn last(&self) -> Option<&Launch> {
+        self.launches.last()
+    }
+
+    /// Adds a launch to the history on disk.
+    pub fn record(launch: Launch) -> Result<(), std::io::Error> {
+        let mut history = Self::load()?;
+        history.launches.push(launch);
+        let excess = history.launches.len().saturating_sub(MAX_LAUNCHES);
+        history.launches.drain(..excess);
+        history.save()
+    }
+}
@@ -0,0 +1,169 @@
+use std::io::Write;
+
+use ansi_term::Color;
+use blrs::config::{BLRSConfig, BLRSPaths};
+use clap::CommandFactory;
+
+use cli_args::Cli;
+use cli_config::CliConfig;
+use commands::Command;
+use events::{emit, json_output, Event};
+use log::{debug, error};
+
+pub mod api;
+mod archive_cache;
+mod cancel;
+mod cli_args;
+mod cli_config;
+mod commands;
+mod crash_report;
+mod deletion;
+mod dirs;
+mod errs;
+mod events;
+mod hooks;
+mod integrity;
+mod launch_history;
+mod network;
+mod notify;
+mod pin;
+mod prompt;
+mod provenance;
+mod repo_auth;
+mod repo_cache;
+mod repo_formatting;
+mod resolving;
+mod run;
+mod target;
+mod tasks;
+#[cfg(test)]
+mod test_support;
+
+/// Runs blrs as a command line program, with the arguments the process was started with.
+pub fn run_cli() -> Result<(), std::io::Error> {
+    #[cfg(target_os = "windows")]
+    let _ = ansi_term::enable_ansi_support();
+
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
+
+    let mut cli = Cli::parse_args();
+
+    cli.apply_portable();
+    // The defaults of `cli.toml` can change the other paths, so only the config folder is known before reading it
+    dirs::set_dirs(cli.config_dir.clone(), None);
+    let mut cli_cfg = CliConfig::load()?;
+    cli.apply_config_defaults(&cli_cfg)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+    dirs::set_dirs(None, cli.download_dir.clone());
+    let cfgfigment =
+        BLRSConfig::default_figment(cli.config_dir.is_some().then(commands::config_path));
+    let mut cfg: BLRSConfig = cfgfigment.extract().unwrap();
+    let configured_paths = cfg.paths.clone();
+    cli.apply_overrides(&mut cfg);
+    let overridden_paths = cfg.paths.clone();
+    cli.apply_global_flags(&cli_cfg);
+    resolving::set_library(&cfg);
+    if let Some(path) = &cli.progress_ipc {
+        events::connect_ipc(path).map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!["Failed to connect to {}: {}", path.display(), e],
+            )
+        })?;
+    }
+
+    debug!("{cli:?}");
+    debug!("{cfg:?}");
+    debug!("{cli_cfg:?}");
+
+    match (&cli.build_or_file, &cli.commands) {
+        (None, None) => {
+            return Cli::command().print_help();
+        }
+        // TODO: If possible, implement this using the Clap derive system
+        (Some(_), Some(_)) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Specifying a file and a subcommand at the same time is not supported",
+            ));
+        }
+        (Some(query), None) => {
+            cli.commands = Some(Command::Run {
+                query: Some(query.to_string()),
+                command: None,
+                output: Default::default(),
+            });
+        }
+        (None, Some(_)) => {}
+    }
+
+    let r = cli.eval(&cfg, &cli_cfg);
+
+    let tasks = match r {
+        Ok(b) => b,
+        Err(e) => {
+            emit(Event::Error {
+                message: e.to_string(),
+                path: None,
+            });
+            match json_output() {
+                true => eprintln!["{}", serde_json::to_string(&e.report()).unwrap()],
+                false => error![
+                    "\n{}\n    {}",
+                    Color::Red.bold().paint("COMMAND EXECUTION ERROR:"),
+                    e
+                ],
+            }
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    let tasks_exist = !tasks.is_empty();
+    tasks
+        .into_iter()
+        .for_each(|task| task.eval(&mut cfg, &mut cli_cfg));
+
+    if tasks_exist {
+        restore_paths(&mut cfg, &configured_paths, &overridden_paths);
+        save_config(&cfg, &cli_cfg)?;
+    }
+
+    Ok(())
+}
+
+/// Undoes the path overrides of the command line before the config is saved, so paths given for one run
+/// are not written to it. Paths a command changed itself are kept.
+fn restore_paths(cfg: &mut BLRSConfig, configured: &BLRSPaths, overridden: &BLRSPaths) {
+    if cfg.paths.library == overridden.library {
+        cfg.paths.library = configured.library.clone();
+    }
+    if cfg.paths.remote_repos == overridden.remote_repos {
+        cfg.paths.remote_repos = configured.remote_repos.clone();
+    }
+}
+
+/// Saves the blrs config and the cli config to their files.
+fn save_config(cfg: &BLRSConfig, cli_cfg: &CliConfig) -> Result<(), std::io::Error> {
+    let config_file = commands::config_path();
+
+    std::fs::create_dir_all(dirs::config_dir()).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!["Failed to save config data: {:?}", e],
+        )
+    })?;
+
+    let mut file = std::fs::File::create(config_file)?;
+    let data = match toml::to_string_pretty(cfg) {
+        Ok(d) => d,
+        Err(e) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!["Failed to save config data: {:?}", e],
+            ))
+        }
+    };
+    file.write_all(data.as_bytes())?;
+
+    cli_cfg.save()
+}
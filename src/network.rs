@@ -0,0 +1,91 @@
+use std::{
+    future::Future,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use blrs::BLRSConfig;
+use reqwest::{ClientBuilder, Proxy};
+use serde::{Deserialize, Serialize};
+
+use crate::{cancel, errs::CommandError};
+
+/// Network settings of blrs-cli.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// The proxy every request goes through, e.g. `http://proxy:8080` or `socks5://localhost:1080`.
+    /// Unset means the proxy from the environment (`HTTPS_PROXY` etc.) is used, if there is one.
+    pub proxy: Option<String>,
+}
+
+/// Decides which proxy requests go through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProxySetting {
+    /// Use the proxy from the environment, if any.
+    #[default]
+    System,
+    /// Connect directly, even if the environment sets a proxy.
+    Disabled,
+    Url(String),
+}
+
+/// Set once at startup from `--proxy`, `--no-proxy`, and the config.
+static PROXY: OnceLock<ProxySetting> = OnceLock::new();
+
+pub fn set_proxy(setting: ProxySetting) {
+    let _ = PROXY.set(setting);
+}
+
+/// Set once at startup from `--timeout`. Requests fail if connecting or reading stalls for longer.
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+/// Set once at startup from `--deadline`. Network commands give up at this point.
+static DEADLINE: OnceLock<(Instant, Duration)> = OnceLock::new();
+
+pub fn set_timeouts(timeout: Option<Duration>, deadline: Option<Duration>) {
+    if let Some(timeout) = timeout {
+        let _ = TIMEOUT.set(timeout);
+    }
+    if let Some(deadline) = deadline {
+        let _ = DEADLINE.set((Instant::now() + deadline, deadline));
+    }
+}
+
+/// Runs a network command, failing with `TimedOut` if it is still running at the deadline.
+pub async fn within_deadline<T>(
+    f: impl Future<Output = Result<T, CommandError>>,
+) -> Result<T, CommandError> {
+    match DEADLINE.get() {
+        Some((at, deadline)) => tokio::time::timeout_at((*at).into(), f)
+            .await
+            .unwrap_or_else(|_| {
+                // Extraction runs on blocking threads that outlive the dropped future
+                cancel::expire();
+                Err(CommandError::TimedOut(format![
+                    "the command did not finish within {}",
+                    humantime::format_duration(*deadline)
+                ]))
+            }),
+        None => f.await,
+    }
+}
+
+/// A client builder for the library's config, with the proxy and timeout settings applied.
+pub fn client_builder(cfg: &BLRSConfig, github: bool) -> Result<ClientBuilder, CommandError> {
+    let mut builder = cfg.client_builder(github);
+    if let Some(timeout) = TIMEOUT.get() {
+        // Not a limit on the whole request, so large downloads are only cut off when they stall
+        builder = builder.connect_timeout(*timeout).read_timeout(*timeout);
+    }
+
+    match PROXY.get().cloned().unwrap_or_default() {
+        ProxySetting::System => Ok(builder),
+        ProxySetting::Disabled => Ok(builder.no_proxy()),
+        ProxySetting::Url(url) => {
+            let proxy = Proxy::all(&url).map_err(|e| {
+                CommandError::InvalidConfig(format!["invalid proxy {:?}: {}", url, e])
+            })?;
+            Ok(builder.proxy(proxy))
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::debug;
+
+/// Set by `--notify` or the `notify` setting. Long running commands report when they finish as desktop notifications.
+pub static NOTIFY: AtomicBool = AtomicBool::new(false);
+
+pub fn notifications_enabled() -> bool {
+    NOTIFY.load(Ordering::Acquire)
+}
+
+/// Shows a desktop notification if notifications are enabled.
+///
+/// Failing to show one is not worth interrupting a command over, so errors are only logged.
+pub fn notify(summary: &str, body: &str) {
+    if !notifications_enabled() {
+        return;
+    }
+
+    if let Err(e) = notify_rust::Notification::new()
+        .appname("blrs")
+        .summary(summary)
+        .body(body)
+        .icon("blender")
+        .show()
+    {
+        debug!["Failed to show a notification: {:?}", e];
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::errs::{error_reading, CommandError};
+
+/// The name of the file that pins a folder and everything below it to a build.
+pub const PIN_FILE: &str = ".blrs.toml";
+
+/// The build a project uses, written by `blrs pin`. Works like `rust-toolchain.toml`:
+/// launches from inside the project use the pinned build instead of guessing one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pin {
+    /// The version matcher of the pinned build.
+    pub build: String,
+}
+
+impl Pin {
+    pub fn read(path: &Path) -> Result<Self, std::io::Error> {
+        let data = std::fs::read_to_string(path)?;
+        toml::from_str(&data).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!["Failed to read {}: {}", path.display(), e],
+            )
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), std::io::Error> {
+        std::fs::write(path, toml::to_string_pretty(self).unwrap())
+    }
+
+    /// Finds the nearest pin file in `start` or any of its parents.
+    pub fn find(start: &Path) -> Option<PathBuf> {
+        let start = std::path::absolute(start).ok()?;
+        start
+            .ancestors()
+            .map(|dir| dir.join(PIN_FILE))
+            .find(|path| path.is_file())
+    }
+}
+
+/// The build pinned for `start`, if any of its folders has a pin file.
+pub fn pinned_build(start: &Path) -> Result<Option<String>, CommandError> {
+    let Some(path) = Pin::find(start) else {
+        return Ok(None);
+    };
+
+    let pin = Pin::read(&path).map_err(|e| error_reading(path.clone(), e))?;
+    info!["Using {} as pinned by {}", pin.build, path.display()];
+    Ok(Some(pin.build))
+}
@@ -0,0 +1,57 @@
+use std::sync::OnceLock;
+
+use log::debug;
+
+/// Answers the questions commands ask when a choice is ambiguous. The terminal prompts are used
+/// unless another prompter is installed, like one given to `api::Session::with_prompter`.
+///
+/// Prompts are only shown when blrs is interactive, see `resolving::is_interactive`.
+pub trait Prompter: Send + Sync {
+    /// Asks a yes/no question. `None` means it was skipped, and the default is used.
+    fn confirm(&self, prompt: &str, default: bool) -> Option<bool>;
+    /// Picks one of `choices`, or `None` to cancel. `cursor` is the index of the suggested choice.
+    fn select(&self, prompt: &str, choices: Vec<String>, cursor: usize) -> Option<String>;
+    /// Picks any number of `choices`, or `None` to cancel.
+    fn multi_select(&self, prompt: &str, choices: Vec<String>) -> Option<Vec<String>>;
+}
+
+/// Prompts on the terminal with inquire.
+struct Terminal;
+
+impl Prompter for Terminal {
+    fn confirm(&self, prompt: &str, default: bool) -> Option<bool> {
+        inquire::Confirm::new(prompt)
+            .with_default(default)
+            .prompt_skippable()
+            .ok()
+            .flatten()
+    }
+
+    fn select(&self, prompt: &str, choices: Vec<String>, cursor: usize) -> Option<String> {
+        inquire::Select::new(prompt, choices)
+            .with_starting_cursor(cursor)
+            .prompt()
+            .ok()
+    }
+
+    fn multi_select(&self, prompt: &str, choices: Vec<String>) -> Option<Vec<String>> {
+        inquire::MultiSelect::new(prompt, choices)
+            .prompt()
+            .inspect_err(|e| debug!["The prompt was not answered: {}", e])
+            .ok()
+    }
+}
+
+static PROMPTER: OnceLock<Box<dyn Prompter>> = OnceLock::new();
+
+/// Installs the prompter. Only the first prompter of the process is kept.
+pub fn set_prompter(prompter: impl Prompter + 'static) {
+    let _ = PROMPTER.set(Box::new(prompter));
+}
+
+pub fn prompter() -> &'static dyn Prompter {
+    match PROMPTER.get() {
+        Some(prompter) => prompter.as_ref(),
+        None => &Terminal,
+    }
+}
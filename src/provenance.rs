@@ -0,0 +1,42 @@
+use std::{fs::File, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::errs::{error_reading, error_writing, CommandError};
+
+/// Where a build's provenance is stored, relative to its folder.
+pub const PROVENANCE_FILE: &str = ".blrs-provenance.json";
+
+/// Where a pulled build came from. Kept next to the build info, as the library's format has no room for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The id of the repo the build was listed in.
+    pub repo: String,
+    /// The URL the repo listed for the build. Mirrors are not recorded.
+    pub url: String,
+    /// The file name of the downloaded archive.
+    pub archive: String,
+    /// The SHA-256 of the archive. Missing for builds that were extracted while downloading.
+    pub sha256: Option<String>,
+    pub installed_at: DateTime<Utc>,
+}
+
+pub fn write(folder: &Path, provenance: &Provenance) -> Result<(), CommandError> {
+    let path = folder.join(PROVENANCE_FILE);
+    let file = File::create(&path).map_err(|e| error_writing(path.clone(), e))?;
+    serde_json::to_writer_pretty(file, provenance).map_err(|e| error_writing(path, e.into()))
+}
+
+/// Reads the provenance saved in a build folder. Builds installed before it was recorded have none.
+pub fn read(folder: &Path) -> Result<Option<Provenance>, CommandError> {
+    let path = folder.join(PROVENANCE_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = File::open(&path).map_err(|e| error_reading(path.clone(), e))?;
+    serde_json::from_reader(file)
+        .map(Some)
+        .map_err(|e| error_reading(path, e.into()))
+}
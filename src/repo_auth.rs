@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use base64::Engine;
+use blrs::{fetching::build_repository::BuildRepo, BLRSConfig};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    Client, Url,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{errs::CommandError, network::client_builder};
+
+/// Where a secret is read from. Secrets from the environment are only read when a request needs them,
+/// so they never end up in the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Secret {
+    /// Written in the config as-is.
+    Plain(String),
+    /// The name of an environment variable holding the secret.
+    Env(String),
+    /// The account of an entry in the OS credential store, saved with `blrs auth login`.
+    Keyring(String),
+}
+
+impl Secret {
+    pub fn resolve(&self) -> Result<String, CommandError> {
+        match self {
+            Secret::Plain(s) => Ok(s.clone()),
+            Secret::Env(var) => std::env::var(var).map_err(|_| {
+                CommandError::InvalidConfig(format!["the environment variable {} is not set", var])
+            }),
+            Secret::Keyring(account) => read_from_keyring(account),
+        }
+    }
+}
+
+/// The service name blrs' entries are stored under in the OS credential store.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "blrs";
+
+#[cfg(feature = "keyring")]
+fn keyring_entry(account: &str) -> Result<keyring::Entry, CommandError> {
+    keyring::Entry::new(KEYRING_SERVICE, account)
+        .map_err(|e| CommandError::CredentialStore(e.to_string()))
+}
+
+#[cfg(feature = "keyring")]
+fn read_from_keyring(account: &str) -> Result<String, CommandError> {
+    keyring_entry(account)?
+        .get_password()
+        .map_err(|e| CommandError::CredentialStore(e.to_string()))
+}
+
+#[cfg(feature = "keyring")]
+pub fn save_to_keyring(account: &str, secret: &str) -> Result<(), CommandError> {
+    keyring_entry(account)?
+        .set_password(secret)
+        .map_err(|e| CommandError::CredentialStore(e.to_string()))
+}
+
+/// Removes the entry, returning whether there was one. An entry that does not exist is not an error.
+#[cfg(feature = "keyring")]
+pub fn delete_from_keyring(account: &str) -> Result<bool, CommandError> {
+    match keyring_entry(account)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(keyring::Error::NoStorageAccess(e)) => Err(CommandError::CredentialStore(format![
+            "no credential store is available: {}",
+            e
+        ])),
+        Err(e) => Err(CommandError::CredentialStore(e.to_string())),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn read_from_keyring(_account: &str) -> Result<String, CommandError> {
+    Err(CommandError::CredentialStore(
+        "blrs was built without the keyring feature".to_string(),
+    ))
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn save_to_keyring(account: &str, _secret: &str) -> Result<(), CommandError> {
+    read_from_keyring(account).map(|_| ())
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn delete_from_keyring(account: &str) -> Result<bool, CommandError> {
+    read_from_keyring(account).map(|_| false)
+}
+
+/// The credentials sent to a repo's host when fetching its build list, and when downloading builds that are on the same host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RepoAuth {
+    /// Sent as `Authorization: Bearer <token>`.
+    Bearer { token: Secret },
+    /// Sent as HTTP basic auth.
+    Basic { user: String, password: Secret },
+}
+
+impl RepoAuth {
+    pub fn header(&self) -> Result<HeaderValue, CommandError> {
+        let value = match self {
+            RepoAuth::Bearer { token } => format!["Bearer {}", token.resolve()?],
+            RepoAuth::Basic { user, password } => {
+                let encoded = base64::engine::general_purpose::STANDARD.encode(format![
+                    "{}:{}",
+                    user,
+                    password.resolve()?
+                ]);
+                format!["Basic {}", encoded]
+            }
+        };
+
+        let mut header = HeaderValue::from_str(&value).map_err(|_| {
+            CommandError::InvalidConfig("credentials contain invalid characters".to_string())
+        })?;
+        header.set_sensitive(true);
+        Ok(header)
+    }
+}
+
+/// Whether `url` is on the repo's host. Only requests to it get the repo's credentials.
+fn is_repo_host(repo: &BuildRepo, url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    // Index repos are fetched from the URL as it was given, the others from the one blrs makes of it
+    Url::parse(&repo.url)
+        .ok()
+        .into_iter()
+        .chain([repo.url()])
+        .any(|repo_url| {
+            repo_url.host_str() == Some(host)
+                && repo_url.port_or_known_default() == url.port_or_known_default()
+        })
+}
+
+/// Builds a client for requesting `url`. The repo's credentials are attached if it has any and `url` is on the repo's host,
+/// so they are not sent to the CDNs and mirrors builds are downloaded from. Redirects to other hosts drop them too.
+pub fn client_for(
+    cfg: &BLRSConfig,
+    auth: &HashMap<String, RepoAuth>,
+    repo: &BuildRepo,
+    url: &Url,
+) -> Result<Client, CommandError> {
+    let mut builder = client_builder(
+        cfg,
+        url.domain().is_some_and(|h| h.contains("api.github.com")),
+    )?;
+
+    if let Some(auth) = auth.get(&repo.repo_id).filter(|_| is_repo_host(repo, url)) {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, auth.header()?);
+        builder = builder.default_headers(headers);
+    }
+
+    builder.build().map_err(CommandError::ReqwestError)
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use blrs::{
+    config::BLRSPaths,
+    fetching::build_repository::BuildRepo,
+    repos::{BuildEntry, RepoEntry, Variants},
+    LocalBuild, RemoteBuild,
+};
+use chrono::{DateTime, Utc};
+use log::debug;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    repo_formatting::SortFormat,
+    resolving::{layout_depth, walk_layout},
+    target,
+};
+
+/// The file the parsed build lists are kept in, next to the cached build lists.
+pub const CACHE_FILE: &str = "parsed-repos.msgpack";
+
+/// What a repo's parsed builds were read from. They are parsed again once any of it changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    /// The modification time and size of the cached build list.
+    listing: Option<(SystemTime, u64)>,
+    /// The modification time of the repo's library folder, which changes when builds are installed or removed.
+    library: Option<SystemTime>,
+}
+
+impl Stamp {
+    fn of(paths: &BLRSPaths, repo: &BuildRepo) -> Self {
+        let listing = paths
+            .remote_repos
+            .join(repo.repo_id.clone() + ".json")
+            .metadata()
+            .ok()
+            .and_then(|m| Some((m.modified().ok()?, m.len())));
+        let library = paths
+            .path_to_repo(repo)
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok();
+
+        Self { listing, library }
+    }
+}
+
+/// The builds of a repo that are not installed, as they were parsed from its cached build list.
+#[derive(Debug, Serialize, Deserialize)]
+struct ParsedRepo {
+    stamp: Stamp,
+    builds: Vec<Variants<RemoteBuild>>,
+}
+
+/// Which of the builds that are not installed are read out of the cache. The others are dropped as it is read,
+/// before anything filters, sorts, or renders them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Window {
+    /// Only the builds committed at or after this.
+    pub since: Option<DateTime<Utc>>,
+    /// Only the last this many builds in this order.
+    pub limit: Option<(usize, SortFormat)>,
+    /// Whether builds without a variant for the target count towards the limit.
+    pub all_targets: bool,
+}
+
+impl Window {
+    fn apply(&self, mut builds: Vec<Variants<RemoteBuild>>) -> Vec<Variants<RemoteBuild>> {
+        if let Some(since) = self.since {
+            builds.retain(|b| b.basic.commit_dt >= since);
+        }
+        let Some((limit, sort)) = self.limit else {
+            return builds;
+        };
+
+        // Stable, so builds that sort the same keep the order a full listing would show them in
+        builds.sort_by(|a, b| sort.cmp_builds(&a.basic, &b.basic));
+        let mut kept = Vec::with_capacity(limit);
+        while kept.len() < limit {
+            let Some(build) = builds.pop() else {
+                break;
+            };
+            let build = match self.all_targets {
+                true => build,
+                false => target::filter_variants(build),
+            };
+            if !build.v.is_empty() {
+                kept.push(build);
+            }
+        }
+        kept.reverse();
+
+        kept
+    }
+}
+
+fn cache_path(paths: &BLRSPaths) -> PathBuf {
+    paths.remote_repos.join(CACHE_FILE)
+}
+
+/// The parsed repos by their id. A missing or unreadable cache is empty, and is written again.
+fn load(path: &Path) -> HashMap<String, ParsedRepo> {
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| {
+            rmp_serde::from_slice(&data)
+                .inspect_err(|e| {
+                    debug![
+                        "Ignoring the unreadable repo cache {}: {}",
+                        path.display(),
+                        e
+                    ]
+                })
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn save(path: &Path, parsed: &HashMap<String, ParsedRepo>) {
+    let written = rmp_serde::to_vec_named(parsed)
+        .map_err(std::io::Error::other)
+        .and_then(|data| std::fs::write(path, data));
+    if let Err(e) = written {
+        debug!["Failed to save the repo cache {}: {}", path.display(), e];
+    }
+}
+
+/// Parses the builds of a repo that are not installed. Its installed builds are read separately.
+fn parse(paths: &BLRSPaths, repo: &BuildRepo) -> Result<Vec<Variants<RemoteBuild>>, RepoEntry> {
+    let entries = blrs::repos::read_repos(vec![repo.clone()], paths, false)
+        .map_err(|e| RepoEntry::Error(repo.nickname.clone(), e))?;
+
+    for entry in entries {
+        match entry {
+            RepoEntry::Registered(r, builds) if r.repo_id == repo.repo_id => {
+                return Ok(builds
+                    .into_iter()
+                    .filter_map(|b| match b {
+                        BuildEntry::NotInstalled(variants) => Some(variants),
+                        _ => None,
+                    })
+                    .collect())
+            }
+            RepoEntry::Error(name, e) if name == repo.nickname || name == repo.repo_id => {
+                return Err(RepoEntry::Error(name, e))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(vec![])
+}
+
+/// Adds the builds a nested library layout put deeper than blrs looks, in place of the folders they are nested in.
+fn read_nested(entries: &mut [RepoEntry], paths: &BLRSPaths) {
+    if layout_depth() == 1 {
+        return;
+    }
+
+    for entry in entries {
+        let (folder, builds) = match entry {
+            RepoEntry::Registered(repo, builds) => (paths.path_to_repo(repo), builds),
+            RepoEntry::Unknown(name, builds) => (paths.library.join(&*name), builds),
+            RepoEntry::Error(_, _) => continue,
+        };
+        let (nesting, found) = walk_layout(&folder);
+        if nesting.is_empty() {
+            continue;
+        }
+
+        builds
+            .retain(|b| !matches!(b, BuildEntry::Errored(_, Some(path)) if nesting.contains(path)));
+        builds.extend(
+            found
+                .into_iter()
+                .filter(|path| path.parent() != Some(folder.as_path()))
+                .map(|path| match LocalBuild::read(&path) {
+                    Ok(build) => {
+                        let name = path.strip_prefix(&folder).unwrap_or(&path);
+                        BuildEntry::Installed(name.to_string_lossy().to_string(), build)
+                    }
+                    Err(e) => BuildEntry::Errored(e, Some(path)),
+                }),
+        );
+    }
+}
+
+/// Parses the build lists of `repos` that changed since they were last parsed, and saves them.
+/// Returns the parsed repos by their id, and the repos whose build list could not be parsed.
+fn refresh<'a>(
+    repos: &'a [BuildRepo],
+    paths: &BLRSPaths,
+) -> (HashMap<String, ParsedRepo>, Vec<(&'a BuildRepo, RepoEntry)>) {
+    let path = cache_path(paths);
+    let mut cache = load(&path);
+    // Stamped before parsing, so changes made while parsing are picked up by the next run
+    let outdated: Vec<(&BuildRepo, Stamp)> = repos
+        .iter()
+        .map(|repo| (repo, Stamp::of(paths, repo)))
+        .filter(|(repo, stamp)| {
+            cache
+                .get(&repo.repo_id)
+                .is_none_or(|parsed| parsed.stamp != *stamp)
+        })
+        .collect();
+    debug!["Parsing the build lists of {} repo(s)", outdated.len()];
+
+    let parsed: Vec<_> = outdated
+        .into_par_iter()
+        .map(|(repo, stamp)| (repo, stamp, parse(paths, repo)))
+        .collect();
+    let changed = !parsed.is_empty();
+    let mut errors = vec![];
+    for (repo, stamp, result) in parsed {
+        match result {
+            Ok(builds) => {
+                cache.insert(repo.repo_id.clone(), ParsedRepo { stamp, builds });
+            }
+            Err(error) => {
+                cache.remove(&repo.repo_id);
+                errors.push((repo, error));
+            }
+        }
+    }
+    if changed {
+        save(&path, &cache);
+    }
+
+    (cache, errors)
+}
+
+/// The repos whose build list cannot be parsed, with why. The others are kept parsed for `read_repos`,
+/// so checking the build lists first does not parse them twice.
+pub fn unparsable(repos: &[BuildRepo], paths: &BLRSPaths) -> Vec<(BuildRepo, String)> {
+    refresh(repos, paths)
+        .1
+        .into_iter()
+        .filter_map(|(repo, error)| match error {
+            RepoEntry::Error(_, e) => Some((repo.clone(), e.to_string())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Like `blrs::repos::read_repos`, but the builds that are not installed are kept parsed between runs,
+/// and builds nested by the library layout are found.
+/// A repo is only parsed again once its cached build list or library folder changed,
+/// and the repos that need it are parsed in parallel.
+pub fn read_repos(
+    repos: Vec<BuildRepo>,
+    paths: &BLRSPaths,
+    installed_only: bool,
+) -> Result<Vec<RepoEntry>, std::io::Error> {
+    read_repos_within(repos, paths, installed_only, &Window::default())
+}
+
+/// Like `read_repos`, but only the builds that are not installed within `window` are read out of the cache.
+pub fn read_repos_within(
+    repos: Vec<BuildRepo>,
+    paths: &BLRSPaths,
+    installed_only: bool,
+    window: &Window,
+) -> Result<Vec<RepoEntry>, std::io::Error> {
+    let mut entries = blrs::repos::read_repos(repos.clone(), paths, true)?;
+    read_nested(&mut entries, paths);
+    if installed_only {
+        return Ok(entries);
+    }
+
+    let (mut cache, errors) = refresh(&repos, paths);
+    entries.extend(errors.into_iter().map(|(_, error)| error));
+
+    for repo in &repos {
+        let Some(parsed) = cache.remove(&repo.repo_id) else {
+            continue;
+        };
+        let remote = window
+            .apply(parsed.builds)
+            .into_iter()
+            .map(BuildEntry::NotInstalled);
+        let installed = entries.iter_mut().find_map(|entry| match entry {
+            RepoEntry::Registered(r, builds) if r.repo_id == repo.repo_id => Some(builds),
+            _ => None,
+        });
+        match installed {
+            Some(builds) => builds.extend(remote),
+            None => entries.push(RepoEntry::Registered(repo.clone(), remote.collect())),
+        }
+    }
+
+    Ok(entries)
+}
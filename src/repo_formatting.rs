@@ -1,21 +1,28 @@
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
     fmt::Display,
     fs,
+    path::PathBuf,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use ansi_term as at;
 use blrs::{
     fetching::build_repository::BuildRepo,
+    info::build_info::BasicBuildInfo,
     repos::{BuildEntry, RepoEntry},
     search::VersionSearchQuery,
 };
 use chrono::{DateTime, TimeZone, Utc};
 use clap::ValueEnum;
+use indicatif::HumanBytes;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use termtree as tt;
 
+use crate::resolving::{build_branch, foreign_target, is_feature_branch};
+
 fn system_time_to_date_time(t: SystemTime) -> DateTime<Utc> {
     let nsec = match t.duration_since(UNIX_EPOCH) {
         Ok(dur) => dur.as_nanos(),
@@ -31,6 +38,14 @@ pub enum SortFormat {
     Datetime,
 }
 impl SortFormat {
+    /// How two builds are ordered, like `sort` orders them.
+    pub fn cmp_builds(&self, a: &BasicBuildInfo, b: &BasicBuildInfo) -> Ordering {
+        match self {
+            SortFormat::Version => (a.version(), a.commit_dt).cmp(&(b.version(), b.commit_dt)),
+            SortFormat::Datetime => (a.commit_dt, a.version()).cmp(&(b.commit_dt, b.version())),
+        }
+    }
+
     pub fn sort(&self, v: &mut [BuildEntry]) {
         match self {
             SortFormat::Version => v.sort_by_key(|e| match e {
@@ -71,8 +86,17 @@ impl SortFormat {
     }
 }
 
+/// The disk usage of installed builds in bytes, keyed by their folder.
+pub type DiskUsage = HashMap<PathBuf, u64>;
+
+fn format_usage(size: u64) -> String {
+    at::Color::Purple
+        .paint(HumanBytes(size).to_string())
+        .to_string()
+}
+
 #[derive(Debug)]
-pub struct BuildEntryTreeConstructor<'a>(pub &'a BuildEntry);
+pub struct BuildEntryTreeConstructor<'a>(pub &'a BuildEntry, pub Option<&'a DiskUsage>);
 impl<'a> BuildEntryTreeConstructor<'a> {
     fn to_tree(&self, show_variants: bool) -> tt::Tree<String> {
         let t = tt::Tree::new(self.to_string());
@@ -106,7 +130,21 @@ impl<'a> Display for BuildEntryTreeConstructor<'a> {
                         .dimmed()
                         .paint(format!["{}", local_build.info.basic.commit_dt]),
                     at::Color::Cyan.paint("(Installed)")
-                ]
+                ]?;
+                if let Some(target) = foreign_target(&local_build.info.basic) {
+                    write![
+                        f,
+                        " {}",
+                        at::Color::White.dimmed().paint(format!["(for {})", target])
+                    ]?;
+                }
+                if local_build.info.is_favorited {
+                    write![f, " {}", at::Color::Yellow.paint("★")]?;
+                }
+                if let Some(size) = self.1.and_then(|u| u.get(&local_build.folder)) {
+                    write![f, " {}", format_usage(*size)]?;
+                }
+                Ok(())
             }
             BuildEntry::Errored(error, path_buf) => write![
                 f,
@@ -121,23 +159,140 @@ impl<'a> Display for BuildEntryTreeConstructor<'a> {
 }
 
 #[derive(Debug)]
-pub struct RepoEntryTreeConstructor<'a>(pub &'a RepoEntry);
+pub struct RepoEntryTreeConstructor<'a>(pub &'a RepoEntry, pub Option<&'a DiskUsage>);
 impl<'a> RepoEntryTreeConstructor<'a> {
     pub fn to_tree(&self, show_variants: bool) -> tt::Tree<String> {
         let s = self.to_string();
         let leaves = match self.0 {
             RepoEntry::Registered(_, vec) => vec,
             RepoEntry::Unknown(_, vec) => vec,
-            RepoEntry::Error(_, _) => todo!(),
+            RepoEntry::Error(_, _) => return tt::Tree::new(s),
         };
 
         tt::Tree::new(s).with_leaves(
             leaves
                 .iter()
-                .map(|e| BuildEntryTreeConstructor(e).to_tree(show_variants)),
+                .map(|e| BuildEntryTreeConstructor(e, self.1).to_tree(show_variants)),
         )
     }
 }
+impl<'a> RepoEntryTreeConstructor<'a> {
+    /// Like `to_tree`, but nests the builds under their version series (4.2, 4.3) and then their branch.
+    /// Builds of feature branches are nested under their branch's name instead.
+    ///
+    /// If `expand` is given, only groups containing a build it accepts show their builds.
+    /// The rest only show how many builds they contain.
+    pub fn to_grouped_tree(
+        &self,
+        show_variants: bool,
+        expand: Option<&dyn Fn(&BuildEntry) -> bool>,
+    ) -> tt::Tree<String> {
+        let builds = match self.0 {
+            RepoEntry::Registered(_, vec) | RepoEntry::Unknown(_, vec) => vec,
+            RepoEntry::Error(_, _) => return tt::Tree::new(self.to_string()),
+        };
+
+        let mut series: BTreeMap<(u64, u64), BTreeMap<String, Vec<&BuildEntry>>> = BTreeMap::new();
+        // Feature branches are found by their name rather than their version, so they get their own group
+        let mut feature_branches: BTreeMap<String, Vec<&BuildEntry>> = BTreeMap::new();
+        let mut errored = vec![];
+        for entry in builds {
+            let Some(basic) = entry_basic(entry) else {
+                errored.push(entry);
+                continue;
+            };
+            let v = basic.version();
+            match build_branch(basic).filter(|branch| is_feature_branch(branch)) {
+                Some(branch) => feature_branches
+                    .entry(branch.to_string())
+                    .or_default()
+                    .push(entry),
+                None => series
+                    .entry((v.major, v.minor))
+                    .or_default()
+                    .entry(v.pre.to_string())
+                    .or_default()
+                    .push(entry),
+            }
+        }
+
+        let is_expanded =
+            |entries: &[&BuildEntry]| expand.is_none_or(|expand| entries.iter().any(|e| expand(e)));
+        let group_label = |name: String, count: usize| {
+            format![
+                "{} {}",
+                at::Color::Blue.bold().paint(name),
+                at::Color::White
+                    .dimmed()
+                    .paint(format!["({} builds)", count])
+            ]
+        };
+
+        let series_trees = series.into_iter().map(|((major, minor), branches)| {
+            let count = branches.values().map(Vec::len).sum();
+            let label = group_label(format!["{}.{}", major, minor], count);
+            if !is_expanded(&branches.values().flatten().copied().collect::<Vec<_>>()) {
+                return tt::Tree::new(label);
+            }
+
+            tt::Tree::new(label).with_leaves(branches.into_iter().map(|(branch, entries)| {
+                let name = match branch.is_empty() {
+                    true => "release".to_string(),
+                    false => branch,
+                };
+                let label = group_label(name, entries.len());
+                if !is_expanded(&entries) {
+                    return tt::Tree::new(label);
+                }
+
+                tt::Tree::new(label).with_leaves(
+                    entries
+                        .into_iter()
+                        .map(|e| BuildEntryTreeConstructor(e, self.1).to_tree(show_variants)),
+                )
+            }))
+        });
+
+        let branch_count = feature_branches.values().map(Vec::len).sum();
+        let branches_tree = (branch_count > 0).then(|| {
+            let label = group_label("branches".to_string(), branch_count);
+            tt::Tree::new(label).with_leaves(feature_branches.into_iter().map(
+                |(branch, entries)| {
+                    let label = group_label(branch, entries.len());
+                    if !is_expanded(&entries) {
+                        return tt::Tree::new(label);
+                    }
+
+                    tt::Tree::new(label).with_leaves(
+                        entries
+                            .into_iter()
+                            .map(|e| BuildEntryTreeConstructor(e, self.1).to_tree(show_variants)),
+                    )
+                },
+            ))
+        });
+
+        tt::Tree::new(self.to_string())
+            .with_leaves(series_trees)
+            .with_leaves(branches_tree)
+            .with_leaves(
+                errored
+                    .into_iter()
+                    .map(|e| BuildEntryTreeConstructor(e, self.1).to_tree(show_variants)),
+            )
+    }
+
+    fn fmt_usage(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        builds: &[BuildEntry],
+    ) -> std::fmt::Result {
+        match self.1 {
+            Some(usage) => write![f, ", {}", format_usage(repo_usage(builds, usage))],
+            None => Ok(()),
+        }
+    }
+}
 impl<'a> Display for RepoEntryTreeConstructor<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.0 {
@@ -147,15 +302,19 @@ impl<'a> Display for RepoEntryTreeConstructor<'a> {
                     "{} - {} builds",
                     format_build_repo(build_repo),
                     builds.len()
-                ]
+                ]?;
+                self.fmt_usage(f, builds)
+            }
+            RepoEntry::Unknown(name, builds) => {
+                write![
+                    f,
+                    "{} {} - {} builds",
+                    at::Color::Yellow.paint(name),
+                    ansi_term::Color::White.dimmed().paint("(Unknown)"),
+                    builds.len(),
+                ]?;
+                self.fmt_usage(f, builds)
             }
-            RepoEntry::Unknown(name, builds) => write![
-                f,
-                "{} {} - {} builds",
-                at::Color::Yellow.paint(name),
-                ansi_term::Color::White.dimmed().paint("(Unknown)"),
-                builds.len(),
-            ],
             RepoEntry::Error(name, error) => write![
                 f,
                 "{} {}",
@@ -166,6 +325,25 @@ impl<'a> Display for RepoEntryTreeConstructor<'a> {
     }
 }
 
+fn entry_basic(entry: &BuildEntry) -> Option<&BasicBuildInfo> {
+    match entry {
+        BuildEntry::NotInstalled(variants) => Some(&variants.basic),
+        BuildEntry::Installed(_, lb) => Some(&lb.info.basic),
+        BuildEntry::Errored(_, _) => None,
+    }
+}
+
+/// The total disk usage of the installed builds in a repo.
+pub fn repo_usage(builds: &[BuildEntry], usage: &DiskUsage) -> u64 {
+    builds
+        .iter()
+        .filter_map(|entry| match entry {
+            BuildEntry::Installed(_, lb) => usage.get(&lb.folder),
+            _ => None,
+        })
+        .sum()
+}
+
 fn format_build_repo(r: &BuildRepo) -> String {
     match r.nickname.as_str() {
         "" => format![
@@ -184,3 +362,37 @@ fn format_build_repo(r: &BuildRepo) -> String {
         ],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        repo_cache::read_repos,
+        test_support::{ListedBuild, TempLibrary},
+    };
+
+    use super::*;
+
+    #[test]
+    fn experimental_builds_are_grouped_by_their_branch() {
+        let library = TempLibrary::with_builds(&[
+            ListedBuild::default().json(),
+            ListedBuild {
+                version: "4.2.0",
+                hash: "a1b2c3d4e5f6",
+                branch: "temp-sculpt-dyntopo",
+                ..Default::default()
+            }
+            .json(),
+        ]);
+        let repos = read_repos(library.cfg.repos.clone(), &library.cfg.paths, false).unwrap();
+
+        let tree = RepoEntryTreeConstructor(&repos[0], None).to_grouped_tree(false, None);
+        let groups: Vec<_> = tree.leaves.iter().map(|leaf| leaf.root.clone()).collect();
+        assert_eq!(groups.len(), 2);
+        assert!(groups[0].contains("4.3"));
+        assert!(groups[1].contains("branches"));
+        assert!(tree.leaves[1].leaves[0]
+            .root
+            .contains("temp-sculpt-dyntopo"));
+    }
+}
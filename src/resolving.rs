@@ -1,54 +1,665 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
-use blrs::build_targets::get_target_setup;
-use blrs::repos::{BuildVariant, Variants};
-use blrs::search::VersionSearchQuery;
-use blrs::{BasicBuildInfo, RemoteBuild};
+use blrs::fetching::build_repository::BuildRepo;
+use blrs::repos::{BuildEntry, BuildVariant, RepoEntry, Variants};
+use blrs::search::{BInfoMatcher, OrdPlacement, VersionSearchQuery, WildPlacement};
+use blrs::{BLRSConfig, BasicBuildInfo, LocalBuild, RemoteBuild};
+
+use log::{info, warn};
+use semver::BuildMetadata;
+use serde::{Deserialize, Serialize};
+
+use crate::errs::{CommandError, IoErrorOrigin};
+use crate::prompt::prompter;
+use crate::repo_cache::read_repos;
+use crate::target;
 
 type RepoNickname = String;
 
-pub fn get_choice_map<B>(matches: &[(B, RepoNickname)]) -> HashMap<String, &B>
+/// Set by `--non-interactive`. Prompts are replaced by deterministic choices or errors.
+pub static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+/// Set by `--yes`. Confirmation prompts are answered with yes.
+pub static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Set from `cli.aliases` before any command runs.
+static ALIASES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+pub fn set_aliases(aliases: HashMap<String, String>) {
+    let _ = ALIASES.set(aliases);
+}
+
+/// Replaces an alias with the query it stands for. Anything else is returned as is.
+pub fn expand_alias(s: &str) -> &str {
+    ALIASES
+        .get()
+        .and_then(|aliases| aliases.get(s))
+        .map(String::as_str)
+        .unwrap_or(s)
+}
+
+/// Set before any command runs, so shorthands like `lts` can be worked out from the build lists.
+static LIBRARY: OnceLock<BLRSConfig> = OnceLock::new();
+
+pub fn set_library(cfg: &BLRSConfig) {
+    let _ = LIBRARY.set(cfg.clone());
+}
+
+/// The nickname of the registered repo whose folder in the library is named `folder`.
+pub fn registered_nickname(folder: &str) -> Option<String> {
+    let cfg = LIBRARY.get()?;
+    cfg.repos
+        .iter()
+        .find(|r| {
+            cfg.paths
+                .path_to_repo(r)
+                .file_name()
+                .is_some_and(|name| name == folder)
+        })
+        .map(|r| r.nickname.clone())
+}
+
+/// The branch official releases are listed under.
+const STABLE_BRANCH: &str = "stable";
+/// Branches that are stages of the release cycle, rather than the feature branches of the experimental builds.
+const RELEASE_CYCLES: [&str; 8] = [
+    "stable",
+    "lts",
+    "candidate",
+    "rc",
+    "beta",
+    "alpha",
+    "daily",
+    "main",
+];
+
+/// Whether builds of a branch come from a feature branch, like `temp-sculpt-dyntopo`,
+/// rather than from `main` or a release branch like `v42`.
+pub fn is_feature_branch(branch: &str) -> bool {
+    let is_release_branch = branch
+        .strip_prefix('v')
+        .is_some_and(|series| !series.is_empty() && series.chars().all(|c| c.is_ascii_digit()));
+    !branch.is_empty()
+        && !is_release_branch
+        && !RELEASE_CYCLES.contains(&branch.to_lowercase().as_str())
+}
+
+/// Names that stand for the newest build of a kind, whatever its version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shorthand {
+    /// The newest patch of the newest long-term support series.
+    Lts,
+    /// The newest release.
+    Stable,
+    /// The newest build that is not a release, like an alpha or beta.
+    Daily,
+    /// The newest build of any branch.
+    Latest,
+}
+
+impl Shorthand {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "lts" => Some(Self::Lts),
+            "stable" => Some(Self::Stable),
+            "daily" => Some(Self::Daily),
+            "latest" => Some(Self::Latest),
+            _ => None,
+        }
+    }
+
+    /// The major, minor, and branch of the series the shorthand currently stands for.
+    fn series(self, builds: &[BasicBuildInfo]) -> Option<(u64, u64, String)> {
+        let series_of = |b: &BasicBuildInfo| {
+            let v = b.version();
+            (v.major, v.minor, v.pre.to_string())
+        };
+        let is_stable = |b: &&BasicBuildInfo| b.version().pre.as_str() == STABLE_BRANCH;
+
+        match self {
+            Self::Latest => builds.iter().max_by_key(|b| b.commit_dt).map(series_of),
+            Self::Stable => builds
+                .iter()
+                .filter(is_stable)
+                .max_by_key(|b| b.version())
+                .map(series_of),
+            Self::Daily => builds
+                .iter()
+                .filter(|b| !is_stable(b))
+                .max_by_key(|b| b.commit_dt)
+                .map(series_of),
+            Self::Lts => {
+                // The first and last release of every series
+                let mut spans = BTreeMap::new();
+                for b in builds.iter().filter(is_stable) {
+                    let v = b.version();
+                    let (first, last) = spans
+                        .entry((v.major, v.minor))
+                        .or_insert((b.commit_dt, b.commit_dt));
+                    *first = (*first).min(b.commit_dt);
+                    *last = (*last).max(b.commit_dt);
+                }
+
+                // A series still getting releases after a newer one came out is supported long-term
+                spans
+                    .iter()
+                    .rev()
+                    .find(|(series, (_, last))| {
+                        spans
+                            .range((
+                                std::ops::Bound::Excluded(*series),
+                                std::ops::Bound::Unbounded,
+                            ))
+                            .any(|(_, (first, _))| first < last)
+                    })
+                    .map(|((major, minor), _)| (*major, *minor, STABLE_BRANCH.to_string()))
+            }
+        }
+    }
+}
+
+/// Every build in the cached build lists and the library.
+fn known_builds(cfg: &BLRSConfig) -> Result<Vec<BasicBuildInfo>, CommandError> {
+    Ok(read_repos(cfg.repos.clone(), &cfg.paths, false)
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
+        .into_iter()
+        .flat_map(|r| match r {
+            RepoEntry::Registered(_, entries) | RepoEntry::Unknown(_, entries) => entries,
+            RepoEntry::Error(..) => vec![],
+        })
+        .filter_map(|entry| match entry {
+            BuildEntry::NotInstalled(variants) => Some(variants.basic),
+            BuildEntry::Installed(_, build) => Some(build.info.basic),
+            BuildEntry::Errored(..) => None,
+        })
+        .collect())
+}
+
+/// The query a shorthand stands for: the newest build of the series it currently points to.
+fn shorthand_query(name: &str, shorthand: Shorthand) -> Result<VersionSearchQuery, CommandError> {
+    let builds = match LIBRARY.get() {
+        Some(cfg) => known_builds(cfg)?,
+        None => vec![],
+    };
+    let (major, minor, branch) = shorthand
+        .series(&builds)
+        .ok_or_else(|| CommandError::QueryResultEmpty(name.to_string()))?;
+    info!["{} is {}.{} on the {} branch", name, major, minor, branch];
+
+    Ok(VersionSearchQuery {
+        major: OrdPlacement::Exact(major),
+        minor: OrdPlacement::Exact(minor),
+        patch: OrdPlacement::Latest,
+        branch: WildPlacement::Exact(branch),
+        commit_dt: OrdPlacement::Latest,
+        ..Default::default()
+    })
+}
+
+/// Hashes shorter than this are too likely to be something else, like a version.
+const MIN_HASH_LEN: usize = 7;
+/// The length of the hash put in folder names, as Blender's own build names use.
+const SHORT_HASH_LEN: usize = 12;
+
+fn looks_like_hash(s: &str) -> bool {
+    s.len() >= MIN_HASH_LEN && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// A query like `branch:temp-sculpt-dyntopo`, or `experimental/branch:temp-sculpt-dyntopo` for one repo,
+/// for the newest build of a branch whatever its version.
+fn branch_query(s: &str) -> Option<VersionSearchQuery> {
+    let (repo, rest) = match s.split_once('/') {
+        Some((repo, rest)) => (Some(repo), rest),
+        None => (None, s),
+    };
+    let branch = rest.strip_prefix("branch:").filter(|b| !b.is_empty())?;
+
+    Some(VersionSearchQuery {
+        repository: repo.map_or(WildPlacement::Any, |r| WildPlacement::Exact(r.to_string())),
+        branch: WildPlacement::Exact(branch.to_string()),
+        commit_dt: OrdPlacement::Latest,
+        ..Default::default()
+    })
+}
+
+/// Parses a query, expanding it first if it is an alias.
+///
+/// `#<hash>` finds a build by (a prefix of) its commit hash, whatever its version or branch.
+/// A bare hash works too, as long as it is not also a valid query.
+///
+/// `lts`, `stable`, `daily`, and `latest` stand for the newest build of their kind,
+/// worked out from the cached build lists and the library.
+///
+/// `branch:<name>` finds the newest build of a branch, like the experimental `branch:temp-sculpt-dyntopo`.
+pub fn parse_query(s: &str) -> Result<VersionSearchQuery, CommandError> {
+    let expanded = expand_alias(s);
+    if let Some(shorthand) = Shorthand::parse(expanded) {
+        return shorthand_query(expanded, shorthand);
+    }
+    if let Some(query) = branch_query(expanded) {
+        return Ok(query);
+    }
+    let hash_query = |hash: &str| VersionSearchQuery {
+        build_hash: WildPlacement::Exact(hash.to_lowercase()),
+        ..Default::default()
+    };
+
+    if let Some(hash) = expanded.strip_prefix('#').filter(|h| looks_like_hash(h)) {
+        return Ok(hash_query(hash));
+    }
+
+    VersionSearchQuery::try_from(expanded).or_else(|e| match looks_like_hash(expanded) {
+        true => Ok(hash_query(expanded)),
+        false => Err(CommandError::CouldNotParseQuery(s.to_string(), e)),
+    })
+}
+
+/// The branch a build was made from, like `main` or `temp-sculpt-dyntopo`, if its version records one.
+/// Its release cycle, like `alpha`, is the version's pre-release instead.
+/// Builds are named like `4.2.0-alpha+temp-sculpt-dyntopo.0123456789ab`, so it comes before the hash.
+pub fn build_branch(basic: &BasicBuildInfo) -> Option<&str> {
+    basic
+        .ver
+        .build
+        .as_str()
+        .split('.')
+        .take_while(|identifier| !looks_like_hash(identifier))
+        .find(|identifier| !identifier.is_empty() && !identifier.starts_with(TARGET_IDENTIFIER))
+}
+
+/// The commit hash a build was made from, if its version records one.
+pub fn commit_hash(basic: &BasicBuildInfo) -> Option<&str> {
+    basic
+        .ver
+        .build
+        .as_str()
+        .split('.')
+        .find(|identifier| looks_like_hash(identifier))
+}
+
+/// The placeholders a library layout can use.
+const LAYOUT_PLACEHOLDERS: [&str; 9] = [
+    "repo",
+    "version",
+    "major",
+    "minor",
+    "patch",
+    "branch",
+    "hash",
+    "short_hash",
+    "target",
+];
+
+/// Set from `--library-layout` or `cli.library_layout` before any command runs.
+static LAYOUT: OnceLock<String> = OnceLock::new();
+
+pub fn set_layout(layout: Option<String>) {
+    match layout.map(|l| parse_layout(&l)) {
+        Some(Ok(layout)) => {
+            let _ = LAYOUT.set(layout);
+        }
+        Some(Err(e)) => warn!["Ignoring the library layout, using the default one: {}", e],
+        None => {}
+    }
+}
+
+/// Checks a library layout, like `{version}+{short_hash}` or `{branch}/{version}`. A leading `{repo}/` is
+/// accepted and dropped, as builds always go into their repo's folder. Folders are nested with `/`.
+pub fn parse_layout(s: &str) -> Result<String, String> {
+    let layout = s.strip_prefix("{repo}/").unwrap_or(s);
+    if layout.contains('\\') {
+        return Err(format![
+            "{:?} has a backslash, nest folders with / instead",
+            s
+        ]);
+    }
+    if layout
+        .split('/')
+        .any(|folder| folder.is_empty() || folder == "." || folder == "..")
+    {
+        return Err(format![
+            "{:?} has an empty, . or .. folder, which would put builds outside their repo's folder",
+            s
+        ]);
+    }
+
+    let mut rest = layout;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!["{:?} has an unclosed placeholder", s]);
+        };
+        let name = &rest[start + 1..start + end];
+        if !LAYOUT_PLACEHOLDERS.contains(&name) {
+            return Err(format![
+                "{:?} has the unknown placeholder {{{}}}, expected one of {{{}}}",
+                s,
+                name,
+                LAYOUT_PLACEHOLDERS.join("}, {")
+            ]);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if !layout.contains("{version}")
+        && !layout.contains("{hash}")
+        && !layout.contains("{short_hash}")
+    {
+        return Err(format![
+            "{:?} has neither {{version}} nor a hash, so every build would get the same folder",
+            s
+        ]);
+    }
+
+    Ok(layout.to_string())
+}
+
+/// How many folders below its repo's folder builds are installed, more than 1 when the layout nests them.
+pub fn layout_depth() -> usize {
+    LAYOUT.get().map_or(1, |layout| layout.split('/').count())
+}
+
+/// Whether a folder in a repo's folder only holds builds nested by the layout, like `main` in `{branch}/{version}`.
+fn is_layout_folder(path: &Path) -> bool {
+    let is_hidden = |p: &Path| {
+        p.file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+    };
+    if is_hidden(path) || LocalBuild::read(path).is_ok() {
+        return false;
+    }
+
+    path.read_dir().is_ok_and(|items| {
+        items
+            .flatten()
+            .map(|item| item.path())
+            .all(|p| is_hidden(&p) || p.is_dir())
+    })
+}
+
+/// Walks a repo's folder as deep as the library layout nests builds. Returns the folders the layout nests
+/// builds in, and the build folders, including the ones whose build info cannot be read.
+pub fn walk_layout(repo_folder: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    fn walk(folder: &Path, levels: usize, nesting: &mut Vec<PathBuf>, builds: &mut Vec<PathBuf>) {
+        let Ok(items) = folder.read_dir() else {
+            return;
+        };
+        let mut items: Vec<PathBuf> = items
+            .flatten()
+            .map(|item| item.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        items.sort();
+        for path in items {
+            match levels > 1 && is_layout_folder(&path) {
+                true => {
+                    walk(&path, levels - 1, nesting, builds);
+                    nesting.push(path);
+                }
+                false => builds.push(path),
+            }
+        }
+    }
+
+    let (mut nesting, mut builds) = (vec![], vec![]);
+    walk(repo_folder, layout_depth(), &mut nesting, &mut builds);
+    (nesting, builds)
+}
+
+/// The name of the repo folder a build folder is in, going by the library, as the layout may nest it deeper.
+pub fn repo_folder_name(folder: &Path) -> Option<String> {
+    let cfg = LIBRARY.get()?;
+    let relative = folder.strip_prefix(&cfg.paths.library).ok()?;
+    relative
+        .components()
+        .next()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+}
+
+/// The folder a build of `repo` is installed into, named by the library layout if one is set.
+/// By default, the short commit hash leads the name, so a daily build can be found by the hash
+/// it is usually referred to by. Nested layouts give a relative path, like `main/4.2.0`.
+pub fn folder_name(basic: &BasicBuildInfo, repo: &str) -> String {
+    let hash = commit_hash(basic);
+    let short_hash = hash.map(|h| &h[..h.len().min(SHORT_HASH_LEN)]);
+    let Some(layout) = LAYOUT.get() else {
+        return match short_hash {
+            Some(hash) => format!["{}_{}", hash, basic.version()],
+            None => basic.version().to_string(),
+        };
+    };
+
+    let ver = basic.version();
+    let branch = match ver.pre.is_empty() {
+        true => STABLE_BRANCH,
+        false => ver.pre.as_str(),
+    };
+    // Values never nest folders themselves, only the separators of the layout do
+    let values = [
+        ("repo", repo.to_string()),
+        ("version", ver.to_string()),
+        ("major", ver.major.to_string()),
+        ("minor", ver.minor.to_string()),
+        ("patch", ver.patch.to_string()),
+        ("branch", branch.to_string()),
+        ("hash", hash.unwrap_or_default().to_string()),
+        ("short_hash", short_hash.unwrap_or_default().to_string()),
+        (
+            "target",
+            foreign_target(basic).unwrap_or_default().to_string(),
+        ),
+    ];
+    let folders: Vec<String> = layout
+        .split('/')
+        .map(|folder| {
+            let name = values
+                .iter()
+                .fold(folder.to_string(), |name, (key, value)| {
+                    name.replace(&format!["{{{}}}", key], &value.replace(['/', '\\'], "-"))
+                });
+            // Builds without a hash leave a trailing separator with layouts like `{version}+{hash}`
+            name.trim_matches(['+', '_', '-', '.']).to_string()
+        })
+        .filter(|folder| !folder.is_empty())
+        .collect();
+    match folders.is_empty() {
+        true => ver.to_string(),
+        false => folders.join("/"),
+    }
+}
+
+/// Finds the builds matching a query. Hashes match by prefix and across branches,
+/// so the short hashes shown by Blender and the build sites work.
+/// Feature branches match the branch a build was made from, rather than its release cycle.
+pub fn find_matches<'a, B>(
+    builds: &'a [(B, RepoNickname)],
+    query: &VersionSearchQuery,
+) -> Vec<&'a (B, RepoNickname)>
 where
     B: AsRef<BasicBuildInfo>,
 {
-    let mut x: Vec<_> = matches
-        .iter()
-        .map(|(b, nick)| {
-            (
-                format![
-                    "{}/{}",
-                    nick,
-                    VersionSearchQuery::from(b.as_ref().clone()).with_commit_dt(None)
-                ],
-                b,
+    match (&query.build_hash, &query.branch) {
+        (WildPlacement::Exact(hash), _) if looks_like_hash(hash) => {
+            let rest = VersionSearchQuery {
+                build_hash: WildPlacement::Any,
+                ..query.clone()
+            };
+            BInfoMatcher::new(builds)
+                .find_all(&rest)
+                .into_iter()
+                .filter(|(b, _)| {
+                    commit_hash(b.as_ref())
+                        .is_some_and(|h| h.to_lowercase().starts_with(hash.as_str()))
+                })
+                .collect()
+        }
+        (_, WildPlacement::Exact(branch)) if is_feature_branch(branch) => {
+            let rest = VersionSearchQuery {
+                branch: WildPlacement::Any,
+                ..query.clone()
+            };
+            BInfoMatcher::new(builds)
+                .find_all(&rest)
+                .into_iter()
+                .filter(|(b, _)| build_branch(b.as_ref()) == Some(branch.as_str()))
+                .collect()
+        }
+        _ => BInfoMatcher::new(builds).find_all(query),
+    }
+}
+
+pub fn is_interactive() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Acquire)
+}
+
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Acquire)
+}
+
+/// Asks a yes/no question, respecting `--yes` and `--non-interactive`.
+/// If the prompt cannot be answered, `default` is returned.
+pub fn confirm(prompt: &str, default: bool) -> bool {
+    if assume_yes() {
+        return true;
+    }
+    if !is_interactive() {
+        return default;
+    }
+
+    prompter().confirm(prompt, default).unwrap_or(default)
+}
+
+/// Gathers every installed build, paired with the nickname of the repo it belongs to.
+///
+/// The cached build lists are skipped, as parsing them is most of the time spent reading large libraries.
+pub fn installed_builds(cfg: &BLRSConfig) -> Result<Vec<(LocalBuild, RepoNickname)>, CommandError> {
+    let builds = read_repos(cfg.repos.clone(), &cfg.paths, true)
+        .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
+        .into_iter()
+        .filter_map(|r| match r {
+            RepoEntry::Registered(
+                BuildRepo {
+                    repo_id: _,
+                    url: _,
+                    nickname,
+                    repo_type: _,
+                },
+                vec,
             )
+            | RepoEntry::Unknown(nickname, vec) => {
+                let local_builds = vec
+                    .into_iter()
+                    .filter_map(|entry| match entry {
+                        BuildEntry::Installed(_, build) => Some(build),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                (!local_builds.is_empty()).then_some((local_builds, nickname))
+            }
+            _ => None,
         })
+        .flat_map(|(builds, nick)| builds.into_iter().map(move |b| (b, nick.clone())))
+        .collect();
+
+    Ok(builds)
+}
+
+/// Formats a query that points to exactly this build in the given repo.
+pub fn build_query_string(b: &BasicBuildInfo, nick: &str) -> String {
+    format![
+        "{}/{}",
+        nick,
+        VersionSearchQuery::from(b.clone()).with_commit_dt(None)
+    ]
+}
+
+/// Prefix of the build metadata identifier that records the platform of a build installed for another platform.
+const TARGET_IDENTIFIER: &str = "target-";
+
+/// Records a variant's target in the version's build metadata, so builds of the same version
+/// for different platforms get separate folders and can be told apart.
+pub fn with_target(basic: &BasicBuildInfo, target: &str) -> BasicBuildInfo {
+    let target: String = target
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '-' {
+            true => c,
+            false => '-',
+        })
+        .collect();
+    let identifier = format!["{TARGET_IDENTIFIER}{target}"];
+
+    let mut basic = basic.clone();
+    let build = match basic.ver.build.is_empty() {
+        true => identifier,
+        false => format!["{}.{}", basic.ver.build, identifier],
+    };
+    basic.ver.build = BuildMetadata::new(&build).unwrap_or(basic.ver.build);
+    basic
+}
+
+/// The target of a build that was installed for another platform, if it is one.
+pub fn foreign_target(basic: &BasicBuildInfo) -> Option<&str> {
+    basic
+        .ver
+        .build
+        .as_str()
+        .split('.')
+        .find_map(|identifier| identifier.strip_prefix(TARGET_IDENTIFIER))
+}
+
+pub fn get_choice_map<B>(matches: &[(B, RepoNickname)]) -> HashMap<String, &(B, RepoNickname)>
+where
+    B: AsRef<BasicBuildInfo>,
+{
+    let mut x: Vec<_> = matches
+        .iter()
+        .map(|entry| (build_query_string(entry.0.as_ref(), &entry.1), entry))
         .collect();
-    x.sort_by_key(|(_, b)| (b.as_ref().commit_dt, b.as_ref().ver.clone()));
+    x.sort_by_key(|(_, (b, _))| (b.as_ref().commit_dt, b.as_ref().ver.clone()));
     let max_choice_size = x.iter().map(|(c, _)| c.len()).max().unwrap_or_default();
     x.into_iter()
-        .map(|(c, build)| {
+        .map(|(c, entry)| {
             (
                 // Apply padding and add the date to the end
                 format![
                     "{:<cs$}  {}",
                     c,
-                    build.as_ref().commit_dt,
+                    entry.0.as_ref().commit_dt,
                     cs = max_choice_size
                 ],
-                build,
+                entry,
             )
         })
         .collect()
 }
 
 // If necessary, prompt the user to select which build to download
-pub fn resolve_match<'a, B>(matches: &'a [(B, RepoNickname)], prompt: &str) -> Option<&'a B>
+pub fn resolve_match<'a, B>(
+    matches: &'a [(B, RepoNickname)],
+    prompt: &str,
+) -> Option<&'a (B, RepoNickname)>
 where
     B: AsRef<BasicBuildInfo>,
 {
     if matches.len() == 1 {
-        return Some(&matches[0].0);
+        return Some(&matches[0]);
+    }
+
+    if !is_interactive() {
+        // Pick the newest build
+        let newest = matches
+            .iter()
+            .max_by_key(|(b, _)| (b.as_ref().commit_dt, b.as_ref().ver.clone()));
+        if let Some((b, nick)) = newest {
+            info![
+                "Picked the newest match {}",
+                build_query_string(b.as_ref(), nick)
+            ];
+        }
+        return newest;
     }
 
     let choice_map = get_choice_map(matches);
@@ -57,29 +668,238 @@ where
 
     // Sort the matches by the commit date, then the version
     choices.sort_by_cached_key(|b| {
-        let build = choice_map[b].as_ref();
+        let build = choice_map[b].0.as_ref();
         build
     });
 
     let last_idx = choices.len() - 1;
 
     println![];
-    let inquiry = inquire::Select::new(prompt, choices)
-        .with_starting_cursor(last_idx)
-        .prompt();
+    prompter()
+        .select(prompt, choices, last_idx)
+        .map(|s| choice_map[&s])
+}
+
+/// The things a variant preference can be about.
+const PREFERENCE_KEYS: [&str; 5] = ["arch", "platform", "libc", "package", "ext"];
+/// Extensions of packages that install Blender instead of unpacking it.
+const INSTALLER_EXTENSIONS: [&str; 4] = ["msi", "msix", "pkg", "exe"];
+
+/// Describes which variant of a build to pick when several fit the platform, written as `key=value`:
+///
+/// - `arch=x64`: the CPU architecture
+/// - `platform=linux`: the platform of the variant
+/// - `libc=glibc-2.28`: the C library in the file name
+/// - `package=portable` or `package=installer`: archives that are unpacked, or installers like `.msi`
+/// - `ext=zip`: the file extension
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VariantPreference {
+    key: String,
+    value: String,
+}
+
+/// Lowercases and drops punctuation, so `glibc-2.28` matches a file named `linux-glibc228-x86_64`.
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Every name the architecture goes by in Blender's file names.
+fn arch_aliases(arch: &str) -> Vec<String> {
+    let arch = normalize(arch);
+    match arch.as_str() {
+        "x64" | "x8664" | "amd64" => vec!["x64".into(), "x8664".into(), "amd64".into()],
+        "arm64" | "aarch64" => vec!["arm64".into(), "aarch64".into()],
+        _ => vec![arch],
+    }
+}
+
+impl VariantPreference {
+    pub fn matches(&self, variant: &BuildVariant<RemoteBuild>) -> bool {
+        let build = &variant.b;
+        let file_name = build.link.rsplit('/').next().unwrap_or_default();
+        let extension = build
+            .file_extension
+            .clone()
+            .unwrap_or_else(|| file_name.rsplit('.').next().unwrap_or_default().to_string())
+            .trim_start_matches('.')
+            .to_lowercase();
+
+        match self.key.as_str() {
+            "arch" => {
+                let haystack = normalize(&format![
+                    "{} {} {}",
+                    build.architecture.as_deref().unwrap_or_default(),
+                    variant.target,
+                    file_name
+                ]);
+                arch_aliases(&self.value)
+                    .iter()
+                    .any(|alias| haystack.contains(alias))
+            }
+            "platform" => normalize(&format![
+                "{} {}",
+                build.platform.as_deref().unwrap_or_default(),
+                variant.target
+            ])
+            .contains(&normalize(&self.value)),
+            "libc" => normalize(file_name).contains(&normalize(&self.value)),
+            "package" => {
+                INSTALLER_EXTENSIONS.contains(&extension.as_str()) == (self.value == "installer")
+            }
+            "ext" => extension == self.value.trim_start_matches('.').to_lowercase(),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for VariantPreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!["expected key=value, got {:?}", s])?;
+        let (key, value) = (key.trim().to_lowercase(), value.trim().to_lowercase());
+
+        if !PREFERENCE_KEYS.contains(&key.as_str()) {
+            return Err(format![
+                "unknown variant preference {:?}, expected one of {}",
+                key,
+                PREFERENCE_KEYS.join(", ")
+            ]);
+        }
+        if key == "package" && value != "portable" && value != "installer" {
+            return Err(format![
+                "package must be portable or installer, got {:?}",
+                value
+            ]);
+        }
+
+        Ok(Self { key, value })
+    }
+}
 
-    match inquiry {
-        Ok(s) => Some(choice_map[&s]),
-        _ => None,
+impl TryFrom<String> for VariantPreference {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<VariantPreference> for String {
+    fn from(p: VariantPreference) -> Self {
+        p.to_string()
+    }
+}
+
+impl Display for VariantPreference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write![f, "{}={}", self.key, self.value]
+    }
+}
+
+/// Keeps the variants that best fit the preferences. Earlier preferences outweigh later ones,
+/// and variants are only dropped if another one fits better.
+fn apply_preferences(variants: &mut Variants<RemoteBuild>, preferences: &[VariantPreference]) {
+    let scores: Vec<Vec<bool>> = variants
+        .v
+        .iter()
+        .map(|variant| preferences.iter().map(|p| p.matches(variant)).collect())
+        .collect();
+    let Some(best) = scores.iter().max().cloned() else {
+        return;
+    };
+
+    let mut scores = scores.into_iter();
+    variants.v.retain(|_| scores.next().unwrap() == best);
+}
+
+/// The file formats a picked variant is chosen by when several match, most preferred first.
+/// Archives come before installers like `.msi` and `.msix`, which cannot be extracted into the library.
+const FORMAT_ORDER: [&str; 10] = [
+    ".tar.xz", ".txz", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".xz", ".zip", ".7z", ".dmg",
+];
+
+fn file_name(variant: &BuildVariant<RemoteBuild>) -> &str {
+    variant.b.link.rsplit('/').next().unwrap_or_default()
+}
+
+/// Keeps the variants in the most preferred file format, see `FORMAT_ORDER`.
+fn prefer_formats(variants: &mut Variants<RemoteBuild>) {
+    let rank = |variant: &BuildVariant<RemoteBuild>| {
+        let name = file_name(variant).to_lowercase();
+        FORMAT_ORDER
+            .iter()
+            .position(|suffix| name.ends_with(suffix))
+            .unwrap_or(FORMAT_ORDER.len())
+    };
+    let Some(best) = variants.v.iter().map(rank).min() else {
+        return;
+    };
+    variants.v.retain(|variant| rank(variant) == best);
+}
+
+/// Names the variant to download, so the platform and preferences are not used to choose one.
+#[derive(Debug, Clone)]
+pub enum VariantPick {
+    /// The variant's target, like `windows-x64`.
+    Target(String),
+    /// A glob matched against the file name of the download, like `*linux*.tar.xz`.
+    FileName(glob::Pattern),
+}
+
+impl VariantPick {
+    pub fn matches(&self, variant: &BuildVariant<RemoteBuild>) -> bool {
+        match self {
+            VariantPick::Target(target) => normalize(&variant.target) == normalize(target),
+            VariantPick::FileName(pattern) => pattern.matches(file_name(variant)),
+        }
+    }
+}
+
+impl Display for VariantPick {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariantPick::Target(target) => write![f, "{}", target],
+            VariantPick::FileName(pattern) => write![f, "{}", pattern],
+        }
     }
 }
 
 pub fn resolve_variant(
     variants: Variants<RemoteBuild>,
     all_platforms: bool,
-) -> Option<RemoteBuild> {
-    let (resolve_txt, variants) = if !all_platforms {
-        let mut v = variants.clone().filter_target(get_target_setup().unwrap());
+    preferences: &[VariantPreference],
+    pick: Option<&VariantPick>,
+) -> Result<Option<BuildVariant<RemoteBuild>>, CommandError> {
+    let (resolve_txt, mut variants) = if let Some(pick) = pick {
+        let mut v = variants.clone();
+        v.v.retain(|variant| pick.matches(variant));
+        if v.v.is_empty() {
+            return Err(CommandError::QueryResultEmpty(format![
+                "{} with a variant matching {} (it has {})",
+                variants.basic.ver,
+                pick,
+                variants
+                    .v
+                    .iter()
+                    .map(|variant| variant.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ]));
+        }
+
+        (
+            "Multiple variants match! select which one you want to download",
+            v,
+        )
+    } else if !all_platforms {
+        let mut v = target::filter_variants(variants.clone());
         v.v.sort_by_key(|variant| variant.to_string());
 
         let v = if v.v.is_empty() { variants } else { v };
@@ -92,12 +912,46 @@ pub fn resolve_variant(
         ("Select which variant you want to download", variants)
     };
 
+    apply_preferences(&mut variants, preferences);
+
+    // A picked variant is never prompted for. Several files can share a target, like the .zip, .msi,
+    // and .msix of a Windows build, so the file format breaks the tie
+    if let Some(pick) = pick {
+        prefer_formats(&mut variants);
+        if variants.v.len() > 1 {
+            return Err(CommandError::AmbiguousVariant(
+                variants.basic.ver.to_string(),
+                pick.to_string(),
+                variants
+                    .v
+                    .iter()
+                    .map(|variant| file_name(variant).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
+    }
+
     // Resolve -- prompt the user which one to download
     if variants.v.len() == 1 {
-        return Some(variants.v[0].b.clone());
+        return Ok(variants.v.into_iter().next());
+    }
+
+    // There is no sensible default between platforms
+    if !is_interactive() {
+        return Err(CommandError::PromptRequired(format![
+            "Build {} has multiple variants ({})",
+            variants.basic.ver,
+            variants
+                .v
+                .iter()
+                .map(|variant| variant.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ]));
     }
 
-    let map: HashMap<String, BuildVariant<_>> = variants
+    let mut map: HashMap<String, BuildVariant<_>> = variants
         .v
         .into_iter()
         .map(|variant| (variant.to_string(), variant))
@@ -105,10 +959,102 @@ pub fn resolve_variant(
 
     let choices = map.keys().cloned().collect();
 
-    let inquiry = inquire::Select::new(resolve_txt, choices).prompt();
+    Ok(prompter()
+        .select(resolve_txt, choices, 0)
+        .and_then(|s| map.remove(&s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{ListedBuild, TempLibrary};
+
+    use super::*;
+
+    /// The variants of the only build in the library's build list.
+    fn listed_variants(library: &TempLibrary) -> Variants<RemoteBuild> {
+        read_repos(library.cfg.repos.clone(), &library.cfg.paths, false)
+            .unwrap()
+            .into_iter()
+            .flat_map(|repo| match repo {
+                RepoEntry::Registered(_, builds) | RepoEntry::Unknown(_, builds) => builds,
+                RepoEntry::Error(_, e) => panic!["{}", e],
+            })
+            .find_map(|build| match build {
+                BuildEntry::NotInstalled(variants) => Some(variants),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn branch_queries_match_the_branch_of_experimental_builds() {
+        // As builder.blender.org's experimental listing has it, whose release cycle is not the branch
+        let experimental = ListedBuild {
+            version: "4.2.0",
+            hash: "a1b2c3d4e5f6",
+            branch: "temp-sculpt-dyntopo",
+            ..Default::default()
+        };
+        let library =
+            TempLibrary::with_builds(&[ListedBuild::default().json(), experimental.json()]);
+        let builds: Vec<_> = read_repos(library.cfg.repos.clone(), &library.cfg.paths, false)
+            .unwrap()
+            .into_iter()
+            .flat_map(|repo| match repo {
+                RepoEntry::Registered(_, builds) | RepoEntry::Unknown(_, builds) => builds,
+                RepoEntry::Error(_, e) => panic!["{}", e],
+            })
+            .filter_map(|build| match build {
+                BuildEntry::NotInstalled(variants) => Some((variants.basic, "test".to_string())),
+                _ => None,
+            })
+            .collect();
+
+        let query = parse_query("branch:temp-sculpt-dyntopo").unwrap();
+        let matches = find_matches(&builds, &query);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(build_branch(&matches[0].0), Some("temp-sculpt-dyntopo"));
+        assert_eq!(matches[0].0.version().pre.as_str(), "alpha");
+    }
+
+    #[test]
+    fn picked_target_with_several_files_takes_the_archive() {
+        let windows = |extension| {
+            ListedBuild {
+                platform: "windows",
+                architecture: "amd64",
+                extension,
+                ..Default::default()
+            }
+            .json()
+        };
+        let library = TempLibrary::with_builds(&[windows("msi"), windows("zip"), windows("msix")]);
+        let variants = listed_variants(&library);
+        assert_eq!(variants.v.len(), 3);
+
+        let pick = VariantPick::Target(variants.v[0].target.clone());
+        let chosen = resolve_variant(variants, false, &[], Some(&pick))
+            .unwrap()
+            .unwrap();
+        assert!(file_name(&chosen).ends_with(".zip"));
+    }
+
+    #[test]
+    fn picked_target_with_several_archives_is_an_error() {
+        let library = TempLibrary::with_builds(&[
+            ListedBuild::default().json(),
+            ListedBuild {
+                architecture: "arm64",
+                ..Default::default()
+            }
+            .json(),
+        ]);
+        let variants = listed_variants(&library);
 
-    match inquiry {
-        Ok(s) => Some(map[&s].b.clone()),
-        _ => None,
+        let pick = VariantPick::FileName(glob::Pattern::new("*.tar.xz").unwrap());
+        assert!(matches!(
+            resolve_variant(variants, true, &[], Some(&pick)),
+            Err(CommandError::AmbiguousVariant(_, _, _))
+        ));
     }
 }
@@ -1,36 +1,151 @@
-use std::{path::PathBuf, process};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+    process::{self, Stdio},
+};
 
 use blrs::{
-    fetching::build_repository::BuildRepo,
+    config::PROJECT_DIRS,
     info::{
         launching::{BlendLaunchTarget, GeneratedParams, LaunchArguments, OSLaunchTarget},
         read_blendfile_header,
     },
-    repos::{read_repos, BuildEntry, RepoEntry},
-    search::{BInfoMatcher, OrdPlacement, VersionSearchQuery, WildPlacement},
-    BLRSConfig,
+    search::{OrdPlacement, VersionSearchQuery, WildPlacement},
+    BLRSConfig, LocalBuild,
 };
 
-use log::{debug, info, warn};
+use clap::ValueEnum;
+use log::{debug, error, info, warn};
+use semver::Version;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    commands::RunCommand,
-    errs::{CommandError, IoErrorOrigin},
-    resolving::resolve_match,
+    cli_config::CliConfig,
+    commands::{BlenderArgs, IsolationArgs, LaunchOutputArgs, RunCommand},
+    crash_report,
+    errs::{error_reading, error_writing, CommandError, IoErrorOrigin},
+    hooks::{self, Hook},
+    launch_history::{Launch, LaunchHistory},
+    pin::pinned_build,
+    resolving::{
+        build_query_string, find_matches, foreign_target, installed_builds, is_interactive,
+        parse_query, resolve_match,
+    },
 };
 
+/// Decides which build to use when a blendfile's version has no exact match among the installed builds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FallbackPolicy {
+    /// Only use builds matching the file's version, and fail if there are none.
+    Exact,
+    /// Use the closest newer build, or the closest older build if there is no newer one.
+    Newer,
+    /// Use the closest older build, or the closest newer build if there is no older one.
+    Older,
+    /// Prompt to select a build.
+    #[default]
+    Ask,
+}
+
+/// Extra arguments and environment variables for a kind of launch, picked with `run build --profile`.
+/// Written either as a list of arguments, or as a table with `args` and `env`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LaunchProfile {
+    Args(Vec<String>),
+    Full {
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+    },
+}
+
+impl LaunchProfile {
+    fn apply(&self, params: &mut GeneratedParams) {
+        match self {
+            LaunchProfile::Args(args) => {
+                params.args.get_or_insert_default().extend(args.clone());
+            }
+            LaunchProfile::Full { args, env } => {
+                params.args.get_or_insert_default().extend(args.clone());
+                params.env.get_or_insert_default().extend(env.clone());
+            }
+        }
+    }
+}
+
+/// Adds the arguments and environment of the named profiles to a launch, in order.
+fn apply_profiles(
+    cli_cfg: &CliConfig,
+    names: &[String],
+    params: &mut GeneratedParams,
+) -> Result<(), CommandError> {
+    for name in names {
+        let Some(profile) = cli_cfg.profiles.get(name) else {
+            let mut known: Vec<_> = cli_cfg.profiles.keys().cloned().collect();
+            known.sort();
+            return Err(CommandError::UnknownProfile(name.clone(), known.join(", ")));
+        };
+        debug!["Applying profile {}: {:?}", name, profile];
+        profile.apply(params);
+    }
+
+    Ok(())
+}
+
 pub fn run(
     cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
     cmd: RunCommand,
+    output: &LaunchOutputArgs,
     fail_on_unresolved_conflict: bool,
 ) -> Result<usize, CommandError> {
+    // Nothing would be left to remove the temporary folder once Blender exits
+    if output.detach
+        && matches!(
+            &cmd,
+            RunCommand::Build { isolation, .. } if isolation.temporary
+        )
+    {
+        return Err(CommandError::InvalidInput);
+    }
+
+    if let RunCommand::File {
+        paths,
+        together: false,
+        prefer,
+        blender,
+    } = &cmd
+    {
+        if paths.len() > 1 {
+            return run_each(cfg, cli_cfg, paths, *prefer, blender, output);
+        }
+    }
+
     let (file, query): (Option<PathBuf>, Option<VersionSearchQuery>) = match &cmd {
-        RunCommand::File { path } => (Some(path.clone()), None),
+        RunCommand::File { paths, .. } => (paths.first().cloned(), None),
+        RunCommand::Last => {
+            let history =
+                LaunchHistory::load().map_err(|e| error_reading(LaunchHistory::path(), e))?;
+            let Some(last) = history.last() else {
+                return Err(CommandError::NotEnoughInput);
+            };
+            info!["Relaunching {}", last.build];
+            let query = parse_query(&last.build)?;
+
+            (last.file.clone(), Some(query))
+        }
         RunCommand::Build {
             build_or_file,
             open_last: _,
+            isolation: _,
+            profile: _,
+            blender: _,
         } => match build_or_file {
-            Some(bof) => match VersionSearchQuery::try_from(bof.as_str()) {
+            Some(bof) => match parse_query(bof) {
                 Ok(q) => (None, Some(q)),
                 Err(_) => {
                     debug![
@@ -44,123 +159,425 @@ pub fn run(
         },
     };
 
-    let query = query.unwrap_or_else(|| {
-        let file = file.as_ref().unwrap();
+    // A pin next to the file decides the build before its header is consulted
+    let query = match (query, &file) {
+        (None, Some(file)) => {
+            let folder = file
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            pinned_build(folder)?
+                .map(|pin| parse_query(&pin))
+                .transpose()?
+        }
+        (query, _) => query,
+    };
 
-        // try to assume a query from the file header
-        read_blendfile_header(file)
+    // try to assume a version from the file header
+    let file_version = match (&query, &file) {
+        (None, Some(file)) => read_blendfile_header(file)
             .map(|header| {
                 debug!["Header: {:?}", header];
                 let ver = header.version();
 
-                VersionSearchQuery {
-                    repository: WildPlacement::default(),
-                    major: OrdPlacement::Exact(ver.major),
-                    minor: OrdPlacement::Exact(ver.minor),
-                    patch: OrdPlacement::default(),
-                    branch: WildPlacement::default(),
-                    build_hash: WildPlacement::default(),
-                    commit_dt: OrdPlacement::default(),
-                }
+                Version::new(ver.major, ver.minor, 0)
             })
             .inspect_err(|e| warn!["Failed to generate a query from {:?}: {:?}", file, e])
-            .unwrap_or_default()
+            .ok(),
+        _ => None,
+    };
+
+    let query = query.unwrap_or_else(|| match &file_version {
+        Some(ver) => VersionSearchQuery {
+            repository: WildPlacement::default(),
+            major: OrdPlacement::Exact(ver.major),
+            minor: OrdPlacement::Exact(ver.minor),
+            patch: OrdPlacement::default(),
+            branch: WildPlacement::default(),
+            build_hash: WildPlacement::default(),
+            commit_dt: OrdPlacement::default(),
+        },
+        None => VersionSearchQuery::default(),
     });
 
-    let chosen_build = {
-        // Get repos with installed builds
-        let builds = read_repos(cfg.repos.clone(), &cfg.paths, false)
-            .map_err(|e| CommandError::IoError(IoErrorOrigin::ReadingRepos, e))?
-            .into_iter()
-            .filter_map(|r| match r {
-                RepoEntry::Registered(
-                    BuildRepo {
-                        repo_id: _,
-                        url: _,
-                        nickname,
-                        repo_type: _,
-                    },
-                    vec,
-                )
-                | RepoEntry::Unknown(nickname, vec) => {
-                    let local_builds = vec
-                        .into_iter()
-                        .filter_map(|entry| match entry {
-                            BuildEntry::Installed(_, build) => Some(build),
-                            _ => None,
-                        })
-                        .collect::<Vec<_>>();
-
-                    match local_builds.is_empty() {
-                        false => Some((local_builds, nickname)),
-                        true => None,
-                    }
-                }
-                _ => None,
-            })
-            .flat_map(|(builds, nick)| builds.into_iter().map(move |b| (b, nick.clone())))
-            .collect::<Vec<_>>();
-
-        let matcher = BInfoMatcher::new(&builds);
-        let initial_matches = matcher.find_all(&query);
-        match (initial_matches.len(), fail_on_unresolved_conflict) {
-            // No conflict found
-            (1, _) => Some(initial_matches[0].0.clone()),
-            // Conflict found and can't resolve
-            (0 | 2.., true) => return Err(CommandError::InvalidInput),
-            // Conflict found and initial matches is empty
-            (0, false) => resolve_match(
-                &builds,
-                &format!["No matches detected for query {query}! select a build"],
-            )
-            .cloned(),
-            // Conflict found and there are initial matches
-            (2.., false) => resolve_match(
-                &initial_matches.into_iter().cloned().collect::<Vec<_>>(),
-                &format!["Multiple matches for query {query}! select a build"],
-            )
-            .cloned(),
+    let prefer = match &cmd {
+        RunCommand::File { prefer, .. } => prefer.unwrap_or(cli_cfg.file_fallback),
+        RunCommand::Build { .. } | RunCommand::Last => cli_cfg.file_fallback,
+    };
+
+    let (chosen_build, nick) = match file_version {
+        Some(ver) if prefer != FallbackPolicy::Ask => {
+            select_nearest_build(cfg, &query, &ver, prefer)?
+        }
+        _ => select_build(cfg, &query, fail_on_unresolved_conflict)?,
+    };
+
+    // Relative paths would point somewhere else when relaunched from another folder
+    let file = file.map(|f| std::path::absolute(&f).unwrap_or(f));
+    let mut launch = Launch {
+        build: build_query_string(&chosen_build.info.basic, &nick),
+        file: file.clone(),
+        time: chrono::Utc::now(),
+        crash: None,
+    };
+
+    let mut params = launch_params(cli_cfg, &chosen_build, &nick, file)?;
+    if let RunCommand::Build { profile, .. } = &cmd {
+        apply_profiles(cli_cfg, profile, &mut params)?;
+    }
+    let args = params.args.get_or_insert_default();
+    if let RunCommand::Build { blender, .. } | RunCommand::File { blender, .. } = &cmd {
+        args.extend(blender.to_args());
+    }
+    if let RunCommand::File {
+        paths,
+        together: true,
+        blender,
+        ..
+    } = &cmd
+    {
+        // Blender applies options to the file before them, so each file gets its own copy
+        for path in paths.iter().skip(1) {
+            let path = std::path::absolute(path).unwrap_or(path.clone());
+            args.push(path.to_string_lossy().into_owned());
+            args.extend(blender.to_args());
+        }
+    }
+    let mut command = command_for(&chosen_build, params);
+
+    let isolation = match &cmd {
+        RunCommand::Build { isolation, .. } if isolation.enabled() => Some(isolation),
+        _ => None,
+    };
+    let user_dir = match isolation {
+        Some(isolation) => {
+            let dir = isolated_user_dir(cli_cfg, isolation, &chosen_build, &nick);
+            std::fs::create_dir_all(&dir).map_err(|e| error_writing(dir.clone(), e))?;
+            info!["Using isolated user folders in {}", dir.display()];
+            command.envs(isolated_env(&dir));
+            Some(dir)
         }
+        None => None,
     };
 
-    let chosen_build = match chosen_build {
-        Some(c) => c,
-        None => return Err(CommandError::InvalidInput),
+    let log_file = match (&output.log_file, output.capture_logs) {
+        (Some(path), _) => Some(path.clone()),
+        (None, true) => Some(PROJECT_DIRS.config_dir().join("logs").join(format![
+            "{}-{}.log",
+            safe_name(&chosen_build, &nick),
+            launch.time.format("%Y%m%d-%H%M%S")
+        ])),
+        (None, false) => None,
     };
+    match &log_file {
+        Some(path) => {
+            let file = create_log_file(path)?;
+            let stderr = file
+                .try_clone()
+                .map_err(|e| error_writing(path.clone(), e))?;
+            info!["Writing Blender's output to {}", path.display()];
+            command.stdout(file).stderr(stderr);
+        }
+        None if output.detach => {
+            command.stdout(Stdio::null()).stderr(Stdio::null());
+        }
+        None => {}
+    }
+
+    hooks::run(Hook::PreRun, &chosen_build, &nick, &[])?;
+
+    info!["Running command {:?}", command];
+
+    let result = match output.detach {
+        true => spawn_detached(&mut command).map(|child| {
+            info!["Started Blender with process id {}", child.id()];
+            0
+        }),
+        false if output.wait_and_report => {
+            crash_report::wait(&mut command, log_file.as_deref(), launch.file.as_deref()).map(
+                |(code, crash)| {
+                    launch.crash = crash;
+                    code
+                },
+            )
+        }
+        false => command
+            .status()
+            .map(|exit_status| exit_status.code().map(|i| i as usize).unwrap_or_default()),
+    }
+    .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e));
+
+    if result.is_ok() {
+        if let Err(e) = LaunchHistory::record(launch) {
+            warn!["Failed to save the launch history: {}", e];
+        }
+    }
+    if let (Ok(code), false) = (&result, output.detach) {
+        hooks::run_after(
+            Hook::PostRun,
+            &chosen_build,
+            &nick,
+            &[("BLRS_EXIT_CODE", code.to_string())],
+        );
+    }
 
+    if let (
+        Some(dir),
+        Some(IsolationArgs {
+            temporary: true, ..
+        }),
+    ) = (user_dir, isolation)
+    {
+        debug!["Removing temporary user folder {}", dir.display()];
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            warn!["Failed to remove {}: {}", dir.display(), e];
+        }
+    }
+
+    result
+}
+
+/// Launches the build matching each file in turn, one file per launch.
+/// Every file is attempted, and the first error is returned.
+fn run_each(
+    cfg: &BLRSConfig,
+    cli_cfg: &CliConfig,
+    paths: &[PathBuf],
+    prefer: Option<FallbackPolicy>,
+    blender: &BlenderArgs,
+    output: &LaunchOutputArgs,
+) -> Result<usize, CommandError> {
+    let mut code = 0;
+    let mut first_error = None;
+    for path in paths {
+        let cmd = RunCommand::File {
+            paths: vec![path.clone()],
+            together: false,
+            prefer,
+            blender: blender.clone(),
+        };
+        match run(cfg, cli_cfg, cmd, output, false) {
+            // The first launch that Blender did not exit cleanly from decides the exit code
+            Ok(c) if code == 0 => code = c,
+            Ok(_) => {}
+            Err(e) => {
+                error!["Failed to open {}: {}", path.display(), e];
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    first_error.map_or(Ok(code), Err)
+}
+
+fn create_log_file(path: &Path) -> Result<File, CommandError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| error_writing(parent.to_path_buf(), e))?;
+    }
+    File::create(path).map_err(|e| error_writing(path.to_path_buf(), e))
+}
+
+/// Starts the command without waiting for it. It gets its own process group,
+/// so closing the terminal or pressing Ctrl+C in it does not stop Blender.
+fn spawn_detached(command: &mut process::Command) -> std::io::Result<process::Child> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+
+    command.stdin(Stdio::null()).spawn()
+}
+
+/// A name for the build that is safe to use in file and folder names.
+pub fn safe_name(build: &LocalBuild, nick: &str) -> String {
+    build_query_string(&build.info.basic, nick)
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "-_.+".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Picks the folder that an isolated launch keeps its preferences and scripts in.
+fn isolated_user_dir(
+    cli_cfg: &CliConfig,
+    isolation: &IsolationArgs,
+    build: &LocalBuild,
+    nick: &str,
+) -> PathBuf {
+    if let Some(dir) = &isolation.config_dir {
+        return dir.clone();
+    }
+    if isolation.temporary {
+        return std::env::temp_dir().join(format!["blrs-{}", uuid::Uuid::new_v4()]);
+    }
+
+    cli_cfg.isolation_root().join(safe_name(build, nick))
+}
+
+/// Points Blender's user folders into `dir`, so it does not touch the shared ones.
+fn isolated_env(dir: &Path) -> [(&'static str, PathBuf); 4] {
+    [
+        ("BLENDER_USER_CONFIG", dir.join("config")),
+        ("BLENDER_USER_SCRIPTS", dir.join("scripts")),
+        ("BLENDER_USER_DATAFILES", dir.join("datafiles")),
+        ("BLENDER_USER_EXTENSIONS", dir.join("extensions")),
+    ]
+}
+
+/// Builds the command that launches a build, applying its custom executable, environment, and arguments.
+pub fn launch_command(
+    cli_cfg: &CliConfig,
+    build: &LocalBuild,
+    nick: &str,
+    file: Option<PathBuf>,
+) -> Result<process::Command, CommandError> {
+    Ok(command_for(
+        build,
+        launch_params(cli_cfg, build, nick, file)?,
+    ))
+}
+
+/// The executable, arguments, and environment a build is launched with, before anything
+/// is added for a single launch.
+fn launch_params(
+    cli_cfg: &CliConfig,
+    build: &LocalBuild,
+    nick: &str,
+    file: Option<PathBuf>,
+) -> Result<GeneratedParams, CommandError> {
     let launch_arguments = LaunchArguments {
         file_target: match file {
             Some(f) => BlendLaunchTarget::File(f),
             None => BlendLaunchTarget::None,
         },
         os_target: OSLaunchTarget::default(),
-        env: None,
+        env: build.info.custom_env.clone(),
     };
 
-    let params = launch_arguments.assemble(&chosen_build);
+    let mut params: GeneratedParams = launch_arguments
+        .assemble(build)
+        .map_err(CommandError::CouldNotGenerateParams)?;
 
-    if let Err(e) = params {
-        return Err(CommandError::CouldNotGenerateParams(e));
-    }
+    let mut args = cli_cfg
+        .build_args
+        .get(&build_query_string(&build.info.basic, nick))
+        .cloned()
+        .unwrap_or_default();
+    args.extend(params.args.unwrap_or_default());
+    params.args = Some(args);
 
-    let params: GeneratedParams = params.unwrap();
+    Ok(params)
+}
 
-    let mut command = process::Command::new(params.exe);
+fn command_for(build: &LocalBuild, params: GeneratedParams) -> process::Command {
+    let mut command = match &build.info.custom_exe {
+        Some(exe) => process::Command::new(build.folder.join(exe)),
+        None => process::Command::new(params.exe),
+    };
+    command
+        .args(params.args.unwrap_or_default())
+        .envs(params.env.unwrap_or_default());
 
     command
-        .args(
-            params
-                .args
-                .unwrap_or_default()
-                .into_iter()
-                .collect::<Vec<String>>(),
+}
+
+/// Installed builds that can run on this platform. Builds pulled for other platforms are skipped.
+fn native_builds(cfg: &BLRSConfig) -> Result<Vec<(LocalBuild, String)>, CommandError> {
+    let mut builds = installed_builds(cfg)?;
+    builds.retain(|(b, _)| foreign_target(&b.info.basic).is_none());
+    Ok(builds)
+}
+
+/// Finds the installed build matching the query, prompting the user if the result is ambiguous.
+pub fn select_build(
+    cfg: &BLRSConfig,
+    query: &VersionSearchQuery,
+    fail_on_unresolved_conflict: bool,
+) -> Result<(LocalBuild, String), CommandError> {
+    let builds = native_builds(cfg)?;
+
+    let initial_matches = find_matches(&builds, query);
+    let chosen_build = match (initial_matches.len(), fail_on_unresolved_conflict) {
+        // No conflict found
+        (1, _) => Some(initial_matches[0].clone()),
+        // Conflict found and can't resolve
+        (0 | 2.., true) => return Err(CommandError::InvalidInput),
+        // Nothing to pick from without prompting
+        (0, false) if !is_interactive() => {
+            return Err(CommandError::QueryResultEmpty(query.to_string()))
+        }
+        // Conflict found and initial matches is empty
+        (0, false) => resolve_match(
+            &builds,
+            &format!["No matches detected for query {query}! select a build"],
+        )
+        .cloned(),
+        // Conflict found and there are initial matches
+        (2.., false) => resolve_match(
+            &initial_matches.into_iter().cloned().collect::<Vec<_>>(),
+            &format!["Multiple matches for query {query}! select a build"],
         )
-        .envs(params.env.clone().unwrap_or_default());
+        .cloned(),
+    };
 
-    info!["Running command {:?}", command];
+    chosen_build.ok_or(CommandError::InvalidInput)
+}
 
-    command
-        .status()
-        .map(|exit_status| exit_status.code().map(|i| i as usize).unwrap_or_default())
-        .map_err(|e| CommandError::IoError(IoErrorOrigin::CommandExecution, e))
+/// Finds the installed build closest to a blendfile's version without prompting.
+pub fn select_nearest_build(
+    cfg: &BLRSConfig,
+    query: &VersionSearchQuery,
+    version: &Version,
+    prefer: FallbackPolicy,
+) -> Result<(LocalBuild, String), CommandError> {
+    let builds = native_builds(cfg)?;
+
+    let mut matches: Vec<_> = find_matches(&builds, query).into_iter().cloned().collect();
+    matches.sort_by_key(|(b, _)| (b.info.basic.version().clone(), b.info.basic.commit_dt));
+
+    // The version exists, so only the direction of the tiebreak matters
+    if !matches.is_empty() {
+        return Ok(match prefer {
+            FallbackPolicy::Older => matches.swap_remove(0),
+            _ => matches.pop().unwrap(),
+        });
+    }
+
+    let (mut newer, mut older): (Vec<_>, Vec<_>) = builds
+        .into_iter()
+        .partition(|(b, _)| b.info.basic.version() >= version);
+    newer.sort_by_key(|(b, _)| (b.info.basic.version().clone(), b.info.basic.commit_dt));
+    older.sort_by_key(|(b, _)| (b.info.basic.version().clone(), b.info.basic.commit_dt));
+
+    let closest_newer = (!newer.is_empty()).then(|| newer.swap_remove(0));
+    let closest_older = older.pop();
+
+    let chosen = match prefer {
+        FallbackPolicy::Newer => closest_newer.or(closest_older),
+        FallbackPolicy::Older => closest_older.or(closest_newer),
+        FallbackPolicy::Exact | FallbackPolicy::Ask => None,
+    };
+
+    match chosen {
+        Some((build, nick)) => {
+            info![
+                "No build matches {}.{}, falling back to {}/{}",
+                version.major,
+                version.minor,
+                nick,
+                build.info.basic.version()
+            ];
+            Ok((build, nick))
+        }
+        None => Err(CommandError::QueryResultEmpty(query.to_string())),
+    }
 }
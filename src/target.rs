@@ -0,0 +1,85 @@
+use std::sync::OnceLock;
+
+use blrs::{
+    build_targets::{filter_repos_by_target, get_target_setup},
+    repos::{BuildEntry, RepoEntry, Variants},
+    RemoteBuild,
+};
+use log::debug;
+
+use crate::resolving::VariantPreference;
+
+/// Set by `--os`, `--arch`, and `--target`. Builds are chosen for this platform instead of the
+/// one blrs runs on, like when pulling Windows builds onto a Linux file server.
+static TARGET_OVERRIDE: OnceLock<Vec<VariantPreference>> = OnceLock::new();
+
+pub fn set_override(os: Option<String>, arch: Option<String>) {
+    let prefs: Vec<VariantPreference> = [("platform", os), ("arch", arch)]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| format!["{}={}", key, v]))
+        .filter_map(|pref| pref.parse().ok())
+        .collect();
+    if !prefs.is_empty() {
+        let _ = TARGET_OVERRIDE.set(prefs);
+    }
+}
+
+/// Splits a target like `windows-x64` into its platform and architecture.
+pub fn parse_target(s: &str) -> Result<(String, String), String> {
+    match s.split_once('-') {
+        Some((os, arch)) if !os.is_empty() && !arch.is_empty() => {
+            Ok((os.to_string(), arch.to_string()))
+        }
+        _ => Err(format![
+            "expected a platform and architecture like windows-x64, got {:?}",
+            s
+        ]),
+    }
+}
+
+/// Keeps the variants for the overridden platform, or for this system if none was given.
+pub fn filter_variants(mut variants: Variants<RemoteBuild>) -> Variants<RemoteBuild> {
+    match TARGET_OVERRIDE.get() {
+        Some(prefs) => {
+            variants
+                .v
+                .retain(|variant| prefs.iter().all(|pref| pref.matches(variant)));
+            variants
+        }
+        None => variants.filter_target(get_target_setup().unwrap()),
+    }
+}
+
+/// Drops the remote builds that have no variant for the target. Installed builds are kept.
+pub fn filter_repos(repos: Vec<RepoEntry>) -> Vec<RepoEntry> {
+    let Some(prefs) = TARGET_OVERRIDE.get() else {
+        let target = get_target_setup().unwrap();
+        debug!["filtering list of builds by the target: {:?}", target];
+        return filter_repos_by_target(repos, Some(target));
+    };
+
+    debug![
+        "filtering list of builds by the overridden target: {:?}",
+        prefs
+    ];
+    let filter = |vec: Vec<BuildEntry>| {
+        vec.into_iter()
+            .filter_map(|entry| match entry {
+                BuildEntry::NotInstalled(variants) => {
+                    let variants = filter_variants(variants);
+                    (!variants.v.is_empty()).then_some(BuildEntry::NotInstalled(variants))
+                }
+                entry => Some(entry),
+            })
+            .collect()
+    };
+
+    repos
+        .into_iter()
+        .map(|repo| match repo {
+            RepoEntry::Registered(repo, vec) => RepoEntry::Registered(repo, filter(vec)),
+            RepoEntry::Unknown(name, vec) => RepoEntry::Unknown(name, filter(vec)),
+            error => error,
+        })
+        .collect()
+}
@@ -1,14 +1,20 @@
 use blrs::{fetching::authentication::GithubAuthentication, BLRSConfig};
 use chrono::Utc;
 
+use crate::cli_config::CliConfig;
+
 #[derive(Debug, Clone)]
 pub enum ConfigTask {
     UpdateGHAuth(GithubAuthentication),
     UpdateLastTimeChecked,
+    SetDefaultBuild(Option<String>),
+    SetBuildArgs(String, Option<Vec<String>>),
+    SetConfig(Box<BLRSConfig>),
+    SetCliConfig(Box<CliConfig>),
 }
 
 impl ConfigTask {
-    pub fn eval(self, cfg: &mut BLRSConfig) {
+    pub fn eval(self, cfg: &mut BLRSConfig, cli_cfg: &mut CliConfig) {
         match self {
             Self::UpdateGHAuth(github_authentication) => {
                 cfg.update_github_authentication(Some(github_authentication));
@@ -17,6 +23,21 @@ impl ConfigTask {
                 let dt = Utc::now();
                 cfg.history.last_time_checked = Some(dt);
             }
+            Self::SetDefaultBuild(query) => {
+                cli_cfg.default_build = query;
+            }
+            Self::SetBuildArgs(query, Some(args)) => {
+                cli_cfg.build_args.insert(query, args);
+            }
+            Self::SetBuildArgs(query, None) => {
+                cli_cfg.build_args.remove(&query);
+            }
+            Self::SetConfig(new) => {
+                *cfg = *new;
+            }
+            Self::SetCliConfig(new) => {
+                *cli_cfg = *new;
+            }
         }
     }
 }
@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use blrs::{
+    fetching::build_repository::{BuildRepo, RepoType},
+    BLRSConfig,
+};
+
+/// A library and remote repos folder in the temporary folder, removed again when dropped,
+/// even when the test using it fails.
+pub struct TempLibrary {
+    root: PathBuf,
+    pub cfg: BLRSConfig,
+}
+
+impl TempLibrary {
+    /// A library with one repo, `test`, whose cached build list holds `builds`.
+    pub fn with_builds(builds: &[serde_json::Value]) -> Self {
+        let root = std::env::temp_dir().join(format!["blrs-test-{}", uuid::Uuid::new_v4()]);
+        let mut cfg: BLRSConfig = BLRSConfig::default_figment(None).extract().unwrap();
+        cfg.paths.library = root.join("library");
+        cfg.paths.remote_repos = root.join("remote-repos");
+        std::fs::create_dir_all(&cfg.paths.library).unwrap();
+        std::fs::create_dir_all(&cfg.paths.remote_repos).unwrap();
+
+        std::fs::write(
+            cfg.paths.remote_repos.join("test.json"),
+            serde_json::to_string(builds).unwrap(),
+        )
+        .unwrap();
+        cfg.repos = vec![BuildRepo {
+            repo_id: "test".to_string(),
+            url: "https://builder.blender.org/download/daily/?format=json&v=1".to_string(),
+            nickname: "test".to_string(),
+            repo_type: RepoType::Blender,
+        }];
+
+        Self { root, cfg }
+    }
+}
+
+impl Drop for TempLibrary {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A build as builder.blender.org lists it, like
+/// `blender-4.3.0-alpha+main.0123456789ab-linux.x86_64-release.tar.xz`.
+pub struct ListedBuild<'a> {
+    pub version: &'a str,
+    pub hash: &'a str,
+    pub branch: &'a str,
+    pub release_cycle: &'a str,
+    pub platform: &'a str,
+    pub architecture: &'a str,
+    pub extension: &'a str,
+}
+
+impl Default for ListedBuild<'_> {
+    fn default() -> Self {
+        Self {
+            version: "4.3.0",
+            hash: "0123456789ab",
+            branch: "main",
+            release_cycle: "alpha",
+            platform: "linux",
+            architecture: "x86_64",
+            extension: "tar.xz",
+        }
+    }
+}
+
+impl ListedBuild<'_> {
+    pub fn json(&self) -> serde_json::Value {
+        let name = format![
+            "blender-{}-{}+{}.{}-{}.{}-release.{}",
+            self.version,
+            self.release_cycle,
+            self.branch,
+            self.hash,
+            self.platform,
+            self.architecture,
+            self.extension
+        ];
+        serde_json::json!({
+            "app": "Blender",
+            "url": format!["https://cdn.builder.blender.org/download/daily/{}", name],
+            "version": self.version,
+            "branch": self.branch,
+            "patch": null,
+            "hash": self.hash,
+            "platform": self.platform,
+            "architecture": self.architecture,
+            "bitness": 64,
+            "file_mtime": 1_700_000_000,
+            "file_name": name,
+            "file_size": 350_000_000,
+            "file_extension": self.extension.rsplit('.').next().unwrap(),
+            "release_cycle": self.release_cycle,
+        })
+    }
+}
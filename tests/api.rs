@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use blrs::BLRSConfig;
+use blrs_cli::api::{CliConfig, CommandError, PullOptions, Session};
+
+/// The library every session of these tests uses, as the settings of a session apply to the whole process.
+fn library() -> PathBuf {
+    let root = std::env::temp_dir().join("blrs-api-tests");
+    std::fs::create_dir_all(root.join("library")).unwrap();
+    std::fs::create_dir_all(root.join("remote-repos")).unwrap();
+    root
+}
+
+fn config(root: PathBuf) -> BLRSConfig {
+    let mut cfg: BLRSConfig = BLRSConfig::default_figment(None).extract().unwrap();
+    cfg.paths.library = root.join("library");
+    cfg.paths.remote_repos = root.join("remote-repos");
+    cfg.repos = vec![];
+    cfg
+}
+
+fn session() -> Session {
+    Session::new(config(library()), CliConfig::default()).unwrap()
+}
+
+#[test]
+fn sessions_with_the_same_settings_can_coexist() {
+    let first = session();
+    let second = session();
+    assert_eq!(first.cfg.paths.library, second.cfg.paths.library);
+}
+
+#[test]
+fn a_session_with_another_library_is_refused() {
+    let _first = session();
+    let other = config(std::env::temp_dir().join("blrs-api-tests-other"));
+
+    assert!(matches!(
+        Session::new(other, CliConfig::default()),
+        Err(CommandError::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn an_empty_library_lists_no_builds() {
+    let repos = session().ls(true).unwrap();
+    assert!(repos.iter().all(|r| !r.has_installed_builds()));
+}
+
+#[test]
+fn commands_need_a_query() {
+    let mut session = session();
+
+    assert!(matches!(
+        session.rm(&[], true),
+        Err(CommandError::MissingQuery)
+    ));
+    assert!(matches!(
+        session.pull(&[], &PullOptions::default()),
+        Err(CommandError::MissingQuery)
+    ));
+}